@@ -0,0 +1,246 @@
+use crate::calendar::{parse_instant, recorded_offset};
+use crate::models::{
+    AbandonedStep, AbortAnalytics, HourlyAbortRate, Routine, Session, SessionOutcome,
+};
+use crate::session_stats::{recorded_labels, run_label, session_outcome, step_position};
+use chrono::{TimeZone, Timelike};
+use std::collections::HashMap;
+
+/// How the sessions in `sessions` ended, the steps stopped and recovered
+/// sessions were abandoned in, and the share of sessions abandoned per local
+/// hour of their start. Hours are read in the zone each session recorded, or
+/// in `zone` for sessions stored without one. A session is abandoned in the
+/// step of its last run; sessions without runs only count towards the totals.
+pub fn calculate_abort_analytics<Tz: TimeZone>(
+    sessions: &[Session],
+    routines: &[Routine],
+    zone: &Tz,
+) -> AbortAnalytics {
+    let mut analytics = AbortAnalytics {
+        by_hour: (0..24)
+            .map(|hour| HourlyAbortRate {
+                hour,
+                ..HourlyAbortRate::default()
+            })
+            .collect(),
+        ..AbortAnalytics::default()
+    };
+    let mut abandoned: HashMap<(&str, &str), Abandonments> = HashMap::new();
+    for session in sessions.iter().filter(|item| !item.excluded_from_stats) {
+        let outcome = session_outcome(session);
+        match outcome {
+            SessionOutcome::Completed => analytics.completed_count += 1,
+            SessionOutcome::Stopped => analytics.stopped_count += 1,
+            SessionOutcome::Recovered => analytics.recovered_count += 1,
+        }
+        let aborted = outcome != SessionOutcome::Completed;
+
+        if let Ok(started_at) = parse_instant(&session.started_at) {
+            let hour = match session.zone.as_ref().and_then(recorded_offset) {
+                Some(offset) => started_at.with_timezone(&offset).hour(),
+                None => started_at.with_timezone(zone).hour(),
+            } as usize;
+            let entry = &mut analytics.by_hour[hour];
+            entry.sessions_count += 1;
+            if aborted {
+                entry.aborted_count += 1;
+            }
+        }
+
+        let Some(run) = session.step_runs.last().filter(|_| aborted) else {
+            continue;
+        };
+        let progress = if run.planned_duration_seconds == 0 {
+            1.0
+        } else {
+            run.actual_duration_seconds as f32 / run.planned_duration_seconds as f32
+        };
+        let entry = abandoned
+            .entry((session.routine_id.as_str(), run.step_id.as_str()))
+            .or_default();
+        if outcome == SessionOutcome::Recovered {
+            entry.recovered += 1;
+        } else {
+            entry.stopped += 1;
+        }
+        entry.progress_sum += progress.min(1.0);
+        entry.quarters[((progress * 4.0) as usize).min(3)] += 1;
+    }
+
+    for entry in &mut analytics.by_hour {
+        entry.abort_rate = if entry.sessions_count == 0 {
+            0.0
+        } else {
+            entry.aborted_count as f32 / entry.sessions_count as f32
+        };
+    }
+
+    let mut keys: Vec<(&str, &str)> = abandoned.keys().copied().collect();
+    keys.sort_by_key(|&(routine_id, step_id)| {
+        let count = abandoned[&(routine_id, step_id)].count();
+        let position = step_position(routines, routine_id, step_id);
+        (
+            std::cmp::Reverse(count),
+            position.is_none(),
+            position,
+            routine_id,
+            step_id,
+        )
+    });
+    let labels = recorded_labels(sessions);
+    analytics.abandoned_steps = keys
+        .into_iter()
+        .map(|(routine_id, step_id)| {
+            let entry = &abandoned[&(routine_id, step_id)];
+            AbandonedStep {
+                routine_id: routine_id.to_string(),
+                step_id: step_id.to_string(),
+                label: run_label(&labels, routines, routine_id, step_id),
+                stopped_count: entry.stopped,
+                recovered_count: entry.recovered,
+                average_progress: entry.progress_sum / entry.count() as f32,
+                progress_quarters: entry.quarters,
+            }
+        })
+        .collect();
+
+    analytics
+}
+
+#[derive(Debug, Default)]
+struct Abandonments {
+    stopped: u32,
+    recovered: u32,
+    progress_sum: f32,
+    quarters: [u32; 4],
+}
+
+impl Abandonments {
+    fn count(&self) -> u32 {
+        self.stopped + self.recovered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::calculate_abort_analytics;
+    use crate::models::{
+        Session, SessionOutcome, SessionTotals, SessionZone, StepRun, StepRunResult,
+    };
+    use chrono::FixedOffset;
+
+    fn run(step_id: &str, result: StepRunResult, actual: u32) -> StepRun {
+        StepRun {
+            step_id: step_id.to_string(),
+            label: None,
+            planned_duration_seconds: 100,
+            actual_duration_seconds: actual,
+            started_at: "2025-01-06T09:00:00Z".to_string(),
+            ended_at: None,
+            result,
+            check_in_result: None,
+            sound_played: false,
+        }
+    }
+
+    fn session(started_at: &str, outcome: SessionOutcome, step_runs: Vec<StepRun>) -> Session {
+        Session {
+            id: format!("session-{started_at}"),
+            routine_id: "routine-1".to_string(),
+            started_at: started_at.to_string(),
+            ended_at: None,
+            step_runs,
+            totals: SessionTotals::default(),
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: Some(outcome),
+        }
+    }
+
+    #[test]
+    fn breaks_down_abandoned_sessions() {
+        let sessions = vec![
+            session(
+                "2025-01-06T00:30:00Z",
+                SessionOutcome::Completed,
+                vec![
+                    run("step-1", StepRunResult::Completed, 100),
+                    run("step-2", StepRunResult::Completed, 100),
+                ],
+            ),
+            session(
+                "2025-01-06T00:45:00Z",
+                SessionOutcome::Stopped,
+                vec![
+                    run("step-1", StepRunResult::Completed, 100),
+                    run("step-2", StepRunResult::Aborted, 10),
+                ],
+            ),
+            session(
+                "2025-01-07T00:10:00Z",
+                SessionOutcome::Recovered,
+                vec![run("step-2", StepRunResult::Aborted, 60)],
+            ),
+            session(
+                "2025-01-07T13:00:00Z",
+                SessionOutcome::Stopped,
+                vec![run("step-1", StepRunResult::Aborted, 150)],
+            ),
+        ];
+        let tokyo = FixedOffset::east_opt(9 * 3600).expect("offset");
+
+        let analytics = calculate_abort_analytics(&sessions, &[], &tokyo);
+
+        assert_eq!(
+            (
+                analytics.completed_count,
+                analytics.stopped_count,
+                analytics.recovered_count
+            ),
+            (1, 2, 1)
+        );
+        assert_eq!(analytics.abandoned_steps.len(), 2);
+        let step_2 = &analytics.abandoned_steps[0];
+        assert_eq!(step_2.step_id, "step-2");
+        assert_eq!((step_2.stopped_count, step_2.recovered_count), (1, 1));
+        assert!((step_2.average_progress - 0.35).abs() < 1e-6);
+        assert_eq!(step_2.progress_quarters, [1, 0, 1, 0]);
+        assert_eq!(analytics.abandoned_steps[1].progress_quarters, [0, 0, 0, 1]);
+
+        assert_eq!(analytics.by_hour.len(), 24);
+        let nine = &analytics.by_hour[9];
+        assert_eq!((nine.sessions_count, nine.aborted_count), (3, 2));
+        assert!((nine.abort_rate - 2.0 / 3.0).abs() < f32::EPSILON);
+        assert_eq!(analytics.by_hour[22].aborted_count, 1);
+        assert_eq!(analytics.by_hour[0].sessions_count, 0);
+    }
+
+    #[test]
+    fn reads_hours_in_the_zone_each_session_recorded() {
+        let mut travelling = session(
+            "2025-01-07T13:00:00Z",
+            SessionOutcome::Stopped,
+            vec![run("step-1", StepRunResult::Aborted, 50)],
+        );
+        travelling.zone = Some(SessionZone {
+            utc_offset_minutes: -5 * 60,
+            time_zone: Some("America/New_York".to_string()),
+        });
+        let at_home = session(
+            "2025-01-07T13:00:00Z",
+            SessionOutcome::Completed,
+            Vec::new(),
+        );
+        let tokyo = FixedOffset::east_opt(9 * 3600).expect("offset");
+
+        let analytics = calculate_abort_analytics(&[travelling, at_home], &[], &tokyo);
+
+        assert_eq!(analytics.by_hour[8].aborted_count, 1);
+        assert_eq!(analytics.by_hour[22].sessions_count, 1);
+        assert_eq!(analytics.by_hour[22].aborted_count, 0);
+    }
+}
@@ -1,5 +1,15 @@
+use crate::calendar::CalendarError;
 use crate::data_manager::DataError;
+use crate::folder_sync::SyncError;
+use crate::goals::GoalError;
+use crate::profiles::ProfileError;
+use crate::routine_file::RoutineFileError;
+use crate::routine_validator::{FieldError, ValidationErrors};
+use crate::session_editor::SessionEditError;
+use crate::session_export::ExportError;
+use crate::sound_assets::SoundAssetError;
 use crate::timer_engine::TimerError;
+use crate::weekly_report::ReportError;
 use serde::Serialize;
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -9,6 +19,7 @@ pub enum AppErrorKind {
     Data,
     Timer,
     Audio,
+    Validation,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -18,6 +29,8 @@ pub struct AppErrorPayload {
     pub message: String,
     pub detail: Option<String>,
     pub recoverable: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub field_errors: Vec<FieldError>,
 }
 
 #[derive(Debug, Clone)]
@@ -26,6 +39,7 @@ pub struct AppError {
     message: String,
     detail: Option<String>,
     recoverable: bool,
+    field_errors: Vec<FieldError>,
 }
 
 impl AppError {
@@ -35,6 +49,7 @@ impl AppError {
             message: message.into(),
             detail: None,
             recoverable,
+            field_errors: Vec::new(),
         }
     }
 
@@ -49,6 +64,7 @@ impl AppError {
             message: message.into(),
             detail: Some(detail.into()),
             recoverable,
+            field_errors: Vec::new(),
         }
     }
 
@@ -60,6 +76,25 @@ impl AppError {
         Self::new(AppErrorKind::Audio, message, false)
     }
 
+    pub fn validation(errors: Vec<FieldError>) -> Self {
+        Self::validation_with_message("ルーチンの入力内容に誤りがあります", errors)
+    }
+
+    fn validation_with_message(message: &str, errors: Vec<FieldError>) -> Self {
+        let detail = errors
+            .iter()
+            .map(|error| format!("{}: {}", error.path, error.message))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self {
+            kind: AppErrorKind::Validation,
+            message: message.to_string(),
+            detail: Some(detail),
+            recoverable: true,
+            field_errors: errors,
+        }
+    }
+
     pub fn kind(&self) -> AppErrorKind {
         self.kind
     }
@@ -78,6 +113,7 @@ impl AppError {
             message: self.message.clone(),
             detail: self.detail.clone(),
             recoverable: self.recoverable,
+            field_errors: self.field_errors.clone(),
         }
     }
 }
@@ -110,6 +146,175 @@ impl From<DataError> for AppError {
             DataError::Io(_) => "データの読み書きに失敗しました",
             DataError::Serde(_) => "データ形式の読み込みに失敗しました",
             DataError::DateTime(_) => "日付の読み込みに失敗しました",
+            DataError::BackupNotFound(_) => "バックアップが見つかりません",
+            DataError::KeyRequired => "暗号化されたデータを開くにはキーが必要です",
+            DataError::WrongKey => "暗号化キーが正しくありません",
+            DataError::Encryption(_) => "データの暗号化処理に失敗しました",
+            DataError::SyncFolderEncrypted => {
+                "同期フォルダは暗号化されています。他の端末と同じパスフレーズかキーファイルで暗号化を有効にしてください"
+            }
+            DataError::Locked(_) => {
+                return Self::with_detail(
+                    AppErrorKind::Data,
+                    "データフォルダは別のMcCallで使用中です",
+                    detail,
+                    false,
+                )
+            }
+        };
+        Self::with_detail(AppErrorKind::Data, message, detail, true)
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        Self::validation(errors.into_errors())
+    }
+}
+
+impl From<RoutineFileError> for AppError {
+    fn from(error: RoutineFileError) -> Self {
+        let detail = error.to_string();
+        let message = match error {
+            RoutineFileError::Data(err) => return Self::from(err),
+            RoutineFileError::Invalid(errors) => return Self::from(errors),
+            RoutineFileError::Io(_) => "ルーチンファイルの読み書きに失敗しました",
+            RoutineFileError::Parse(_) => "ルーチンファイルの形式が正しくありません",
+            RoutineFileError::Format(_) => "ルーチンファイルの内容を解釈できません",
+            RoutineFileError::NotFound(_) => "ルーチンが見つかりません",
+        };
+        Self::with_detail(AppErrorKind::Data, message, detail, true)
+    }
+}
+
+impl From<ExportError> for AppError {
+    fn from(error: ExportError) -> Self {
+        let detail = error.to_string();
+        let message = match error {
+            ExportError::Data(err) => return Self::from(err),
+            ExportError::Io(_) => "エクスポートファイルの書き込みに失敗しました",
+            ExportError::Serde(_) => "エクスポートデータの生成に失敗しました",
+        };
+        Self::with_detail(AppErrorKind::Data, message, detail, true)
+    }
+}
+
+impl From<ReportError> for AppError {
+    fn from(error: ReportError) -> Self {
+        let detail = error.to_string();
+        match error {
+            ReportError::Data(err) => Self::from(err),
+            ReportError::Calendar(err) => Self::from(err),
+            ReportError::Io(_) => Self::with_detail(
+                AppErrorKind::Data,
+                "週次レポートの書き込みに失敗しました",
+                detail,
+                true,
+            ),
+        }
+    }
+}
+
+impl From<SoundAssetError> for AppError {
+    fn from(error: SoundAssetError) -> Self {
+        let detail = error.to_string();
+        let message = match error {
+            SoundAssetError::Io(_) => "サウンドファイルの読み書きに失敗しました",
+            SoundAssetError::InvalidName(_) => "サウンド名が正しくありません",
+            SoundAssetError::NotFound(_) => "サウンドファイルが見つかりません",
+            SoundAssetError::UnsupportedFormat(_) => "対応していない音声ファイルです",
+        };
+        Self::with_detail(AppErrorKind::Audio, message, detail, true)
+    }
+}
+
+impl From<CalendarError> for AppError {
+    fn from(error: CalendarError) -> Self {
+        let field = match &error {
+            CalendarError::InvalidInstant(_) => {
+                FieldError::new("at", "日時の形式が正しくありません")
+            }
+            CalendarError::InvalidRolloverHour(_) => FieldError::new(
+                "calendar.dayRolloverHour",
+                "日付の切り替え時刻は0〜23時で指定してください",
+            ),
+            CalendarError::InvalidTimeOfDay(_) => {
+                FieldError::new("filter.timeOfDay", "時刻はHH:MMの形式で指定してください")
+            }
+            CalendarError::TooManyPeriods => {
+                FieldError::new("to", "期間が長すぎます。集計単位を大きくしてください")
+            }
+        };
+        Self::validation_with_message("日付の設定に誤りがあります", vec![field])
+    }
+}
+
+impl From<SyncError> for AppError {
+    fn from(error: SyncError) -> Self {
+        let detail = error.to_string();
+        let message = match error {
+            SyncError::Data(err) => return Self::from(err),
+            SyncError::NotConfigured => "同期フォルダが設定されていません",
+            SyncError::ConflictNotFound(_) => "同期の競合が見つかりません",
+        };
+        Self::with_detail(AppErrorKind::Data, message, detail, true)
+    }
+}
+
+impl AppError {
+    pub fn sync_conflicts(count: u32) -> Self {
+        Self::new(
+            AppErrorKind::Data,
+            format!("同期中にルーチンの競合が{count}件見つかりました。内容を確認してください"),
+            true,
+        )
+    }
+}
+
+impl From<GoalError> for AppError {
+    fn from(error: GoalError) -> Self {
+        match error {
+            GoalError::Data(err) => Self::from(err),
+            GoalError::Invalid(errors) => {
+                Self::validation_with_message("目標の入力内容に誤りがあります", errors)
+            }
+        }
+    }
+}
+
+impl From<ProfileError> for AppError {
+    fn from(error: ProfileError) -> Self {
+        let detail = error.to_string();
+        let message = match error {
+            ProfileError::Data(err) => return Self::from(err),
+            ProfileError::Invalid(field) => {
+                return Self::validation_with_message(
+                    "プロファイルの入力内容に誤りがあります",
+                    vec![field],
+                )
+            }
+            ProfileError::NotFound(_) => "プロファイルが見つかりません",
+            ProfileError::SessionInProgress => {
+                "セッションの実行中はプロファイルを切り替えられません"
+            }
+        };
+        Self::with_detail(AppErrorKind::Data, message, detail, true)
+    }
+}
+
+impl From<SessionEditError> for AppError {
+    fn from(error: SessionEditError) -> Self {
+        let detail = error.to_string();
+        let message = match error {
+            SessionEditError::Data(err) => return Self::from(err),
+            SessionEditError::Invalid(field) => {
+                return Self::validation_with_message(
+                    "セッションの入力内容に誤りがあります",
+                    vec![field],
+                )
+            }
+            SessionEditError::NotFound(_) => "セッションが見つかりません",
+            SessionEditError::StepRunNotFound { .. } => "ステップの記録が見つかりません",
         };
         Self::with_detail(AppErrorKind::Data, message, detail, true)
     }
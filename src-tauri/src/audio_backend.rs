@@ -0,0 +1,335 @@
+use crate::sound_assets::{probe_sound, SoundFormat};
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Command-line players tried in order, with the one format a player is
+/// limited to. On Linux PipeWire's and PulseAudio's players come first as
+/// they decode compressed files; ALSA's only handles WAV.
+#[cfg(target_os = "macos")]
+const PLAYERS: &[(&str, &[&str], Option<SoundFormat>)] = &[("afplay", &[], None)];
+#[cfg(not(target_os = "macos"))]
+const PLAYERS: &[(&str, &[&str], Option<SoundFormat>)] = &[
+    ("pw-play", &[], None),
+    ("paplay", &[], None),
+    ("aplay", &["-q"], Some(SoundFormat::Wav)),
+];
+
+#[derive(Debug)]
+pub enum PlaybackError {
+    NoBackend,
+    MissingFile(PathBuf),
+    UnsupportedFormat(PathBuf),
+    Spawn(io::Error),
+}
+
+impl std::fmt::Display for PlaybackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlaybackError::NoBackend => write!(f, "No sound player is available"),
+            PlaybackError::MissingFile(path) => {
+                write!(f, "Sound file not found: {}", path.display())
+            }
+            PlaybackError::UnsupportedFormat(path) => {
+                write!(f, "No sound player can play: {}", path.display())
+            }
+            PlaybackError::Spawn(err) => write!(f, "Failed to start sound player: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for PlaybackError {}
+
+impl From<io::Error> for PlaybackError {
+    fn from(value: io::Error) -> Self {
+        Self::Spawn(value)
+    }
+}
+
+/// Something that can get a sound file out of the speakers.
+pub trait PlaybackBackend: std::fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether this backend can decode `path`.
+    fn can_play(&self, _path: &Path) -> bool {
+        true
+    }
+
+    /// Starts playing `path` without waiting for it to finish.
+    fn play(&self, path: &Path) -> Result<(), PlaybackError>;
+}
+
+/// Plays files by spawning an external player with the file as last argument.
+#[derive(Debug, Clone)]
+pub struct CommandBackend {
+    name: String,
+    program: PathBuf,
+    args: Vec<String>,
+    only: Option<SoundFormat>,
+}
+
+impl CommandBackend {
+    pub fn new(program: impl Into<PathBuf>, args: &[&str]) -> Self {
+        let program = program.into();
+        Self {
+            name: program
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            program,
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            only: None,
+        }
+    }
+
+    /// Restricts the player to files of `format`, judged by their header.
+    pub fn only(mut self, format: SoundFormat) -> Self {
+        self.only = Some(format);
+        self
+    }
+
+    /// The player `name` when an executable of that name is on `search_path`.
+    pub fn find(name: &str, args: &[&str], search_path: &OsStr) -> Option<Self> {
+        std::env::split_paths(search_path)
+            .map(|dir| dir.join(name))
+            .find(|candidate| is_executable(candidate))
+            .map(|program| Self::new(program, args))
+    }
+}
+
+impl PlaybackBackend for CommandBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn can_play(&self, path: &Path) -> bool {
+        self.only
+            .is_none_or(|format| probe_sound(path).is_ok_and(|found| found == format))
+    }
+
+    fn play(&self, path: &Path) -> Result<(), PlaybackError> {
+        if !path.is_file() {
+            return Err(PlaybackError::MissingFile(path.to_path_buf()));
+        }
+        if !self.can_play(path) {
+            return Err(PlaybackError::UnsupportedFormat(path.to_path_buf()));
+        }
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        std::thread::spawn(move || {
+            let _ = child.wait();
+        });
+        Ok(())
+    }
+}
+
+/// Plays each file through the first of its players that handles the file's
+/// format.
+#[derive(Debug)]
+pub struct FallbackBackend {
+    players: Vec<Arc<dyn PlaybackBackend>>,
+}
+
+impl FallbackBackend {
+    pub fn new(players: Vec<Arc<dyn PlaybackBackend>>) -> Self {
+        Self { players }
+    }
+}
+
+impl PlaybackBackend for FallbackBackend {
+    /// The preferred player's name.
+    fn name(&self) -> &str {
+        self.players
+            .first()
+            .map(|player| player.name())
+            .unwrap_or("")
+    }
+
+    fn can_play(&self, path: &Path) -> bool {
+        self.players.iter().any(|player| player.can_play(path))
+    }
+
+    fn play(&self, path: &Path) -> Result<(), PlaybackError> {
+        if !path.is_file() {
+            return Err(PlaybackError::MissingFile(path.to_path_buf()));
+        }
+        self.players
+            .iter()
+            .find(|player| player.can_play(path))
+            .ok_or_else(|| PlaybackError::UnsupportedFormat(path.to_path_buf()))?
+            .play(path)
+    }
+}
+
+/// The known players found on `PATH`, in order: `afplay` on macOS, PipeWire,
+/// PulseAudio and ALSA players elsewhere. A file goes to the first one that
+/// handles its format, so WAV-only `aplay` is passed over for other files.
+pub fn detect_backend() -> Option<Arc<dyn PlaybackBackend>> {
+    let search_path = std::env::var_os("PATH")?;
+    let players: Vec<Arc<dyn PlaybackBackend>> = PLAYERS
+        .iter()
+        .filter_map(|(name, args, only)| {
+            let backend = CommandBackend::find(name, args, &search_path)?;
+            let backend = match only {
+                Some(format) => backend.only(*format),
+                None => backend,
+            };
+            Some(Arc::new(backend) as Arc<dyn PlaybackBackend>)
+        })
+        .collect();
+    (!players.is_empty()).then(|| Arc::new(FallbackBackend::new(players)) as _)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Plays nothing and remembers what it was asked to play.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct RecordingBackend {
+    played: std::sync::Mutex<Vec<PathBuf>>,
+    fail: bool,
+}
+
+#[cfg(test)]
+impl RecordingBackend {
+    pub fn failing() -> Self {
+        Self {
+            fail: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn played(&self) -> Vec<PathBuf> {
+        self.played
+            .lock()
+            .map(|items| items.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+impl PlaybackBackend for RecordingBackend {
+    fn name(&self) -> &str {
+        "recording"
+    }
+
+    fn play(&self, path: &Path) -> Result<(), PlaybackError> {
+        if self.fail {
+            return Err(PlaybackError::Spawn(io::Error::other(
+                "recording backend set to fail",
+            )));
+        }
+        if let Ok(mut played) = self.played.lock() {
+            played.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandBackend, FallbackBackend, PlaybackBackend, PlaybackError};
+    use crate::sound_assets::SoundFormat;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_audio_backend_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_executable_players_on_search_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let empty = temp_dir();
+        let bin = temp_dir();
+        let player = bin.join("paplay");
+        fs::write(&player, "#!/bin/sh\n").expect("write player");
+        fs::set_permissions(&player, fs::Permissions::from_mode(0o755)).expect("chmod");
+        fs::write(bin.join("aplay"), "").expect("write non-executable");
+        let search_path = std::env::join_paths([&empty, &bin]).expect("join paths");
+
+        let found = CommandBackend::find("paplay", &[], &search_path).expect("found");
+        assert_eq!(found.name(), "paplay");
+        assert!(CommandBackend::find("aplay", &["-q"], &search_path).is_none());
+        assert!(CommandBackend::find("pw-play", &[], &search_path).is_none());
+
+        fs::remove_dir_all(empty).ok();
+        fs::remove_dir_all(bin).ok();
+    }
+
+    #[test]
+    fn refuses_missing_files_before_spawning() {
+        let dir = temp_dir();
+        let backend = CommandBackend::new("/nonexistent/player", &[]);
+        let result = backend.play(&dir.join("missing.oga"));
+        assert!(matches!(result, Err(PlaybackError::MissingFile(_))));
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn passes_over_players_that_cannot_decode_the_file() {
+        let dir = temp_dir();
+        let wav = dir.join("chime.wav");
+        fs::write(&wav, b"RIFF\x24\x00\x00\x00WAVEfmt ").expect("write wav");
+        let ogg = dir.join("bell.oga");
+        fs::write(&ogg, b"OggS\x00\x02").expect("write ogg");
+
+        let aplay = CommandBackend::new("/nonexistent/aplay", &["-q"]).only(SoundFormat::Wav);
+        assert!(aplay.can_play(&wav));
+        assert!(!aplay.can_play(&ogg));
+        assert!(matches!(
+            aplay.play(&ogg),
+            Err(PlaybackError::UnsupportedFormat(_))
+        ));
+
+        let backend = FallbackBackend::new(vec![Arc::new(aplay)]);
+        assert_eq!(backend.name(), "aplay");
+        assert!(matches!(
+            backend.play(&ogg),
+            Err(PlaybackError::UnsupportedFormat(_))
+        ));
+        let backend = FallbackBackend::new(vec![
+            Arc::new(CommandBackend::new("/nonexistent/aplay", &[]).only(SoundFormat::Wav)),
+            Arc::new(CommandBackend::new("/nonexistent/paplay", &[])),
+        ]);
+        assert!(backend.can_play(&ogg));
+        assert!(matches!(backend.play(&ogg), Err(PlaybackError::Spawn(_))));
+        fs::remove_dir_all(dir).ok();
+    }
+}
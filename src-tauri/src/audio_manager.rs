@@ -1,12 +1,17 @@
-use crate::models::{SoundOverride, SoundScheme, SoundSetting};
+use crate::audio_backend::{detect_backend, PlaybackBackend, PlaybackError};
+use crate::models::{SoundAssets, SoundOverride, SoundScheme, SoundSetting};
+use crate::sound_assets::resolve_sound;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::sync::Arc;
 use std::time::SystemTime;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SoundEvent {
     StepTransition,
     RoutineCompleted,
+    CheckInRequired,
+    CountdownWarning,
+    HalfwayWarning,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -16,6 +21,66 @@ pub enum SoundPlaybackReason {
     SettingDisabled,
     PlaybackDisabled,
     PlaybackFailed,
+    /// Neither an imported sound nor the scheme has one for the event.
+    NoSound,
+}
+
+/// What to play for an event: the first of the imported sounds in `custom`
+/// that exists and decodes, else the scheme's built-in sound.
+#[derive(Debug, Clone)]
+pub struct SoundChoice {
+    pub scheme: SoundScheme,
+    pub base_dir: Option<PathBuf>,
+    pub custom: Vec<String>,
+}
+
+impl SoundChoice {
+    pub fn scheme(scheme: SoundScheme) -> Self {
+        Self {
+            scheme,
+            base_dir: None,
+            custom: Vec::new(),
+        }
+    }
+
+    /// Tries `custom`, names of sounds imported into `base_dir`, first.
+    pub fn with_custom(mut self, base_dir: PathBuf, custom: Vec<String>) -> Self {
+        self.base_dir = Some(base_dir);
+        self.custom = custom;
+        self
+    }
+}
+
+/// Imported sounds configured for `event`, most specific first: the step's,
+/// for a transition the `step_end` of the step that just ended, then the
+/// routine's.
+pub fn custom_sound_names(
+    event: SoundEvent,
+    routine: &SoundAssets,
+    step: Option<&SoundAssets>,
+    previous_step: Option<&SoundAssets>,
+) -> Vec<String> {
+    let candidates = match event {
+        SoundEvent::StepTransition => vec![
+            step.and_then(|sounds| sounds.step_start.as_ref()),
+            previous_step.and_then(|sounds| sounds.step_end.as_ref()),
+            routine.step_start.as_ref(),
+            routine.step_end.as_ref(),
+        ],
+        SoundEvent::RoutineCompleted => vec![
+            previous_step.and_then(|sounds| sounds.routine_completed.as_ref()),
+            routine.routine_completed.as_ref(),
+        ],
+        SoundEvent::CheckInRequired => vec![
+            step.and_then(|sounds| sounds.check_in_required.as_ref()),
+            routine.check_in_required.as_ref(),
+        ],
+        SoundEvent::CountdownWarning | SoundEvent::HalfwayWarning => vec![
+            step.and_then(|sounds| sounds.countdown_warning.as_ref()),
+            routine.countdown_warning.as_ref(),
+        ],
+    };
+    candidates.into_iter().flatten().cloned().collect()
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -36,33 +101,49 @@ pub struct SoundPlaybackRecord {
     pub timestamp: SystemTime,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct AudioManager {
     global_mute: bool,
     playback_mode: PlaybackMode,
+    backend: Option<Arc<dyn PlaybackBackend>>,
     log: Vec<SoundPlaybackRecord>,
     failure_notified: bool,
 }
 
+impl Default for AudioManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AudioManager {
     pub fn new() -> Self {
-        Self {
-            global_mute: false,
-            playback_mode: PlaybackMode::System,
-            log: Vec::new(),
-            failure_notified: false,
-        }
+        Self::with_playback_mode(PlaybackMode::System)
     }
 
     pub fn with_playback_mode(playback_mode: PlaybackMode) -> Self {
         Self {
             global_mute: false,
             playback_mode,
+            backend: detect_backend(),
             log: Vec::new(),
             failure_notified: false,
         }
     }
 
+    /// System playback through `backend` instead of the detected player.
+    pub fn with_backend(backend: Arc<dyn PlaybackBackend>) -> Self {
+        Self {
+            backend: Some(backend),
+            ..Self::with_playback_mode(PlaybackMode::System)
+        }
+    }
+
+    /// Name of the player sounds go through, if one was found.
+    pub fn backend_name(&self) -> Option<&str> {
+        self.backend.as_deref().map(|backend| backend.name())
+    }
+
     pub fn is_muted(&self) -> bool {
         self.global_mute
     }
@@ -104,7 +185,7 @@ impl AudioManager {
         step_id: Option<&str>,
         routine_default: SoundSetting,
         step_override: SoundOverride,
-        sound_scheme: SoundScheme,
+        choice: &SoundChoice,
         event: SoundEvent,
     ) -> SoundPlaybackRecord {
         let mut played = false;
@@ -119,17 +200,21 @@ impl AudioManager {
             SoundPlaybackReason::SettingDisabled
         } else if matches!(self.playback_mode, PlaybackMode::Disabled) {
             SoundPlaybackReason::PlaybackDisabled
-        } else {
-            let path = self.sound_path(sound_scheme, event);
-            let result = self.play_system_sound(&path);
+        } else if let Some(path) = self.sound_path(choice, event) {
+            let result = self.play_file(&path);
             sound_path = Some(path.to_string_lossy().to_string());
             match result {
                 Ok(()) => {
                     played = true;
                     SoundPlaybackReason::Played
                 }
-                Err(()) => SoundPlaybackReason::PlaybackFailed,
+                Err(err) => {
+                    eprintln!("Failed to play sound: {err}");
+                    SoundPlaybackReason::PlaybackFailed
+                }
             }
+        } else {
+            SoundPlaybackReason::NoSound
         };
 
         let record = SoundPlaybackRecord {
@@ -171,48 +256,66 @@ impl AudioManager {
         }
     }
 
-    fn sound_path(&self, scheme: SoundScheme, event: SoundEvent) -> PathBuf {
-        let (default, end) = (
-            PathBuf::from("/System/Library/Sounds/Ping.aiff"),
-            PathBuf::from("/System/Library/Sounds/Glass.aiff"),
-        );
+    /// The most specific imported sound in `choice` that resolves, else the
+    /// scheme's sound for `event`. Check-ins only have imported sounds.
+    pub fn sound_path(&self, choice: &SoundChoice, event: SoundEvent) -> Option<PathBuf> {
+        let custom = choice.base_dir.as_deref().and_then(|base_dir| {
+            choice
+                .custom
+                .iter()
+                .find_map(|name| match resolve_sound(base_dir, name) {
+                    Ok(path) => Some(path),
+                    Err(err) => {
+                        eprintln!("Skipping sound {name}: {err}");
+                        None
+                    }
+                })
+        });
+        custom.or_else(|| builtin_sound_path(&choice.scheme, event))
+    }
 
-        match scheme {
-            SoundScheme::Default => default,
-            SoundScheme::EndDifferent => match event {
-                SoundEvent::RoutineCompleted => end,
-                SoundEvent::StepTransition => default,
-            },
-        }
+    fn play_file(&self, path: &Path) -> Result<(), PlaybackError> {
+        self.backend
+            .as_deref()
+            .ok_or(PlaybackError::NoBackend)?
+            .play(path)
     }
+}
 
-    fn play_system_sound(&self, path: &Path) -> Result<(), ()> {
-        #[cfg(target_os = "macos")]
-        {
-            let child = Command::new("afplay").arg(path).spawn();
-            match child {
-                Ok(mut child) => {
-                    std::thread::spawn(move || {
-                        let _ = child.wait();
-                    });
-                    Ok(())
-                }
-                Err(_) => Err(()),
-            }
-        }
-        #[cfg(not(target_os = "macos"))]
-        {
-            let _ = path;
-            Err(())
-        }
+fn builtin_sound_path(scheme: &SoundScheme, event: SoundEvent) -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    let (default, end, warning) = (
+        PathBuf::from("/System/Library/Sounds/Ping.aiff"),
+        PathBuf::from("/System/Library/Sounds/Glass.aiff"),
+        PathBuf::from("/System/Library/Sounds/Tink.aiff"),
+    );
+    #[cfg(not(target_os = "macos"))]
+    let (default, end, warning) = (
+        PathBuf::from("/usr/share/sounds/freedesktop/stereo/message.oga"),
+        PathBuf::from("/usr/share/sounds/freedesktop/stereo/complete.oga"),
+        PathBuf::from("/usr/share/sounds/freedesktop/stereo/bell.oga"),
+    );
+
+    match (scheme, event) {
+        (_, SoundEvent::CheckInRequired) => None,
+        (_, SoundEvent::CountdownWarning | SoundEvent::HalfwayWarning) => Some(warning),
+        (SoundScheme::EndDifferent, SoundEvent::RoutineCompleted) => Some(end),
+        _ => Some(default),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{AudioManager, PlaybackMode, SoundEvent, SoundPlaybackReason, SoundPlaybackRecord};
-    use crate::models::{SoundOverride, SoundScheme, SoundSetting};
-    use std::time::SystemTime;
+    use super::{
+        custom_sound_names, AudioManager, PlaybackMode, SoundChoice, SoundEvent,
+        SoundPlaybackReason, SoundPlaybackRecord,
+    };
+    use crate::audio_backend::RecordingBackend;
+    use crate::models::{SoundAssets, SoundOverride, SoundScheme, SoundSetting};
+    use crate::sound_assets::sounds_dir;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::{SystemTime, UNIX_EPOCH};
 
     #[test]
     fn global_mute_blocks_playback() {
@@ -224,7 +327,7 @@ mod tests {
             Some("step-1"),
             SoundSetting::On,
             SoundOverride::On,
-            SoundScheme::Default,
+            &SoundChoice::scheme(SoundScheme::Default),
             SoundEvent::StepTransition,
         );
 
@@ -232,6 +335,47 @@ mod tests {
         assert_eq!(record.reason, SoundPlaybackReason::Muted);
     }
 
+    #[test]
+    fn warnings_honor_global_mute_and_are_logged() {
+        let backend = Arc::new(RecordingBackend::default());
+        let mut manager = AudioManager::with_backend(backend.clone());
+        let choice = SoundChoice::scheme(SoundScheme::Default);
+        manager.set_global_mute(true);
+
+        for event in [SoundEvent::HalfwayWarning, SoundEvent::CountdownWarning] {
+            let record = manager.play_for_event(
+                Some("routine-1"),
+                Some("step-1"),
+                SoundSetting::On,
+                SoundOverride::Inherit,
+                &choice,
+                event,
+            );
+            assert_eq!(record.reason, SoundPlaybackReason::Muted);
+        }
+        manager.set_global_mute(false);
+        let record = manager.play_for_event(
+            Some("routine-1"),
+            Some("step-1"),
+            SoundSetting::On,
+            SoundOverride::Inherit,
+            &choice,
+            SoundEvent::CountdownWarning,
+        );
+
+        assert!(record.played);
+        assert_eq!(backend.played().len(), 1);
+        let events: Vec<SoundEvent> = manager.logs().iter().map(|record| record.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                SoundEvent::HalfwayWarning,
+                SoundEvent::CountdownWarning,
+                SoundEvent::CountdownWarning,
+            ]
+        );
+    }
+
     #[test]
     fn step_override_on_wins_over_routine_default() {
         let manager = AudioManager::with_playback_mode(PlaybackMode::Disabled);
@@ -260,7 +404,7 @@ mod tests {
             Some("step-1"),
             SoundSetting::On,
             SoundOverride::Inherit,
-            SoundScheme::Default,
+            &SoundChoice::scheme(SoundScheme::Default),
             SoundEvent::StepTransition,
         );
 
@@ -278,7 +422,7 @@ mod tests {
             Some("step-1"),
             SoundSetting::Off,
             SoundOverride::Inherit,
-            SoundScheme::Default,
+            &SoundChoice::scheme(SoundScheme::Default),
             SoundEvent::StepTransition,
         );
 
@@ -287,6 +431,152 @@ mod tests {
         assert_eq!(manager.logs().len(), 1);
     }
 
+    #[test]
+    fn plays_through_backend() {
+        let backend = Arc::new(RecordingBackend::default());
+        let mut manager = AudioManager::with_backend(backend.clone());
+
+        let record = manager.play_for_event(
+            Some("routine-1"),
+            Some("step-1"),
+            SoundSetting::On,
+            SoundOverride::Inherit,
+            &SoundChoice::scheme(SoundScheme::EndDifferent),
+            SoundEvent::RoutineCompleted,
+        );
+
+        assert!(record.played);
+        assert_eq!(record.reason, SoundPlaybackReason::Played);
+        assert_eq!(manager.backend_name(), Some("recording"));
+        let played = backend.played();
+        assert_eq!(played.len(), 1);
+        assert_eq!(
+            record.sound_path.as_deref(),
+            Some(played[0].to_string_lossy().as_ref())
+        );
+    }
+
+    #[test]
+    fn backend_errors_are_playback_failures() {
+        let mut manager = AudioManager::with_backend(Arc::new(RecordingBackend::failing()));
+
+        let record = manager.play_for_event(
+            None,
+            None,
+            SoundSetting::On,
+            SoundOverride::Inherit,
+            &SoundChoice::scheme(SoundScheme::Default),
+            SoundEvent::StepTransition,
+        );
+
+        assert!(!record.played);
+        assert_eq!(record.reason, SoundPlaybackReason::PlaybackFailed);
+        assert!(manager.should_notify_failure(&record));
+    }
+
+    #[test]
+    fn prefers_the_most_specific_custom_sound() {
+        let routine = SoundAssets {
+            step_start: Some("routine-start.wav".to_string()),
+            check_in_required: Some("routine-check.wav".to_string()),
+            ..SoundAssets::default()
+        };
+        let step = SoundAssets {
+            check_in_required: Some("step-check.wav".to_string()),
+            ..SoundAssets::default()
+        };
+        let previous = SoundAssets {
+            step_end: Some("previous-end.wav".to_string()),
+            ..SoundAssets::default()
+        };
+
+        assert_eq!(
+            custom_sound_names(
+                SoundEvent::StepTransition,
+                &routine,
+                Some(&step),
+                Some(&previous)
+            ),
+            vec!["previous-end.wav", "routine-start.wav"]
+        );
+        assert_eq!(
+            custom_sound_names(SoundEvent::CheckInRequired, &routine, Some(&step), None),
+            vec!["step-check.wav", "routine-check.wav"]
+        );
+        assert!(custom_sound_names(SoundEvent::RoutineCompleted, &routine, None, None).is_empty());
+        let warning = SoundAssets {
+            countdown_warning: Some("tick.wav".to_string()),
+            ..SoundAssets::default()
+        };
+        assert_eq!(
+            custom_sound_names(SoundEvent::HalfwayWarning, &warning, Some(&step), None),
+            vec!["tick.wav"]
+        );
+    }
+
+    #[test]
+    fn resolves_imported_sounds_before_the_scheme() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let base = std::env::temp_dir().join(format!(
+            "mccall_audio_manager_test_{nanos}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(sounds_dir(&base)).expect("create sounds dir");
+        // Two samples of 16-bit mono PCM silence.
+        let gong: &[u8] = b"RIFF\x28\x00\x00\x00WAVEfmt \x10\x00\x00\x00\x01\x00\x01\x00\
+            \x40\x1f\x00\x00\x80\x3e\x00\x00\x02\x00\x10\x00data\x04\x00\x00\x00\x00\x00\x00\x00";
+        fs::write(sounds_dir(&base).join("gong.wav"), gong).expect("write sound");
+        fs::write(sounds_dir(&base).join("broken.wav"), b"not audio").expect("write broken");
+        let backend = Arc::new(RecordingBackend::default());
+        let mut manager = AudioManager::with_backend(backend.clone());
+
+        let choice = SoundChoice::scheme(SoundScheme::Default).with_custom(
+            base.clone(),
+            vec![
+                "missing.wav".to_string(),
+                "broken.wav".to_string(),
+                "gong.wav".to_string(),
+            ],
+        );
+        assert_eq!(
+            manager.sound_path(&choice, SoundEvent::StepTransition),
+            Some(sounds_dir(&base).join("gong.wav"))
+        );
+        let fallback = SoundChoice::scheme(SoundScheme::Default)
+            .with_custom(base.clone(), vec!["missing.wav".to_string()]);
+        assert_eq!(
+            manager.sound_path(&fallback, SoundEvent::StepTransition),
+            manager.sound_path(
+                &SoundChoice::scheme(SoundScheme::Default),
+                SoundEvent::StepTransition
+            )
+        );
+
+        let record = manager.play_for_event(
+            None,
+            None,
+            SoundSetting::On,
+            SoundOverride::Inherit,
+            &fallback,
+            SoundEvent::CheckInRequired,
+        );
+        assert_eq!(record.reason, SoundPlaybackReason::NoSound);
+        let record = manager.play_for_event(
+            None,
+            None,
+            SoundSetting::On,
+            SoundOverride::Inherit,
+            &choice,
+            SoundEvent::CheckInRequired,
+        );
+        assert!(record.played);
+        assert_eq!(backend.played(), vec![sounds_dir(&base).join("gong.wav")]);
+        fs::remove_dir_all(base).ok();
+    }
+
     #[test]
     fn notify_failure_only_once_until_played() {
         let mut manager = AudioManager::with_playback_mode(PlaybackMode::Disabled);
@@ -0,0 +1,324 @@
+//! Day and week boundaries in the user's local zone.
+//!
+//! Timestamps stay UTC on disk; this module answers "which local day or week
+//! contains this instant" using the configured week start and day-rollover
+//! hour, and splits ranges into consecutive local periods for charts.
+//! Boundaries are resolved through the zone itself, so days around a DST
+//! change are 23 or 25 hours long.
+
+use crate::models::{CalendarSettings, DayOfWeek, SessionZone, WeekStart};
+use chrono::{
+    DateTime, Datelike, Days, FixedOffset, Local, LocalResult, Months, NaiveDate, NaiveDateTime,
+    NaiveTime, TimeDelta, TimeZone, Timelike, Utc, Weekday,
+};
+use serde::{Deserialize, Serialize};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const HOUR_FORMAT: &str = "%Y-%m-%dT%H:%M";
+const TIME_OF_DAY_FORMAT: &str = "%H:%M";
+/// Upper bound on periods in one range, e.g. about a year of hours.
+pub const MAX_PERIODS: usize = 10_000;
+
+#[derive(Debug)]
+pub enum CalendarError {
+    InvalidInstant(String),
+    InvalidRolloverHour(u8),
+    InvalidTimeOfDay(String),
+    TooManyPeriods,
+}
+
+impl std::fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CalendarError::InvalidInstant(value) => write!(f, "Invalid timestamp: {value}"),
+            CalendarError::InvalidRolloverHour(hour) => {
+                write!(f, "Day rollover hour must be 0-23, got {hour}")
+            }
+            CalendarError::InvalidTimeOfDay(value) => write!(f, "Invalid time of day: {value}"),
+            CalendarError::TooManyPeriods => {
+                write!(f, "Range spans more than {MAX_PERIODS} periods")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CalendarError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PeriodKind {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+/// A local hour, day, week or month. `from` and `to` are inclusive UTC bounds
+/// in the form `get_session_stats` accepts; `start_date` is the local date it
+/// begins on, with the hour for hourly periods.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Period {
+    pub kind: PeriodKind,
+    pub start_date: String,
+    pub from: String,
+    pub to: String,
+}
+
+pub fn validate_calendar_settings(settings: &CalendarSettings) -> Result<(), CalendarError> {
+    if settings.day_rollover_hour > 23 {
+        return Err(CalendarError::InvalidRolloverHour(
+            settings.day_rollover_hour,
+        ));
+    }
+    Ok(())
+}
+
+pub fn parse_instant(value: &str) -> Result<DateTime<Utc>, CalendarError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|value| value.with_timezone(&Utc))
+        .map_err(|_| CalendarError::InvalidInstant(value.to_string()))
+}
+
+/// Parses an "HH:MM" wall-clock time.
+pub fn parse_time_of_day(value: &str) -> Result<NaiveTime, CalendarError> {
+    NaiveTime::parse_from_str(value, TIME_OF_DAY_FORMAT)
+        .map_err(|_| CalendarError::InvalidTimeOfDay(value.to_string()))
+}
+
+pub fn day_of_week(date: NaiveDate) -> DayOfWeek {
+    match date.weekday() {
+        Weekday::Mon => DayOfWeek::Monday,
+        Weekday::Tue => DayOfWeek::Tuesday,
+        Weekday::Wed => DayOfWeek::Wednesday,
+        Weekday::Thu => DayOfWeek::Thursday,
+        Weekday::Fri => DayOfWeek::Friday,
+        Weekday::Sat => DayOfWeek::Saturday,
+        Weekday::Sun => DayOfWeek::Sunday,
+    }
+}
+
+/// Position of `day` in the week as configured, 0 for the first day.
+pub fn week_position(day: DayOfWeek, settings: &CalendarSettings) -> u32 {
+    let first = first_weekday(settings.week_start).num_days_from_monday();
+    (day as u32 + 7 - first) % 7
+}
+
+/// The system zone right now, recorded on each session at start.
+pub fn current_zone() -> SessionZone {
+    SessionZone {
+        utc_offset_minutes: Local::now().offset().local_minus_utc() / 60,
+        time_zone: iana_time_zone::get_timezone().ok(),
+    }
+}
+
+/// The UTC offset `zone` had when it was recorded, or `None` when the stored
+/// offset is out of range.
+pub fn recorded_offset(zone: &SessionZone) -> Option<FixedOffset> {
+    FixedOffset::east_opt(zone.utc_offset_minutes.checked_mul(60)?)
+}
+
+pub fn local_period_containing(
+    kind: PeriodKind,
+    instant: DateTime<Utc>,
+    settings: &CalendarSettings,
+) -> Period {
+    period_containing(kind, instant, &Local, settings)
+}
+
+pub fn period_containing<Tz: TimeZone>(
+    kind: PeriodKind,
+    instant: DateTime<Utc>,
+    zone: &Tz,
+    settings: &CalendarSettings,
+) -> Period {
+    let local = instant.with_timezone(zone).naive_local();
+    let rollover = rollover(settings);
+    let day = day_containing(instant, zone, settings);
+    let (start, end, label) = match kind {
+        PeriodKind::Hour => {
+            let start = day_time(local.date(), TimeDelta::hours(i64::from(local.hour())));
+            (
+                start,
+                start + TimeDelta::hours(1),
+                start.format(HOUR_FORMAT),
+            )
+        }
+        PeriodKind::Day | PeriodKind::Week | PeriodKind::Month => {
+            let (start_date, end_date) = match kind {
+                PeriodKind::Week => {
+                    let offset = day.weekday().days_since(first_weekday(settings.week_start));
+                    let start_date = day - Days::new(u64::from(offset));
+                    (start_date, start_date + Days::new(7))
+                }
+                PeriodKind::Month => {
+                    let start_date = day.with_day(1).unwrap_or(day);
+                    (start_date, start_date + Months::new(1))
+                }
+                _ => (day, day + Days::new(1)),
+            };
+            (
+                day_time(start_date, rollover),
+                day_time(end_date, rollover),
+                start_date.format(DATE_FORMAT),
+            )
+        }
+    };
+    let from = local_start(zone, start);
+    let to = local_start(zone, end) - TimeDelta::milliseconds(1);
+    Period {
+        kind,
+        start_date: label.to_string(),
+        from: from.to_rfc3339(),
+        to: to.to_rfc3339(),
+    }
+}
+
+pub fn local_periods_between(
+    kind: PeriodKind,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    settings: &CalendarSettings,
+) -> Result<Vec<Period>, CalendarError> {
+    periods_between(kind, from, to, &Local, settings)
+}
+
+/// Local date `instant` counts towards once the rollover hour is applied.
+pub fn day_containing<Tz: TimeZone>(
+    instant: DateTime<Utc>,
+    zone: &Tz,
+    settings: &CalendarSettings,
+) -> NaiveDate {
+    (instant.with_timezone(zone).naive_local() - rollover(settings)).date()
+}
+
+/// Consecutive periods of `kind` covering `from..=to`, in order.
+pub fn periods_between<Tz: TimeZone>(
+    kind: PeriodKind,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    zone: &Tz,
+    settings: &CalendarSettings,
+) -> Result<Vec<Period>, CalendarError> {
+    let mut periods = Vec::new();
+    let mut cursor = from;
+    while cursor <= to {
+        if periods.len() == MAX_PERIODS {
+            return Err(CalendarError::TooManyPeriods);
+        }
+        let period = period_containing(kind, cursor, zone, settings);
+        cursor = parse_instant(&period.to)? + TimeDelta::milliseconds(1);
+        periods.push(period);
+    }
+    Ok(periods)
+}
+
+fn rollover(settings: &CalendarSettings) -> TimeDelta {
+    TimeDelta::hours(i64::from(settings.day_rollover_hour.min(23)))
+}
+
+fn first_weekday(week_start: WeekStart) -> Weekday {
+    match week_start {
+        WeekStart::Monday => Weekday::Mon,
+        WeekStart::Sunday => Weekday::Sun,
+        WeekStart::Saturday => Weekday::Sat,
+    }
+}
+
+fn day_time(date: NaiveDate, offset: TimeDelta) -> NaiveDateTime {
+    date.and_time(NaiveTime::MIN) + offset
+}
+
+/// First instant of the local time `local` in `zone`. A time that falls into
+/// a DST gap moves to the end of the gap; one that occurs twice uses the
+/// earlier occurrence.
+fn local_start<Tz: TimeZone>(zone: &Tz, mut local: NaiveDateTime) -> DateTime<Utc> {
+    for _ in 0..4 {
+        match zone.from_local_datetime(&local) {
+            LocalResult::Single(value) | LocalResult::Ambiguous(value, _) => {
+                return value.with_timezone(&Utc)
+            }
+            LocalResult::None => local += TimeDelta::minutes(30),
+        }
+    }
+    Utc.from_utc_datetime(&local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_instant, period_containing, periods_between, Period, PeriodKind};
+    use crate::models::{CalendarSettings, WeekStart};
+    use chrono::FixedOffset;
+
+    fn tokyo() -> FixedOffset {
+        FixedOffset::east_opt(9 * 3600).expect("offset")
+    }
+
+    fn settings(week_start: WeekStart, day_rollover_hour: u8) -> CalendarSettings {
+        CalendarSettings {
+            week_start,
+            day_rollover_hour,
+        }
+    }
+
+    fn period(kind: PeriodKind, at: &str, settings: &CalendarSettings) -> Period {
+        period_containing(kind, parse_instant(at).expect("parse"), &tokyo(), settings)
+    }
+
+    #[test]
+    fn day_follows_local_zone_and_rollover_hour() {
+        // 2025-03-09 01:30 in Tokyo.
+        let at = "2025-03-08T16:30:00Z";
+
+        let midnight = period(PeriodKind::Day, at, &settings(WeekStart::Monday, 0));
+        assert_eq!(midnight.start_date, "2025-03-09");
+        assert_eq!(midnight.from, "2025-03-08T15:00:00+00:00");
+        assert_eq!(midnight.to, "2025-03-09T14:59:59.999+00:00");
+
+        let late_night = period(PeriodKind::Day, at, &settings(WeekStart::Monday, 4));
+        assert_eq!(late_night.start_date, "2025-03-08");
+        assert_eq!(late_night.from, "2025-03-07T19:00:00+00:00");
+        assert_eq!(late_night.to, "2025-03-08T18:59:59.999+00:00");
+    }
+
+    #[test]
+    fn week_starts_on_configured_day() {
+        // Sunday 2025-03-09 10:00 in Tokyo.
+        let at = "2025-03-09T01:00:00Z";
+
+        let monday = period(PeriodKind::Week, at, &settings(WeekStart::Monday, 0));
+        assert_eq!(monday.start_date, "2025-03-03");
+        assert_eq!(monday.to, "2025-03-09T14:59:59.999+00:00");
+
+        let sunday = period(PeriodKind::Week, at, &settings(WeekStart::Sunday, 0));
+        assert_eq!(sunday.start_date, "2025-03-09");
+
+        let saturday = period(PeriodKind::Week, at, &settings(WeekStart::Saturday, 4));
+        assert_eq!(saturday.start_date, "2025-03-08");
+        assert_eq!(saturday.from, "2025-03-07T19:00:00+00:00");
+    }
+
+    #[test]
+    fn splits_range_into_hours_and_months() {
+        let monday = settings(WeekStart::Monday, 4);
+        let month = period(PeriodKind::Month, "2025-03-31T20:00:00Z", &monday);
+        assert_eq!(month.start_date, "2025-04-01");
+        assert_eq!(month.from, "2025-03-31T19:00:00+00:00");
+        assert_eq!(month.to, "2025-04-30T18:59:59.999+00:00");
+
+        let hours = periods_between(
+            PeriodKind::Hour,
+            parse_instant("2025-03-08T16:30:00Z").expect("from"),
+            parse_instant("2025-03-08T18:00:00Z").expect("to"),
+            &tokyo(),
+            &monday,
+        )
+        .expect("hours");
+        let starts: Vec<&str> = hours.iter().map(|item| item.start_date.as_str()).collect();
+        assert_eq!(
+            starts,
+            ["2025-03-09T01:00", "2025-03-09T02:00", "2025-03-09T03:00"]
+        );
+        assert_eq!(hours[0].from, "2025-03-08T16:00:00+00:00");
+    }
+}
@@ -0,0 +1,309 @@
+use crate::calendar::{parse_instant, Period};
+use crate::models::{
+    CheckInAnalytics, CheckInMode, CheckInResult, ModeResponseTimes, ResponseTimeSummary, Routine,
+    Session, StepResponseTimes, StepRun, WaitBucket, WeeklyResponseTimes,
+};
+use crate::session_stats::{recorded_labels, run_label, step_position};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::HashMap;
+
+/// Upper bounds of the gate wait buckets; the last bucket is open-ended.
+const GATE_WAIT_BOUNDS_MS: [u64; 6] = [1_000, 3_000, 10_000, 30_000, 60_000, 300_000];
+
+/// Response times of the check-ins in `sessions`, per step and mode, per mode,
+/// as a histogram of gate waits, and per period in `weeks`. A check-in counts
+/// towards the period it was answered in, or the one its step ended in when it
+/// timed out.
+pub fn calculate_check_in_analytics(
+    sessions: &[Session],
+    routines: &[Routine],
+    weeks: &[Period],
+) -> CheckInAnalytics {
+    let ranges: Vec<Option<(DateTime<Utc>, DateTime<Utc>)>> = weeks
+        .iter()
+        .map(|period| {
+            let from = parse_instant(&period.from).ok()?;
+            let to = parse_instant(&period.to).ok()?;
+            Some((from, to + TimeDelta::milliseconds(1)))
+        })
+        .collect();
+
+    let mut by_step: HashMap<(&str, &str, CheckInMode), Samples> = HashMap::new();
+    let mut by_mode: HashMap<CheckInMode, Samples> = HashMap::new();
+    let mut weekly: Vec<Samples> = weeks.iter().map(|_| Samples::default()).collect();
+    let mut gate_waits = vec![0u32; GATE_WAIT_BOUNDS_MS.len() + 1];
+    for session in sessions.iter().filter(|item| !item.excluded_from_stats) {
+        for run in &session.step_runs {
+            let Some(check_in) = run.check_in_result.as_ref() else {
+                continue;
+            };
+            by_step
+                .entry((
+                    session.routine_id.as_str(),
+                    run.step_id.as_str(),
+                    check_in.mode,
+                ))
+                .or_default()
+                .add(check_in);
+            by_mode.entry(check_in.mode).or_default().add(check_in);
+            if let Some(instant) = answered_at(run, check_in) {
+                let week = ranges.iter().position(|range| {
+                    range.is_some_and(|(from, to)| from <= instant && instant < to)
+                });
+                if let Some(index) = week {
+                    weekly[index].add(check_in);
+                }
+            }
+            if let (CheckInMode::Gate, Some(wait)) = (check_in.mode, check_in.response_time_ms) {
+                let index = GATE_WAIT_BOUNDS_MS.partition_point(|&bound| bound <= wait);
+                gate_waits[index] += 1;
+            }
+        }
+    }
+
+    let mut step_keys: Vec<(&str, &str, CheckInMode)> = by_step.keys().copied().collect();
+    step_keys.sort_by_key(|&(routine_id, step_id, mode)| {
+        let position = step_position(routines, routine_id, step_id);
+        (
+            position.is_none(),
+            position,
+            routine_id,
+            step_id,
+            mode == CheckInMode::Gate,
+        )
+    });
+    let labels = recorded_labels(sessions);
+    let by_step = step_keys
+        .into_iter()
+        .map(|key| {
+            let (routine_id, step_id, mode) = key;
+            StepResponseTimes {
+                routine_id: routine_id.to_string(),
+                step_id: step_id.to_string(),
+                label: run_label(&labels, routines, routine_id, step_id),
+                mode,
+                summary: by_step.remove(&key).unwrap_or_default().summary(),
+            }
+        })
+        .collect();
+
+    let by_mode = [CheckInMode::Prompt, CheckInMode::Gate]
+        .into_iter()
+        .filter_map(|mode| {
+            Some(ModeResponseTimes {
+                mode,
+                summary: by_mode.remove(&mode)?.summary(),
+            })
+        })
+        .collect();
+
+    let gate_waits = gate_waits
+        .into_iter()
+        .enumerate()
+        .map(|(index, count)| WaitBucket {
+            min_ms: index
+                .checked_sub(1)
+                .map(|previous| GATE_WAIT_BOUNDS_MS[previous])
+                .unwrap_or(0),
+            max_ms: GATE_WAIT_BOUNDS_MS.get(index).copied(),
+            count,
+        })
+        .collect();
+
+    let weekly = weeks
+        .iter()
+        .zip(weekly)
+        .map(|(period, samples)| WeeklyResponseTimes {
+            start_date: period.start_date.clone(),
+            from: period.from.clone(),
+            to: period.to.clone(),
+            summary: samples.summary(),
+        })
+        .collect();
+
+    CheckInAnalytics {
+        by_step,
+        by_mode,
+        gate_waits,
+        weekly,
+    }
+}
+
+fn answered_at(run: &StepRun, check_in: &CheckInResult) -> Option<DateTime<Utc>> {
+    [
+        check_in.responded_at.as_deref(),
+        run.ended_at.as_deref(),
+        Some(run.started_at.as_str()),
+    ]
+    .into_iter()
+    .flatten()
+    .find_map(|value| parse_instant(value).ok())
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    times: Vec<u64>,
+    timed_out: u32,
+}
+
+impl Samples {
+    fn add(&mut self, check_in: &CheckInResult) {
+        if check_in.timed_out {
+            self.timed_out += 1;
+        }
+        self.times.extend(check_in.response_time_ms);
+    }
+
+    fn summary(mut self) -> ResponseTimeSummary {
+        self.times.sort_unstable();
+        ResponseTimeSummary {
+            responses: self.times.len() as u32,
+            timed_out_count: self.timed_out,
+            p50_ms: percentile(&self.times, 0.5),
+            p90_ms: percentile(&self.times, 0.9),
+        }
+    }
+}
+
+/// Linearly interpolated percentile of sorted `values`.
+fn percentile(values: &[u64], fraction: f64) -> Option<u64> {
+    let last = values.len().checked_sub(1)?;
+    let rank = fraction * last as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f64;
+    let value = values[lower] as f64 + (values[upper] as f64 - values[lower] as f64) * weight;
+    Some(value.round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{calculate_check_in_analytics, percentile};
+    use crate::calendar::{parse_instant, periods_between, PeriodKind};
+    use crate::models::{
+        CalendarSettings, CheckInChoice, CheckInMode, CheckInResult, Session, SessionTotals,
+        StepRun, StepRunResult,
+    };
+    use chrono::Utc;
+
+    fn run(
+        step_id: &str,
+        mode: CheckInMode,
+        responded_at: &str,
+        response_time_ms: Option<u64>,
+    ) -> StepRun {
+        StepRun {
+            step_id: step_id.to_string(),
+            label: None,
+            planned_duration_seconds: 60,
+            actual_duration_seconds: 60,
+            started_at: responded_at.to_string(),
+            ended_at: Some(responded_at.to_string()),
+            result: StepRunResult::Completed,
+            check_in_result: Some(CheckInResult {
+                mode,
+                responded_at: response_time_ms.map(|_| responded_at.to_string()),
+                choice: response_time_ms.map(|_| CheckInChoice::Done),
+                response_time_ms,
+                timed_out: response_time_ms.is_none(),
+            }),
+            sound_played: false,
+        }
+    }
+
+    fn session(step_runs: Vec<StepRun>) -> Session {
+        Session {
+            id: "session-1".to_string(),
+            routine_id: "routine-1".to_string(),
+            started_at: "2025-01-06T09:00:00Z".to_string(),
+            ended_at: None,
+            step_runs,
+            totals: SessionTotals::default(),
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn interpolates_percentiles() {
+        assert_eq!(percentile(&[], 0.5), None);
+        assert_eq!(percentile(&[400], 0.9), Some(400));
+        assert_eq!(percentile(&[100, 200, 300, 400], 0.5), Some(250));
+        assert_eq!(percentile(&[100, 200, 300, 400], 0.9), Some(370));
+    }
+
+    #[test]
+    fn summarizes_response_times() {
+        let sessions = vec![session(vec![
+            run(
+                "step-1",
+                CheckInMode::Gate,
+                "2025-01-06T09:10:00Z",
+                Some(500),
+            ),
+            run(
+                "step-1",
+                CheckInMode::Gate,
+                "2025-01-07T09:10:00Z",
+                Some(45_000),
+            ),
+            run(
+                "step-1",
+                CheckInMode::Gate,
+                "2025-01-14T09:10:00Z",
+                Some(400_000),
+            ),
+            run(
+                "step-2",
+                CheckInMode::Prompt,
+                "2025-01-14T09:20:00Z",
+                Some(2_000),
+            ),
+            run("step-2", CheckInMode::Prompt, "2025-01-14T09:30:00Z", None),
+        ])];
+        let weeks = periods_between(
+            PeriodKind::Week,
+            parse_instant("2025-01-06T00:00:00Z").expect("from"),
+            parse_instant("2025-01-19T23:59:59Z").expect("to"),
+            &Utc,
+            &CalendarSettings::default(),
+        )
+        .expect("weeks");
+
+        let analytics = calculate_check_in_analytics(&sessions, &[], &weeks);
+
+        assert_eq!(analytics.by_step.len(), 2);
+        let gate = &analytics.by_step[0].summary;
+        assert_eq!((gate.responses, gate.timed_out_count), (3, 0));
+        assert_eq!(gate.p50_ms, Some(45_000));
+        let prompt = &analytics.by_step[1].summary;
+        assert_eq!((prompt.responses, prompt.timed_out_count), (1, 1));
+        assert_eq!(
+            analytics
+                .by_mode
+                .iter()
+                .map(|item| item.mode)
+                .collect::<Vec<_>>(),
+            vec![CheckInMode::Prompt, CheckInMode::Gate]
+        );
+
+        let counts: Vec<u32> = analytics
+            .gate_waits
+            .iter()
+            .map(|bucket| bucket.count)
+            .collect();
+        assert_eq!(counts, vec![1, 0, 0, 0, 1, 0, 1]);
+        assert_eq!(analytics.gate_waits[6].min_ms, 300_000);
+        assert_eq!(analytics.gate_waits[6].max_ms, None);
+
+        assert_eq!(analytics.weekly.len(), 2);
+        assert_eq!(analytics.weekly[0].summary.responses, 2);
+        assert_eq!(analytics.weekly[1].summary.responses, 2);
+        assert_eq!(analytics.weekly[1].summary.timed_out_count, 1);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::data_crypto;
+use crate::data_integrity::{verify_data, IssueSeverity};
+use crate::data_manager::{DataDirLock, DataManager};
+use crate::profiles::{ProfileError, ProfileStore};
+use std::env;
+use std::path::PathBuf;
+
+const VERIFY_DATA_COMMAND: &str = "verify-data";
+const USAGE: &str = "Usage: mccall verify-data [--repair] [--data-dir <path>] [--profile <id>]";
+
+/// Runs a command-line subcommand when one is given and returns its exit code.
+/// Returns `None` so the caller starts the desktop app for any other arguments.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some(VERIFY_DATA_COMMAND) => Some(run_verify_data(&args[1..])),
+        _ => None,
+    }
+}
+
+/// The directory the desktop app opens by default: Tauri's `app_data_dir`,
+/// the platform data directory joined with the bundle identifier.
+fn app_data_dir() -> Option<PathBuf> {
+    let config: serde_json::Value =
+        serde_json::from_str(include_str!("../tauri.conf.json")).ok()?;
+    let identifier = config.get("identifier")?.as_str()?;
+    Some(dirs::data_dir()?.join(identifier))
+}
+
+fn run_verify_data(args: &[String]) -> i32 {
+    let mut repair = false;
+    let mut data_dir = env::var("MCCALL_DATA_DIR").ok().map(PathBuf::from);
+    let mut profile_id = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--repair" => repair = true,
+            "--data-dir" => match iter.next() {
+                Some(path) => data_dir = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("{USAGE}");
+                    return 2;
+                }
+            },
+            "--profile" => match iter.next() {
+                Some(id) => profile_id = Some(id.clone()),
+                None => {
+                    eprintln!("{USAGE}");
+                    return 2;
+                }
+            },
+            _ => {
+                eprintln!("{USAGE}");
+                return 2;
+            }
+        }
+    }
+
+    let Some(data_dir) = data_dir.or_else(|| app_data_dir().filter(|dir| dir.is_dir())) else {
+        eprintln!("No data directory found. Set MCCALL_DATA_DIR or pass --data-dir.\n{USAGE}");
+        return 2;
+    };
+    let _lock = match DataDirLock::acquire(&data_dir) {
+        Ok(lock) => lock,
+        Err(err) => {
+            eprintln!("Failed to open data directory: {err}");
+            return 1;
+        }
+    };
+    let profile_dir = match ProfileStore::open(&data_dir)
+        .map_err(ProfileError::from)
+        .and_then(|store| store.resolve_dir(profile_id.as_deref()))
+    {
+        Ok(dir) => dir,
+        Err(err) => {
+            eprintln!("Failed to open profile: {err}");
+            return 1;
+        }
+    };
+    let data_manager = match data_crypto::startup_key_source(&profile_dir)
+        .and_then(|key_source| DataManager::open(profile_dir, key_source.as_ref()))
+    {
+        Ok(manager) => manager,
+        Err(err) => {
+            eprintln!("Failed to open data directory: {err}");
+            return 1;
+        }
+    };
+    let report = match verify_data(&data_manager, repair) {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Failed to verify data: {err}");
+            return 1;
+        }
+    };
+
+    for issue in &report.issues {
+        let severity = match issue.severity {
+            IssueSeverity::Info => "info",
+            IssueSeverity::Warning => "warning",
+            IssueSeverity::Error => "error",
+        };
+        let status = if issue.repaired {
+            " (repaired)"
+        } else if issue.repairable {
+            " (repairable)"
+        } else {
+            ""
+        };
+        let record = issue
+            .record_id
+            .as_deref()
+            .map(|id| format!(" {id}"))
+            .unwrap_or_default();
+        println!(
+            "[{severity}] {}{record}: {} [{}]{status}",
+            issue.file, issue.message, issue.code
+        );
+    }
+    println!(
+        "{} issue(s), {} repaired",
+        report.issues.len(),
+        report.repaired_count
+    );
+
+    if report.has_unrepaired(IssueSeverity::Error) {
+        1
+    } else {
+        0
+    }
+}
@@ -1,15 +1,44 @@
+use crate::abort_stats::calculate_abort_analytics;
 use crate::app_error::{AppError, AppErrorKind};
 use crate::audio_manager::AudioManager;
-use crate::data_manager::DataManager;
+use crate::calendar::{
+    local_period_containing, local_periods_between, parse_instant, validate_calendar_settings,
+    Period, PeriodKind,
+};
+use crate::check_in_stats::calculate_check_in_analytics;
+use crate::data_crypto::{self, KeySource, UnlockState};
+use crate::data_integrity::{self, IntegrityReport};
+use crate::data_manager::{BackupInfo, DataError, DataManager, EncryptionStatus};
 use crate::events::emit_app_error;
+use crate::folder_sync::{self, SyncConflict, SyncError, SyncReport};
+use crate::goal_actions::{self, GoalState};
+use crate::goals::{prepare_goals, GoalProgress};
+use crate::history_retention::{self, PruneReport};
+use crate::ids::{new_id, IdKind};
 use crate::menu_bar;
-use crate::models::{AppSettings, CheckInResponse, Routine, SessionStats, TimerState};
+use crate::models::{
+    AbortAnalytics, AppSettings, CheckInAnalytics, CheckInResponse, Goal, Routine, Session,
+    SessionStats, StatsBucket, StatsFilter, StatsGroup, StatsGroupBy, StepRunResult, StepStats,
+    TimerState,
+};
+use crate::profiles::{ProfileError, ProfileInfo, ProfileList, ProfileStats, ProfileStore};
+use crate::routine_file::{self, ImportConflictStrategy};
+use crate::routine_validator::validate_routine;
 use crate::runtime_state::RuntimeState;
+use crate::session_editor;
+use crate::session_export::{self, ExportFormat};
 use crate::session_recovery;
-use crate::session_stats::calculate_session_stats;
+use crate::session_stats::{
+    calculate_grouped_stats, calculate_session_stats, calculate_stats_series, calculate_step_stats,
+};
 use crate::session_tracker::SessionTracker;
+use crate::sound_assets::{self, SoundAsset};
 use crate::timer_actions;
 use crate::timer_engine::TimerEngine;
+use crate::weekly_report::{load_weekly_report, render_report, write_report, ReportFormat};
+use crate::{start_app, LockedData};
+use chrono::{Local, Utc};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager, State};
 
@@ -124,12 +153,19 @@ pub async fn save_routine(
     data_manager: State<'_, DataManager>,
     app: AppHandle,
 ) -> Result<(), String> {
+    validate_routine(&routine).map_err(|err| report_error(&app, AppError::from(err)))?;
     data_manager
         .save_routine(routine)
         .map_err(|err| report_error(&app, AppError::from(err)))?;
     Ok(())
 }
 
+/// Hands the editor ids for routines and steps it is about to create.
+#[tauri::command]
+pub async fn new_ids(kind: IdKind, count: usize) -> Vec<String> {
+    (0..count).map(|_| new_id(kind)).collect()
+}
+
 #[tauri::command]
 pub async fn load_routines(
     data_manager: State<'_, DataManager>,
@@ -140,6 +176,61 @@ pub async fn load_routines(
         .map_err(|err| report_error(&app, AppError::from(err)))
 }
 
+#[tauri::command]
+pub async fn export_routine(
+    routine_id: String,
+    path: PathBuf,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<(), String> {
+    routine_file::export_routine(&data_manager, &routine_id, &path)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn import_routine(
+    path: PathBuf,
+    strategy: Option<ImportConflictStrategy>,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Routine, String> {
+    routine_file::import_routine(&data_manager, &path, strategy.unwrap_or_default())
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+/// Copies a sound file into the data directory so routines and steps can
+/// refer to it by the returned name.
+#[tauri::command]
+pub async fn import_sound(
+    path: PathBuf,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<SoundAsset, String> {
+    sound_assets::import_sound(data_manager.base_dir(), &path)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn list_sounds(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<SoundAsset>, String> {
+    sound_assets::list_sounds(data_manager.base_dir())
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+/// Removes an imported sound. Routines still naming it fall back to their
+/// next configured sound.
+#[tauri::command]
+pub async fn delete_sound(
+    name: String,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<(), String> {
+    sound_assets::delete_sound(data_manager.base_dir(), &name)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
 #[tauri::command]
 pub async fn load_settings(
     data_manager: State<'_, DataManager>,
@@ -156,12 +247,149 @@ pub async fn save_settings(
     data_manager: State<'_, DataManager>,
     app: AppHandle,
 ) -> Result<(), String> {
+    validate_calendar_settings(&settings.calendar)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
     data_manager
         .save_settings(&settings)
         .map_err(|err| report_error(&app, AppError::from(err)))?;
     Ok(())
 }
 
+#[tauri::command]
+pub async fn list_backups(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<BackupInfo>, String> {
+    data_manager
+        .list_backups()
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn restore_backup(
+    file_name: String,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<BackupInfo, String> {
+    data_manager
+        .restore_backup(&file_name)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn get_unlock_state(
+    locked_data: State<'_, Mutex<Option<LockedData>>>,
+    app: AppHandle,
+) -> Result<UnlockState, String> {
+    let locked = locked_data
+        .lock()
+        .map_err(|_| report_error(&app, AppError::system("起動状態の取得に失敗しました")))?;
+    let key_source = match locked.as_ref() {
+        Some(locked) => data_crypto::configured_key_source(&locked.profile_dir)
+            .map_err(|err| report_error(&app, AppError::from(err)))?,
+        None => None,
+    };
+    Ok(UnlockState {
+        locked: locked.is_some(),
+        key_source,
+    })
+}
+
+/// Opens the data directory startup could not unlock on its own, then
+/// finishes starting the app.
+#[tauri::command]
+pub async fn unlock_data(
+    key_source: KeySource,
+    locked_data: State<'_, Mutex<Option<LockedData>>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let mut locked = locked_data
+        .lock()
+        .map_err(|_| report_error(&app, AppError::system("起動状態の取得に失敗しました")))?;
+    let Some(profile_dir) = locked.as_ref().map(|locked| locked.profile_dir.clone()) else {
+        return Ok(());
+    };
+    let data_manager = DataManager::open(profile_dir, Some(&key_source))
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let Some(locked) = locked.take() else {
+        return Ok(());
+    };
+    start_app(
+        &app,
+        locked.data_lock,
+        data_manager,
+        locked.profile_store,
+        &locked.profile_name,
+    )
+    .map_err(|err| {
+        report_error(
+            &app,
+            AppError::system(format!("アプリの起動に失敗しました: {err}")),
+        )
+    })
+}
+
+#[tauri::command]
+pub async fn get_encryption_status(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    data_manager
+        .encryption_status()
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn enable_encryption(
+    key_source: KeySource,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    data_manager
+        .set_encryption(Some(&key_source))
+        .and_then(|()| data_manager.encryption_status())
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn rotate_encryption_key(
+    key_source: KeySource,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    let rotate = || {
+        if !data_manager.encryption_status()?.enabled {
+            return Err(DataError::Encryption(
+                "encryption is not enabled".to_string(),
+            ));
+        }
+        data_manager.set_encryption(Some(&key_source))?;
+        data_manager.encryption_status()
+    };
+    rotate().map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn disable_encryption(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    data_manager
+        .set_encryption(None)
+        .and_then(|()| data_manager.encryption_status())
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn verify_data(
+    repair: bool,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<IntegrityReport, String> {
+    data_integrity::verify_data(&data_manager, repair)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
 #[tauri::command]
 pub async fn respond_to_check_in(
     response: CheckInResponse,
@@ -214,10 +442,448 @@ pub async fn get_session_stats(
     data_manager: State<'_, DataManager>,
     app: AppHandle,
 ) -> Result<SessionStats, String> {
+    data_manager
+        .load_session_stats(&from, &to)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+/// Rebuilds the per-day stats cache from every stored session.
+#[tauri::command]
+pub async fn rebuild_stats_cache(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<(), String> {
+    data_manager
+        .rebuild_stats_cache()
+        .map(|_| ())
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+/// Stats for sessions started between `from` and `to` that match `filter`,
+/// split by `group_by` when given.
+#[tauri::command]
+pub async fn get_grouped_stats(
+    from: String,
+    to: String,
+    filter: Option<StatsFilter>,
+    group_by: Option<StatsGroupBy>,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<StatsGroup>, String> {
     let sessions = data_manager
         .load_sessions_in_range(&from, &to)
         .map_err(|err| report_error(&app, AppError::from(err)))?;
-    Ok(calculate_session_stats(&sessions))
+    let aggregates = data_manager
+        .load_daily_aggregates_in_range(&from, &to)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let routines = data_manager
+        .load_routines()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let settings = data_manager
+        .load_settings()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    calculate_grouped_stats(
+        &sessions,
+        &aggregates,
+        &routines,
+        &filter.unwrap_or_default(),
+        group_by,
+        &Local,
+        &settings.calendar,
+    )
+    .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+/// Per-step breakdown of sessions started between `from` and `to`.
+#[tauri::command]
+pub async fn get_step_stats(
+    from: String,
+    to: String,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<StepStats>, String> {
+    let sessions = data_manager
+        .load_sessions_in_range(&from, &to)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let routines = data_manager
+        .load_routines()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    Ok(calculate_step_stats(&sessions, &routines))
+}
+
+/// Stats for each local `bucket` period overlapping `from`..`to`.
+#[tauri::command]
+pub async fn get_stats_series(
+    from: String,
+    to: String,
+    bucket: PeriodKind,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<StatsBucket>, String> {
+    let settings = data_manager
+        .load_settings()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let periods = parse_instant(&from)
+        .and_then(|from| Ok((from, parse_instant(&to)?)))
+        .and_then(|(from, to)| local_periods_between(bucket, from, to, &settings.calendar))
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let (Some(first), Some(last)) = (periods.first(), periods.last()) else {
+        return Ok(Vec::new());
+    };
+    let sessions = data_manager
+        .load_sessions_in_range(&first.from, &last.to)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let aggregates = data_manager
+        .load_daily_aggregates_in_range(&first.from, &last.to)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let routines = data_manager
+        .load_routines()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    Ok(calculate_stats_series(
+        &sessions,
+        &aggregates,
+        &routines,
+        &periods,
+    ))
+}
+
+/// Check-in response times for sessions in the local weeks overlapping
+/// `from`..`to`, with one trend entry per week.
+#[tauri::command]
+pub async fn get_check_in_analytics(
+    from: String,
+    to: String,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<CheckInAnalytics, String> {
+    let settings = data_manager
+        .load_settings()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let weeks = parse_instant(&from)
+        .and_then(|from| Ok((from, parse_instant(&to)?)))
+        .and_then(|(from, to)| {
+            local_periods_between(PeriodKind::Week, from, to, &settings.calendar)
+        })
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let (Some(first), Some(last)) = (weeks.first(), weeks.last()) else {
+        return Ok(CheckInAnalytics::default());
+    };
+    let sessions = data_manager
+        .load_sessions_in_range(&first.from, &last.to)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let routines = data_manager
+        .load_routines()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    Ok(calculate_check_in_analytics(&sessions, &routines, &weeks))
+}
+
+/// How sessions started between `from` and `to` ended, where stopped and
+/// recovered ones were abandoned, and the abort rate per local hour.
+#[tauri::command]
+pub async fn get_abort_analytics(
+    from: String,
+    to: String,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<AbortAnalytics, String> {
+    let sessions = data_manager
+        .load_sessions_in_range(&from, &to)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let routines = data_manager
+        .load_routines()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    Ok(calculate_abort_analytics(&sessions, &routines, &Local))
+}
+
+/// Weekly report for the local week containing `week_of` (default: now),
+/// rendered in `format`. With `folder`, it is also written there in both
+/// formats.
+#[tauri::command]
+pub async fn generate_weekly_report(
+    week_of: Option<String>,
+    format: ReportFormat,
+    folder: Option<PathBuf>,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<String, String> {
+    let instant = match week_of {
+        Some(value) => {
+            parse_instant(&value).map_err(|err| report_error(&app, AppError::from(err)))?
+        }
+        None => Utc::now(),
+    };
+    let settings = data_manager
+        .load_settings()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let report = load_weekly_report(&data_manager, instant, &Local, &settings.calendar)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    if let Some(folder) = folder {
+        write_report(&folder, &report).map_err(|err| report_error(&app, AppError::from(err)))?;
+    }
+    Ok(render_report(&report, format))
+}
+
+#[tauri::command]
+pub async fn load_goals(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<Goal>, String> {
+    data_manager
+        .load_goals()
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+/// Replaces every goal; goals without an id get one. Returns the saved goals.
+#[tauri::command]
+pub async fn save_goals(
+    mut goals: Vec<Goal>,
+    data_manager: State<'_, DataManager>,
+    goal_state: State<'_, Mutex<GoalState>>,
+    app: AppHandle,
+) -> Result<Vec<Goal>, String> {
+    let routines = data_manager
+        .load_routines()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    prepare_goals(&mut goals, &routines).map_err(|err| report_error(&app, AppError::from(err)))?;
+    data_manager
+        .save_goals(&goals)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    if let Ok(mut state) = goal_state.lock() {
+        state.invalidate();
+    }
+    Ok(goals)
+}
+
+#[tauri::command]
+pub async fn get_goal_progress(
+    data_manager: State<'_, DataManager>,
+    session_tracker: State<'_, Mutex<SessionTracker>>,
+    app: AppHandle,
+) -> Result<Vec<GoalProgress>, String> {
+    let live = session_tracker
+        .lock()
+        .ok()
+        .and_then(|tracker| tracker.current_session());
+    goal_actions::current_goal_progress(&data_manager, live.as_ref())
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn get_period(
+    kind: PeriodKind,
+    at: Option<String>,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Period, String> {
+    let instant = match at {
+        Some(value) => {
+            parse_instant(&value).map_err(|err| report_error(&app, AppError::from(err)))?
+        }
+        None => Utc::now(),
+    };
+    let settings = data_manager
+        .load_settings()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    Ok(local_period_containing(kind, instant, &settings.calendar))
+}
+
+#[tauri::command]
+pub async fn list_sessions(
+    from: String,
+    to: String,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<Session>, String> {
+    data_manager
+        .load_sessions_in_range(&from, &to)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn delete_session(
+    session_id: String,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<(), String> {
+    session_editor::delete_session(&data_manager, &session_id)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn set_session_excluded(
+    session_id: String,
+    excluded: bool,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Session, String> {
+    session_editor::set_excluded_from_stats(&data_manager, &session_id, excluded)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn set_session_tags(
+    session_id: String,
+    tags: Vec<String>,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Session, String> {
+    session_editor::set_tags(&data_manager, &session_id, tags)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn set_session_annotation(
+    session_id: String,
+    annotation: Option<String>,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Session, String> {
+    session_editor::set_annotation(&data_manager, &session_id, annotation)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn correct_step_run_result(
+    session_id: String,
+    run_index: usize,
+    result: StepRunResult,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Session, String> {
+    session_editor::correct_step_run_result(&data_manager, &session_id, run_index, result)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn prune_history(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<PruneReport, String> {
+    let settings = data_manager
+        .load_settings()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    history_retention::prune_history(&data_manager, &settings.retention, Utc::now())
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn sync_now(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<SyncReport, String> {
+    let settings = data_manager
+        .load_settings()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    let folder = settings
+        .sync
+        .folder
+        .ok_or_else(|| report_error(&app, AppError::from(SyncError::NotConfigured)))?;
+    folder_sync::sync_folder(&data_manager, Path::new(&folder), Utc::now())
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn list_sync_conflicts(
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<SyncConflict>, String> {
+    folder_sync::list_conflicts(&data_manager)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn resolve_sync_conflict(
+    conflict_id: String,
+    restore: bool,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<SyncConflict, String> {
+    folder_sync::resolve_conflict(&data_manager, &conflict_id, restore)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn export_sessions(
+    from: String,
+    to: String,
+    format: ExportFormat,
+    directory: PathBuf,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<Vec<PathBuf>, String> {
+    session_export::export_sessions(&data_manager, &from, &to, format, &directory)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn list_profiles(
+    profile_store: State<'_, ProfileStore>,
+    app: AppHandle,
+) -> Result<ProfileList, String> {
+    profile_store
+        .list()
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+#[tauri::command]
+pub async fn create_profile(
+    name: String,
+    profile_store: State<'_, ProfileStore>,
+    app: AppHandle,
+) -> Result<ProfileInfo, String> {
+    profile_store
+        .create(&name)
+        .map_err(|err| report_error(&app, AppError::from(err)))
+}
+
+/// Makes `profile_id` active and restarts the app so every piece of state is
+/// reloaded from the profile's directory.
+#[tauri::command]
+pub async fn switch_profile(
+    profile_id: String,
+    profile_store: State<'_, ProfileStore>,
+    timer_engine: State<'_, Mutex<TimerEngine>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let running = timer_engine
+        .lock()
+        .map_err(|_| report_error(&app, AppError::system("タイマー状態の取得に失敗しました")))?
+        .is_running();
+    if running {
+        return Err(report_error(
+            &app,
+            AppError::from(ProfileError::SessionInProgress),
+        ));
+    }
+    let active = profile_store
+        .active()
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    if active.id == profile_id {
+        return Ok(());
+    }
+    profile_store
+        .set_active(&profile_id)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    app.restart()
+}
+
+/// Stats for `profile_id`, or summed across every profile when `None`.
+#[tauri::command]
+pub async fn get_profile_stats(
+    from: String,
+    to: String,
+    profile_id: Option<String>,
+    profile_store: State<'_, ProfileStore>,
+    data_manager: State<'_, DataManager>,
+    app: AppHandle,
+) -> Result<ProfileStats, String> {
+    let history = profile_store
+        .load_history(&data_manager, profile_id.as_deref(), &from, &to)
+        .map_err(|err| report_error(&app, AppError::from(err)))?;
+    Ok(ProfileStats {
+        stats: calculate_session_stats(&history.sessions, &history.aggregates),
+        skipped_profiles: history.skipped,
+    })
 }
 
 fn report_error(app: &AppHandle, error: AppError) -> String {
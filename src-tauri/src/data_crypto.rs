@@ -0,0 +1,521 @@
+//! Opt-in encryption at rest for the data directory.
+//!
+//! Encryption is on when `encryption.json` exists in the data directory. Every
+//! data file is then written as an XChaCha20-Poly1305 envelope. The key comes
+//! either from a passphrase (Argon2id with the salt kept in `encryption.json`)
+//! or from a keyfile holding 32 random bytes. `encryption.json` also holds an
+//! encrypted verifier so a wrong key is reported before any file is read.
+//!
+//! Changing the key is staged in `encryption.pending.json`, which holds the
+//! incoming config with each key sealed under the other. It is written before
+//! the first file is re-encrypted and removed once the new config is in place,
+//! so a rotation cut short is finished on the next start with either the old
+//! or the new credential.
+//!
+//! A sync folder has a key of its own, kept in the folder's
+//! `mccall-sync-key.json` sealed under the same passphrase or keyfile. Every
+//! device that unlocks with that credential can read the others' change logs
+//! even though each data directory derives its own key from its own salt.
+
+use crate::data_manager::{DataError, DataResult};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE: &str = "encryption.json";
+pub const FOLDER_KEY_FILE: &str = "mccall-sync-key.json";
+pub const PENDING_FILE: &str = "encryption.pending.json";
+pub const PASSPHRASE_ENV: &str = "MCCALL_PASSPHRASE";
+const CONFIG_VERSION: u32 = 1;
+const ENVELOPE_FORMAT: &str = "mccall-encrypted";
+const ENVELOPE_PREFIX: &[u8] = b"{\"format\":\"mccall-encrypted\"";
+const ENVELOPE_VERSION: u32 = 1;
+const VERIFIER_PLAINTEXT: &[u8] = b"mccall-data-key";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+#[derive(Clone)]
+pub struct DataKey([u8; KEY_LEN]);
+
+impl std::fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DataKey(..)")
+    }
+}
+
+impl DataKey {
+    fn generate() -> Self {
+        let mut key = [0u8; KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    fn derive(passphrase: &str, salt: &[u8]) -> DataResult<Self> {
+        let mut key = [0u8; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|err| DataError::Encryption(format!("key derivation failed: {err}")))?;
+        Ok(Self(key))
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.0.into())
+    }
+}
+
+/// Key material supplied by the user to enable, unlock or rotate encryption.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum KeySource {
+    Passphrase { passphrase: String },
+    Keyfile { path: PathBuf },
+}
+
+impl std::fmt::Debug for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySource::Passphrase { .. } => f.write_str("Passphrase(..)"),
+            KeySource::Keyfile { path } => f.debug_tuple("Keyfile").field(path).finish(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum KeySourceConfig {
+    Passphrase { salt: String },
+    Keyfile { path: PathBuf },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionConfig {
+    pub version: u32,
+    pub key_source: KeySourceConfig,
+    verifier: Envelope,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    format: String,
+    version: u32,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// A key sealed under another key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SealedKey(Envelope);
+
+/// A key change in progress.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingRotation {
+    /// Config to commit, `None` when encryption is being turned off.
+    pub next: Option<EncryptionConfig>,
+    /// The outgoing key sealed under the incoming one.
+    pub previous_key: Option<SealedKey>,
+    /// The incoming key sealed under the outgoing one.
+    pub next_key: Option<SealedKey>,
+    pub folder: Option<PendingFolderKey>,
+}
+
+/// A sync folder key being re-sealed under the incoming credential, and this
+/// device's log in that folder once it has synced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingFolderKey {
+    pub folder: PathBuf,
+    pub log: Option<PathBuf>,
+    pub config: FolderKeyConfig,
+    /// The folder key sealed under the incoming data key.
+    pub key: SealedKey,
+}
+
+pub fn load_config(base_dir: &Path) -> DataResult<Option<EncryptionConfig>> {
+    read_json(&base_dir.join(CONFIG_FILE))
+}
+
+pub fn load_pending(base_dir: &Path) -> DataResult<Option<PendingRotation>> {
+    read_json(&base_dir.join(PENDING_FILE))
+}
+
+pub fn save_pending(base_dir: &Path, pending: &PendingRotation) -> DataResult<()> {
+    write_json(&base_dir.join(PENDING_FILE), pending)
+}
+
+pub fn remove_pending(base_dir: &Path) -> DataResult<()> {
+    match fs::remove_file(base_dir.join(PENDING_FILE)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(DataError::from(err)),
+        _ => Ok(()),
+    }
+}
+
+/// Makes `config` the directory's config, or turns encryption off.
+pub fn commit_config(base_dir: &Path, config: Option<&EncryptionConfig>) -> DataResult<()> {
+    let path = base_dir.join(CONFIG_FILE);
+    match config {
+        Some(config) => write_json(&path, config),
+        None => match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(DataError::from(err)),
+            _ => Ok(()),
+        },
+    }
+}
+
+pub fn seal_key(wrapping: &DataKey, key: &DataKey) -> DataResult<SealedKey> {
+    Ok(SealedKey(seal(wrapping, &key.0)?))
+}
+
+pub fn open_sealed_key(wrapping: &DataKey, sealed: &SealedKey) -> DataResult<DataKey> {
+    open_key(wrapping, &sealed.0)
+}
+
+/// Recovers the outgoing and incoming keys of an interrupted rotation from
+/// either credential. `current` is the config on disk, which is already the
+/// incoming one when only the cleanup was cut short.
+pub fn unlock_pending(
+    current: Option<&EncryptionConfig>,
+    pending: &PendingRotation,
+    source: Option<&KeySource>,
+) -> DataResult<(Option<DataKey>, Option<DataKey>)> {
+    let previous = current.filter(|config| Some(*config) != pending.next.as_ref());
+    if let (Some(config), Some(source)) = (previous, source) {
+        if let Ok(previous_key) = unlock(config, source) {
+            let next_key = match (&pending.next, &pending.next_key) {
+                (Some(_), Some(sealed)) => Some(open_sealed_key(&previous_key, sealed)?),
+                _ => None,
+            };
+            return Ok((Some(previous_key), next_key));
+        }
+    }
+    match (&pending.next, previous) {
+        (Some(config), _) => {
+            let next_key = unlock(config, source.ok_or(DataError::KeyRequired)?)?;
+            let previous_key = match (previous, &pending.previous_key) {
+                (Some(_), Some(sealed)) => Some(open_sealed_key(&next_key, sealed)?),
+                _ => None,
+            };
+            Ok((previous_key, Some(next_key)))
+        }
+        (None, Some(config)) => {
+            let previous_key = unlock(config, source.ok_or(DataError::KeyRequired)?)?;
+            Ok((Some(previous_key), None))
+        }
+        (None, None) => Ok((None, None)),
+    }
+}
+
+/// Whether the app is waiting for the data directory to be unlocked, and with
+/// what kind of key.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockState {
+    pub locked: bool,
+    pub key_source: Option<KeySourceConfig>,
+}
+
+/// How the directory is encrypted, counting a rotation still in progress.
+pub fn configured_key_source(base_dir: &Path) -> DataResult<Option<KeySourceConfig>> {
+    Ok(match load_config(base_dir)? {
+        Some(config) => Some(config.key_source),
+        None => load_pending(base_dir)?
+            .and_then(|pending| pending.next)
+            .map(|config| config.key_source),
+    })
+}
+
+/// Key source to unlock the directory with at startup: the configured keyfile,
+/// or a passphrase from `MCCALL_PASSPHRASE`. Without either the app asks.
+pub fn startup_key_source(base_dir: &Path) -> DataResult<Option<KeySource>> {
+    Ok(match configured_key_source(base_dir)? {
+        Some(KeySourceConfig::Keyfile { path }) => Some(KeySource::Keyfile { path }),
+        Some(KeySourceConfig::Passphrase { .. }) => env::var(PASSPHRASE_ENV)
+            .ok()
+            .map(|passphrase| KeySource::Passphrase { passphrase }),
+        None => None,
+    })
+}
+
+/// Key shared by the devices syncing through one folder: a random key sealed
+/// under a key derived from the user's credential.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderKeyConfig {
+    pub version: u32,
+    pub key_source: KeySourceConfig,
+    sealed_key: Envelope,
+}
+
+pub fn load_folder_key(folder: &Path) -> DataResult<Option<FolderKeyConfig>> {
+    read_json(&folder.join(FOLDER_KEY_FILE))
+}
+
+pub fn save_folder_key(folder: &Path, config: &FolderKeyConfig) -> DataResult<()> {
+    fs::create_dir_all(folder)?;
+    write_json(&folder.join(FOLDER_KEY_FILE), config)
+}
+
+/// Generates a folder key and seals it under `source`.
+pub fn create_folder_key(source: &KeySource) -> DataResult<(DataKey, FolderKeyConfig)> {
+    let key = DataKey::generate();
+    let config = seal_folder_key(&key, source)?;
+    Ok((key, config))
+}
+
+/// Seals an existing folder key under `source`, as when the credential changes.
+pub fn seal_folder_key(key: &DataKey, source: &KeySource) -> DataResult<FolderKeyConfig> {
+    let (wrapping, key_source) = new_wrapping_key(source)?;
+    Ok(FolderKeyConfig {
+        version: CONFIG_VERSION,
+        key_source,
+        sealed_key: seal(&wrapping, &key.0)?,
+    })
+}
+
+pub fn unlock_folder_key(config: &FolderKeyConfig, source: &KeySource) -> DataResult<DataKey> {
+    let wrapping = wrapping_key(&config.key_source, source)?;
+    open_key(&wrapping, &config.sealed_key)
+}
+
+/// Builds a fresh key and the matching config. A missing keyfile is created.
+pub fn create_key(source: &KeySource) -> DataResult<(DataKey, EncryptionConfig)> {
+    let (key, key_source) = new_wrapping_key(source)?;
+    let verifier = seal(&key, VERIFIER_PLAINTEXT)?;
+    let config = EncryptionConfig {
+        version: CONFIG_VERSION,
+        key_source,
+        verifier,
+    };
+    Ok((key, config))
+}
+
+/// Recovers the key for an existing config and checks it against the verifier.
+pub fn unlock(config: &EncryptionConfig, source: &KeySource) -> DataResult<DataKey> {
+    let key = wrapping_key(&config.key_source, source)?;
+    open(&key, &config.verifier)?;
+    Ok(key)
+}
+
+/// A key derived from `source` with a fresh salt, or read from the keyfile.
+fn new_wrapping_key(source: &KeySource) -> DataResult<(DataKey, KeySourceConfig)> {
+    Ok(match source {
+        KeySource::Passphrase { passphrase } => {
+            if passphrase.is_empty() {
+                return Err(DataError::Encryption(
+                    "passphrase must not be empty".to_string(),
+                ));
+            }
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let key = DataKey::derive(passphrase, &salt)?;
+            let salt = BASE64.encode(salt);
+            (key, KeySourceConfig::Passphrase { salt })
+        }
+        KeySource::Keyfile { path } => {
+            let key = if path.exists() {
+                read_keyfile(path)?
+            } else {
+                write_keyfile(path)?
+            };
+            (key, KeySourceConfig::Keyfile { path: path.clone() })
+        }
+    })
+}
+
+/// The key `source` yields for a stored key source.
+fn wrapping_key(config: &KeySourceConfig, source: &KeySource) -> DataResult<DataKey> {
+    Ok(match (config, source) {
+        (KeySourceConfig::Passphrase { salt }, KeySource::Passphrase { passphrase }) => {
+            let salt = BASE64
+                .decode(salt)
+                .map_err(|err| DataError::Encryption(format!("invalid salt: {err}")))?;
+            DataKey::derive(passphrase, &salt)?
+        }
+        (KeySourceConfig::Keyfile { .. }, KeySource::Keyfile { path }) => read_keyfile(path)?,
+        _ => return Err(DataError::WrongKey),
+    })
+}
+
+fn open_key(wrapping: &DataKey, envelope: &Envelope) -> DataResult<DataKey> {
+    let bytes = open(wrapping, envelope)?;
+    let key: [u8; KEY_LEN] = bytes
+        .try_into()
+        .map_err(|_| DataError::Encryption("sealed key has the wrong length".to_string()))?;
+    Ok(DataKey(key))
+}
+
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(ENVELOPE_PREFIX)
+}
+
+pub fn encrypt(key: &DataKey, plaintext: &[u8]) -> DataResult<Vec<u8>> {
+    let mut bytes = serde_json::to_vec(&seal(key, plaintext)?)?;
+    bytes.push(b'\n');
+    Ok(bytes)
+}
+
+pub fn decrypt(key: &DataKey, bytes: &[u8]) -> DataResult<Vec<u8>> {
+    let envelope: Envelope = serde_json::from_slice(bytes)?;
+    open(key, &envelope)
+}
+
+fn seal(key: &DataKey, plaintext: &[u8]) -> DataResult<Envelope> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = key
+        .cipher()
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| DataError::Encryption("encryption failed".to_string()))?;
+    Ok(Envelope {
+        format: ENVELOPE_FORMAT.to_string(),
+        version: ENVELOPE_VERSION,
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+fn open(key: &DataKey, envelope: &Envelope) -> DataResult<Vec<u8>> {
+    if envelope.format != ENVELOPE_FORMAT || envelope.version != ENVELOPE_VERSION {
+        return Err(DataError::Encryption(format!(
+            "unsupported envelope {} v{}",
+            envelope.format, envelope.version
+        )));
+    }
+    let nonce = BASE64
+        .decode(&envelope.nonce)
+        .ok()
+        .filter(|nonce| nonce.len() == 24)
+        .ok_or_else(|| DataError::Encryption("invalid nonce".to_string()))?;
+    let ciphertext = BASE64
+        .decode(&envelope.ciphertext)
+        .map_err(|err| DataError::Encryption(format!("invalid ciphertext: {err}")))?;
+    key.cipher()
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| DataError::WrongKey)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> DataResult<Option<T>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(DataError::from(err)),
+    }
+}
+
+/// Writes through a temporary file so a crash never leaves half a config.
+fn write_json<T: Serialize>(path: &Path, value: &T) -> DataResult<()> {
+    let temp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&temp_path)?;
+    file.write_all(&serde_json::to_vec_pretty(value)?)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+fn read_keyfile(path: &Path) -> DataResult<DataKey> {
+    let contents = fs::read_to_string(path)?;
+    let bytes = BASE64
+        .decode(contents.trim())
+        .map_err(|err| DataError::Encryption(format!("invalid keyfile: {err}")))?;
+    let key: [u8; KEY_LEN] = bytes
+        .try_into()
+        .map_err(|_| DataError::Encryption("keyfile must hold a 32-byte key".to_string()))?;
+    Ok(DataKey(key))
+}
+
+fn write_keyfile(path: &Path) -> DataResult<DataKey> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let key = DataKey::generate();
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    writeln!(file, "{}", BASE64.encode(key.0))?;
+    file.flush()?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_key, decrypt, encrypt, is_encrypted, unlock, KeySource};
+    use crate::data_manager::DataError;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_crypto_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn passphrase(value: &str) -> KeySource {
+        KeySource::Passphrase {
+            passphrase: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn passphrase_round_trip_and_wrong_key() {
+        let (key, config) = create_key(&passphrase("correct horse")).expect("create key");
+        let sealed = encrypt(&key, b"[1,2,3]").expect("encrypt");
+        assert!(is_encrypted(&sealed));
+        assert!(!is_encrypted(b"[1,2,3]"));
+
+        let unlocked = unlock(&config, &passphrase("correct horse")).expect("unlock");
+        assert_eq!(decrypt(&unlocked, &sealed).expect("decrypt"), b"[1,2,3]");
+        assert!(matches!(
+            unlock(&config, &passphrase("wrong")),
+            Err(DataError::WrongKey)
+        ));
+    }
+
+    #[test]
+    fn keyfile_is_created_once_and_reused() {
+        let dir = temp_dir();
+        let source = KeySource::Keyfile {
+            path: dir.join("keys").join("mccall.key"),
+        };
+        let (key, config) = create_key(&source).expect("create key");
+        let sealed = encrypt(&key, b"{}").expect("encrypt");
+
+        let (reused, _) = create_key(&source).expect("reuse keyfile");
+        assert_eq!(decrypt(&reused, &sealed).expect("decrypt"), b"{}");
+        unlock(&config, &source).expect("unlock");
+        assert!(matches!(
+            unlock(&config, &passphrase("anything")),
+            Err(DataError::WrongKey)
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
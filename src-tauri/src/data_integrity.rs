@@ -0,0 +1,689 @@
+use crate::data_manager::{BackupReason, DataFile, DataManager, DataResult};
+use crate::ids::{new_id, IdKind};
+use crate::models::{Routine, Session, SessionOutcome, SessionTotals, StepRunResult};
+use crate::routine_validator::collect_routine_errors;
+use crate::session_tracker::calculate_totals;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityIssue {
+    pub severity: IssueSeverity,
+    pub file: String,
+    pub record_id: Option<String>,
+    pub code: String,
+    pub message: String,
+    pub repairable: bool,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    pub checked_at: String,
+    pub issues: Vec<IntegrityIssue>,
+    pub repaired_count: u32,
+}
+
+impl IntegrityReport {
+    pub fn has_unrepaired(&self, severity: IssueSeverity) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity >= severity && !issue.repaired)
+    }
+}
+
+struct IssueSink {
+    issues: Vec<IntegrityIssue>,
+    repair: bool,
+}
+
+impl IssueSink {
+    fn push(
+        &mut self,
+        severity: IssueSeverity,
+        file: &str,
+        record_id: Option<&str>,
+        code: &str,
+        message: String,
+        repairable: bool,
+    ) -> bool {
+        let repaired = repairable && self.repair;
+        self.issues.push(IntegrityIssue {
+            severity,
+            file: file.to_string(),
+            record_id: record_id.map(str::to_string),
+            code: code.to_string(),
+            message,
+            repairable,
+            repaired,
+        });
+        repaired
+    }
+}
+
+const ROUTINES_FILE: &str = "routines.json";
+const SESSIONS_FILE: &str = "sessions.json";
+const SETTINGS_FILE: &str = "settings.json";
+const ACTIVE_SESSION_FILE: &str = "active_session.json";
+
+pub fn verify_data(data_manager: &DataManager, repair: bool) -> DataResult<IntegrityReport> {
+    let _updates = data_manager.lock_updates();
+    let mut sink = IssueSink {
+        issues: Vec::new(),
+        repair,
+    };
+
+    let mut routines = match data_manager.load_routines() {
+        Ok(routines) => Some(routines),
+        Err(err) => {
+            sink.push(
+                IssueSeverity::Error,
+                ROUTINES_FILE,
+                None,
+                "unreadable-file",
+                format!("File cannot be read: {err}"),
+                false,
+            );
+            None
+        }
+    };
+    let mut sessions = match data_manager.load_sessions() {
+        Ok(sessions) => Some(sessions),
+        Err(err) => {
+            sink.push(
+                IssueSeverity::Error,
+                SESSIONS_FILE,
+                None,
+                "unreadable-file",
+                format!("File cannot be read: {err}"),
+                false,
+            );
+            None
+        }
+    };
+    if let Err(err) = data_manager.load_settings() {
+        sink.push(
+            IssueSeverity::Error,
+            SETTINGS_FILE,
+            None,
+            "unreadable-file",
+            format!("File cannot be read: {err}"),
+            false,
+        );
+    }
+
+    let mut routines_changed = false;
+    let mut sessions_changed = false;
+
+    if let Some(routines) = routines.as_mut() {
+        routines_changed = check_routines(routines, &mut sink);
+    }
+    if let (Some(sessions), Some(routines)) = (sessions.as_mut(), routines.as_ref()) {
+        sessions_changed = check_sessions(sessions, routines, &mut sink);
+    }
+
+    match data_manager.load_active_session() {
+        Ok(Some(snapshot)) => {
+            let known = routines
+                .as_ref()
+                .map(|items| items.iter().any(|item| item.id == snapshot.routine_id))
+                .unwrap_or(true);
+            if !known {
+                sink.push(
+                    IssueSeverity::Warning,
+                    ACTIVE_SESSION_FILE,
+                    Some(&snapshot.session_id),
+                    "missing-routine",
+                    format!(
+                        "Active session references deleted routine {}",
+                        snapshot.routine_id
+                    ),
+                    false,
+                );
+            }
+        }
+        Ok(None) => {}
+        Err(err) => {
+            sink.push(
+                IssueSeverity::Error,
+                ACTIVE_SESSION_FILE,
+                None,
+                "unreadable-file",
+                format!("File cannot be read: {err}"),
+                false,
+            );
+        }
+    }
+
+    if repair {
+        if let (true, Some(routines)) = (routines_changed, routines.as_ref()) {
+            data_manager.create_backup(DataFile::Routines, BackupReason::Repair)?;
+            data_manager.save_routines(routines)?;
+        }
+        if let (true, Some(sessions)) = (sessions_changed, sessions.as_ref()) {
+            data_manager.create_backup(DataFile::Sessions, BackupReason::Repair)?;
+            data_manager.save_sessions(sessions)?;
+        }
+    }
+
+    let repaired_count = sink
+        .issues
+        .iter()
+        .filter(|issue| issue.repaired)
+        .count()
+        .try_into()
+        .unwrap_or(u32::MAX);
+    let mut issues = sink.issues;
+    issues.sort_by_key(|issue| Reverse(issue.severity));
+
+    Ok(IntegrityReport {
+        checked_at: Utc::now().to_rfc3339(),
+        issues,
+        repaired_count,
+    })
+}
+
+fn check_routines(routines: &mut [Routine], sink: &mut IssueSink) -> bool {
+    let mut changed = false;
+    let mut seen: HashSet<String> = HashSet::new();
+    for routine in routines.iter_mut() {
+        if !seen.insert(routine.id.clone()) {
+            sink.push(
+                IssueSeverity::Error,
+                ROUTINES_FILE,
+                Some(&routine.id),
+                "duplicate-routine-id",
+                "Routine id is used more than once".to_string(),
+                false,
+            );
+        }
+
+        let errors = collect_routine_errors(routine);
+        let order_broken = errors.iter().any(|error| error.path.ends_with(".order"));
+        for error in errors
+            .iter()
+            .filter(|error| !error.path.ends_with(".order"))
+        {
+            sink.push(
+                IssueSeverity::Error,
+                ROUTINES_FILE,
+                Some(&routine.id),
+                "invalid-routine",
+                format!("{}: {}", error.path, error.message),
+                false,
+            );
+        }
+        if order_broken {
+            let repaired = sink.push(
+                IssueSeverity::Warning,
+                ROUTINES_FILE,
+                Some(&routine.id),
+                "step-order",
+                "Step order is not contiguous".to_string(),
+                true,
+            );
+            if repaired {
+                for (index, step) in routine.steps.iter_mut().enumerate() {
+                    step.order = index.try_into().unwrap_or(u32::MAX);
+                }
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+fn check_sessions(sessions: &mut Vec<Session>, routines: &[Routine], sink: &mut IssueSink) -> bool {
+    let mut changed = dedupe_sessions(sessions, sink);
+
+    for session in sessions.iter_mut() {
+        let started_at = parse_utc(&session.started_at);
+        if started_at.is_none() {
+            sink.push(
+                IssueSeverity::Error,
+                SESSIONS_FILE,
+                Some(&session.id),
+                "invalid-timestamp",
+                format!("startedAt is not RFC3339: {}", session.started_at),
+                false,
+            );
+        }
+
+        if session.ended_at.is_none() {
+            let ended_at = inferred_end(session, started_at);
+            let repaired = sink.push(
+                IssueSeverity::Warning,
+                SESSIONS_FILE,
+                Some(&session.id),
+                "missing-ended-at",
+                "Session has no endedAt".to_string(),
+                ended_at.is_some(),
+            );
+            if repaired {
+                session.ended_at = ended_at;
+                changed = true;
+            }
+        }
+
+        let Some(routine) = routines.iter().find(|item| item.id == session.routine_id) else {
+            sink.push(
+                IssueSeverity::Warning,
+                SESSIONS_FILE,
+                Some(&session.id),
+                "missing-routine",
+                format!("Routine {} no longer exists", session.routine_id),
+                false,
+            );
+            continue;
+        };
+
+        let unknown_steps: Vec<&str> = session
+            .step_runs
+            .iter()
+            .filter(|run| routine.steps.iter().all(|step| step.id != run.step_id))
+            .map(|run| run.step_id.as_str())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if !unknown_steps.is_empty() {
+            let mut unknown_steps = unknown_steps;
+            unknown_steps.sort_unstable();
+            sink.push(
+                IssueSeverity::Info,
+                SESSIONS_FILE,
+                Some(&session.id),
+                "unknown-step",
+                format!(
+                    "Step runs reference deleted steps: {}",
+                    unknown_steps.join(", ")
+                ),
+                false,
+            );
+        }
+
+        let last_step_id = routine
+            .steps
+            .last()
+            .map(|step| step.id.as_str())
+            .unwrap_or_default();
+        let computed = calculate_totals(&session.step_runs, &routine.steps, last_step_id);
+        // Retention pruning drops step runs but keeps totals, so there is
+        // nothing to check them against.
+        let pruned = session.step_runs.is_empty();
+        // Sessions saved before adherence was tracked have none in their
+        // totals; that alone is not a mismatch.
+        let expected = if session.totals.planned_seconds == 0 {
+            SessionTotals {
+                planned_seconds: 0,
+                executed_seconds: 0,
+                overtime_seconds: 0,
+                skip_loss_seconds: 0,
+                skipped_count: 0,
+                ..computed.clone()
+            }
+        } else {
+            computed.clone()
+        };
+        if expected != session.totals && !pruned && !is_recovered_totals(session, &expected) {
+            let repaired = sink.push(
+                IssueSeverity::Warning,
+                SESSIONS_FILE,
+                Some(&session.id),
+                "totals-mismatch",
+                "Totals do not match step runs".to_string(),
+                true,
+            );
+            if repaired {
+                session.totals = computed;
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+fn dedupe_sessions(sessions: &mut Vec<Session>, sink: &mut IssueSink) -> bool {
+    let mut changed = false;
+    let mut seen: HashMap<String, serde_json::Value> = HashMap::new();
+    let mut kept = Vec::with_capacity(sessions.len());
+
+    for mut session in std::mem::take(sessions) {
+        let value = serde_json::to_value(&session).unwrap_or_default();
+        let Some(first) = seen.get(&session.id) else {
+            seen.insert(session.id.clone(), value);
+            kept.push(session);
+            continue;
+        };
+
+        let identical = *first == value;
+        let message = if identical {
+            "Session is stored more than once".to_string()
+        } else {
+            "Session id is shared by different sessions".to_string()
+        };
+        let repaired = sink.push(
+            IssueSeverity::Error,
+            SESSIONS_FILE,
+            Some(&session.id),
+            "duplicate-session-id",
+            message,
+            true,
+        );
+        if !repaired {
+            kept.push(session);
+            continue;
+        }
+        changed = true;
+        if identical {
+            continue;
+        }
+        session.id = new_id(IdKind::Session);
+        seen.insert(
+            session.id.clone(),
+            serde_json::to_value(&session).unwrap_or_default(),
+        );
+        kept.push(session);
+    }
+
+    *sessions = kept;
+    changed
+}
+
+/// Sessions rebuilt by crash recovery account the whole wall-clock span as
+/// work, so their totals legitimately exceed the sum of their single aborted
+/// run. Once that run's result is corrected only the outcome tells them apart.
+fn is_recovered_totals(session: &Session, computed: &SessionTotals) -> bool {
+    let stored = &session.totals;
+    let recovered = session.outcome == Some(SessionOutcome::Recovered)
+        || session
+            .step_runs
+            .last()
+            .map(|run| matches!(run.result, StepRunResult::Aborted))
+            .unwrap_or(false);
+    recovered
+        && stored.total_seconds >= computed.total_seconds
+        && stored.break_seconds == computed.break_seconds
+        && stored.work_seconds.saturating_add(stored.break_seconds) == stored.total_seconds
+        && stored.cycles_count == computed.cycles_count
+        && stored.check_in_done_count == computed.check_in_done_count
+        && stored.check_in_skip_count == computed.check_in_skip_count
+        && stored.planned_seconds == computed.planned_seconds
+        && stored.executed_seconds == computed.executed_seconds
+        && stored.overtime_seconds == computed.overtime_seconds
+        && stored.skip_loss_seconds == computed.skip_loss_seconds
+        && stored.skipped_count == computed.skipped_count
+}
+
+fn inferred_end(session: &Session, started_at: Option<DateTime<Utc>>) -> Option<String> {
+    if let Some(ended_at) = session
+        .step_runs
+        .iter()
+        .rev()
+        .find_map(|run| run.ended_at.clone())
+    {
+        return Some(ended_at);
+    }
+    started_at.map(|started| {
+        (started + Duration::seconds(i64::from(session.totals.total_seconds))).to_rfc3339()
+    })
+}
+
+fn parse_utc(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_data, IssueSeverity};
+    use crate::data_manager::{BackupReason, DataManager};
+    use crate::ids::{is_current_id, new_id, IdKind};
+    use crate::models::{Session, SessionTotals, StepRun, StepRunResult};
+    use crate::session_recovery::{recover_aborted_session, start_active_session};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_integrity_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn run(step_id: &str, seconds: u32, ended_at: Option<&str>) -> StepRun {
+        StepRun {
+            step_id: step_id.to_string(),
+            label: None,
+            planned_duration_seconds: seconds,
+            actual_duration_seconds: seconds,
+            started_at: "2025-01-01T10:00:00Z".to_string(),
+            ended_at: ended_at.map(str::to_string),
+            result: StepRunResult::Completed,
+            check_in_result: None,
+            sound_played: false,
+        }
+    }
+
+    fn session(id: &str, step_runs: Vec<StepRun>, totals: SessionTotals) -> Session {
+        Session {
+            id: id.to_string(),
+            routine_id: "routine-template-10min".to_string(),
+            started_at: "2025-01-01T10:00:00Z".to_string(),
+            ended_at: Some("2025-01-01T10:10:00Z".to_string()),
+            step_runs,
+            totals,
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    fn totals(total: u32, work: u32, brk: u32) -> SessionTotals {
+        SessionTotals {
+            total_seconds: total,
+            work_seconds: work,
+            break_seconds: brk,
+            cycles_count: 0,
+            check_in_done_count: 0,
+            check_in_skip_count: 0,
+            ..SessionTotals::default()
+        }
+    }
+
+    fn codes(report: &super::IntegrityReport) -> Vec<&str> {
+        let mut codes: Vec<&str> = report
+            .issues
+            .iter()
+            .map(|issue| issue.code.as_str())
+            .collect();
+        codes.sort_unstable();
+        codes
+    }
+
+    #[test]
+    fn clean_data_has_no_issues() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        manager
+            .save_sessions(&[session(
+                "session-1",
+                vec![
+                    run("step-template-4", 240, None),
+                    run("step-template-7", 90, None),
+                ],
+                totals(330, 240, 90),
+            )])
+            .expect("save sessions");
+
+        let report = verify_data(&manager, false).expect("verify");
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reports_problems_without_repairing() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let mut open = session(
+            "session-1",
+            vec![run("step-template-4", 240, Some("2025-01-01T10:04:00Z"))],
+            totals(999, 999, 0),
+        );
+        open.ended_at = None;
+        let mut orphan = session(
+            "session-2",
+            vec![run("deleted-step", 10, None)],
+            totals(10, 10, 0),
+        );
+        orphan.ended_at = Some("2025-01-01T10:00:10Z".to_string());
+        manager
+            .save_sessions(&[open.clone(), open, orphan])
+            .expect("save sessions");
+
+        let report = verify_data(&manager, false).expect("verify");
+
+        assert_eq!(
+            codes(&report),
+            vec![
+                "duplicate-session-id",
+                "missing-ended-at",
+                "missing-ended-at",
+                "totals-mismatch",
+                "totals-mismatch",
+                "unknown-step"
+            ]
+        );
+        assert_eq!(report.issues[0].severity, IssueSeverity::Error);
+        assert_eq!(report.repaired_count, 0);
+        assert_eq!(manager.load_sessions().expect("load").len(), 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn repairs_safe_problems_with_backup() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let mut open = session(
+            "session-1",
+            vec![
+                run("step-template-4", 240, Some("2025-01-01T10:04:00Z")),
+                run("step-template-7", 90, Some("2025-01-01T10:05:30Z")),
+            ],
+            totals(0, 0, 0),
+        );
+        open.ended_at = None;
+        let mut conflicting = open.clone();
+        conflicting.started_at = "2025-01-02T10:00:00Z".to_string();
+        manager
+            .save_sessions(&[open.clone(), open, conflicting])
+            .expect("save sessions");
+
+        let report = verify_data(&manager, true).expect("repair");
+        assert!(!report.has_unrepaired(IssueSeverity::Warning));
+
+        let sessions = manager.load_sessions().expect("load sessions");
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].id, "session-1");
+        assert_ne!(sessions[1].id, "session-1");
+        assert!(is_current_id(IdKind::Session, &sessions[1].id));
+        assert_eq!(
+            sessions[0].ended_at.as_deref(),
+            Some("2025-01-01T10:05:30Z")
+        );
+        assert_eq!(
+            sessions[0].totals,
+            SessionTotals {
+                planned_seconds: 330,
+                executed_seconds: 330,
+                ..totals(330, 240, 90)
+            }
+        );
+        assert!(manager
+            .list_backups()
+            .expect("list backups")
+            .iter()
+            .any(|item| item.reason == BackupReason::Repair));
+
+        let second = verify_data(&manager, false).expect("verify again");
+        assert!(second.issues.is_empty(), "{:?}", second.issues);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn recovered_sessions_are_not_flagged() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let routine = manager.load_routines().expect("load routines").remove(0);
+        let mut snapshot = start_active_session(
+            &manager,
+            &new_id(IdKind::Session),
+            &routine.id,
+            &routine.steps[0],
+            false,
+        )
+        .expect("start session");
+        snapshot.started_at = "2025-01-01T10:00:00Z".to_string();
+        snapshot.current_step_started_at = "2025-01-01T10:08:00Z".to_string();
+        snapshot.paused_at = Some("2025-01-01T10:09:00Z".to_string());
+        manager
+            .save_active_session(&snapshot)
+            .expect("save snapshot");
+        recover_aborted_session(&manager).expect("recover session");
+
+        let report = verify_data(&manager, false).expect("verify");
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn renumbers_step_order() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let mut routines = manager.load_routines().expect("load routines");
+        routines[0].steps[2].order = 7;
+        manager.save_routines(&routines).expect("save routines");
+
+        let report = verify_data(&manager, true).expect("repair");
+        assert_eq!(codes(&report), vec!["step-order"]);
+
+        let routines = manager.load_routines().expect("load routines");
+        assert_eq!(routines[0].steps[2].order, 2);
+        assert_eq!(routines[0].steps[7].order, 7);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,19 +1,35 @@
+use crate::calendar::current_zone;
+use crate::data_crypto::{
+    self, DataKey, KeySource, KeySourceConfig, PendingFolderKey, PendingRotation,
+};
+use crate::folder_sync::{self, SyncState};
 use crate::models::{
-    AppSettings, CheckInConfig, CheckInMode, RepeatMode, Routine, Session, SoundOverride,
-    SoundScheme, SoundSetting, Step,
+    AppSettings, CalendarSettings, CheckInConfig, CheckInMode, DailyAggregate, Goal, RepeatMode,
+    Routine, Session, SessionStats, SoundAssets, SoundOverride, SoundScheme, SoundSetting, Step,
+    StepWarnings,
 };
 use crate::recovery_state::ActiveSessionSnapshot;
-use chrono::DateTime;
-use serde::Serialize;
+use crate::session_stats::{aggregate_instant, calculate_session_stats, StatsCache};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Debug)]
 pub enum DataError {
     Io(io::Error),
     Serde(serde_json::Error),
     DateTime(String),
+    BackupNotFound(String),
+    Locked(PathBuf),
+    KeyRequired,
+    WrongKey,
+    Encryption(String),
+    SyncFolderEncrypted,
 }
 
 impl std::fmt::Display for DataError {
@@ -22,6 +38,23 @@ impl std::fmt::Display for DataError {
             DataError::Io(err) => write!(f, "I/O error: {err}"),
             DataError::Serde(err) => write!(f, "Serialization error: {err}"),
             DataError::DateTime(err) => write!(f, "DateTime parse error: {err}"),
+            DataError::BackupNotFound(name) => write!(f, "Backup not found: {name}"),
+            DataError::Locked(path) => write!(
+                f,
+                "Data directory is already in use by another McCall instance: {}",
+                path.display()
+            ),
+            DataError::KeyRequired => write!(
+                f,
+                "Data directory is encrypted; set {} or provide the keyfile",
+                data_crypto::PASSPHRASE_ENV
+            ),
+            DataError::WrongKey => write!(f, "Encryption key does not match the data directory"),
+            DataError::Encryption(err) => write!(f, "Encryption error: {err}"),
+            DataError::SyncFolderEncrypted => write!(
+                f,
+                "Sync folder is encrypted; enable encryption with the passphrase or keyfile the other devices use"
+            ),
         }
     }
 }
@@ -48,32 +81,285 @@ impl From<chrono::ParseError> for DataError {
 
 pub type DataResult<T> = Result<T, DataError>;
 
+const BACKUP_DIR: &str = "backups";
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+const DAILY_BACKUPS_KEPT: usize = 7;
+const EVENT_BACKUPS_KEPT: usize = 5;
+const LOCK_FILE: &str = ".lock";
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DataFile {
+    Routines,
+    Sessions,
+    Settings,
+    DailyAggregates,
+    Goals,
+}
+
+impl DataFile {
+    pub const ALL: [DataFile; 5] = [
+        DataFile::Routines,
+        DataFile::Sessions,
+        DataFile::Settings,
+        DataFile::DailyAggregates,
+        DataFile::Goals,
+    ];
+    /// Files the UI keeps in memory and must reload when edited outside the app.
+    pub const WATCHED: [DataFile; 3] = [DataFile::Routines, DataFile::Settings, DataFile::Goals];
+
+    fn stem(self) -> &'static str {
+        match self {
+            DataFile::Routines => "routines",
+            DataFile::Sessions => "sessions",
+            DataFile::Settings => "settings",
+            DataFile::DailyAggregates => "daily_aggregates",
+            DataFile::Goals => "goals",
+        }
+    }
+
+    fn from_stem(stem: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|file| file.stem() == stem)
+    }
+
+    fn check_contents(self, contents: &str) -> DataResult<()> {
+        if contents.trim().is_empty() {
+            return Ok(());
+        }
+        match self {
+            DataFile::Routines => serde_json::from_str::<Vec<Routine>>(contents).map(|_| ()),
+            DataFile::Sessions => serde_json::from_str::<Vec<Session>>(contents).map(|_| ()),
+            DataFile::Settings => serde_json::from_str::<AppSettings>(contents).map(|_| ()),
+            DataFile::DailyAggregates => {
+                serde_json::from_str::<Vec<DailyAggregate>>(contents).map(|_| ())
+            }
+            DataFile::Goals => serde_json::from_str::<Vec<Goal>>(contents).map(|_| ()),
+        }
+        .map_err(DataError::from)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupReason {
+    Daily,
+    Migration,
+    Import,
+    Restore,
+    Repair,
+    Prune,
+    Edit,
+    Sync,
+}
+
+impl BackupReason {
+    const ALL: [BackupReason; 8] = [
+        BackupReason::Daily,
+        BackupReason::Migration,
+        BackupReason::Import,
+        BackupReason::Restore,
+        BackupReason::Repair,
+        BackupReason::Prune,
+        BackupReason::Edit,
+        BackupReason::Sync,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            BackupReason::Daily => "daily",
+            BackupReason::Migration => "migration",
+            BackupReason::Import => "import",
+            BackupReason::Restore => "restore",
+            BackupReason::Repair => "repair",
+            BackupReason::Prune => "prune",
+            BackupReason::Edit => "edit",
+            BackupReason::Sync => "sync",
+        }
+    }
+
+    fn from_str(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|reason| reason.as_str() == value)
+    }
+
+    fn kept(self) -> usize {
+        match self {
+            BackupReason::Daily => DAILY_BACKUPS_KEPT,
+            _ => EVENT_BACKUPS_KEPT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub data_file: DataFile,
+    pub reason: BackupReason,
+    pub created_at: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct StartupRecovery {
+    pub data_file: DataFile,
+    pub restored_from: Option<String>,
+    pub corrupt_copy: PathBuf,
+}
+
+/// Advisory lock on a data directory, held for the lifetime of the process.
+/// The OS releases it when the process exits, so a crash never leaves it stale.
+#[derive(Debug)]
+pub struct DataDirLock {
+    _file: fs::File,
+}
+
+impl DataDirLock {
+    pub fn acquire(base_dir: impl AsRef<Path>) -> DataResult<Self> {
+        let base_dir = base_dir.as_ref();
+        fs::create_dir_all(base_dir)?;
+        let path = base_dir.join(LOCK_FILE);
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(fs::TryLockError::WouldBlock) => return Err(DataError::Locked(base_dir.into())),
+            Err(fs::TryLockError::Error(err)) => return Err(DataError::Io(err)),
+        }
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        file.flush()?;
+        Ok(Self { _file: file })
+    }
+
+    /// Like `acquire`, but keeps retrying for up to `wait` while another
+    /// process holds the lock, so a restarting instance can take over.
+    pub fn acquire_within(base_dir: impl AsRef<Path>, wait: Duration) -> DataResult<Self> {
+        let deadline = Instant::now() + wait;
+        loop {
+            match Self::acquire(base_dir.as_ref()) {
+                Err(DataError::Locked(_)) if Instant::now() < deadline => {
+                    std::thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DataManager {
     base_dir: PathBuf,
     routines_path: PathBuf,
     sessions_path: PathBuf,
     settings_path: PathBuf,
+    daily_aggregates_path: PathBuf,
+    goals_path: PathBuf,
     active_session_path: PathBuf,
+    sync_state_path: PathBuf,
+    stats_cache_path: PathBuf,
+    backups_dir: PathBuf,
+    startup_recoveries: Vec<StartupRecovery>,
+    /// Content hash of each watched file as last written or acknowledged by
+    /// this process. Shared between clones so the watcher sees our own writes.
+    file_stamps: Arc<Mutex<HashMap<DataFile, u64>>>,
+    /// Content hash of each data file as last read or written, with the
+    /// length and modification time it had then, so an unchanged file is not
+    /// read and hashed again on every stats query or watcher poll.
+    hashed_files: Arc<Mutex<HashMap<DataFile, (FileMark, u64)>>>,
+    /// Key used to encrypt files at rest, or `None` when encryption is off.
+    key: Arc<RwLock<Option<DataKey>>>,
+    /// The credential the key came from, kept to unlock a sync folder's key.
+    credentials: Arc<RwLock<Option<KeySource>>>,
+    /// Held across each load-modify-save of the data files; see `lock_updates`.
+    updates: Arc<Mutex<()>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub key_source: Option<KeySourceConfig>,
 }
 
 impl DataManager {
     pub fn new(base_dir: impl Into<PathBuf>) -> DataResult<Self> {
+        Self::open(base_dir, None)
+    }
+
+    /// Opens a data directory, unlocking it with `key_source` when encryption
+    /// is enabled there. Fails with `KeyRequired` or `WrongKey` before any data
+    /// file is touched. A key change cut short by a crash is finished first.
+    pub fn open(base_dir: impl Into<PathBuf>, key_source: Option<&KeySource>) -> DataResult<Self> {
         let base_dir = base_dir.into();
         fs::create_dir_all(&base_dir)?;
+        let config = data_crypto::load_config(&base_dir)?;
+        let mut rotation = None;
+        let (key, credentials) = match (data_crypto::load_pending(&base_dir)?, config) {
+            (Some(pending), config) => {
+                let (previous, next) =
+                    data_crypto::unlock_pending(config.as_ref(), &pending, key_source)?;
+                // The old credential finishes the rotation but no longer
+                // opens the sync folder's key.
+                let credentials = key_source.filter(|source| {
+                    pending
+                        .next
+                        .as_ref()
+                        .is_some_and(|config| data_crypto::unlock(config, source).is_ok())
+                });
+                rotation = Some((pending, previous));
+                (next, credentials.cloned())
+            }
+            (None, Some(config)) => {
+                let source = key_source.ok_or(DataError::KeyRequired)?;
+                (
+                    Some(data_crypto::unlock(&config, source)?),
+                    Some(source.clone()),
+                )
+            }
+            (None, None) => (None, None),
+        };
         let routines_path = base_dir.join("routines.json");
         let sessions_path = base_dir.join("sessions.json");
         let settings_path = base_dir.join("settings.json");
+        let daily_aggregates_path = base_dir.join("daily_aggregates.json");
+        let goals_path = base_dir.join("goals.json");
         let active_session_path = base_dir.join("active_session.json");
+        let sync_state_path = base_dir.join("sync_state.json");
+        let stats_cache_path = base_dir.join("stats_cache.json");
+        let backups_dir = base_dir.join(BACKUP_DIR);
 
-        let manager = Self {
+        let mut manager = Self {
             base_dir,
             routines_path,
             sessions_path,
             settings_path,
+            daily_aggregates_path,
+            goals_path,
             active_session_path,
+            sync_state_path,
+            stats_cache_path,
+            backups_dir,
+            startup_recoveries: Vec::new(),
+            file_stamps: Arc::new(Mutex::new(HashMap::new())),
+            hashed_files: Arc::new(Mutex::new(HashMap::new())),
+            key: Arc::new(RwLock::new(key)),
+            credentials: Arc::new(RwLock::new(credentials)),
+            updates: Arc::new(Mutex::new(())),
         };
 
+        if let Some((pending, previous)) = rotation {
+            let next = manager.read_key().clone();
+            let staged = manager.stage_rotation(&pending, previous.as_ref(), next.as_ref())?;
+            manager.finish_rotation(staged, &pending)?;
+        }
+        manager.startup_recoveries = manager.recover_unreadable_files()?;
+
         if !manager.routines_path.exists() {
             manager.write_json(&manager.routines_path, &Vec::<Routine>::new())?;
         }
@@ -90,6 +376,15 @@ impl DataManager {
             manager.save_routines(&[template])?;
         }
 
+        {
+            let mut stamps = manager.lock_stamps();
+            for data_file in DataFile::WATCHED {
+                if let Some(stamp) = manager.read_stamp(data_file)? {
+                    stamps.insert(data_file, stamp);
+                }
+            }
+        }
+
         Ok(manager)
     }
 
@@ -109,15 +404,47 @@ impl DataManager {
         &self.settings_path
     }
 
+    pub fn daily_aggregates_path(&self) -> &Path {
+        &self.daily_aggregates_path
+    }
+
+    pub fn goals_path(&self) -> &Path {
+        &self.goals_path
+    }
+
     pub fn active_session_path(&self) -> &Path {
         &self.active_session_path
     }
 
+    pub fn backups_dir(&self) -> &Path {
+        &self.backups_dir
+    }
+
+    pub fn startup_recoveries(&self) -> &[StartupRecovery] {
+        &self.startup_recoveries
+    }
+
+    fn data_file_path(&self, data_file: DataFile) -> &Path {
+        match data_file {
+            DataFile::Routines => &self.routines_path,
+            DataFile::Sessions => &self.sessions_path,
+            DataFile::Settings => &self.settings_path,
+            DataFile::DailyAggregates => &self.daily_aggregates_path,
+            DataFile::Goals => &self.goals_path,
+        }
+    }
+
+    fn data_file_for_path(&self, path: &Path) -> Option<DataFile> {
+        DataFile::ALL
+            .into_iter()
+            .find(|file| self.data_file_path(*file) == path)
+    }
+
     pub fn load_routines(&self) -> DataResult<Vec<Routine>> {
         if !self.routines_path.exists() {
             return Ok(Vec::new());
         }
-        let contents = fs::read_to_string(&self.routines_path)?;
+        let contents = self.read_contents(&self.routines_path)?;
         if contents.trim().is_empty() {
             return Ok(Vec::new());
         }
@@ -126,6 +453,7 @@ impl DataManager {
     }
 
     pub fn save_routine(&self, routine: Routine) -> DataResult<()> {
+        let _updates = self.lock_updates();
         let mut routines = self.load_routines()?;
         if let Some(existing) = routines.iter_mut().find(|item| item.id == routine.id) {
             *existing = routine;
@@ -143,7 +471,7 @@ impl DataManager {
         if !self.sessions_path.exists() {
             return Ok(Vec::new());
         }
-        let contents = fs::read_to_string(&self.sessions_path)?;
+        let contents = self.read_contents(&self.sessions_path)?;
         if contents.trim().is_empty() {
             return Ok(Vec::new());
         }
@@ -155,7 +483,7 @@ impl DataManager {
         if !self.settings_path.exists() {
             return Ok(AppSettings::default());
         }
-        let contents = fs::read_to_string(&self.settings_path)?;
+        let contents = self.read_contents(&self.settings_path)?;
         if contents.trim().is_empty() {
             return Ok(AppSettings::default());
         }
@@ -171,7 +499,7 @@ impl DataManager {
         if !self.active_session_path.exists() {
             return Ok(None);
         }
-        let contents = fs::read_to_string(&self.active_session_path)?;
+        let contents = self.read_contents(&self.active_session_path)?;
         if contents.trim().is_empty() {
             return Ok(None);
         }
@@ -190,18 +518,198 @@ impl DataManager {
         Ok(())
     }
 
+    pub fn load_sync_state(&self) -> DataResult<Option<SyncState>> {
+        if !self.sync_state_path.exists() {
+            return Ok(None);
+        }
+        let contents = self.read_contents(&self.sync_state_path)?;
+        if contents.trim().is_empty() {
+            return Ok(None);
+        }
+        let state = serde_json::from_str(&contents)?;
+        Ok(Some(state))
+    }
+
+    pub fn save_sync_state(&self, state: &SyncState) -> DataResult<()> {
+        self.write_json(&self.sync_state_path, state)
+    }
+
+    /// Key for the change logs in a sync folder, `None` when they are plain.
+    /// An encrypted device creates the folder's key on its first sync; every
+    /// device then needs the same passphrase or keyfile to take part.
+    pub fn sync_key(&self, folder: &Path) -> DataResult<Option<DataKey>> {
+        let credentials = self.read_credentials();
+        match (data_crypto::load_folder_key(folder)?, credentials.as_ref()) {
+            (Some(config), Some(source)) => {
+                data_crypto::unlock_folder_key(&config, source).map(Some)
+            }
+            (Some(_), None) => Err(DataError::SyncFolderEncrypted),
+            (None, Some(source)) => {
+                let (key, config) = data_crypto::create_folder_key(source)?;
+                data_crypto::save_folder_key(folder, &config)?;
+                Ok(Some(key))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Appends records to a sync change log, one per line, encrypted with the
+    /// folder's key when it has one.
+    pub fn append_log_lines(
+        &self,
+        path: &Path,
+        lines: &[String],
+        folder_key: Option<&DataKey>,
+    ) -> DataResult<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        // Held until appended so a key rotation cannot re-key the log under us.
+        let _key = self.read_key();
+        let mut bytes = Vec::new();
+        for line in lines {
+            let encoded = encode_contents(folder_key, line.as_bytes().to_vec())?;
+            bytes.extend_from_slice(encoded.trim_ascii_end());
+            bytes.push(b'\n');
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        file.write_all(&bytes)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Decodes a change log line. Lines written before folders had a key of
+    /// their own were sealed with the writing device's data key.
+    pub fn decode_log_line(&self, line: &[u8], folder_key: Option<&DataKey>) -> DataResult<String> {
+        match decode_contents(folder_key, line.to_vec()) {
+            Err(DataError::KeyRequired | DataError::WrongKey) => {
+                decode_contents(self.read_key().as_ref(), line.to_vec())
+            }
+            result => result,
+        }
+    }
+
+    /// Adds or replaces one session and updates the stats cache in place.
     pub fn save_session(&self, session: Session) -> DataResult<()> {
+        let _updates = self.lock_updates();
         let mut sessions = self.load_sessions()?;
-        if let Some(existing) = sessions.iter_mut().find(|item| item.id == session.id) {
-            *existing = session;
-        } else {
-            sessions.push(session);
-        }
-        self.save_sessions(&sessions)
+        let previous_stamp = self.read_stamp(DataFile::Sessions)?;
+        let replaced = match sessions.iter_mut().find(|item| item.id == session.id) {
+            Some(existing) => Some(std::mem::replace(existing, session.clone())),
+            None => {
+                sessions.push(session.clone());
+                None
+            }
+        };
+        let stamp = self.write_json_stamped(&self.sessions_path, &sessions)?;
+        let updated = self.load_stats_cache().and_then(|cache| {
+            let settings = self.load_settings()?.calendar;
+            let Some(mut cache) = cache.filter(|cache| {
+                Some(cache.sessions_stamp) == previous_stamp && cache.is_for(&zone_key(), &settings)
+            }) else {
+                return self.rebuild_stats_cache_from(&sessions, stamp);
+            };
+            if let Some(replaced) = &replaced {
+                cache.remove_session(replaced, &Local, &settings);
+            }
+            cache.add_session(&session, &Local, &settings);
+            cache.sessions_stamp = stamp;
+            self.write_json(&self.stats_cache_path, &cache)
+        });
+        self.drop_stats_cache_on_error(updated);
+        Ok(())
     }
 
     pub fn save_sessions(&self, sessions: &[Session]) -> DataResult<()> {
-        self.write_json(&self.sessions_path, sessions)
+        let stamp = self.write_json_stamped(&self.sessions_path, sessions)?;
+        let rebuilt = self.rebuild_stats_cache_from(sessions, stamp);
+        self.drop_stats_cache_on_error(rebuilt);
+        Ok(())
+    }
+
+    /// Stats for sessions started within `from..=to` plus the daily aggregates
+    /// in that range. Whole local days come from the stats cache; a range that
+    /// cuts through a day falls back to summing the sessions themselves.
+    pub fn load_session_stats(&self, from: &str, to: &str) -> DataResult<SessionStats> {
+        let from_at = Self::parse_datetime(from)?.with_timezone(&Utc);
+        let to_at = Self::parse_datetime(to)?.with_timezone(&Utc);
+        let aggregates = self.load_daily_aggregates_in_range(from, to)?;
+        let settings = self.load_settings()?.calendar;
+        let cached = self.current_stats_cache(&settings)?.stats_between(
+            &aggregates,
+            from_at,
+            to_at,
+            &Local,
+            &settings,
+        );
+        match cached {
+            Some(stats) => Ok(stats),
+            None => Ok(calculate_session_stats(
+                &self.load_sessions_in_range(from, to)?,
+                &aggregates,
+            )),
+        }
+    }
+
+    /// Rebuilds the stats cache from every stored session.
+    pub fn rebuild_stats_cache(&self) -> DataResult<StatsCache> {
+        let stamp = self.read_stamp(DataFile::Sessions)?.unwrap_or_default();
+        let cache = StatsCache::build(
+            &self.load_sessions()?,
+            stamp,
+            &Local,
+            &zone_key(),
+            &self.load_settings()?.calendar,
+        );
+        self.write_json(&self.stats_cache_path, &cache)?;
+        Ok(cache)
+    }
+
+    /// The stats cache, rebuilt first when it is missing, unreadable, cut for
+    /// another zone or rollover hour, or behind the sessions file.
+    fn current_stats_cache(&self, settings: &CalendarSettings) -> DataResult<StatsCache> {
+        let stamp = self.read_stamp(DataFile::Sessions)?.unwrap_or_default();
+        match self.load_stats_cache() {
+            Ok(Some(cache))
+                if cache.sessions_stamp == stamp && cache.is_for(&zone_key(), settings) =>
+            {
+                Ok(cache)
+            }
+            _ => self.rebuild_stats_cache(),
+        }
+    }
+
+    fn load_stats_cache(&self) -> DataResult<Option<StatsCache>> {
+        if !self.stats_cache_path.exists() {
+            return Ok(None);
+        }
+        let contents = self.read_contents(&self.stats_cache_path)?;
+        if contents.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&contents)?))
+    }
+
+    fn rebuild_stats_cache_from(&self, sessions: &[Session], stamp: u64) -> DataResult<()> {
+        let cache = StatsCache::build(
+            sessions,
+            stamp,
+            &Local,
+            &zone_key(),
+            &self.load_settings()?.calendar,
+        );
+        self.write_json(&self.stats_cache_path, &cache)
+    }
+
+    /// The cache is derived data: rather than fail a save over it, remove it
+    /// so the next query rebuilds it.
+    fn drop_stats_cache_on_error(&self, result: DataResult<()>) {
+        if result.is_err() {
+            let _ = fs::remove_file(&self.stats_cache_path);
+        }
     }
 
     pub fn load_sessions_in_range(&self, from: &str, to: &str) -> DataResult<Vec<Session>> {
@@ -219,32 +727,664 @@ impl DataManager {
             })
     }
 
+    pub fn load_daily_aggregates(&self) -> DataResult<Vec<DailyAggregate>> {
+        if !self.daily_aggregates_path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = self.read_contents(&self.daily_aggregates_path)?;
+        if contents.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let aggregates = serde_json::from_str(&contents)?;
+        Ok(aggregates)
+    }
+
+    pub fn save_daily_aggregates(&self, aggregates: &[DailyAggregate]) -> DataResult<()> {
+        self.write_json(&self.daily_aggregates_path, aggregates)
+    }
+
+    pub fn load_goals(&self) -> DataResult<Vec<Goal>> {
+        if !self.goals_path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = self.read_contents(&self.goals_path)?;
+        if contents.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let goals = serde_json::from_str(&contents)?;
+        Ok(goals)
+    }
+
+    pub fn save_goals(&self, goals: &[Goal]) -> DataResult<()> {
+        self.write_json(&self.goals_path, goals)
+    }
+
+    /// Aggregates whose `aggregate_instant`, noon UTC of their UTC date, lies
+    /// within `from..=to`. Each aggregate has a single such instant, so
+    /// consecutive periods never both count it.
+    pub fn load_daily_aggregates_in_range(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> DataResult<Vec<DailyAggregate>> {
+        let from_at = Self::parse_datetime(from)?.with_timezone(&Utc);
+        let to_at = Self::parse_datetime(to)?.with_timezone(&Utc);
+        let aggregates = self.load_daily_aggregates()?;
+        aggregates
+            .into_iter()
+            .try_fold(Vec::new(), |mut acc, aggregate| {
+                NaiveDate::parse_from_str(&aggregate.date, "%Y-%m-%d")?;
+                if aggregate_instant(&aggregate).is_some_and(|at| at >= from_at && at <= to_at) {
+                    acc.push(aggregate);
+                }
+                Ok(acc)
+            })
+    }
+
+    pub fn create_backup(
+        &self,
+        data_file: DataFile,
+        reason: BackupReason,
+    ) -> DataResult<Option<BackupInfo>> {
+        self.create_backup_at(data_file, reason, Utc::now())
+    }
+
+    pub fn backup_all(&self, reason: BackupReason) -> DataResult<Vec<BackupInfo>> {
+        let now = Utc::now();
+        let mut created = Vec::new();
+        for data_file in DataFile::ALL {
+            if let Some(info) = self.create_backup_at(data_file, reason, now)? {
+                created.push(info);
+            }
+        }
+        Ok(created)
+    }
+
+    pub fn list_backups(&self) -> DataResult<Vec<BackupInfo>> {
+        if !self.backups_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(&self.backups_dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some((data_file, reason, created_at)) = parse_backup_name(&file_name) else {
+                continue;
+            };
+            backups.push(BackupInfo {
+                file_name,
+                data_file,
+                reason,
+                created_at: created_at.to_rfc3339(),
+                size_bytes: entry.metadata()?.len(),
+            });
+        }
+        backups.sort_by(|left, right| {
+            right
+                .created_at
+                .cmp(&left.created_at)
+                .then_with(|| right.file_name.cmp(&left.file_name))
+        });
+        Ok(backups)
+    }
+
+    pub fn restore_backup(&self, file_name: &str) -> DataResult<BackupInfo> {
+        let _updates = self.lock_updates();
+        let info = self
+            .list_backups()?
+            .into_iter()
+            .find(|item| item.file_name == file_name)
+            .ok_or_else(|| DataError::BackupNotFound(file_name.to_string()))?;
+        let contents = self.read_contents(&self.backups_dir.join(&info.file_name))?;
+        info.data_file.check_contents(&contents)?;
+
+        self.create_backup(info.data_file, BackupReason::Restore)?;
+        let path = self.data_file_path(info.data_file);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, self.encode_contents(contents.into_bytes())?)?;
+        replace_file(&temp_path, path)?;
+        Ok(info)
+    }
+
+    fn create_backup_at(
+        &self,
+        data_file: DataFile,
+        reason: BackupReason,
+        now: DateTime<Utc>,
+    ) -> DataResult<Option<BackupInfo>> {
+        let source = self.data_file_path(data_file);
+        if !source.exists() {
+            return Ok(None);
+        }
+        let now = now.trunc_subsecs(3);
+        fs::create_dir_all(&self.backups_dir)?;
+
+        let timestamp = now.format(BACKUP_TIMESTAMP_FORMAT).to_string();
+        let mut file_name = backup_name(data_file, &timestamp, reason, 0);
+        let mut attempt = 0;
+        while self.backups_dir.join(&file_name).exists() {
+            attempt += 1;
+            file_name = backup_name(data_file, &timestamp, reason, attempt);
+        }
+        let target = self.backups_dir.join(&file_name);
+        let size_bytes = fs::copy(source, &target)?;
+        self.prune_backups(data_file, reason)?;
+
+        Ok(Some(BackupInfo {
+            file_name,
+            data_file,
+            reason,
+            created_at: now.to_rfc3339(),
+            size_bytes,
+        }))
+    }
+
+    fn backup_daily_if_due(&self, data_file: DataFile, now: DateTime<Utc>) -> DataResult<()> {
+        let today = now.date_naive();
+        let has_today = self.list_backups()?.iter().any(|item| {
+            item.data_file == data_file
+                && item.reason == BackupReason::Daily
+                && DateTime::parse_from_rfc3339(&item.created_at)
+                    .map(|created| created.with_timezone(&Utc).date_naive() == today)
+                    .unwrap_or(false)
+        });
+        if !has_today {
+            self.create_backup_at(data_file, BackupReason::Daily, now)?;
+        }
+        Ok(())
+    }
+
+    fn prune_backups(&self, data_file: DataFile, reason: BackupReason) -> DataResult<()> {
+        let stale = self
+            .list_backups()?
+            .into_iter()
+            .filter(|item| item.data_file == data_file && item.reason == reason)
+            .skip(reason.kept());
+        for item in stale {
+            fs::remove_file(self.backups_dir.join(item.file_name))?;
+        }
+        Ok(())
+    }
+
+    fn recover_unreadable_files(&self) -> DataResult<Vec<StartupRecovery>> {
+        let mut recoveries = Vec::new();
+        for data_file in DataFile::ALL {
+            let path = self.data_file_path(data_file);
+            if !path.exists() {
+                continue;
+            }
+            let readable = self
+                .read_contents(path)
+                .and_then(|contents| data_file.check_contents(&contents));
+            match readable {
+                Ok(()) => continue,
+                // A key mismatch is not corruption; leave the file alone.
+                Err(err @ (DataError::KeyRequired | DataError::WrongKey)) => return Err(err),
+                Err(_) => {}
+            }
+
+            let timestamp = Utc::now().format(BACKUP_TIMESTAMP_FORMAT);
+            let corrupt_copy = self
+                .base_dir
+                .join(format!("{}.corrupt-{timestamp}.json", data_file.stem()));
+            fs::rename(path, &corrupt_copy)?;
+
+            let mut restored_from = None;
+            for backup in self
+                .list_backups()?
+                .into_iter()
+                .filter(|item| item.data_file == data_file)
+            {
+                let backup_path = self.backups_dir.join(&backup.file_name);
+                let Ok(contents) = self.read_contents(&backup_path) else {
+                    continue;
+                };
+                if data_file.check_contents(&contents).is_ok() {
+                    fs::write(path, self.encode_contents(contents.into_bytes())?)?;
+                    restored_from = Some(backup.file_name);
+                    break;
+                }
+            }
+
+            recoveries.push(StartupRecovery {
+                data_file,
+                restored_from,
+                corrupt_copy,
+            });
+        }
+        Ok(recoveries)
+    }
+
+    pub fn encryption_status(&self) -> DataResult<EncryptionStatus> {
+        let config = data_crypto::load_config(&self.base_dir)?;
+        Ok(EncryptionStatus {
+            enabled: config.is_some(),
+            key_source: config.map(|config| config.key_source),
+        })
+    }
+
+    /// Re-encrypts every data file, backup and this device's sync log under a
+    /// new key, or writes them back as plain JSON when `key_source` is `None`.
+    ///
+    /// The rotation is recorded in `encryption.pending.json` before anything is
+    /// re-encoded. Files are staged into temporary copies, so a staging failure
+    /// leaves the directory untouched; once the first copy is swapped in, the
+    /// record lets the next `open` finish the rotation with either credential.
+    pub fn set_encryption(&self, key_source: Option<&KeySource>) -> DataResult<()> {
+        let next = key_source.map(data_crypto::create_key).transpose()?;
+        let next_key = next.as_ref().map(|(key, _)| key.clone());
+        let folder = match (key_source, &next_key) {
+            (Some(source), Some(next)) => self.pending_folder_key(source, next)?,
+            _ => None,
+        };
+        // Held throughout so no concurrent write lands under the old key.
+        let mut current_key = self.write_key();
+        let previous_key = current_key.clone();
+
+        let pending = PendingRotation {
+            previous_key: match (&previous_key, &next_key) {
+                (Some(previous), Some(next)) => Some(data_crypto::seal_key(next, previous)?),
+                _ => None,
+            },
+            next_key: match (&previous_key, &next_key) {
+                (Some(previous), Some(next)) => Some(data_crypto::seal_key(previous, next)?),
+                _ => None,
+            },
+            folder,
+            next: next.map(|(_, config)| config),
+        };
+        data_crypto::save_pending(&self.base_dir, &pending)?;
+        let staged = self
+            .stage_rotation(&pending, previous_key.as_ref(), next_key.as_ref())
+            .inspect_err(|_| {
+                let _ = data_crypto::remove_pending(&self.base_dir);
+            })?;
+        self.finish_rotation(staged, &pending)?;
+
+        *current_key = next_key;
+        *self.write_credentials() = key_source.cloned();
+        Ok(())
+    }
+
+    /// The sync folder's key re-sealed under the incoming credential, when a
+    /// folder is set up and its key can be opened with either credential.
+    fn pending_folder_key(
+        &self,
+        source: &KeySource,
+        next_key: &DataKey,
+    ) -> DataResult<Option<PendingFolderKey>> {
+        let Some(folder) = self.load_settings()?.sync.folder.map(PathBuf::from) else {
+            return Ok(None);
+        };
+        let folder_key = match data_crypto::load_folder_key(&folder)? {
+            Some(config) => {
+                let current = self
+                    .read_credentials()
+                    .as_ref()
+                    .and_then(|current| data_crypto::unlock_folder_key(&config, current).ok());
+                match current.or_else(|| data_crypto::unlock_folder_key(&config, source).ok()) {
+                    Some(key) => key,
+                    // Someone else's folder; leave it to the next sync to report.
+                    None => return Ok(None),
+                }
+            }
+            None => data_crypto::create_folder_key(source)?.0,
+        };
+        Ok(Some(PendingFolderKey {
+            log: self
+                .load_sync_state()?
+                .map(|state| folder_sync::log_path(&folder, &state.device_id)),
+            config: data_crypto::seal_folder_key(&folder_key, source)?,
+            key: data_crypto::seal_key(next_key, &folder_key)?,
+            folder,
+        }))
+    }
+
+    /// Re-encodes every file of a rotation into a temporary copy. A file may
+    /// already carry the incoming key when an earlier attempt was cut short.
+    fn stage_rotation(
+        &self,
+        pending: &PendingRotation,
+        previous_key: Option<&DataKey>,
+        next_key: Option<&DataKey>,
+    ) -> DataResult<Vec<(PathBuf, PathBuf, u64)>> {
+        let folder_key = match (&pending.folder, next_key) {
+            (Some(folder), Some(next)) => Some(data_crypto::open_sealed_key(next, &folder.key)?),
+            _ => None,
+        };
+        let keys = [next_key, previous_key];
+
+        let mut paths: Vec<PathBuf> = DataFile::ALL
+            .into_iter()
+            .map(|file| self.data_file_path(file).to_path_buf())
+            .collect();
+        paths.push(self.active_session_path.clone());
+        paths.push(self.sync_state_path.clone());
+        paths.push(self.stats_cache_path.clone());
+        paths.extend(
+            self.list_backups()?
+                .into_iter()
+                .map(|item| self.backups_dir.join(item.file_name)),
+        );
+        let log = pending
+            .folder
+            .as_ref()
+            .and_then(|folder| folder.log.clone());
+
+        let mut staged: Vec<(PathBuf, PathBuf, u64)> = Vec::new();
+        for path in paths.iter().chain(&log).filter(|path| path.exists()) {
+            let temp_path = path.with_extension("rekey");
+            let encoded = if Some(path) == log.as_ref() {
+                fs::read(path).map_err(DataError::from).and_then(|bytes| {
+                    // Lines from before the folder had a key carry the data key.
+                    let log_keys = [folder_key.as_ref(), previous_key, next_key];
+                    let mut encoded = Vec::new();
+                    for line in bytes.split(|byte| *byte == b'\n') {
+                        if line.trim_ascii().is_empty() {
+                            continue;
+                        }
+                        let plaintext = decode_with_any(&log_keys, line)?;
+                        let line = encode_contents(folder_key.as_ref(), plaintext.into_bytes())?;
+                        encoded.extend_from_slice(line.trim_ascii_end());
+                        encoded.push(b'\n');
+                    }
+                    Ok(encoded)
+                })
+            } else {
+                fs::read(path)
+                    .map_err(DataError::from)
+                    .and_then(|bytes| decode_with_any(&keys, &bytes))
+                    .and_then(|plaintext| encode_contents(next_key, plaintext.into_bytes()))
+            };
+            let stage = encoded.and_then(|bytes| {
+                fs::write(&temp_path, &bytes)?;
+                Ok(content_hash(&bytes))
+            });
+            match stage {
+                Ok(stamp) => staged.push((temp_path, path.clone(), stamp)),
+                Err(err) => {
+                    let _ = fs::remove_file(&temp_path);
+                    for (temp_path, _, _) in staged {
+                        let _ = fs::remove_file(temp_path);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(staged)
+    }
+
+    /// Swaps the staged copies in, then commits the new config and drops the
+    /// rotation record. Safe to repeat after a crash at any point.
+    fn finish_rotation(
+        &self,
+        staged: Vec<(PathBuf, PathBuf, u64)>,
+        pending: &PendingRotation,
+    ) -> DataResult<()> {
+        let mut stamps = self.lock_stamps();
+        for (temp_path, path, stamp) in staged {
+            replace_file(&temp_path, &path)?;
+            if let Some(data_file) = self
+                .data_file_for_path(&path)
+                .filter(|file| DataFile::WATCHED.contains(file))
+            {
+                stamps.insert(data_file, stamp);
+            }
+        }
+        if let Some(folder) = &pending.folder {
+            data_crypto::save_folder_key(&folder.folder, &folder.config)?;
+        }
+        data_crypto::commit_config(&self.base_dir, pending.next.as_ref())?;
+        data_crypto::remove_pending(&self.base_dir)
+    }
+
+    /// Returns watched files whose contents changed on disk since this process
+    /// last wrote or checked them. Each change is reported once.
+    pub fn take_external_changes(&self) -> DataResult<Vec<DataFile>> {
+        let mut stamps = self.lock_stamps();
+        let mut changed = Vec::new();
+        for data_file in DataFile::WATCHED {
+            let current = self.read_stamp(data_file)?;
+            if current != stamps.get(&data_file).copied() {
+                match current {
+                    Some(stamp) => stamps.insert(data_file, stamp),
+                    None => stamps.remove(&data_file),
+                };
+                changed.push(data_file);
+            }
+        }
+        Ok(changed)
+    }
+
+    fn lock_stamps(&self) -> std::sync::MutexGuard<'_, HashMap<DataFile, u64>> {
+        self.file_stamps
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Serializes updates that load a data file, change it and save it back,
+    /// so a save made meanwhile is not overwritten by a stale copy.
+    /// `save_session` and `save_routine` take it themselves; callers holding
+    /// it write through the whole-file `save_*` methods, as it is not
+    /// reentrant.
+    pub fn lock_updates(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.updates
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn read_key(&self) -> std::sync::RwLockReadGuard<'_, Option<DataKey>> {
+        self.key
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write_key(&self) -> std::sync::RwLockWriteGuard<'_, Option<DataKey>> {
+        self.key
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn read_credentials(&self) -> std::sync::RwLockReadGuard<'_, Option<KeySource>> {
+        self.credentials
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write_credentials(&self) -> std::sync::RwLockWriteGuard<'_, Option<KeySource>> {
+        self.credentials
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Reads a data file, decrypting it when it was written encrypted.
+    fn read_contents(&self, path: &Path) -> DataResult<String> {
+        let bytes = fs::read(path)?;
+        decode_contents(self.read_key().as_ref(), bytes)
+    }
+
+    fn encode_contents(&self, plaintext: Vec<u8>) -> DataResult<Vec<u8>> {
+        encode_contents(self.read_key().as_ref(), plaintext)
+    }
+
+    /// Content hash of `data_file` on disk, reused while its length and
+    /// modification time stay as they were when it was last hashed.
+    fn read_stamp(&self, data_file: DataFile) -> DataResult<Option<u64>> {
+        let path = self.data_file_path(data_file);
+        let mark = match fs::metadata(path) {
+            Ok(metadata) => file_mark(&metadata),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.lock_hashed_files().remove(&data_file);
+                return Ok(None);
+            }
+            Err(err) => return Err(DataError::from(err)),
+        };
+        if let Some((known, stamp)) = self.lock_hashed_files().get(&data_file) {
+            if mark.as_ref() == Some(known) {
+                return Ok(Some(*stamp));
+            }
+        }
+        let stamp = match fs::read(path) {
+            Ok(bytes) => content_hash(&bytes),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(DataError::from(err)),
+        };
+        if let Some(mark) = mark {
+            self.lock_hashed_files().insert(data_file, (mark, stamp));
+        }
+        Ok(Some(stamp))
+    }
+
+    fn lock_hashed_files(&self) -> std::sync::MutexGuard<'_, HashMap<DataFile, (FileMark, u64)>> {
+        self.hashed_files
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
     fn parse_datetime(value: &str) -> DataResult<DateTime<chrono::FixedOffset>> {
         Ok(DateTime::parse_from_rfc3339(value)?)
     }
 
     fn write_json<T: Serialize + ?Sized>(&self, path: &Path, value: &T) -> DataResult<()> {
+        self.write_json_stamped(path, value).map(|_| ())
+    }
+
+    /// Writes `value` like `write_json` and returns the content hash of the
+    /// bytes written.
+    fn write_json_stamped<T: Serialize + ?Sized>(&self, path: &Path, value: &T) -> DataResult<u64> {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
+        if let Some(data_file) = self.data_file_for_path(path) {
+            if path.exists() {
+                self.backup_daily_if_due(data_file, Utc::now())?;
+            }
+        }
+        let watched = self
+            .data_file_for_path(path)
+            .filter(|file| DataFile::WATCHED.contains(file));
+        let mut bytes = serde_json::to_vec_pretty(value)?;
+        bytes.push(b'\n');
+        // Held until the file is replaced so a key rotation cannot interleave.
+        let key = self.read_key();
+        let bytes = encode_contents(key.as_ref(), bytes)?;
+
+        // Hold the stamps while replacing the file so the watcher never sees
+        // our own write as an external change.
+        let mut stamps = self.lock_stamps();
         let temp_path = path.with_extension("tmp");
         let file = fs::File::create(&temp_path)?;
         let mut writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(&mut writer, value)?;
-        writer.write_all(b"\n")?;
+        writer.write_all(&bytes)?;
         writer.flush()?;
+        drop(writer);
 
-        match fs::rename(&temp_path, path) {
-            Ok(()) => Ok(()),
-            Err(_err) if path.exists() => {
-                let _ = fs::remove_file(path);
-                fs::rename(&temp_path, path).map_err(DataError::from)
-            }
-            Err(err) => Err(DataError::from(err)),
+        replace_file(&temp_path, path)?;
+        let stamp = content_hash(&bytes);
+        if let Some(data_file) = watched {
+            stamps.insert(data_file, stamp);
+        }
+        if let Some(data_file) = self.data_file_for_path(path) {
+            match fs::metadata(path).ok().as_ref().and_then(file_mark) {
+                Some(mark) => self.lock_hashed_files().insert(data_file, (mark, stamp)),
+                None => self.lock_hashed_files().remove(&data_file),
+            };
+        }
+        Ok(stamp)
+    }
+}
+
+/// Names the local zone days are cut in, for telling whether the stats cache
+/// still applies.
+fn zone_key() -> String {
+    let zone = current_zone();
+    zone.time_zone
+        .unwrap_or_else(|| format!("UTC{:+}", zone.utc_offset_minutes))
+}
+
+fn decode_contents(key: Option<&DataKey>, bytes: Vec<u8>) -> DataResult<String> {
+    let bytes = if data_crypto::is_encrypted(&bytes) {
+        data_crypto::decrypt(key.ok_or(DataError::KeyRequired)?, &bytes)?
+    } else {
+        bytes
+    };
+    String::from_utf8(bytes)
+        .map_err(|err| DataError::Io(io::Error::new(io::ErrorKind::InvalidData, err)))
+}
+
+/// Decodes with the first key that opens `bytes`.
+fn decode_with_any(keys: &[Option<&DataKey>], bytes: &[u8]) -> DataResult<String> {
+    let mut result = Err(DataError::KeyRequired);
+    for key in keys {
+        result = decode_contents(*key, bytes.to_vec());
+        if !matches!(result, Err(DataError::KeyRequired | DataError::WrongKey)) {
+            break;
+        }
+    }
+    result
+}
+
+fn encode_contents(key: Option<&DataKey>, plaintext: Vec<u8>) -> DataResult<Vec<u8>> {
+    match key {
+        Some(key) => data_crypto::encrypt(key, &plaintext),
+        None => Ok(plaintext),
+    }
+}
+
+/// 64-bit FNV-1a of `bytes`. Stamps are stored in the stats cache, so the
+/// hash must not change between builds the way `DefaultHasher` may.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Length and modification time of a data file as it was hashed.
+type FileMark = (u64, SystemTime);
+
+fn file_mark(metadata: &fs::Metadata) -> Option<FileMark> {
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+fn replace_file(temp_path: &Path, path: &Path) -> DataResult<()> {
+    match fs::rename(temp_path, path) {
+        Ok(()) => Ok(()),
+        Err(_err) if path.exists() => {
+            let _ = fs::remove_file(path);
+            fs::rename(temp_path, path).map_err(DataError::from)
         }
+        Err(err) => Err(DataError::from(err)),
+    }
+}
+
+fn backup_name(data_file: DataFile, timestamp: &str, reason: BackupReason, attempt: u32) -> String {
+    if attempt == 0 {
+        format!("{}-{timestamp}-{}.json", data_file.stem(), reason.as_str())
+    } else {
+        format!(
+            "{}-{timestamp}.{attempt}-{}.json",
+            data_file.stem(),
+            reason.as_str()
+        )
     }
 }
 
+fn parse_backup_name(file_name: &str) -> Option<(DataFile, BackupReason, DateTime<Utc>)> {
+    let name = file_name.strip_suffix(".json")?;
+    let mut parts = name.rsplitn(3, '-');
+    let reason = BackupReason::from_str(parts.next()?)?;
+    let timestamp = parts.next()?;
+    let data_file = DataFile::from_stem(parts.next()?)?;
+    let timestamp = timestamp
+        .split_once('.')
+        .map(|(base, _attempt)| base)
+        .unwrap_or(timestamp);
+    let created_at = NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT)
+        .ok()?
+        .and_utc();
+    Some((data_file, reason, created_at))
+}
+
 fn default_template_routine() -> Routine {
     Routine {
         id: "routine-template-10min".to_string(),
@@ -304,6 +1444,7 @@ fn default_template_routine() -> Routine {
         notifications: true,
         sound_default: SoundSetting::On,
         sound_scheme: SoundScheme::Default,
+        sounds: SoundAssets::default(),
     }
 }
 
@@ -342,18 +1483,30 @@ fn template_step(
         instruction: instruction.to_string(),
         sound_override: SoundOverride::Inherit,
         count_as_break,
+        sounds: SoundAssets::default(),
+        warnings: StepWarnings::default(),
         check_in,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{DataError, DataManager};
-    use crate::models::{AppSettings, CheckInMode, Session, SessionTotals, SoundSetting};
+    use super::{
+        content_hash, replace_file, BackupReason, DataDirLock, DataError, DataFile, DataManager,
+        EVENT_BACKUPS_KEPT,
+    };
+    use crate::calendar::{local_period_containing, parse_instant, PeriodKind};
+    use crate::data_crypto::{self, KeySource, PendingRotation};
+    use crate::folder_sync;
+    use crate::models::{
+        AppSettings, CalendarSettings, CheckInMode, DailyAggregate, Session, SessionTotals,
+        SoundSetting,
+    };
+    use crate::session_stats::calculate_session_stats;
     use std::fs;
     use std::path::PathBuf;
     use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::time::{SystemTime, UNIX_EPOCH};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
@@ -380,6 +1533,7 @@ mod tests {
             cycles_count: 0,
             check_in_done_count: 0,
             check_in_skip_count: 0,
+            ..SessionTotals::default()
         }
     }
 
@@ -392,9 +1546,76 @@ mod tests {
             step_runs: Vec::new(),
             totals: sample_totals(),
             muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
         }
     }
 
+    #[test]
+    fn cached_stats_match_summed_sessions() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let starts = [
+            "2025-03-08T10:00:00Z",
+            "2025-03-10T12:00:00Z",
+            "2025-03-10T13:00:00Z",
+            "2025-03-11T09:00:00Z",
+        ];
+        for (index, started_at) in starts.iter().enumerate() {
+            let mut session = sample_session(&format!("session-{index}"), started_at);
+            session.totals.work_seconds = 100 * (index as u32 + 1);
+            session.muted_during_session = index % 2 == 0;
+            manager.save_session(session).expect("save session");
+        }
+        let mut edited = sample_session("session-1", starts[1]);
+        edited.totals.work_seconds = 999;
+        manager.save_session(edited).expect("replace session");
+        assert!(dir.join("stats_cache.json").exists());
+
+        let settings = CalendarSettings::default();
+        let at = parse_instant(starts[1]).expect("instant");
+        let day = local_period_containing(PeriodKind::Day, at, &settings);
+        let week = local_period_containing(PeriodKind::Week, at, &settings);
+        let ranges = [
+            (day.from.as_str(), day.to.as_str()),
+            (day.from.as_str(), "2025-03-10T12:30:00Z"),
+            ("2025-03-10T12:30:00Z", day.to.as_str()),
+            (week.from.as_str(), week.to.as_str()),
+        ];
+        let check = |manager: &DataManager| {
+            for (from, to) in ranges {
+                let expected = calculate_session_stats(
+                    &manager.load_sessions_in_range(from, to).expect("sessions"),
+                    &manager
+                        .load_daily_aggregates_in_range(from, to)
+                        .expect("aggregates"),
+                );
+                let cached = manager.load_session_stats(from, to).expect("cached stats");
+                assert_eq!(
+                    serde_json::to_value(cached).expect("cached json"),
+                    serde_json::to_value(expected).expect("expected json"),
+                    "{from}..{to}"
+                );
+            }
+        };
+        check(&manager);
+
+        let mut sessions = manager.load_sessions().expect("load sessions");
+        sessions[0].started_at = starts[2].to_string();
+        fs::write(
+            dir.join("sessions.json"),
+            serde_json::to_vec(&sessions).expect("encode sessions"),
+        )
+        .expect("write sessions elsewhere");
+        check(&manager);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn save_and_load_session_roundtrip() {
         let dir = temp_dir();
@@ -464,6 +1685,44 @@ mod tests {
         let _ = fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn aggregates_count_in_one_of_consecutive_local_days() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let aggregate = |date: &str| DailyAggregate {
+            date: date.to_string(),
+            routine_id: "routine-1".to_string(),
+            sessions_count: 1,
+            muted_sessions_count: 0,
+            stopped_sessions_count: 0,
+            recovered_sessions_count: 0,
+            totals: SessionTotals::default(),
+        };
+        manager
+            .save_daily_aggregates(&[aggregate("2025-01-02"), aggregate("2025-01-03")])
+            .expect("save aggregates");
+
+        // Local days in UTC+9: each overlaps two UTC dates.
+        let dates = |from: &str, to: &str| -> Vec<String> {
+            manager
+                .load_daily_aggregates_in_range(from, to)
+                .expect("aggregates in range")
+                .into_iter()
+                .map(|item| item.date)
+                .collect()
+        };
+        assert_eq!(
+            dates("2025-01-01T15:00:00Z", "2025-01-02T14:59:59.999Z"),
+            vec!["2025-01-02"]
+        );
+        assert_eq!(
+            dates("2025-01-02T15:00:00Z", "2025-01-03T14:59:59.999Z"),
+            vec!["2025-01-03"]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn load_sessions_in_range_returns_error_on_invalid_date() {
         let dir = temp_dir();
@@ -526,6 +1785,7 @@ mod tests {
         let settings = AppSettings {
             notifications_enabled: false,
             sound_default: SoundSetting::Off,
+            ..AppSettings::default()
         };
 
         manager.save_settings(&settings).expect("save settings");
@@ -548,4 +1808,379 @@ mod tests {
 
         let _ = fs::remove_dir_all(&dir);
     }
+
+    #[test]
+    fn writes_one_daily_backup_per_day() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let before = fs::read_to_string(manager.sessions_path()).expect("read sessions");
+
+        manager
+            .save_session(sample_session("session-1", "2025-01-01T10:00:00Z"))
+            .expect("save session");
+        manager
+            .save_session(sample_session("session-2", "2025-01-02T10:00:00Z"))
+            .expect("save session");
+
+        let daily: Vec<_> = manager
+            .list_backups()
+            .expect("list backups")
+            .into_iter()
+            .filter(|item| item.data_file == DataFile::Sessions)
+            .collect();
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].reason, BackupReason::Daily);
+        let contents = fs::read_to_string(manager.backups_dir().join(&daily[0].file_name))
+            .expect("read backup");
+        assert_eq!(contents, before);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn event_backups_are_rotated() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+
+        for _ in 0..EVENT_BACKUPS_KEPT + 3 {
+            manager
+                .create_backup(DataFile::Routines, BackupReason::Import)
+                .expect("create backup");
+        }
+
+        let imports = manager
+            .list_backups()
+            .expect("list backups")
+            .into_iter()
+            .filter(|item| item.reason == BackupReason::Import)
+            .count();
+        assert_eq!(imports, EVENT_BACKUPS_KEPT);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restore_backup_replaces_file_and_keeps_current_copy() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let backup = manager
+            .create_backup(DataFile::Routines, BackupReason::Import)
+            .expect("create backup")
+            .expect("backup created");
+        manager.save_routines(&[]).expect("wipe routines");
+
+        manager
+            .restore_backup(&backup.file_name)
+            .expect("restore backup");
+
+        assert_eq!(manager.load_routines().expect("load routines").len(), 1);
+        assert!(manager
+            .list_backups()
+            .expect("list backups")
+            .iter()
+            .any(|item| item.reason == BackupReason::Restore));
+        assert!(matches!(
+            manager.restore_backup("../routines.json"),
+            Err(DataError::BackupNotFound(_))
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn startup_falls_back_to_latest_backup() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        manager
+            .create_backup(DataFile::Routines, BackupReason::Daily)
+            .expect("create backup");
+        fs::write(manager.routines_path(), "{invalid json").expect("corrupt routines");
+
+        let reopened = DataManager::new(&dir).expect("reopen manager");
+
+        assert_eq!(reopened.load_routines().expect("load routines").len(), 1);
+        let recoveries = reopened.startup_recoveries();
+        assert_eq!(recoveries.len(), 1);
+        assert_eq!(recoveries[0].data_file, DataFile::Routines);
+        assert!(recoveries[0].restored_from.is_some());
+        assert_eq!(
+            fs::read_to_string(&recoveries[0].corrupt_copy).expect("read corrupt copy"),
+            "{invalid json"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn startup_without_backup_reseeds_and_keeps_corrupt_copy() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        fs::write(manager.settings_path(), "not json").expect("corrupt settings");
+
+        let reopened = DataManager::new(&dir).expect("reopen manager");
+
+        assert_eq!(
+            reopened.load_settings().expect("load settings"),
+            AppSettings::default()
+        );
+        let recoveries = reopened.startup_recoveries();
+        assert_eq!(recoveries.len(), 1);
+        assert!(recoveries[0].restored_from.is_none());
+        assert!(recoveries[0].corrupt_copy.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn data_dir_lock_is_exclusive_until_dropped() {
+        let dir = temp_dir();
+        let lock = DataDirLock::acquire(&dir).expect("first lock");
+        assert!(matches!(
+            DataDirLock::acquire(&dir),
+            Err(DataError::Locked(_))
+        ));
+        drop(lock);
+        DataDirLock::acquire(&dir).expect("lock after release");
+    }
+
+    #[test]
+    fn reports_external_changes_once_and_ignores_own_writes() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        assert!(manager.take_external_changes().expect("check").is_empty());
+
+        let settings = AppSettings {
+            notifications_enabled: false,
+            ..AppSettings::default()
+        };
+        manager.save_settings(&settings).expect("save settings");
+        assert!(manager.take_external_changes().expect("check").is_empty());
+
+        let other = DataManager::new(&dir).expect("second manager");
+        let mut routines = other.load_routines().expect("load routines");
+        routines[0].name = "Edited elsewhere".to_string();
+        other.save_routines(&routines).expect("save routines");
+
+        assert_eq!(
+            manager.take_external_changes().expect("check"),
+            vec![DataFile::Routines]
+        );
+        assert!(manager.take_external_changes().expect("check").is_empty());
+    }
+
+    #[test]
+    fn stamps_do_not_depend_on_the_build() {
+        assert_eq!(content_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn saves_wait_for_an_update_in_progress() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let updates = manager.lock_updates();
+        let sessions = manager.load_sessions().expect("load sessions");
+
+        let (saved, waiting) = std::sync::mpsc::channel();
+        let other = manager.clone();
+        let saver = std::thread::spawn(move || {
+            other
+                .save_session(sample_session("session-1", "2025-01-01T10:00:00Z"))
+                .expect("save session");
+            saved.send(()).expect("report save");
+        });
+        assert!(waiting.recv_timeout(Duration::from_millis(200)).is_err());
+        manager.save_sessions(&sessions).expect("save sessions");
+        drop(updates);
+
+        saver.join().expect("join saver");
+        let ids: Vec<String> = manager
+            .load_sessions()
+            .expect("load sessions")
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(ids, vec!["session-1"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn passphrase(value: &str) -> KeySource {
+        KeySource::Passphrase {
+            passphrase: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn encrypts_files_and_requires_the_right_key() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        manager
+            .save_session(sample_session("session-1", "2025-01-01T10:00:00Z"))
+            .expect("save session");
+        manager
+            .create_backup(DataFile::Sessions, BackupReason::Import)
+            .expect("backup");
+
+        manager
+            .set_encryption(Some(&passphrase("secret")))
+            .expect("enable encryption");
+        let raw = fs::read_to_string(manager.sessions_path()).expect("read raw sessions");
+        assert!(!raw.contains("session-1"));
+        assert_eq!(manager.load_sessions().expect("load").len(), 1);
+        assert!(manager.encryption_status().expect("status").enabled);
+
+        assert!(matches!(
+            DataManager::new(&dir),
+            Err(DataError::KeyRequired)
+        ));
+        assert!(matches!(
+            DataManager::open(&dir, Some(&passphrase("wrong"))),
+            Err(DataError::WrongKey)
+        ));
+        let reopened = DataManager::open(&dir, Some(&passphrase("secret"))).expect("open with key");
+        assert_eq!(reopened.load_sessions().expect("load").len(), 1);
+
+        let backup = reopened
+            .list_backups()
+            .expect("list backups")
+            .into_iter()
+            .find(|item| item.reason == BackupReason::Import)
+            .expect("import backup");
+        reopened
+            .restore_backup(&backup.file_name)
+            .expect("restore encrypted backup");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rotates_and_disables_encryption() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        manager
+            .set_encryption(Some(&passphrase("first")))
+            .expect("enable encryption");
+
+        let keyfile = KeySource::Keyfile {
+            path: dir.join("keys").join("data.key"),
+        };
+        manager.set_encryption(Some(&keyfile)).expect("rotate key");
+        assert!(matches!(
+            DataManager::open(&dir, Some(&passphrase("first"))),
+            Err(DataError::WrongKey)
+        ));
+        let reopened = DataManager::open(&dir, Some(&keyfile)).expect("open with keyfile");
+        assert_eq!(reopened.load_routines().expect("load routines").len(), 1);
+
+        reopened.set_encryption(None).expect("disable encryption");
+        let raw = fs::read_to_string(reopened.routines_path()).expect("read raw routines");
+        assert!(raw.trim_start().starts_with('['));
+        DataManager::new(&dir).expect("open without key");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Leaves `dir` as a crash would mid-way through rotating from "first" to
+    /// "second": the rotation is recorded and only the sessions file is swapped.
+    fn interrupt_rotation(dir: &PathBuf) {
+        let manager = DataManager::open(dir, Some(&passphrase("first"))).expect("open");
+        let previous = manager.read_key().clone().expect("current key");
+        let (next, config) = data_crypto::create_key(&passphrase("second")).expect("next key");
+        let pending = PendingRotation {
+            next: Some(config),
+            previous_key: Some(data_crypto::seal_key(&next, &previous).expect("seal previous")),
+            next_key: Some(data_crypto::seal_key(&previous, &next).expect("seal next")),
+            folder: None,
+        };
+        data_crypto::save_pending(dir, &pending).expect("save pending");
+        let staged = manager
+            .stage_rotation(&pending, Some(&previous), Some(&next))
+            .expect("stage");
+        let (temp_path, path, _) = staged
+            .into_iter()
+            .find(|(_, path, _)| path == manager.sessions_path())
+            .expect("staged sessions");
+        replace_file(&temp_path, &path).expect("swap sessions");
+    }
+
+    #[test]
+    fn finishes_an_interrupted_rotation_with_either_passphrase() {
+        for credential in ["first", "second"] {
+            let dir = temp_dir();
+            let manager = DataManager::new(&dir).expect("create manager");
+            manager
+                .save_session(sample_session("session-1", "2025-01-01T10:00:00Z"))
+                .expect("save session");
+            manager
+                .set_encryption(Some(&passphrase("first")))
+                .expect("enable encryption");
+            drop(manager);
+            interrupt_rotation(&dir);
+
+            let reopened =
+                DataManager::open(&dir, Some(&passphrase(credential))).expect("finish rotation");
+            assert_eq!(reopened.load_sessions().expect("load sessions").len(), 1);
+            assert_eq!(reopened.load_routines().expect("load routines").len(), 1);
+            assert!(!dir.join(data_crypto::PENDING_FILE).exists());
+            assert!(matches!(
+                DataManager::open(&dir, Some(&passphrase("first"))),
+                Err(DataError::WrongKey)
+            ));
+            let rotated = DataManager::open(&dir, Some(&passphrase("second"))).expect("open");
+            assert_eq!(rotated.load_sessions().expect("load sessions").len(), 1);
+
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn rotation_reseals_the_sync_folder_key_and_rekeys_the_own_log() {
+        let dir = temp_dir();
+        let folder = dir.join("shared");
+        let manager = DataManager::new(dir.join("device")).expect("create manager");
+        let mut settings = manager.load_settings().expect("load settings");
+        settings.sync.folder = Some(folder.to_string_lossy().to_string());
+        manager.save_settings(&settings).expect("save settings");
+        manager
+            .set_encryption(Some(&passphrase("first")))
+            .expect("enable encryption");
+        folder_sync::sync_folder(&manager, &folder, chrono::Utc::now()).expect("sync");
+
+        // A line from before the folder had a key, sealed with the data key.
+        let device_id = manager
+            .load_sync_state()
+            .expect("load sync state")
+            .expect("sync state")
+            .device_id;
+        let log = folder_sync::log_path(&folder, &device_id);
+        let legacy = manager
+            .encode_contents(b"{\"legacy\":true}".to_vec())
+            .expect("encode legacy line");
+        let mut bytes = fs::read(&log).expect("read log");
+        bytes.extend_from_slice(legacy.trim_ascii_end());
+        bytes.push(b'\n');
+        fs::write(&log, bytes).expect("append legacy line");
+
+        manager
+            .set_encryption(Some(&passphrase("second")))
+            .expect("rotate key");
+        let config = data_crypto::load_folder_key(&folder)
+            .expect("load folder key")
+            .expect("folder key");
+        assert!(data_crypto::unlock_folder_key(&config, &passphrase("first")).is_err());
+        let folder_key =
+            data_crypto::unlock_folder_key(&config, &passphrase("second")).expect("unlock");
+        let contents = fs::read(&log).expect("read log");
+        let lines: Vec<&[u8]> = contents
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .collect();
+        assert!(lines.len() > 1);
+        for line in lines {
+            assert!(data_crypto::is_encrypted(line));
+            data_crypto::decrypt(&folder_key, line).expect("sealed with the folder key");
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
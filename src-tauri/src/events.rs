@@ -1,5 +1,8 @@
 use crate::app_error::AppErrorPayload;
+use crate::data_manager::DataFile;
+use crate::goals::GoalProgress;
 use crate::models::{CheckInConfig, Step};
+use crate::step_warnings::StepWarning;
 use serde::Serialize;
 use tauri::{AppHandle, Emitter};
 
@@ -11,6 +14,9 @@ const TIMER_PAUSED_EVENT: &str = "timer-paused";
 const TIMER_RESUMED_EVENT: &str = "timer-resumed";
 const TIMER_STOPPED_EVENT: &str = "timer-stopped";
 const APP_ERROR_EVENT: &str = "app-error";
+const DATA_CHANGED_EXTERNALLY_EVENT: &str = "data-changed-externally";
+const GOAL_REACHED_EVENT: &str = "goal-reached";
+const STEP_WARNING_EVENT: &str = "step-warning";
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -39,6 +45,23 @@ pub struct CheckInTimeoutPayload {
     pub step_id: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepWarningPayload {
+    pub step_id: String,
+    pub step_label: String,
+    pub warning: StepWarning,
+    pub message: String,
+    /// Whether the step asked for a notification alongside the sound.
+    pub notify: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataChangedExternallyPayload {
+    pub file: DataFile,
+}
+
 fn emit_event<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
     if let Err(err) = app.emit(event, payload) {
         eprintln!("Failed to emit {event}: {err}");
@@ -95,3 +118,29 @@ pub fn emit_timer_stopped(app: &AppHandle) {
 pub fn emit_app_error(app: &AppHandle, payload: AppErrorPayload) {
     emit_event(app, APP_ERROR_EVENT, payload);
 }
+
+pub fn emit_data_changed_externally(app: &AppHandle, file: DataFile) {
+    emit_event(
+        app,
+        DATA_CHANGED_EXTERNALLY_EVENT,
+        DataChangedExternallyPayload { file },
+    );
+}
+
+pub fn emit_goal_reached(app: &AppHandle, progress: GoalProgress) {
+    emit_event(app, GOAL_REACHED_EVENT, progress);
+}
+
+pub fn emit_step_warning(app: &AppHandle, step: &Step, warning: StepWarning) {
+    emit_event(
+        app,
+        STEP_WARNING_EVENT,
+        StepWarningPayload {
+            step_id: step.id.clone(),
+            step_label: step.label.clone(),
+            warning,
+            message: warning.message(),
+            notify: step.warnings.notify,
+        },
+    );
+}
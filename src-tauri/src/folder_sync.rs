@@ -0,0 +1,935 @@
+//! Serverless sync through a shared folder (Syncthing, Dropbox, ...).
+//!
+//! Each device appends its own changes to `<folder>/<device id>.jsonl` and
+//! never writes another device's log, so the file-syncing tool never has to
+//! merge a file. A sync first exports local changes made since the previous
+//! sync, then applies the lines other devices added since it last read them:
+//!
+//! - Sessions are union-merged by id. When both sides hold a session, the copy
+//!   with more edits wins, then the one edited last, then the one with the
+//!   higher fingerprint, so every device keeps the same copy. Deleted sessions
+//!   leave a tombstone so they are not brought back by a device that still
+//!   has them.
+//! - Routines carry a version (revision, device). An edit made on top of the
+//!   version this device holds fast-forwards. Concurrent edits resolve to the
+//!   higher version on every device, and the losing copy is kept as a
+//!   conflict for the user to review or restore.
+//!
+//! With encryption on, log lines are sealed with the folder's own key (see
+//! `data_crypto`), so every device must unlock with the same passphrase or
+//! keyfile. A device without encryption cannot join an encrypted folder.
+
+use crate::data_manager::{BackupReason, DataError, DataFile, DataManager, DataResult};
+use crate::ids::{new_id, IdKind};
+use crate::models::{RetentionSettings, Routine, Session};
+use chrono::{DateTime, Months, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LOG_EXTENSION: &str = "jsonl";
+
+#[derive(Debug)]
+pub enum SyncError {
+    Data(DataError),
+    NotConfigured,
+    ConflictNotFound(String),
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::Data(err) => write!(f, "{err}"),
+            SyncError::NotConfigured => write!(f, "Sync folder is not configured"),
+            SyncError::ConflictNotFound(id) => write!(f, "Sync conflict not found: {id}"),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+impl From<DataError> for SyncError {
+    fn from(value: DataError) -> Self {
+        Self::Data(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutineVersion {
+    pub revision: u64,
+    pub device_id: String,
+}
+
+/// Per-device bookkeeping kept in `sync_state.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub device_id: String,
+    /// Complete lines already applied from each other device's log.
+    #[serde(default)]
+    cursors: BTreeMap<String, usize>,
+    #[serde(default)]
+    routines: BTreeMap<String, TrackedRoutine>,
+    #[serde(default)]
+    sessions: BTreeMap<String, TrackedSession>,
+    #[serde(default)]
+    deleted_sessions: BTreeSet<String>,
+    #[serde(default)]
+    conflicts: Vec<SyncConflict>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackedRoutine {
+    version: RoutineVersion,
+    /// Fingerprint of the content at `version`; `None` once deleted.
+    fingerprint: Option<u64>,
+    /// Earlier versions this device has seen, so replayed or superseded edits
+    /// are recognized.
+    #[serde(default)]
+    seen: BTreeSet<RoutineVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackedSession {
+    fingerprint: u64,
+    started_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConflict {
+    pub id: String,
+    pub routine_id: String,
+    pub detected_at: String,
+    pub kept: RoutineVersion,
+    pub discarded: RoutineVersion,
+    /// The losing copy, or `None` when the losing side deleted the routine.
+    pub discarded_routine: Option<Routine>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    pub exported: u32,
+    pub imported: u32,
+    pub skipped_lines: u32,
+    pub conflicts: u32,
+    pub routines_changed: bool,
+    pub sessions_changed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogEntry {
+    device_id: String,
+    recorded_at: String,
+    #[serde(flatten)]
+    change: LogChange,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum LogChange {
+    SessionSaved {
+        session: Box<Session>,
+    },
+    #[serde(rename_all = "camelCase")]
+    SessionDeleted {
+        session_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    RoutineChanged {
+        routine_id: String,
+        version: RoutineVersion,
+        base: Option<RoutineVersion>,
+        /// `None` records a deletion.
+        routine: Option<Box<Routine>>,
+    },
+}
+
+impl SyncState {
+    fn new() -> Self {
+        Self {
+            device_id: new_id(IdKind::Device),
+            cursors: BTreeMap::new(),
+            routines: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+            deleted_sessions: BTreeSet::new(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// Moves the bookkeeping of routines and sessions whose ids were rewritten,
+    /// so the next sync sends them as edits instead of deletions.
+    pub fn rename_ids(
+        &mut self,
+        routine_ids: &HashMap<String, String>,
+        session_ids: &HashMap<String, String>,
+    ) -> bool {
+        let mut changed = false;
+        for (old_id, new_id) in routine_ids {
+            if let Some(tracked) = self.routines.remove(old_id) {
+                self.routines.insert(new_id.clone(), tracked);
+                changed = true;
+            }
+        }
+        for (old_id, new_id) in session_ids {
+            if let Some(tracked) = self.sessions.remove(old_id) {
+                self.sessions.insert(new_id.clone(), tracked);
+                changed = true;
+            }
+        }
+        for conflict in &mut self.conflicts {
+            if let Some(new_id) = routine_ids.get(&conflict.routine_id) {
+                conflict.routine_id = new_id.clone();
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Losing copies kept for the user to review.
+    pub fn discarded_routines_mut(&mut self) -> impl Iterator<Item = &mut Routine> {
+        self.conflicts
+            .iter_mut()
+            .filter_map(|conflict| conflict.discarded_routine.as_mut())
+    }
+
+    /// Records a local edit and returns its version and the version it replaces.
+    fn bump_routine(
+        &mut self,
+        routine_id: &str,
+        fingerprint: Option<u64>,
+    ) -> (RoutineVersion, Option<RoutineVersion>) {
+        let device_id = self.device_id.clone();
+        match self.routines.get_mut(routine_id) {
+            Some(tracked) => {
+                let base = tracked.version.clone();
+                let version = RoutineVersion {
+                    revision: base.revision + 1,
+                    device_id,
+                };
+                tracked.seen.insert(base.clone());
+                tracked.version = version.clone();
+                tracked.fingerprint = fingerprint;
+                (version, Some(base))
+            }
+            None => {
+                let version = RoutineVersion {
+                    revision: 1,
+                    device_id,
+                };
+                self.routines.insert(
+                    routine_id.to_string(),
+                    TrackedRoutine {
+                        version: version.clone(),
+                        fingerprint,
+                        seen: BTreeSet::new(),
+                    },
+                );
+                (version, None)
+            }
+        }
+    }
+}
+
+pub fn sync_folder(
+    data_manager: &DataManager,
+    folder: &Path,
+    now: DateTime<Utc>,
+) -> DataResult<SyncReport> {
+    fs::create_dir_all(folder)?;
+    let _updates = data_manager.lock_updates();
+    let mut state = data_manager
+        .load_sync_state()?
+        .unwrap_or_else(SyncState::new);
+    let mut routines = data_manager.load_routines()?;
+    let mut sessions = data_manager.load_sessions()?;
+    let retention = data_manager.load_settings()?.retention;
+    let folder_key = data_manager.sync_key(folder)?;
+    let mut report = SyncReport::default();
+
+    // Deletion wins: a session restored locally after another device deleted
+    // it is dropped again.
+    let before = sessions.len();
+    sessions.retain(|item| !state.deleted_sessions.contains(&item.id));
+    report.sessions_changed = sessions.len() != before;
+
+    let entries = export_changes(&mut state, &routines, &sessions, &retention, now);
+    if !entries.is_empty() {
+        let lines = entries
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        data_manager.append_log_lines(
+            &log_path(folder, &state.device_id),
+            &lines,
+            folder_key.as_ref(),
+        )?;
+        // Saved right away so a failed import never exports the same edits twice.
+        data_manager.save_sync_state(&state)?;
+        report.exported = lines.len() as u32;
+    }
+
+    for (device_id, path) in remote_logs(folder, &state.device_id)? {
+        let bytes = fs::read(&path)?;
+        let cursor = state.cursors.get(&device_id).copied().unwrap_or(0);
+        let lines = complete_lines(&bytes);
+        for line in lines.iter().skip(cursor) {
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            // A line sealed with a key this device does not hold is skipped
+            // like a corrupt one rather than stalling every later line.
+            let entry = data_manager
+                .decode_log_line(line, folder_key.as_ref())
+                .and_then(|text| Ok(serde_json::from_str::<LogEntry>(&text)?));
+            match entry {
+                Ok(entry) => {
+                    apply_change(
+                        &mut state,
+                        &mut routines,
+                        &mut sessions,
+                        entry.change,
+                        now,
+                        &mut report,
+                    );
+                    report.imported += 1;
+                }
+                Err(_) => report.skipped_lines += 1,
+            }
+        }
+        state.cursors.insert(device_id, lines.len().max(cursor));
+    }
+
+    if report.routines_changed {
+        data_manager.create_backup(DataFile::Routines, BackupReason::Sync)?;
+        data_manager.save_routines(&routines)?;
+    }
+    if report.sessions_changed {
+        data_manager.create_backup(DataFile::Sessions, BackupReason::Sync)?;
+        data_manager.save_sessions(&sessions)?;
+    }
+    data_manager.save_sync_state(&state)?;
+    Ok(report)
+}
+
+pub fn list_conflicts(data_manager: &DataManager) -> DataResult<Vec<SyncConflict>> {
+    Ok(data_manager
+        .load_sync_state()?
+        .map(|state| state.conflicts)
+        .unwrap_or_default())
+}
+
+/// Dismisses a conflict. With `restore`, the discarded copy replaces the
+/// current routine and goes out to other devices on the next sync.
+pub fn resolve_conflict(
+    data_manager: &DataManager,
+    conflict_id: &str,
+    restore: bool,
+) -> Result<SyncConflict, SyncError> {
+    let _updates = data_manager.lock_updates();
+    let mut state = data_manager
+        .load_sync_state()?
+        .ok_or_else(|| SyncError::ConflictNotFound(conflict_id.to_string()))?;
+    let index = state
+        .conflicts
+        .iter()
+        .position(|item| item.id == conflict_id)
+        .ok_or_else(|| SyncError::ConflictNotFound(conflict_id.to_string()))?;
+    let conflict = state.conflicts.remove(index);
+    if restore {
+        let mut routines = data_manager.load_routines()?;
+        set_routine(
+            &mut routines,
+            &conflict.routine_id,
+            conflict.discarded_routine.clone(),
+        );
+        data_manager.create_backup(DataFile::Routines, BackupReason::Sync)?;
+        data_manager.save_routines(&routines)?;
+    }
+    data_manager.save_sync_state(&state)?;
+    Ok(conflict)
+}
+
+fn export_changes(
+    state: &mut SyncState,
+    routines: &[Routine],
+    sessions: &[Session],
+    retention: &RetentionSettings,
+    now: DateTime<Utc>,
+) -> Vec<LogEntry> {
+    let mut changes = Vec::new();
+
+    for routine in routines {
+        let fingerprint = fingerprint(routine);
+        let tracked = state.routines.get(&routine.id);
+        if tracked.is_some_and(|tracked| tracked.fingerprint == Some(fingerprint)) {
+            continue;
+        }
+        let (version, base) = state.bump_routine(&routine.id, Some(fingerprint));
+        changes.push(LogChange::RoutineChanged {
+            routine_id: routine.id.clone(),
+            version,
+            base,
+            routine: Some(Box::new(routine.clone())),
+        });
+    }
+    let local_routines: HashSet<&str> = routines.iter().map(|item| item.id.as_str()).collect();
+    let deleted_routines: Vec<String> = state
+        .routines
+        .iter()
+        .filter(|(id, tracked)| {
+            tracked.fingerprint.is_some() && !local_routines.contains(id.as_str())
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+    for routine_id in deleted_routines {
+        let (version, base) = state.bump_routine(&routine_id, None);
+        changes.push(LogChange::RoutineChanged {
+            routine_id,
+            version,
+            base,
+            routine: None,
+        });
+    }
+
+    for session in sessions {
+        let fingerprint = fingerprint(session);
+        let tracked = state.sessions.get(&session.id);
+        if tracked.is_some_and(|tracked| tracked.fingerprint == fingerprint) {
+            continue;
+        }
+        state.sessions.insert(
+            session.id.clone(),
+            TrackedSession {
+                fingerprint,
+                started_at: session.started_at.clone(),
+            },
+        );
+        changes.push(LogChange::SessionSaved {
+            session: Box::new(session.clone()),
+        });
+    }
+    let local_sessions: HashSet<&str> = sessions.iter().map(|item| item.id.as_str()).collect();
+    let missing_sessions: Vec<(String, String)> = state
+        .sessions
+        .iter()
+        .filter(|(id, _)| !local_sessions.contains(id.as_str()))
+        .map(|(id, tracked)| (id.clone(), tracked.started_at.clone()))
+        .collect();
+    let aggregated_before = retention
+        .session_totals_months
+        .and_then(|months| now.checked_sub_months(Months::new(months)));
+    for (session_id, started_at) in missing_sessions {
+        state.sessions.remove(&session_id);
+        // Sessions folded into daily aggregates were pruned, not deleted; every
+        // device prunes on its own schedule.
+        let pruned = DateTime::parse_from_rfc3339(&started_at)
+            .is_ok_and(|started_at| aggregated_before.is_some_and(|cutoff| started_at < cutoff));
+        if pruned {
+            continue;
+        }
+        state.deleted_sessions.insert(session_id.clone());
+        changes.push(LogChange::SessionDeleted { session_id });
+    }
+
+    let recorded_at = now.to_rfc3339();
+    changes
+        .into_iter()
+        .map(|change| LogEntry {
+            device_id: state.device_id.clone(),
+            recorded_at: recorded_at.clone(),
+            change,
+        })
+        .collect()
+}
+
+fn apply_change(
+    state: &mut SyncState,
+    routines: &mut Vec<Routine>,
+    sessions: &mut Vec<Session>,
+    change: LogChange,
+    now: DateTime<Utc>,
+    report: &mut SyncReport,
+) {
+    match change {
+        LogChange::SessionSaved { session } => {
+            if state.deleted_sessions.contains(&session.id) {
+                return;
+            }
+            let merged = match sessions.iter_mut().find(|item| item.id == session.id) {
+                Some(local) => {
+                    if session_rank(&session) > session_rank(local) {
+                        *local = *session;
+                        report.sessions_changed = true;
+                    }
+                    local
+                }
+                None => {
+                    sessions.push(*session);
+                    report.sessions_changed = true;
+                    sessions.last_mut().expect("session was just pushed")
+                }
+            };
+            state.sessions.insert(
+                merged.id.clone(),
+                TrackedSession {
+                    fingerprint: fingerprint(merged),
+                    started_at: merged.started_at.clone(),
+                },
+            );
+        }
+        LogChange::SessionDeleted { session_id } => {
+            let before = sessions.len();
+            sessions.retain(|item| item.id != session_id);
+            report.sessions_changed |= sessions.len() != before;
+            state.sessions.remove(&session_id);
+            state.deleted_sessions.insert(session_id);
+        }
+        LogChange::RoutineChanged {
+            routine_id,
+            version,
+            base,
+            routine,
+        } => {
+            let routine = routine.map(|routine| *routine);
+            let incoming = routine.as_ref().map(fingerprint);
+            let Some(tracked) = state.routines.get_mut(&routine_id) else {
+                state.routines.insert(
+                    routine_id.clone(),
+                    TrackedRoutine {
+                        version,
+                        fingerprint: incoming,
+                        seen: base.into_iter().collect(),
+                    },
+                );
+                report.routines_changed |= set_routine(routines, &routine_id, routine);
+                return;
+            };
+            if tracked.version == version || tracked.seen.contains(&version) {
+                return;
+            }
+
+            let fast_forward = match &base {
+                Some(base) if *base == tracked.version => true,
+                // The base came from a device whose log has not been read yet.
+                Some(base) => !tracked.seen.contains(base) && version > tracked.version,
+                None => false,
+            };
+            if let Some(base) = base {
+                tracked.seen.insert(base);
+            }
+            if fast_forward {
+                let previous = std::mem::replace(&mut tracked.version, version);
+                tracked.seen.insert(previous);
+                tracked.fingerprint = incoming;
+                report.routines_changed |= set_routine(routines, &routine_id, routine);
+                return;
+            }
+
+            let local = routines.iter().find(|item| item.id == routine_id).cloned();
+            let conflict = if version > tracked.version {
+                let discarded = std::mem::replace(&mut tracked.version, version);
+                tracked.seen.insert(discarded.clone());
+                tracked.fingerprint = incoming;
+                report.routines_changed |= set_routine(routines, &routine_id, routine);
+                SyncConflict {
+                    id: new_id(IdKind::Conflict),
+                    routine_id,
+                    detected_at: now.to_rfc3339(),
+                    kept: tracked.version.clone(),
+                    discarded,
+                    discarded_routine: local,
+                }
+            } else {
+                tracked.seen.insert(version.clone());
+                SyncConflict {
+                    id: new_id(IdKind::Conflict),
+                    routine_id,
+                    detected_at: now.to_rfc3339(),
+                    kept: tracked.version.clone(),
+                    discarded: version,
+                    discarded_routine: routine,
+                }
+            };
+            state.conflicts.push(conflict);
+            report.conflicts += 1;
+        }
+    }
+}
+
+/// Orders two copies of a session: more edits, then a later last edit, then
+/// the fingerprint as a tie-break every device computes alike.
+fn session_rank(session: &Session) -> (usize, Option<DateTime<Utc>>, u64) {
+    let last_edit = session
+        .edits
+        .iter()
+        .filter_map(|edit| DateTime::parse_from_rfc3339(&edit.edited_at).ok())
+        .map(|edited_at| edited_at.with_timezone(&Utc))
+        .max();
+    (session.edits.len(), last_edit, fingerprint(session))
+}
+
+/// Replaces, adds or (with `None`) removes a routine. Returns whether the
+/// list changed.
+fn set_routine(routines: &mut Vec<Routine>, routine_id: &str, routine: Option<Routine>) -> bool {
+    let index = routines.iter().position(|item| item.id == routine_id);
+    match (routine, index) {
+        (Some(routine), Some(index)) => {
+            if fingerprint(&routines[index]) == fingerprint(&routine) {
+                return false;
+            }
+            routines[index] = routine;
+        }
+        (Some(routine), None) => routines.push(routine),
+        (None, Some(index)) => {
+            routines.remove(index);
+        }
+        (None, None) => return false,
+    }
+    true
+}
+
+pub fn log_path(folder: &Path, device_id: &str) -> PathBuf {
+    folder.join(format!("{device_id}.{LOG_EXTENSION}"))
+}
+
+fn remote_logs(folder: &Path, own_device_id: &str) -> DataResult<Vec<(String, PathBuf)>> {
+    let mut logs = Vec::new();
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.extension().and_then(|value| value.to_str()) != Some(LOG_EXTENSION) {
+            continue;
+        }
+        let Some(device_id) = path.file_stem().and_then(|value| value.to_str()) else {
+            continue;
+        };
+        if device_id != own_device_id {
+            logs.push((device_id.to_string(), path.clone()));
+        }
+    }
+    logs.sort();
+    Ok(logs)
+}
+
+/// Lines terminated by a newline. A trailing partial line, such as one still
+/// being copied in by the sync tool, is left for the next sync.
+fn complete_lines(bytes: &[u8]) -> Vec<&[u8]> {
+    let mut lines: Vec<&[u8]> = bytes.split(|byte| *byte == b'\n').collect();
+    lines.pop();
+    lines
+}
+
+/// FNV-1a over the JSON form; stable across builds, unlike `DefaultHasher`.
+fn fingerprint<T: Serialize>(value: &T) -> u64 {
+    let bytes = serde_json::to_vec(value).unwrap_or_default();
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{list_conflicts, resolve_conflict, sync_folder, SyncReport};
+    use crate::data_crypto::{self, KeySource};
+    use crate::data_manager::{DataError, DataManager};
+    use crate::models::{Session, SessionChange, SessionEdit, SessionTotals};
+    use chrono::{DateTime, Utc};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_sync_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-06-15T12:00:00Z")
+            .expect("parse now")
+            .with_timezone(&Utc)
+    }
+
+    fn session(id: &str, routine_id: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            routine_id: routine_id.to_string(),
+            started_at: "2025-06-14T09:00:00Z".to_string(),
+            ended_at: Some("2025-06-14T09:10:00Z".to_string()),
+            step_runs: Vec::new(),
+            totals: SessionTotals::default(),
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    fn sync(manager: &DataManager, folder: &Path) -> SyncReport {
+        sync_folder(manager, folder, now()).expect("sync")
+    }
+
+    /// Two devices that share the default template routine after one round.
+    fn paired_devices() -> (PathBuf, DataManager, DataManager, PathBuf) {
+        let root = temp_dir();
+        let desktop = DataManager::new(root.join("desktop")).expect("desktop");
+        let laptop = DataManager::new(root.join("laptop")).expect("laptop");
+        let folder = root.join("shared");
+        sync(&desktop, &folder);
+        // Both start with the same template routine; clear the laptop's copy so
+        // it adopts the desktop's instead of conflicting with it.
+        laptop.save_routines(&[]).expect("clear laptop routines");
+        sync(&laptop, &folder);
+        sync(&desktop, &folder);
+        (root, desktop, laptop, folder)
+    }
+
+    fn encrypted_device(dir: PathBuf, passphrase: &str) -> DataManager {
+        let manager = DataManager::new(dir).expect("manager");
+        manager
+            .set_encryption(Some(&KeySource::Passphrase {
+                passphrase: passphrase.to_string(),
+            }))
+            .expect("enable encryption");
+        manager
+    }
+
+    #[test]
+    fn encrypted_devices_sync_through_the_folder_key() {
+        let root = temp_dir();
+        let folder = root.join("shared");
+        let desktop = encrypted_device(root.join("desktop"), "correct horse");
+        let laptop = encrypted_device(root.join("laptop"), "correct horse");
+        sync(&desktop, &folder);
+        laptop.save_routines(&[]).expect("clear laptop routines");
+        sync(&laptop, &folder);
+        let routine_id = laptop.load_routines().expect("routines")[0].id.clone();
+        laptop
+            .save_session(session("session-laptop", &routine_id))
+            .expect("save laptop session");
+        sync(&laptop, &folder);
+
+        let report = sync(&desktop, &folder);
+        assert_eq!(report.skipped_lines, 0);
+        let ids: Vec<String> = desktop
+            .load_sessions()
+            .expect("sessions")
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(ids, vec!["session-laptop"]);
+        for entry in fs::read_dir(&folder).expect("read folder") {
+            let path = entry.expect("entry").path();
+            if path.extension().is_some_and(|ext| ext == "jsonl") {
+                let bytes = fs::read(&path).expect("read log");
+                assert!(bytes
+                    .split(|byte| *byte == b'\n')
+                    .all(|line| line.is_empty() || data_crypto::is_encrypted(line)));
+            }
+        }
+
+        let stranger = encrypted_device(root.join("stranger"), "battery staple");
+        assert!(matches!(
+            sync_folder(&stranger, &folder, now()),
+            Err(DataError::WrongKey)
+        ));
+        let plain = DataManager::new(root.join("plain")).expect("plain");
+        assert!(matches!(
+            sync_folder(&plain, &folder, now()),
+            Err(DataError::SyncFolderEncrypted)
+        ));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn sessions_union_merge_and_deletions_propagate() {
+        let (root, desktop, laptop, folder) = paired_devices();
+        let routine_id = desktop.load_routines().expect("routines")[0].id.clone();
+        desktop
+            .save_session(session("session-desktop", &routine_id))
+            .expect("save desktop session");
+        laptop
+            .save_session(session("session-laptop", &routine_id))
+            .expect("save laptop session");
+
+        sync(&desktop, &folder);
+        sync(&laptop, &folder);
+        sync(&desktop, &folder);
+        for manager in [&desktop, &laptop] {
+            let mut ids: Vec<String> = manager
+                .load_sessions()
+                .expect("sessions")
+                .into_iter()
+                .map(|item| item.id)
+                .collect();
+            ids.sort();
+            assert_eq!(ids, vec!["session-desktop", "session-laptop"]);
+        }
+
+        laptop.save_sessions(&[]).expect("delete on laptop");
+        sync(&laptop, &folder);
+        let report = sync(&desktop, &folder);
+        assert!(report.sessions_changed);
+        assert!(desktop.load_sessions().expect("sessions").is_empty());
+
+        let mut edited = session("session-laptop", &routine_id);
+        edited.tags = vec!["edited".to_string()];
+
+        // A device that still has the session cannot bring it back.
+        desktop.save_session(edited).expect("resave");
+        sync(&desktop, &folder);
+        sync(&laptop, &folder);
+        assert!(desktop.load_sessions().expect("sessions").is_empty());
+        assert!(laptop.load_sessions().expect("sessions").is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn diverged_sessions_with_as_many_edits_settle_on_one_copy() {
+        let (root, desktop, laptop, folder) = paired_devices();
+        let routine_id = desktop.load_routines().expect("routines")[0].id.clone();
+        desktop
+            .save_session(session("session-shared", &routine_id))
+            .expect("save session");
+        sync(&desktop, &folder);
+        sync(&laptop, &folder);
+
+        for (manager, annotation, edited_at) in [
+            (&desktop, "desktop", "2025-06-14T10:00:00Z"),
+            (&laptop, "laptop", "2025-06-14T11:00:00Z"),
+        ] {
+            let mut edited = session("session-shared", &routine_id);
+            edited.annotation = Some(annotation.to_string());
+            edited.edits.push(SessionEdit {
+                id: format!("revision-{annotation}"),
+                edited_at: edited_at.to_string(),
+                change: SessionChange::Annotation { previous: None },
+            });
+            manager.save_session(edited).expect("edit session");
+        }
+        sync(&desktop, &folder);
+        sync(&laptop, &folder);
+        sync(&desktop, &folder);
+
+        for manager in [&desktop, &laptop] {
+            let sessions = manager.load_sessions().expect("sessions");
+            assert_eq!(sessions[0].annotation.as_deref(), Some("laptop"));
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn sequential_routine_edits_fast_forward() {
+        let (root, desktop, laptop, folder) = paired_devices();
+        let mut routine = laptop.load_routines().expect("routines").remove(0);
+        routine.name = "朝のルーチン".to_string();
+        laptop
+            .save_routine(routine.clone())
+            .expect("edit on laptop");
+        sync(&laptop, &folder);
+        let report = sync(&desktop, &folder);
+        assert!(report.routines_changed);
+        assert_eq!(report.conflicts, 0);
+
+        let mut routine = desktop.load_routines().expect("routines").remove(0);
+        assert_eq!(routine.name, "朝のルーチン");
+        routine.name = "夜のルーチン".to_string();
+        desktop.save_routine(routine).expect("edit on desktop");
+        sync(&desktop, &folder);
+        let report = sync(&laptop, &folder);
+        assert_eq!(report.conflicts, 0);
+        assert_eq!(
+            laptop.load_routines().expect("routines")[0].name,
+            "夜のルーチン"
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn concurrent_routine_edits_converge_and_surface_conflict() {
+        let (root, desktop, laptop, folder) = paired_devices();
+        let mut on_desktop = desktop.load_routines().expect("routines").remove(0);
+        on_desktop.name = "デスクトップ版".to_string();
+        desktop.save_routine(on_desktop).expect("edit desktop");
+        let mut on_laptop = laptop.load_routines().expect("routines").remove(0);
+        on_laptop.name = "ラップトップ版".to_string();
+        laptop.save_routine(on_laptop).expect("edit laptop");
+
+        sync(&desktop, &folder);
+        sync(&laptop, &folder);
+        sync(&desktop, &folder);
+
+        let desktop_name = desktop.load_routines().expect("routines")[0].name.clone();
+        let laptop_name = laptop.load_routines().expect("routines")[0].name.clone();
+        assert_eq!(desktop_name, laptop_name);
+
+        let desktop_conflicts = list_conflicts(&desktop).expect("desktop conflicts");
+        let laptop_conflicts = list_conflicts(&laptop).expect("laptop conflicts");
+        assert_eq!(desktop_conflicts.len(), 1);
+        assert_eq!(laptop_conflicts.len(), 1);
+        assert_eq!(desktop_conflicts[0].kept, laptop_conflicts[0].kept);
+
+        // The device whose edit lost can restore it; it then wins everywhere.
+        let (loser, winner, conflict) = if desktop_name == "ラップトップ版" {
+            (&desktop, &laptop, &desktop_conflicts[0])
+        } else {
+            (&laptop, &desktop, &laptop_conflicts[0])
+        };
+        let discarded_name = conflict
+            .discarded_routine
+            .as_ref()
+            .expect("discarded copy")
+            .name
+            .clone();
+        assert_ne!(discarded_name, desktop_name);
+        resolve_conflict(loser, &conflict.id, true).expect("restore");
+        assert!(list_conflicts(loser).expect("conflicts").is_empty());
+        sync(loser, &folder);
+        sync(winner, &folder);
+        assert_eq!(
+            winner.load_routines().expect("routines")[0].name,
+            discarded_name
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn partial_and_corrupt_lines_are_handled() {
+        let (root, desktop, _laptop, folder) = paired_devices();
+        let log = folder.join("device-partial.jsonl");
+        fs::write(&log, "not json\n{\"deviceId\":\"device-partial\"").expect("write log");
+
+        let report = sync(&desktop, &folder);
+        assert_eq!(report.skipped_lines, 1);
+        assert_eq!(report.imported, 0);
+
+        let again = sync(&desktop, &folder);
+        assert_eq!(again.skipped_lines, 0);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::calendar::day_containing;
+use crate::data_manager::{DataManager, DataResult};
+use crate::events::emit_goal_reached;
+use crate::goals::{format_progress_line, GoalAnnouncer, GoalHistory, GoalProgress};
+use crate::menu_bar;
+use crate::models::{CalendarSettings, Session};
+use crate::session_tracker::SessionTracker;
+use chrono::{Local, NaiveDate, Utc};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// Sessions can be saved or edited outside the timer loop, so the goal
+/// history is rebuilt from disk at least this often.
+const HISTORY_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default)]
+pub struct GoalState {
+    history: Option<GoalHistory>,
+    calendar: CalendarSettings,
+    built_for: Option<NaiveDate>,
+    built_at: Option<Instant>,
+    had_live_session: bool,
+    announcer: GoalAnnouncer,
+}
+
+impl GoalState {
+    /// Forces a rebuild on the next update, e.g. after goals were edited.
+    pub fn invalidate(&mut self) {
+        self.history = None;
+    }
+}
+
+/// Goal history and calendar settings as currently stored.
+pub fn load_goal_history(
+    data_manager: &DataManager,
+) -> DataResult<(GoalHistory, CalendarSettings)> {
+    let settings = data_manager.load_settings()?;
+    let history = GoalHistory::build(
+        data_manager.load_goals()?,
+        &data_manager.load_sessions()?,
+        &data_manager.load_daily_aggregates()?,
+        &Local,
+        &settings.calendar,
+    );
+    Ok((history, settings.calendar))
+}
+
+/// Today's progress for every goal, counting the running session.
+pub fn current_goal_progress(
+    data_manager: &DataManager,
+    live: Option<&Session>,
+) -> DataResult<Vec<GoalProgress>> {
+    let (history, calendar) = load_goal_history(data_manager)?;
+    let today = day_containing(Utc::now(), &Local, &calendar);
+    Ok(history.progress(today, live))
+}
+
+/// Refreshes the tray line and emits `goal-reached` for goals the running
+/// session has just pushed over their target. Called every timer tick.
+pub fn update_goal_progress(app: &AppHandle) {
+    let (Some(goal_state), Some(data_manager)) = (
+        app.try_state::<Mutex<GoalState>>(),
+        app.try_state::<DataManager>(),
+    ) else {
+        return;
+    };
+    let live = app
+        .try_state::<Mutex<SessionTracker>>()
+        .and_then(|tracker| {
+            let tracker = tracker.lock().ok()?;
+            tracker.current_session()
+        });
+
+    let mut state = match goal_state.lock() {
+        Ok(state) => state,
+        Err(_) => {
+            eprintln!("Goal state lock failed");
+            return;
+        }
+    };
+    let today = day_containing(Utc::now(), &Local, &state.calendar);
+    let stale = state.history.is_none()
+        || state.built_for != Some(today)
+        || state
+            .built_at
+            .is_none_or(|built_at| built_at.elapsed() >= HISTORY_REFRESH_INTERVAL)
+        || (state.had_live_session && live.is_none());
+    if stale {
+        match load_goal_history(&data_manager) {
+            Ok((history, calendar)) => {
+                state.history = Some(history);
+                state.calendar = calendar;
+            }
+            Err(err) => eprintln!("Failed to load goal progress: {err}"),
+        }
+        state.built_at = Some(Instant::now());
+    }
+    let today = day_containing(Utc::now(), &Local, &state.calendar);
+    state.built_for = Some(today);
+    state.had_live_session = live.is_some();
+
+    let progress = state
+        .history
+        .as_ref()
+        .map(|history| history.progress(today, live.as_ref()))
+        .unwrap_or_default();
+    let reached = state.announcer.take_newly_reached(&progress);
+    drop(state);
+
+    menu_bar::set_goal_line(app, &format_progress_line(&progress));
+    for item in reached {
+        emit_goal_reached(app, item);
+    }
+}
@@ -0,0 +1,418 @@
+//! Daily goals, their progress and streaks.
+//!
+//! Each goal sums one metric per local day (sessions, work minutes or cycles)
+//! for one routine or all of them. A day counts towards a streak when the
+//! goal applies that day and the target was met; days the goal does not
+//! apply to (weekends for a weekday goal) neither extend nor break a streak,
+//! and an unmet today does not break a streak while it is still in progress.
+
+use crate::calendar::day_containing;
+use crate::data_manager::DataError;
+use crate::ids::{new_id, IdKind};
+use crate::models::{
+    CalendarSettings, DailyAggregate, Goal, GoalDays, GoalMetric, Routine, Session,
+};
+use crate::routine_validator::FieldError;
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone, Utc, Weekday};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug)]
+pub enum GoalError {
+    Data(DataError),
+    Invalid(Vec<FieldError>),
+}
+
+impl std::fmt::Display for GoalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GoalError::Data(err) => write!(f, "{err}"),
+            GoalError::Invalid(errors) => {
+                let detail: Vec<String> = errors
+                    .iter()
+                    .map(|error| format!("{}: {}", error.path, error.message))
+                    .collect();
+                write!(f, "Invalid goals: {}", detail.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GoalError {}
+
+impl From<DataError> for GoalError {
+    fn from(value: DataError) -> Self {
+        Self::Data(value)
+    }
+}
+
+/// Where one goal stands today.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub date: String,
+    pub applies_today: bool,
+    pub value: u32,
+    pub reached: bool,
+    pub current_streak: u32,
+    pub best_streak: u32,
+}
+
+/// Checks `goals` against the existing routines and gives new goals an id.
+pub fn prepare_goals(goals: &mut [Goal], routines: &[Routine]) -> Result<(), GoalError> {
+    let mut errors = Vec::new();
+    for (index, goal) in goals.iter_mut().enumerate() {
+        if goal.target == 0 {
+            errors.push(FieldError::new(
+                format!("goals[{index}].target"),
+                "目標値は1以上にしてください",
+            ));
+        }
+        if let Some(routine_id) = goal.routine_id.as_deref() {
+            if !routines.iter().any(|routine| routine.id == routine_id) {
+                errors.push(FieldError::new(
+                    format!("goals[{index}].routineId"),
+                    "ルーチンが見つかりません",
+                ));
+            }
+        }
+        if goal.id.trim().is_empty() {
+            goal.id = new_id(IdKind::Goal);
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(GoalError::Invalid(errors))
+    }
+}
+
+/// Per-day totals of every goal's metric, built once from stored history so
+/// progress can be recomputed cheaply while a session runs.
+#[derive(Debug, Clone, Default)]
+pub struct GoalHistory {
+    goals: Vec<Goal>,
+    /// Raw amounts per goal and local day: sessions, work seconds or cycles.
+    daily: Vec<BTreeMap<NaiveDate, u64>>,
+}
+
+impl GoalHistory {
+    /// Sessions count on the local day they started; aggregates, which only
+    /// know their UTC date, on that date.
+    pub fn build<Tz: TimeZone>(
+        goals: Vec<Goal>,
+        sessions: &[Session],
+        aggregates: &[DailyAggregate],
+        zone: &Tz,
+        settings: &CalendarSettings,
+    ) -> Self {
+        let mut daily = vec![BTreeMap::new(); goals.len()];
+        for session in sessions.iter().filter(|item| !item.excluded_from_stats) {
+            let Ok(started_at) = DateTime::parse_from_rfc3339(&session.started_at) else {
+                continue;
+            };
+            let day = day_containing(started_at.with_timezone(&Utc), zone, settings);
+            for (goal, days) in goals.iter().zip(daily.iter_mut()) {
+                if let Some(amount) = session_amount(goal, session) {
+                    *days.entry(day).or_insert(0) += amount;
+                }
+            }
+        }
+        for aggregate in aggregates {
+            let Ok(day) = NaiveDate::parse_from_str(&aggregate.date, DATE_FORMAT) else {
+                continue;
+            };
+            for (goal, days) in goals.iter().zip(daily.iter_mut()) {
+                if goal
+                    .routine_id
+                    .as_deref()
+                    .is_some_and(|routine_id| routine_id != aggregate.routine_id)
+                {
+                    continue;
+                }
+                let amount = match goal.metric {
+                    GoalMetric::Sessions => aggregate.sessions_count,
+                    GoalMetric::WorkMinutes => aggregate.totals.work_seconds,
+                    GoalMetric::Cycles => aggregate.totals.cycles_count,
+                };
+                *days.entry(day).or_insert(0) += u64::from(amount);
+            }
+        }
+        Self { goals, daily }
+    }
+
+    /// Progress on `today`, counting `live` (the running session) as today's.
+    pub fn progress(&self, today: NaiveDate, live: Option<&Session>) -> Vec<GoalProgress> {
+        self.goals
+            .iter()
+            .zip(&self.daily)
+            .map(|(goal, days)| {
+                let live_amount = live
+                    .and_then(|session| session_amount(goal, session))
+                    .unwrap_or(0);
+                goal_progress(goal, days, today, live_amount)
+            })
+            .collect()
+    }
+}
+
+fn session_amount(goal: &Goal, session: &Session) -> Option<u64> {
+    if goal
+        .routine_id
+        .as_deref()
+        .is_some_and(|routine_id| routine_id != session.routine_id)
+    {
+        return None;
+    }
+    Some(match goal.metric {
+        GoalMetric::Sessions => 1,
+        GoalMetric::WorkMinutes => u64::from(session.totals.work_seconds),
+        GoalMetric::Cycles => u64::from(session.totals.cycles_count),
+    })
+}
+
+fn goal_progress(
+    goal: &Goal,
+    days: &BTreeMap<NaiveDate, u64>,
+    today: NaiveDate,
+    live_amount: u64,
+) -> GoalProgress {
+    let value_on = |day: NaiveDate| {
+        let amount =
+            days.get(&day).copied().unwrap_or(0) + if day == today { live_amount } else { 0 };
+        let value = match goal.metric {
+            GoalMetric::WorkMinutes => amount / 60,
+            GoalMetric::Sessions | GoalMetric::Cycles => amount,
+        };
+        value.min(u64::from(u32::MAX)) as u32
+    };
+    let met = |day: NaiveDate| value_on(day) >= goal.target;
+
+    let mut best_streak = 0;
+    let mut run = 0;
+    let first_day = days.keys().next().copied().unwrap_or(today).min(today);
+    for day in first_day.iter_days().take_while(|day| *day <= today) {
+        if !applies_on(goal.days, day) {
+            continue;
+        }
+        if met(day) {
+            run += 1;
+            best_streak = best_streak.max(run);
+        } else if day != today {
+            run = 0;
+        }
+    }
+
+    let mut current_streak = 0;
+    let mut day = today;
+    while day >= first_day {
+        if applies_on(goal.days, day) {
+            if met(day) {
+                current_streak += 1;
+            } else if day != today {
+                break;
+            }
+        }
+        match day.checked_sub_days(Days::new(1)) {
+            Some(previous) => day = previous,
+            None => break,
+        }
+    }
+
+    let value = value_on(today);
+    GoalProgress {
+        goal: goal.clone(),
+        date: today.format(DATE_FORMAT).to_string(),
+        applies_today: applies_on(goal.days, today),
+        value,
+        reached: value >= goal.target,
+        current_streak,
+        best_streak,
+    }
+}
+
+fn applies_on(days: GoalDays, day: NaiveDate) -> bool {
+    let weekend = matches!(day.weekday(), Weekday::Sat | Weekday::Sun);
+    match days {
+        GoalDays::Every => true,
+        GoalDays::Weekdays => !weekend,
+        GoalDays::Weekends => weekend,
+    }
+}
+
+/// One tray line summarizing the goals that apply today.
+pub fn format_progress_line(progress: &[GoalProgress]) -> String {
+    let parts: Vec<String> = progress
+        .iter()
+        .filter(|item| item.applies_today)
+        .map(|item| {
+            let unit = match item.goal.metric {
+                GoalMetric::Sessions => "sessions",
+                GoalMetric::WorkMinutes => "min",
+                GoalMetric::Cycles => "cycles",
+            };
+            let mark = if item.reached { " ✓" } else { "" };
+            format!("{}/{} {unit}{mark}", item.value, item.goal.target)
+        })
+        .collect();
+    if parts.is_empty() {
+        "Today: no goals".to_string()
+    } else {
+        format!("Today: {}", parts.join(", "))
+    }
+}
+
+/// Remembers which goals were already reached today so each one is announced
+/// once, including across history rebuilds.
+#[derive(Debug, Default)]
+pub struct GoalAnnouncer {
+    date: Option<String>,
+    announced: HashSet<String>,
+}
+
+impl GoalAnnouncer {
+    /// Goals reached since the last call. Goals already reached the first
+    /// time a day is seen (for example at startup) are not announced.
+    pub fn take_newly_reached(&mut self, progress: &[GoalProgress]) -> Vec<GoalProgress> {
+        let Some(date) = progress.first().map(|item| item.date.clone()) else {
+            return Vec::new();
+        };
+        let first_look = self.date.as_deref() != Some(date.as_str());
+        if first_look {
+            self.date = Some(date);
+            self.announced.clear();
+        }
+        let mut reached = Vec::new();
+        for item in progress.iter().filter(|item| item.reached) {
+            if self.announced.insert(item.goal.id.clone()) && !first_look {
+                reached.push(item.clone());
+            }
+        }
+        reached
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_progress_line, prepare_goals, GoalAnnouncer, GoalError, GoalHistory};
+    use crate::models::{CalendarSettings, Goal, GoalDays, GoalMetric, Session, SessionTotals};
+    use chrono::{NaiveDate, Utc};
+
+    fn goal(metric: GoalMetric, target: u32, days: GoalDays) -> Goal {
+        Goal {
+            id: "goal-1".to_string(),
+            routine_id: None,
+            metric,
+            target,
+            days,
+        }
+    }
+
+    fn session(started_at: &str, work_seconds: u32) -> Session {
+        Session {
+            id: format!("session-{started_at}"),
+            routine_id: "routine-1".to_string(),
+            started_at: started_at.to_string(),
+            ended_at: None,
+            step_runs: Vec::new(),
+            totals: SessionTotals {
+                work_seconds,
+                ..SessionTotals::default()
+            },
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").expect("date")
+    }
+
+    #[test]
+    fn weekday_streak_skips_weekends_and_waits_for_today() {
+        // 2025-06-13 is a Friday, 2025-06-16 a Monday.
+        let sessions: Vec<Session> = [
+            "2025-06-10T09:00:00Z",
+            "2025-06-11T09:00:00Z",
+            "2025-06-11T10:00:00Z",
+            "2025-06-12T09:00:00Z",
+            "2025-06-12T10:00:00Z",
+            "2025-06-13T09:00:00Z",
+            "2025-06-13T10:00:00Z",
+            "2025-06-16T09:00:00Z",
+        ]
+        .iter()
+        .map(|started_at| session(started_at, 1_500))
+        .collect();
+        let history = GoalHistory::build(
+            vec![goal(GoalMetric::Sessions, 2, GoalDays::Weekdays)],
+            &sessions,
+            &[],
+            &Utc,
+            &CalendarSettings::default(),
+        );
+
+        let monday = history.progress(date("2025-06-16"), None).remove(0);
+        assert_eq!(monday.value, 1);
+        assert!(!monday.reached);
+        assert_eq!(monday.current_streak, 3);
+        assert_eq!(monday.best_streak, 3);
+
+        let live = session("2025-06-16T11:00:00Z", 600);
+        let with_live = history.progress(date("2025-06-16"), Some(&live)).remove(0);
+        assert!(with_live.reached);
+        assert_eq!(with_live.current_streak, 4);
+        assert_eq!(with_live.best_streak, 4);
+
+        let tuesday = history.progress(date("2025-06-17"), None).remove(0);
+        assert_eq!(tuesday.current_streak, 0);
+        assert_eq!(tuesday.best_streak, 3);
+    }
+
+    #[test]
+    fn work_minutes_and_announcements() {
+        let sessions = vec![session("2025-06-16T09:00:00Z", 3_600)];
+        let history = GoalHistory::build(
+            vec![goal(GoalMetric::WorkMinutes, 90, GoalDays::Every)],
+            &sessions,
+            &[],
+            &Utc,
+            &CalendarSettings::default(),
+        );
+        let mut announcer = GoalAnnouncer::default();
+
+        let before = history.progress(date("2025-06-16"), None);
+        assert_eq!(before[0].value, 60);
+        assert_eq!(format_progress_line(&before), "Today: 60/90 min");
+        assert!(announcer.take_newly_reached(&before).is_empty());
+
+        let live = session("2025-06-16T10:00:00Z", 1_800);
+        let after = history.progress(date("2025-06-16"), Some(&live));
+        assert_eq!(announcer.take_newly_reached(&after).len(), 1);
+        assert!(announcer.take_newly_reached(&after).is_empty());
+        assert_eq!(format_progress_line(&after), "Today: 90/90 min ✓");
+    }
+
+    #[test]
+    fn rejects_invalid_goals_and_assigns_ids() {
+        let mut goals = vec![goal(GoalMetric::Cycles, 1, GoalDays::Every)];
+        goals[0].id = String::new();
+        prepare_goals(&mut goals, &[]).expect("valid goal");
+        assert!(goals[0].id.starts_with("goal-"));
+
+        let mut invalid = vec![goal(GoalMetric::Cycles, 0, GoalDays::Every)];
+        invalid[0].routine_id = Some("routine-missing".to_string());
+        match prepare_goals(&mut invalid, &[]) {
+            Err(GoalError::Invalid(errors)) => assert_eq!(errors.len(), 2),
+            other => panic!("expected invalid goals, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,378 @@
+//! Retention pruning for session history.
+//!
+//! Sessions older than `stepRunsDays` keep their totals but drop per-step runs.
+//! Sessions older than `sessionTotalsMonths` are folded into per-day,
+//! per-routine aggregates, which are kept forever. Both steps leave the numbers
+//! `calculate_session_stats` reports unchanged.
+
+use crate::data_manager::{BackupReason, DataFile, DataManager, DataResult};
+use crate::models::{DailyAggregate, RetentionSettings, Session, SessionTotals};
+use crate::session_stats::{aborted_counts, add_session_totals, effective_totals, session_outcome};
+use chrono::{DateTime, Duration, Months, Utc};
+use serde::Serialize;
+
+const AGGREGATE_DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneReport {
+    pub step_runs_pruned: u32,
+    pub sessions_aggregated: u32,
+}
+
+impl PruneReport {
+    fn is_empty(&self) -> bool {
+        self.step_runs_pruned == 0 && self.sessions_aggregated == 0
+    }
+}
+
+pub fn prune_history(
+    data_manager: &DataManager,
+    retention: &RetentionSettings,
+    now: DateTime<Utc>,
+) -> DataResult<PruneReport> {
+    let _updates = data_manager.lock_updates();
+    let mut sessions = data_manager.load_sessions()?;
+    let mut aggregates = data_manager.load_daily_aggregates()?;
+    let report = apply_retention(&mut sessions, &mut aggregates, retention, now);
+    if report.is_empty() {
+        return Ok(report);
+    }
+
+    // Aggregates are written first so an interrupted prune never drops sessions
+    // that have not been folded in yet.
+    if report.sessions_aggregated > 0 {
+        data_manager.create_backup(DataFile::DailyAggregates, BackupReason::Prune)?;
+        data_manager.save_daily_aggregates(&aggregates)?;
+    }
+    data_manager.create_backup(DataFile::Sessions, BackupReason::Prune)?;
+    data_manager.save_sessions(&sessions)?;
+    Ok(report)
+}
+
+pub fn apply_retention(
+    sessions: &mut Vec<Session>,
+    aggregates: &mut Vec<DailyAggregate>,
+    retention: &RetentionSettings,
+    now: DateTime<Utc>,
+) -> PruneReport {
+    let aggregate_before = retention
+        .session_totals_months
+        .and_then(|months| now.checked_sub_months(Months::new(months)));
+    let trim_before = retention
+        .step_runs_days
+        .and_then(|days| now.checked_sub_signed(Duration::days(i64::from(days))));
+
+    let mut report = PruneReport::default();
+    let mut kept = Vec::with_capacity(sessions.len());
+    for mut session in std::mem::take(sessions) {
+        let Some(started_at) = parse_started_at(&session) else {
+            kept.push(session);
+            continue;
+        };
+
+        if aggregate_before.is_some_and(|cutoff| started_at < cutoff) {
+            // Excluded sessions never count toward stats, so they are dropped
+            // rather than folded in.
+            if !session.excluded_from_stats {
+                fold_session(aggregates, &session, started_at);
+            }
+            report.sessions_aggregated += 1;
+            continue;
+        }
+        if trim_before.is_some_and(|cutoff| started_at < cutoff) && !session.step_runs.is_empty() {
+            // Older sessions may lack adherence totals and an outcome; fill
+            // them in while the step runs they come from are still there.
+            session.totals = effective_totals(&session);
+            session.outcome = Some(session_outcome(&session));
+            session.step_runs.clear();
+            report.step_runs_pruned += 1;
+        }
+        kept.push(session);
+    }
+    *sessions = kept;
+
+    if report.sessions_aggregated > 0 {
+        aggregates.sort_by(|left, right| {
+            left.date
+                .cmp(&right.date)
+                .then_with(|| left.routine_id.cmp(&right.routine_id))
+        });
+    }
+    report
+}
+
+fn parse_started_at(session: &Session) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&session.started_at)
+        .ok()
+        .map(|value| value.with_timezone(&Utc))
+}
+
+fn fold_session(
+    aggregates: &mut Vec<DailyAggregate>,
+    session: &Session,
+    started_at: DateTime<Utc>,
+) {
+    let date = started_at.format(AGGREGATE_DATE_FORMAT).to_string();
+    let index = match aggregates
+        .iter()
+        .position(|item| item.date == date && item.routine_id == session.routine_id)
+    {
+        Some(index) => index,
+        None => {
+            aggregates.push(DailyAggregate {
+                date,
+                routine_id: session.routine_id.clone(),
+                sessions_count: 0,
+                muted_sessions_count: 0,
+                stopped_sessions_count: 0,
+                recovered_sessions_count: 0,
+                totals: SessionTotals::default(),
+            });
+            aggregates.len() - 1
+        }
+    };
+
+    let aggregate = &mut aggregates[index];
+    aggregate.sessions_count = aggregate.sessions_count.saturating_add(1);
+    if session.muted_during_session {
+        aggregate.muted_sessions_count = aggregate.muted_sessions_count.saturating_add(1);
+    }
+    let (stopped, recovered) = aborted_counts(session);
+    aggregate.stopped_sessions_count = aggregate.stopped_sessions_count.saturating_add(stopped);
+    aggregate.recovered_sessions_count =
+        aggregate.recovered_sessions_count.saturating_add(recovered);
+    add_session_totals(&mut aggregate.totals, &effective_totals(session));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_retention, prune_history, PruneReport};
+    use crate::data_manager::{BackupReason, DataManager};
+    use crate::models::{
+        AppSettings, DailyAggregate, RetentionSettings, Session, SessionOutcome, SessionTotals,
+        StepRun, StepRunResult,
+    };
+    use crate::session_stats::calculate_session_stats;
+    use chrono::{DateTime, Utc};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_retention_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-06-15T12:00:00Z")
+            .expect("parse now")
+            .with_timezone(&Utc)
+    }
+
+    fn session(id: &str, started_at: &str, work: u32, muted: bool) -> Session {
+        Session {
+            id: id.to_string(),
+            routine_id: "routine-1".to_string(),
+            started_at: started_at.to_string(),
+            ended_at: Some(started_at.to_string()),
+            step_runs: vec![StepRun {
+                step_id: "step-1".to_string(),
+                label: None,
+                planned_duration_seconds: work,
+                actual_duration_seconds: work,
+                started_at: started_at.to_string(),
+                ended_at: Some(started_at.to_string()),
+                result: StepRunResult::Completed,
+                check_in_result: None,
+                sound_played: false,
+            }],
+            totals: SessionTotals {
+                total_seconds: work + 60,
+                work_seconds: work,
+                break_seconds: 60,
+                cycles_count: 1,
+                check_in_done_count: 1,
+                check_in_skip_count: 0,
+                ..SessionTotals::default()
+            },
+            muted_during_session: muted,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    fn retention(days: u32, months: u32) -> RetentionSettings {
+        RetentionSettings {
+            step_runs_days: Some(days),
+            session_totals_months: Some(months),
+        }
+    }
+
+    fn stats_json(sessions: &[Session], aggregates: &[DailyAggregate]) -> serde_json::Value {
+        serde_json::to_value(calculate_session_stats(sessions, aggregates)).expect("stats json")
+    }
+
+    #[test]
+    fn trims_and_aggregates_without_changing_stats() {
+        let mut sessions = vec![
+            session("recent", "2025-06-10T09:00:00Z", 600, false),
+            session("old", "2025-02-01T09:00:00Z", 300, true),
+            session("ancient-1", "2024-01-05T09:00:00Z", 1200, true),
+            session("ancient-2", "2024-01-05T18:00:00Z", 600, false),
+            session("ancient-3", "2023-12-31T23:30:00Z", 900, false),
+        ];
+        let mut aggregates = Vec::new();
+        let before = stats_json(&sessions, &aggregates);
+
+        let report = apply_retention(&mut sessions, &mut aggregates, &retention(30, 12), now());
+
+        assert_eq!(
+            report,
+            PruneReport {
+                step_runs_pruned: 1,
+                sessions_aggregated: 3,
+            }
+        );
+        let ids: Vec<&str> = sessions.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, vec!["recent", "old"]);
+        assert_eq!(sessions[0].step_runs.len(), 1);
+        assert!(sessions[1].step_runs.is_empty());
+        assert_eq!(sessions[1].totals.work_seconds, 300);
+
+        let dates: Vec<&str> = aggregates.iter().map(|item| item.date.as_str()).collect();
+        assert_eq!(dates, vec!["2023-12-31", "2024-01-05"]);
+        assert_eq!(aggregates[1].sessions_count, 2);
+        assert_eq!(aggregates[1].muted_sessions_count, 1);
+        assert_eq!(aggregates[1].totals.work_seconds, 1800);
+
+        assert_eq!(stats_json(&sessions, &aggregates), before);
+    }
+
+    #[test]
+    fn trimming_keeps_how_legacy_sessions_ended() {
+        let mut stopped = session("stopped", "2025-02-01T09:00:00Z", 300, false);
+        stopped.step_runs[0].result = StepRunResult::Aborted;
+        let mut recovered = session("recovered", "2025-02-02T09:00:00Z", 300, false);
+        recovered.step_runs[0].result = StepRunResult::Aborted;
+        recovered.step_runs[0].started_at = "2025-02-02T09:05:00Z".to_string();
+        let mut sessions = vec![stopped, recovered];
+        let mut aggregates = Vec::new();
+        let before = calculate_session_stats(&sessions, &aggregates);
+        assert_eq!(
+            (
+                before.stopped_sessions_count,
+                before.recovered_sessions_count
+            ),
+            (1, 1)
+        );
+
+        let report = apply_retention(&mut sessions, &mut aggregates, &retention(30, 12), now());
+
+        assert_eq!(report.step_runs_pruned, 2);
+        assert!(sessions.iter().all(|item| item.step_runs.is_empty()));
+        assert_eq!(
+            sessions.iter().map(|item| item.outcome).collect::<Vec<_>>(),
+            vec![
+                Some(SessionOutcome::Stopped),
+                Some(SessionOutcome::Recovered)
+            ]
+        );
+        assert_eq!(
+            stats_json(&sessions, &aggregates),
+            serde_json::to_value(before).expect("stats json")
+        );
+    }
+
+    #[test]
+    fn keeps_history_by_default() {
+        let mut sessions = vec![session("ancient", "2020-01-01T00:00:00Z", 600, false)];
+        let mut aggregates = Vec::new();
+        let settings: AppSettings =
+            serde_json::from_str(r#"{"notificationsEnabled":true,"soundDefault":"on"}"#)
+                .expect("parse settings");
+        assert_eq!(settings.retention, RetentionSettings::default());
+
+        let report = apply_retention(&mut sessions, &mut aggregates, &settings.retention, now());
+
+        assert_eq!(report, PruneReport::default());
+        assert_eq!(sessions[0].step_runs.len(), 1);
+        assert!(aggregates.is_empty());
+    }
+
+    #[test]
+    fn prune_merges_into_existing_aggregates_and_backs_up() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        manager
+            .save_sessions(&[
+                session("ancient", "2024-01-05T09:00:00Z", 600, false),
+                session("recent", "2025-06-14T09:00:00Z", 600, false),
+            ])
+            .expect("save sessions");
+        manager
+            .save_daily_aggregates(&[DailyAggregate {
+                date: "2024-01-05".to_string(),
+                routine_id: "routine-1".to_string(),
+                sessions_count: 1,
+                muted_sessions_count: 1,
+                stopped_sessions_count: 0,
+                recovered_sessions_count: 0,
+                totals: SessionTotals {
+                    work_seconds: 100,
+                    total_seconds: 100,
+                    ..SessionTotals::default()
+                },
+            }])
+            .expect("save aggregates");
+
+        let report = prune_history(&manager, &retention(30, 12), now()).expect("prune");
+        assert_eq!(report.sessions_aggregated, 1);
+
+        let aggregates = manager.load_daily_aggregates().expect("load aggregates");
+        assert_eq!(aggregates.len(), 1);
+        assert_eq!(aggregates[0].sessions_count, 2);
+        assert_eq!(aggregates[0].totals.work_seconds, 700);
+        assert_eq!(manager.load_sessions().expect("load sessions").len(), 1);
+
+        let stats = calculate_session_stats(
+            &manager
+                .load_sessions_in_range("2024-01-01T00:00:00Z", "2025-06-15T00:00:00Z")
+                .expect("sessions in range"),
+            &manager
+                .load_daily_aggregates_in_range("2024-01-01T00:00:00Z", "2025-06-15T00:00:00Z")
+                .expect("aggregates in range"),
+        );
+        assert_eq!(stats.sessions_count, 3);
+
+        let prune_backups = manager
+            .list_backups()
+            .expect("list backups")
+            .into_iter()
+            .filter(|item| item.reason == BackupReason::Prune)
+            .count();
+        assert_eq!(prune_backups, 2);
+
+        let again = prune_history(&manager, &retention(30, 12), now()).expect("prune again");
+        assert_eq!(again, PruneReport::default());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,500 @@
+//! One-time rewrite of legacy ids into the format produced by `ids`.
+//!
+//! Legacy routine and step ids (timestamps, UUIDs, template names) are replaced
+//! everywhere they are referenced: routines, sessions and their edit history,
+//! the active session snapshot, daily aggregates, goals and the sync state's
+//! bookkeeping and conflict copies. Step ids are mapped
+//! per routine, so steps that shared an id across routines stay distinct.
+//! New ids are derived from the legacy ones rather than drawn at random, so
+//! devices that sync the same records migrate them to the same ids. Running
+//! the migration again is a no-op.
+
+use crate::data_manager::{BackupReason, DataManager, DataResult};
+use crate::folder_sync::SyncState;
+use crate::ids::{derived_id, is_current_id, IdKind};
+use crate::models::{DailyAggregate, Goal, Routine, Session, SessionChange};
+use crate::recovery_state::ActiveSessionSnapshot;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdMigrationReport {
+    pub routines: u32,
+    pub steps: u32,
+    pub sessions: u32,
+    pub revisions: u32,
+}
+
+impl IdMigrationReport {
+    fn is_empty(&self) -> bool {
+        self.routines == 0 && self.steps == 0 && self.sessions == 0 && self.revisions == 0
+    }
+}
+
+#[derive(Debug, Default)]
+struct IdMap {
+    routines: HashMap<String, String>,
+    steps: HashMap<String, HashMap<String, String>>,
+    sessions: HashMap<String, String>,
+}
+
+impl IdMap {
+    fn routine(&self, routine_id: &str) -> Option<&String> {
+        self.routines.get(routine_id)
+    }
+
+    fn step(&self, routine_id: &str, step_id: &str) -> Option<&String> {
+        self.steps.get(routine_id)?.get(step_id)
+    }
+}
+
+pub fn migrate_legacy_ids(data_manager: &DataManager) -> DataResult<IdMigrationReport> {
+    let _updates = data_manager.lock_updates();
+    let mut routines = data_manager.load_routines()?;
+    let mut sessions = data_manager.load_sessions()?;
+    let mut aggregates = data_manager.load_daily_aggregates()?;
+    let mut snapshot = data_manager.load_active_session()?;
+    let mut goals = data_manager.load_goals()?;
+    let mut sync_state = data_manager.load_sync_state()?;
+
+    let mut report = IdMigrationReport::default();
+    let mut map = rewrite_routines(&mut routines, &mut report);
+    let sessions_changed = rewrite_sessions(&mut sessions, &mut map, &mut report);
+    let aggregates_changed = rewrite_aggregates(&mut aggregates, &map);
+    let snapshot_changed = snapshot
+        .as_mut()
+        .is_some_and(|snapshot| rewrite_snapshot(snapshot, &map));
+    let goals_changed = rewrite_goals(&mut goals, &map);
+    let sync_state_changed = sync_state
+        .as_mut()
+        .is_some_and(|state| rewrite_sync_state(state, &map));
+    if report.is_empty()
+        && !sessions_changed
+        && !aggregates_changed
+        && !snapshot_changed
+        && !goals_changed
+        && !sync_state_changed
+    {
+        return Ok(report);
+    }
+
+    data_manager.backup_all(BackupReason::Migration)?;
+    if report.routines > 0 || report.steps > 0 {
+        data_manager.save_routines(&routines)?;
+    }
+    if sessions_changed {
+        data_manager.save_sessions(&sessions)?;
+    }
+    if aggregates_changed {
+        data_manager.save_daily_aggregates(&aggregates)?;
+    }
+    if let Some(snapshot) = snapshot.filter(|_| snapshot_changed) {
+        data_manager.save_active_session(&snapshot)?;
+    }
+    if goals_changed {
+        data_manager.save_goals(&goals)?;
+    }
+    if let Some(state) = sync_state.filter(|_| sync_state_changed) {
+        data_manager.save_sync_state(&state)?;
+    }
+    Ok(report)
+}
+
+fn rewrite_routines(routines: &mut [Routine], report: &mut IdMigrationReport) -> IdMap {
+    let mut map = IdMap::default();
+    for routine in routines {
+        let old_routine_id = routine.id.clone();
+        let mut step_map = HashMap::new();
+        for step in &mut routine.steps {
+            if !is_current_id(IdKind::Step, &step.id) {
+                let seed = format!("{old_routine_id}/{}", step.id);
+                let new_step_id = derived_id(IdKind::Step, None, &seed);
+                step_map.insert(
+                    std::mem::replace(&mut step.id, new_step_id.clone()),
+                    new_step_id,
+                );
+                report.steps += 1;
+            }
+        }
+        if !step_map.is_empty() {
+            map.steps.insert(old_routine_id.clone(), step_map);
+        }
+        if !is_current_id(IdKind::Routine, &routine.id) {
+            routine.id = derived_id(IdKind::Routine, None, &old_routine_id);
+            map.routines.insert(old_routine_id, routine.id.clone());
+            report.routines += 1;
+        }
+    }
+    map
+}
+
+fn rewrite_sessions(
+    sessions: &mut [Session],
+    map: &mut IdMap,
+    report: &mut IdMigrationReport,
+) -> bool {
+    let mut changed = false;
+    for session in sessions {
+        let old_routine_id = session.routine_id.clone();
+        let old_session_id = session.id.clone();
+        for run in &mut session.step_runs {
+            if let Some(step_id) = map.step(&old_routine_id, &run.step_id) {
+                run.step_id = step_id.clone();
+                changed = true;
+            }
+        }
+        for (index, edit) in session.edits.iter_mut().enumerate() {
+            if let SessionChange::StepRunResult { step_id, .. } = &mut edit.change {
+                if let Some(new_step_id) = map.step(&old_routine_id, step_id) {
+                    *step_id = new_step_id.clone();
+                    changed = true;
+                }
+            }
+            if !is_current_id(IdKind::Revision, &edit.id) {
+                let seed = format!("{old_session_id}/{index}/{}", edit.id);
+                edit.id = id_for(IdKind::Revision, &edit.edited_at, &seed);
+                report.revisions += 1;
+            }
+        }
+        if let Some(routine_id) = map.routine(&old_routine_id) {
+            session.routine_id = routine_id.clone();
+            changed = true;
+        }
+        if !is_current_id(IdKind::Session, &session.id) {
+            let seed = format!("{old_session_id}/{}", session.started_at);
+            session.id = id_for(IdKind::Session, &session.started_at, &seed);
+            map.sessions.insert(old_session_id, session.id.clone());
+            report.sessions += 1;
+        }
+    }
+    changed || report.sessions > 0 || report.revisions > 0
+}
+
+fn rewrite_aggregates(aggregates: &mut [DailyAggregate], map: &IdMap) -> bool {
+    let mut changed = false;
+    for aggregate in aggregates {
+        if let Some(routine_id) = map.routine(&aggregate.routine_id) {
+            aggregate.routine_id = routine_id.clone();
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn rewrite_snapshot(snapshot: &mut ActiveSessionSnapshot, map: &IdMap) -> bool {
+    let mut changed = false;
+    if let Some(step_id) = map.step(&snapshot.routine_id, &snapshot.current_step_id) {
+        snapshot.current_step_id = step_id.clone();
+        changed = true;
+    }
+    if let Some(routine_id) = map.routine(&snapshot.routine_id) {
+        snapshot.routine_id = routine_id.clone();
+        changed = true;
+    }
+    if !is_current_id(IdKind::Session, &snapshot.session_id) {
+        let seed = format!("{}/{}", snapshot.session_id, snapshot.started_at);
+        snapshot.session_id = id_for(IdKind::Session, &snapshot.started_at, &seed);
+        changed = true;
+    }
+    changed
+}
+
+fn rewrite_goals(goals: &mut [Goal], map: &IdMap) -> bool {
+    let mut changed = false;
+    for goal in goals {
+        if let Some(routine_id) = goal.routine_id.as_deref().and_then(|id| map.routine(id)) {
+            goal.routine_id = Some(routine_id.clone());
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn rewrite_sync_state(state: &mut SyncState, map: &IdMap) -> bool {
+    let mut changed = state.rename_ids(&map.routines, &map.sessions);
+    // Ids derive from the legacy ones, so a conflict copy lands on the same
+    // routine and step ids as the routine it lost to.
+    let mut discarded = IdMigrationReport::default();
+    for routine in state.discarded_routines_mut() {
+        rewrite_routines(std::slice::from_mut(routine), &mut discarded);
+    }
+    changed |= !discarded.is_empty();
+    changed
+}
+
+/// Id derived from `seed`, stamped with the record's own time so migrated ids
+/// keep their order.
+fn id_for(kind: IdKind, timestamp: &str, seed: &str) -> String {
+    let timestamp = DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|value| value.with_timezone(&Utc));
+    derived_id(kind, timestamp, seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate_legacy_ids, IdMigrationReport};
+    use crate::data_manager::{BackupReason, DataManager};
+    use crate::folder_sync::sync_folder;
+    use crate::ids::{is_current_id, IdKind};
+    use crate::models::{
+        DailyAggregate, Goal, GoalDays, GoalMetric, Session, SessionChange, SessionEdit,
+        SessionTotals, StepRun, StepRunResult,
+    };
+    use crate::recovery_state::ActiveSessionSnapshot;
+    use chrono::Utc;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_id_migration_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn legacy_session(id: &str, started_at: &str, step_id: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            routine_id: "routine-template-10min".to_string(),
+            started_at: started_at.to_string(),
+            ended_at: Some(started_at.to_string()),
+            step_runs: vec![StepRun {
+                step_id: step_id.to_string(),
+                label: None,
+                planned_duration_seconds: 240,
+                actual_duration_seconds: 240,
+                started_at: started_at.to_string(),
+                ended_at: Some(started_at.to_string()),
+                result: StepRunResult::Completed,
+                check_in_result: None,
+                sound_played: false,
+            }],
+            totals: SessionTotals::default(),
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: vec![SessionEdit {
+                id: String::new(),
+                edited_at: started_at.to_string(),
+                change: SessionChange::StepRunResult {
+                    run_index: 0,
+                    step_id: step_id.to_string(),
+                    previous: StepRunResult::Skipped,
+                    result: StepRunResult::Completed,
+                    previous_totals: SessionTotals::default(),
+                },
+            }],
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn rewrites_legacy_ids_consistently() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        manager
+            .save_sessions(&[
+                legacy_session(
+                    "session-1736000000000000000-4242",
+                    "2025-01-01T10:00:00Z",
+                    "step-template-4",
+                ),
+                legacy_session(
+                    "session-1736000000000000000-4242",
+                    "2025-01-02T10:00:00Z",
+                    "step-template-7",
+                ),
+            ])
+            .expect("save sessions");
+        manager
+            .save_daily_aggregates(&[DailyAggregate {
+                date: "2024-01-05".to_string(),
+                routine_id: "routine-template-10min".to_string(),
+                sessions_count: 1,
+                muted_sessions_count: 0,
+                stopped_sessions_count: 0,
+                recovered_sessions_count: 0,
+                totals: SessionTotals::default(),
+            }])
+            .expect("save aggregates");
+        manager
+            .save_active_session(&ActiveSessionSnapshot {
+                session_id: "session-1736000000000000001-4242".to_string(),
+                routine_id: "routine-template-10min".to_string(),
+                started_at: "2025-01-03T10:00:00Z".to_string(),
+                current_step_id: "step-template-4".to_string(),
+                current_step_started_at: "2025-01-03T10:00:00Z".to_string(),
+                current_step_sound_played: false,
+                paused_at: None,
+                muted_during_session: false,
+                zone: None,
+            })
+            .expect("save snapshot");
+        manager
+            .save_goals(&[Goal {
+                id: "goal-1".to_string(),
+                routine_id: Some("routine-template-10min".to_string()),
+                metric: GoalMetric::Sessions,
+                target: 3,
+                days: GoalDays::Every,
+            }])
+            .expect("save goals");
+
+        let report = migrate_legacy_ids(&manager).expect("migrate");
+        assert_eq!(
+            report,
+            IdMigrationReport {
+                routines: 1,
+                steps: 8,
+                sessions: 2,
+                revisions: 2,
+            }
+        );
+
+        let routine = manager.load_routines().expect("load routines").remove(0);
+        assert!(is_current_id(IdKind::Routine, &routine.id));
+        let focus_step = &routine.steps[3].id;
+        let recovery_step = &routine.steps[6].id;
+
+        let sessions = manager.load_sessions().expect("load sessions");
+        assert_ne!(sessions[0].id, sessions[1].id);
+        assert!(sessions[0].id < sessions[1].id);
+        assert!(sessions.iter().all(|item| item.routine_id == routine.id));
+        assert_eq!(&sessions[0].step_runs[0].step_id, focus_step);
+        assert_eq!(&sessions[1].step_runs[0].step_id, recovery_step);
+        assert!(matches!(
+            &sessions[1].edits[0].change,
+            SessionChange::StepRunResult { step_id, .. } if step_id == recovery_step
+        ));
+        assert!(is_current_id(IdKind::Revision, &sessions[0].edits[0].id));
+
+        let aggregates = manager.load_daily_aggregates().expect("load aggregates");
+        assert_eq!(aggregates[0].routine_id, routine.id);
+
+        let snapshot = manager
+            .load_active_session()
+            .expect("load snapshot")
+            .expect("snapshot exists");
+        assert_eq!(snapshot.routine_id, routine.id);
+        assert_eq!(&snapshot.current_step_id, focus_step);
+        assert!(is_current_id(IdKind::Session, &snapshot.session_id));
+
+        let goals = manager.load_goals().expect("load goals");
+        assert_eq!(goals[0].routine_id.as_ref(), Some(&routine.id));
+
+        let migration_backups = manager
+            .list_backups()
+            .expect("list backups")
+            .into_iter()
+            .filter(|item| item.reason == BackupReason::Migration)
+            .count();
+        assert!(migration_backups > 0);
+
+        let again = migrate_legacy_ids(&manager).expect("migrate again");
+        assert_eq!(again, IdMigrationReport::default());
+        let reloaded: Vec<String> = manager
+            .load_sessions()
+            .expect("reload sessions")
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        let ids: Vec<String> = sessions.into_iter().map(|item| item.id).collect();
+        assert_eq!(reloaded, ids);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn devices_migrate_the_same_records_to_the_same_ids() {
+        let migrate = || {
+            let dir = temp_dir();
+            let manager = DataManager::new(&dir).expect("create manager");
+            manager
+                .save_sessions(&[legacy_session(
+                    "session-1736000000000000000-4242",
+                    "2025-01-01T10:00:00Z",
+                    "step-template-4",
+                )])
+                .expect("save sessions");
+            migrate_legacy_ids(&manager).expect("migrate");
+            let routines = manager.load_routines().expect("load routines");
+            let sessions = manager.load_sessions().expect("load sessions");
+            let _ = fs::remove_dir_all(&dir);
+            (routines, sessions)
+        };
+
+        let (left_routines, left_sessions) = migrate();
+        let (right_routines, right_sessions) = migrate();
+        assert_eq!(left_routines[0].id, right_routines[0].id);
+        let step_ids = |routines: &[crate::models::Routine]| -> Vec<String> {
+            routines[0]
+                .steps
+                .iter()
+                .map(|step| step.id.clone())
+                .collect()
+        };
+        assert_eq!(step_ids(&left_routines), step_ids(&right_routines));
+        assert_eq!(left_sessions[0].id, right_sessions[0].id);
+        assert_eq!(left_sessions[0].edits[0].id, right_sessions[0].edits[0].id);
+    }
+
+    #[test]
+    fn synced_records_keep_their_sync_history() {
+        let dir = temp_dir();
+        let folder = dir.join("sync");
+        let manager = DataManager::new(dir.join("data")).expect("create manager");
+        manager
+            .save_sessions(&[legacy_session(
+                "session-1736000000000000000-4242",
+                "2025-01-01T10:00:00Z",
+                "step-template-4",
+            )])
+            .expect("save sessions");
+        sync_folder(&manager, &folder, Utc::now()).expect("first sync");
+
+        migrate_legacy_ids(&manager).expect("migrate");
+        let exported = sync_folder(&manager, &folder, Utc::now()).expect("second sync");
+
+        let log = fs::read_dir(&folder)
+            .expect("read folder")
+            .map(|entry| entry.expect("entry").path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .expect("log exists");
+        let lines: Vec<serde_json::Value> = fs::read_to_string(log)
+            .expect("read log")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("parse line"))
+            .collect();
+        let new_lines = &lines[lines.len() - exported.exported as usize..];
+        assert!(!new_lines.is_empty());
+        assert!(new_lines
+            .iter()
+            .all(|line| line["kind"] != "sessionDeleted"));
+        for line in new_lines
+            .iter()
+            .filter(|line| line["kind"] == "routineChanged")
+        {
+            assert!(!line["routine"].is_null());
+            assert!(!line["base"].is_null());
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
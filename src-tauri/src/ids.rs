@@ -0,0 +1,194 @@
+//! Identifier generation shared by every record type.
+//!
+//! Ids look like `session-01JA8Z3M4Q6V9C2D7E5F1G0H3K`: a kind prefix followed
+//! by a ULID, i.e. a 48-bit millisecond timestamp and 80 random bits in
+//! Crockford base32. They sort by creation time, and ids generated by this
+//! process are strictly increasing even if the clock steps backwards.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+const ULID_LEN: usize = 26;
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const RANDOM_BITS: u32 = 80;
+const RANDOM_MASK: u128 = (1 << RANDOM_BITS) - 1;
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+static LAST_VALUE: Mutex<u128> = Mutex::new(0);
+static FALLBACK_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IdKind {
+    Session,
+    Routine,
+    Step,
+    Revision,
+    Device,
+    Conflict,
+    Profile,
+    Goal,
+}
+
+impl IdKind {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            IdKind::Session => "session",
+            IdKind::Routine => "routine",
+            IdKind::Step => "step",
+            IdKind::Revision => "rev",
+            IdKind::Device => "device",
+            IdKind::Conflict => "conflict",
+            IdKind::Profile => "profile",
+            IdKind::Goal => "goal",
+        }
+    }
+}
+
+/// Returns a new id for `kind`, ordered after every id this process made before.
+pub fn new_id(kind: IdKind) -> String {
+    let millis = Utc::now().timestamp_millis().max(0) as u128;
+    let candidate = (millis << RANDOM_BITS) | random_bits();
+    let mut last = LAST_VALUE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let value = if candidate > *last {
+        candidate
+    } else {
+        *last + 1
+    };
+    *last = value;
+    format_id(kind, value)
+}
+
+/// Returns the id `seed` always maps to: the timestamp part is `timestamp`, or
+/// zero for records with no time of their own, and the rest is a hash of
+/// `seed`. Lets every device migrate the same legacy record to the same id.
+pub fn derived_id(kind: IdKind, timestamp: Option<DateTime<Utc>>, seed: &str) -> String {
+    let millis =
+        timestamp.map_or(0, |value| value.timestamp_millis().clamp(0, (1 << 48) - 1)) as u128;
+    format_id(
+        kind,
+        (millis << RANDOM_BITS) | (stable_hash(seed) & RANDOM_MASK),
+    )
+}
+
+/// Whether `id` has the current format for `kind`.
+pub fn is_current_id(kind: IdKind, id: &str) -> bool {
+    let Some(ulid) = id
+        .strip_prefix(kind.prefix())
+        .and_then(|rest| rest.strip_prefix('-'))
+    else {
+        return false;
+    };
+    ulid.len() == ULID_LEN
+        && ulid.as_bytes()[0] <= b'7'
+        && ulid.bytes().all(|byte| CROCKFORD.contains(&byte))
+}
+
+fn format_id(kind: IdKind, value: u128) -> String {
+    let mut ulid = [0u8; ULID_LEN];
+    for (index, slot) in ulid.iter_mut().enumerate() {
+        let shift = 5 * (ULID_LEN - 1 - index);
+        *slot = CROCKFORD[((value >> shift) & 0x1f) as usize];
+    }
+    let ulid = std::str::from_utf8(&ulid).expect("crockford alphabet is ascii");
+    format!("{}-{ulid}", kind.prefix())
+}
+
+/// 128-bit FNV-1a, fixed so derived ids never change between builds.
+fn stable_hash(seed: &str) -> u128 {
+    seed.bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u128::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn random_bits() -> u128 {
+    let mut bytes = [0u8; 16];
+    if getrandom::getrandom(&mut bytes).is_err() {
+        // Without an OS source, fall back to per-process random hasher keys
+        // mixed with a counter; still unique within the process.
+        let state = RandomState::new();
+        for chunk in bytes.chunks_mut(8) {
+            let mut hasher = state.build_hasher();
+            hasher.write_u64(FALLBACK_COUNTER.fetch_add(1, Ordering::Relaxed));
+            chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+        }
+    }
+    u128::from_le_bytes(bytes) & RANDOM_MASK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{derived_id, is_current_id, new_id, IdKind};
+    use chrono::{DateTime, Utc};
+    use std::collections::HashSet;
+
+    #[test]
+    fn ids_are_unique_and_sorted() {
+        let ids: Vec<String> = (0..1000).map(|_| new_id(IdKind::Session)).collect();
+        let unique: HashSet<&String> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, ids);
+        assert!(ids.iter().all(|id| is_current_id(IdKind::Session, id)));
+    }
+
+    #[test]
+    fn derived_ids_are_stable() {
+        let at = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .expect("parse")
+            .with_timezone(&Utc);
+        let id = derived_id(
+            IdKind::Session,
+            Some(at),
+            "session-1736000000000000000-4242",
+        );
+        assert_eq!(id, "session-01HK153X0038D6YGX6EX5YK7A4");
+        assert_eq!(
+            derived_id(IdKind::Routine, None, "routine-template-10min"),
+            derived_id(IdKind::Routine, None, "routine-template-10min")
+        );
+        assert_ne!(
+            derived_id(IdKind::Step, None, "a"),
+            derived_id(IdKind::Step, None, "b")
+        );
+        assert!(is_current_id(IdKind::Session, &id));
+    }
+
+    #[test]
+    fn timestamped_ids_sort_by_time() {
+        let earlier = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .expect("parse")
+            .with_timezone(&Utc);
+        let later = DateTime::parse_from_rfc3339("2024-01-02T00:00:00Z")
+            .expect("parse")
+            .with_timezone(&Utc);
+        assert!(
+            derived_id(IdKind::Step, Some(earlier), "z")
+                < derived_id(IdKind::Step, Some(later), "a")
+        );
+        assert!(derived_id(IdKind::Step, Some(later), "z") < new_id(IdKind::Step));
+    }
+
+    #[test]
+    fn recognizes_legacy_ids() {
+        assert!(!is_current_id(
+            IdKind::Session,
+            "session-1736000000000000000-4242"
+        ));
+        assert!(!is_current_id(
+            IdKind::Step,
+            "3f2b8c1e-8d4f-4f6a-9d3e-1a2b3c4d5e6f"
+        ));
+        assert!(!is_current_id(IdKind::Routine, &new_id(IdKind::Step)));
+        assert!(is_current_id(IdKind::Revision, &new_id(IdKind::Revision)));
+    }
+}
@@ -1,39 +1,84 @@
+mod abort_stats;
 mod app_error;
+mod audio_backend;
 #[allow(dead_code)]
 mod audio_manager;
+mod calendar;
+mod check_in_stats;
+mod cli;
+mod data_crypto;
+mod data_integrity;
 mod events;
+mod folder_sync;
 use tauri::Manager;
 mod commands;
 #[allow(dead_code)]
 mod data_manager;
 mod global_shortcuts;
+mod goal_actions;
+mod goals;
+mod history_retention;
+mod id_migration;
+mod ids;
 mod menu_bar;
 mod models;
+mod profiles;
 mod recovery_state;
+mod routine_file;
+mod routine_validator;
 mod runtime_state;
+mod session_editor;
+mod session_export;
 mod session_recovery;
 mod session_stats;
 mod session_tracker;
 mod sound_actions;
+mod sound_assets;
+mod step_warnings;
 mod timer_actions;
 #[allow(dead_code)]
 mod timer_engine;
+mod weekly_report;
 
 use crate::app_error::AppError;
 use crate::audio_manager::SoundEvent;
+use crate::data_manager::{DataDirLock, DataError, DataFile, DataManager};
 use crate::events::{
-    emit_app_error, emit_check_in_required, emit_check_in_timeout, emit_step_changed,
-    emit_timer_paused, emit_timer_stopped, emit_timer_tick,
+    emit_app_error, emit_check_in_required, emit_check_in_timeout, emit_data_changed_externally,
+    emit_step_changed, emit_step_warning, emit_timer_paused, emit_timer_stopped, emit_timer_tick,
 };
-use crate::models::StepRunResult;
+use crate::models::{SessionOutcome, StepRunResult};
+use crate::profiles::ProfileStore;
 use crate::session_tracker::SessionTracker;
 use crate::sound_actions::{build_sound_context, play_sound_for_event};
+use crate::step_warnings::WarningTracker;
 use crate::timer_engine::{AdvanceResult, TimerEngine, TimerError};
-use chrono::Utc;
+use chrono::{Local, Utc};
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use std::time::Duration;
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+pub use crate::cli::run_cli;
+
+const DATA_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+const FOLDER_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to check whether last week's report is due; it is written on
+/// the first check after the week ends.
+const WEEKLY_REPORT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long startup waits for the data folder lock; covers the previous
+/// instance still shutting down after a profile switch restart.
+const DATA_LOCK_WAIT: Duration = Duration::from_secs(3);
+
+/// What startup holds on to while the data directory waits to be unlocked.
+/// Managed as `Mutex<Option<LockedData>>`, `None` once the app has started.
+struct LockedData {
+    data_lock: DataDirLock,
+    profile_store: ProfileStore,
+    profile_dir: PathBuf,
+    profile_name: String,
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -42,32 +87,67 @@ pub fn run() {
             let data_dir = env::var("MCCALL_DATA_DIR")
                 .map(PathBuf::from)
                 .unwrap_or(app.path().app_data_dir()?);
-            let data_manager = data_manager::DataManager::new(data_dir)?;
-            if let Err(err) = session_recovery::recover_aborted_session(&data_manager) {
-                eprintln!("Failed to recover session: {err}");
-            }
-            app.manage(data_manager);
-            app.manage(Mutex::new(timer_engine::TimerEngine::new()));
-            app.manage(Mutex::new(audio_manager::AudioManager::new()));
-            app.manage(Mutex::new(runtime_state::RuntimeState::default()));
-            app.manage(Mutex::new(session_tracker::SessionTracker::new()));
-            let app_handle = app.handle();
-            let menu = menu_bar::create_menu_bar(app_handle)?;
-            app.manage(Mutex::new(menu));
-            menu_bar::sync_menu_bar(app_handle);
-            global_shortcuts::register_global_shortcuts(app_handle);
-            spawn_timer_loop(app_handle.clone());
+            let data_lock = match DataDirLock::acquire_within(&data_dir, DATA_LOCK_WAIT) {
+                Ok(lock) => lock,
+                Err(err @ DataError::Locked(_)) => {
+                    eprintln!("{err}. Close the other instance or set MCCALL_DATA_DIR to a different folder.");
+                    // Started from a launcher there is no terminal to read, so
+                    // say why in a dialog and quit once it is dismissed.
+                    if let Some(window) = app.get_webview_window("main") {
+                        let _ = window.hide();
+                    }
+                    app.dialog()
+                        .message(format!(
+                            "{}。もう一方のウィンドウを閉じてから、もう一度起動してください。",
+                            AppError::from(err).message()
+                        ))
+                        .title("mccall")
+                        .kind(MessageDialogKind::Error)
+                        .show(|_| std::process::exit(1));
+                    return Ok(());
+                }
+                Err(err) => return Err(err.into()),
+            };
+            let profile_store = ProfileStore::open(&data_dir)?;
+            let active_profile = profile_store.active()?;
+            let profile_dir = profile_store.profile_dir(&active_profile.id);
+            let key_source = data_crypto::startup_key_source(&profile_dir)?;
+            let data_manager = match DataManager::open(&profile_dir, key_source.as_ref()) {
+                Ok(manager) => manager,
+                // Left to the unlock screen, which calls `unlock_data`.
+                Err(DataError::KeyRequired | DataError::WrongKey) => {
+                    app.manage(Mutex::new(Some(LockedData {
+                        data_lock,
+                        profile_store,
+                        profile_dir,
+                        profile_name: active_profile.name,
+                    })));
+                    return Ok(());
+                }
+                Err(err) => return Err(err.into()),
+            };
+            app.manage(Mutex::new(None::<LockedData>));
+            start_app(
+                app.handle(),
+                data_lock,
+                data_manager,
+                profile_store,
+                &active_profile.name,
+            )?;
             Ok(())
         })
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_notification::init());
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_dialog::init());
 
     #[cfg(debug_assertions)]
     let builder = builder.plugin(tauri_plugin_automation::init());
 
     builder
         .invoke_handler(tauri::generate_handler![
+            commands::get_unlock_state,
+            commands::unlock_data,
             commands::start_routine,
             commands::pause_timer,
             commands::resume_timer,
@@ -75,20 +155,188 @@ pub fn run() {
             commands::stop_timer,
             commands::get_timer_state,
             commands::save_routine,
+            commands::new_ids,
             commands::load_routines,
+            commands::export_routine,
+            commands::import_routine,
+            commands::import_sound,
+            commands::list_sounds,
+            commands::delete_sound,
             commands::load_settings,
             commands::save_settings,
+            commands::list_backups,
+            commands::restore_backup,
+            commands::verify_data,
+            commands::get_encryption_status,
+            commands::enable_encryption,
+            commands::rotate_encryption_key,
+            commands::disable_encryption,
             commands::respond_to_check_in,
             commands::toggle_global_mute,
             commands::get_session_stats,
+            commands::rebuild_stats_cache,
+            commands::get_step_stats,
+            commands::get_stats_series,
+            commands::get_grouped_stats,
+            commands::get_check_in_analytics,
+            commands::get_abort_analytics,
+            commands::generate_weekly_report,
+            commands::load_goals,
+            commands::save_goals,
+            commands::get_goal_progress,
+            commands::get_period,
+            commands::prune_history,
+            commands::list_sessions,
+            commands::delete_session,
+            commands::set_session_excluded,
+            commands::set_session_tags,
+            commands::set_session_annotation,
+            commands::correct_step_run_result,
+            commands::sync_now,
+            commands::list_sync_conflicts,
+            commands::resolve_sync_conflict,
+            commands::export_sessions,
+            commands::list_profiles,
+            commands::create_profile,
+            commands::switch_profile,
+            commands::get_profile_stats,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
+/// Everything past opening the data directory: startup repairs, app state and
+/// the background loops. Runs from `setup`, or from `unlock_data` when the
+/// directory needed a passphrase first.
+fn start_app(
+    app_handle: &tauri::AppHandle,
+    data_lock: DataDirLock,
+    data_manager: DataManager,
+    profile_store: ProfileStore,
+    profile_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for recovery in data_manager.startup_recoveries() {
+        let corrupt_copy = recovery.corrupt_copy.display();
+        match recovery.restored_from.as_deref() {
+            Some(backup) => eprintln!(
+                "Restored {:?} from backup {backup} (unreadable copy: {corrupt_copy})",
+                recovery.data_file
+            ),
+            None => eprintln!(
+                "Reset unreadable {:?} with no usable backup (unreadable copy: {corrupt_copy})",
+                recovery.data_file
+            ),
+        }
+    }
+    if let Err(err) = session_recovery::recover_aborted_session(&data_manager) {
+        eprintln!("Failed to recover session: {err}");
+    }
+    if let Err(err) = id_migration::migrate_legacy_ids(&data_manager) {
+        eprintln!("Failed to migrate legacy ids: {err}");
+    }
+    let retention = data_manager
+        .load_settings()
+        .map(|settings| settings.retention)
+        .unwrap_or_default();
+    if let Err(err) = history_retention::prune_history(&data_manager, &retention, Utc::now()) {
+        eprintln!("Failed to prune session history: {err}");
+    }
+    app_handle.manage(data_lock);
+    app_handle.manage(data_manager);
+    app_handle.manage(profile_store);
+    app_handle.manage(Mutex::new(timer_engine::TimerEngine::new()));
+    app_handle.manage(Mutex::new(audio_manager::AudioManager::new()));
+    app_handle.manage(Mutex::new(runtime_state::RuntimeState::default()));
+    app_handle.manage(Mutex::new(session_tracker::SessionTracker::new()));
+    app_handle.manage(Mutex::new(goal_actions::GoalState::default()));
+    let menu = menu_bar::create_menu_bar(app_handle, profile_name)?;
+    app_handle.manage(Mutex::new(menu));
+    menu_bar::sync_menu_bar(app_handle);
+    global_shortcuts::register_global_shortcuts(app_handle);
+    spawn_timer_loop(app_handle.clone());
+    spawn_data_watcher(app_handle.clone());
+    spawn_folder_sync(app_handle.clone());
+    spawn_weekly_reports(app_handle.clone());
+    Ok(())
+}
+
+fn spawn_data_watcher(app_handle: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(DATA_WATCH_INTERVAL);
+
+        let data_manager = app_handle.state::<DataManager>();
+        match data_manager.take_external_changes() {
+            Ok(changed) => {
+                for file in changed {
+                    emit_data_changed_externally(&app_handle, file);
+                }
+            }
+            Err(err) => eprintln!("Failed to check data files for external changes: {err}"),
+        }
+    });
+}
+
+fn spawn_folder_sync(app_handle: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        let data_manager = app_handle.state::<DataManager>();
+        let folder = match data_manager.load_settings() {
+            Ok(settings) => settings.sync.folder,
+            Err(err) => {
+                eprintln!("Failed to load settings for sync: {err}");
+                None
+            }
+        };
+        if let Some(folder) = folder {
+            match folder_sync::sync_folder(&data_manager, Path::new(&folder), Utc::now()) {
+                Ok(report) => {
+                    if report.routines_changed {
+                        emit_data_changed_externally(&app_handle, DataFile::Routines);
+                    }
+                    if report.sessions_changed {
+                        emit_data_changed_externally(&app_handle, DataFile::Sessions);
+                    }
+                    if report.conflicts > 0 {
+                        emit_app_error(
+                            &app_handle,
+                            AppError::sync_conflicts(report.conflicts).payload(),
+                        );
+                    }
+                }
+                Err(err) => eprintln!("Failed to sync with {folder}: {err}"),
+            }
+        }
+        std::thread::sleep(FOLDER_SYNC_INTERVAL);
+    });
+}
+
+fn spawn_weekly_reports(app_handle: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        let data_manager = app_handle.state::<DataManager>();
+        match data_manager.load_settings() {
+            Ok(settings) => {
+                if let Some(folder) = settings.report.folder {
+                    if let Err(err) = weekly_report::write_finished_week_report(
+                        &data_manager,
+                        Path::new(&folder),
+                        Utc::now(),
+                        &Local,
+                        &settings.calendar,
+                    ) {
+                        eprintln!("Failed to write weekly report to {folder}: {err}");
+                    }
+                }
+            }
+            Err(err) => eprintln!("Failed to load settings for weekly report: {err}"),
+        }
+        std::thread::sleep(WEEKLY_REPORT_INTERVAL);
+    });
+}
+
 fn spawn_timer_loop(app_handle: tauri::AppHandle) {
+    let mut warning_tracker = WarningTracker::default();
     std::thread::spawn(move || loop {
         std::thread::sleep(Duration::from_secs(1));
+        goal_actions::update_goal_progress(&app_handle);
 
         let state = app_handle.state::<Mutex<TimerEngine>>();
         let mut engine = match state.lock() {
@@ -100,6 +348,7 @@ fn spawn_timer_loop(app_handle: tauri::AppHandle) {
         };
 
         if !engine.is_running() {
+            warning_tracker.reset();
             continue;
         }
 
@@ -150,15 +399,30 @@ fn spawn_timer_loop(app_handle: tauri::AppHandle) {
             })
         });
 
+        let step_warnings = match (engine.current_step(), engine.remaining_time()) {
+            (Some(step), Ok(remaining)) => {
+                let warnings = warning_tracker.observe(step, remaining);
+                (!warnings.is_empty()).then(|| (step.clone(), warnings))
+            }
+            _ => None,
+        };
+
         let routine_completed = matches!(advance_result, AdvanceResult::RoutineCompleted);
         let step_sound_context = step_changed
             .as_ref()
-            .and_then(|(step, _)| build_sound_context(&engine, Some(step)));
+            .and_then(|(step, _)| build_sound_context(&engine, Some(step)))
+            .map(|context| context.after_step(previous_step.as_ref()));
         let routine_sound_context = if routine_completed {
-            routine_base_context
+            routine_base_context.map(|context| context.after_step(previous_step.as_ref()))
         } else {
             None
         };
+        let check_in_sound_context = check_in_required
+            .as_ref()
+            .and_then(|(_, step, _)| build_sound_context(&engine, Some(step)));
+        let warning_sound_context = step_warnings
+            .as_ref()
+            .and_then(|(step, _)| build_sound_context(&engine, Some(step)));
         drop(engine);
 
         let step_sound_record = step_sound_context.as_ref().and_then(|context| {
@@ -171,6 +435,18 @@ fn spawn_timer_loop(app_handle: tauri::AppHandle) {
         if let Some(context) = routine_sound_context {
             let _ = play_sound_for_event(&app_handle, Some(context), SoundEvent::RoutineCompleted);
         }
+        if let Some(context) = check_in_sound_context {
+            let _ = play_sound_for_event(&app_handle, Some(context), SoundEvent::CheckInRequired);
+        }
+        if let Some((_, warnings)) = step_warnings.as_ref() {
+            for warning in warnings {
+                let _ = play_sound_for_event(
+                    &app_handle,
+                    warning_sound_context.clone(),
+                    warning.sound_event(),
+                );
+            }
+        }
 
         if let Some(data_manager) = app_handle.try_state::<data_manager::DataManager>() {
             if let Some((step, _)) = step_changed.as_ref() {
@@ -242,7 +518,9 @@ fn spawn_timer_loop(app_handle: tauri::AppHandle) {
                         );
                     }
 
-                    if let Some(session) = tracker.finish_session(now_rfc3339()) {
+                    if let Some(session) =
+                        tracker.finish_session(now_rfc3339(), SessionOutcome::Completed)
+                    {
                         if let Some(data_manager) =
                             app_handle.try_state::<data_manager::DataManager>()
                         {
@@ -270,6 +548,11 @@ fn spawn_timer_loop(app_handle: tauri::AppHandle) {
         if let Some(step_id) = check_in_timeout {
             emit_check_in_timeout(&app_handle, step_id);
         }
+        if let Some((step, warnings)) = step_warnings {
+            for warning in warnings {
+                emit_step_warning(&app_handle, &step, warning);
+            }
+        }
         if routine_completed {
             emit_timer_stopped(&app_handle);
         }
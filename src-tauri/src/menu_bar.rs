@@ -19,6 +19,8 @@ const MENU_PAUSE_ID: &str = "menu-pause";
 const MENU_SKIP_ID: &str = "menu-skip";
 const MENU_STOP_ID: &str = "menu-stop";
 const MENU_MUTE_ID: &str = "menu-mute";
+const MENU_PROFILE_ID: &str = "menu-profile";
+const MENU_GOALS_ID: &str = "menu-goals";
 const MAX_STEP_LABEL_CHARS: usize = 12;
 
 #[derive(Debug, Clone, Default)]
@@ -37,15 +39,24 @@ pub struct MenuBarState {
     skip_item: MenuItem<Wry>,
     stop_item: MenuItem<Wry>,
     mute_item: MenuItem<Wry>,
+    goal_item: MenuItem<Wry>,
     last_title: Option<String>,
     last_running: Option<bool>,
     last_paused: Option<bool>,
     last_muted: Option<bool>,
     last_pause_label: Option<String>,
     last_mute_label: Option<String>,
+    last_goal_line: Option<String>,
 }
 
-pub fn create_menu_bar(app: &AppHandle) -> tauri::Result<MenuBarState> {
+pub fn create_menu_bar(app: &AppHandle, profile_name: &str) -> tauri::Result<MenuBarState> {
+    let profile_item =
+        MenuItemBuilder::with_id(MENU_PROFILE_ID, format!("Profile: {profile_name}"))
+            .enabled(false)
+            .build(app)?;
+    let goal_item = MenuItemBuilder::with_id(MENU_GOALS_ID, "Today: no goals")
+        .enabled(false)
+        .build(app)?;
     let start_item = MenuItemBuilder::with_id(MENU_START_ID, "Start").build(app)?;
     let pause_item = MenuItemBuilder::with_id(MENU_PAUSE_ID, "Pause").build(app)?;
     let skip_item = MenuItemBuilder::with_id(MENU_SKIP_ID, "Skip").build(app)?;
@@ -53,6 +64,8 @@ pub fn create_menu_bar(app: &AppHandle) -> tauri::Result<MenuBarState> {
     let mute_item = MenuItemBuilder::with_id(MENU_MUTE_ID, "Mute").build(app)?;
 
     let menu = MenuBuilder::new(app)
+        .items(&[&profile_item, &goal_item])
+        .separator()
         .items(&[&start_item, &pause_item, &skip_item, &stop_item])
         .separator()
         .item(&mute_item)
@@ -61,7 +74,7 @@ pub fn create_menu_bar(app: &AppHandle) -> tauri::Result<MenuBarState> {
     let mut builder = TrayIconBuilder::with_id(TRAY_ID)
         .menu(&menu)
         .title("McCall")
-        .tooltip("McCall")
+        .tooltip(format!("McCall ({profile_name})"))
         .show_menu_on_left_click(true)
         .on_menu_event(handle_menu_event);
 
@@ -78,12 +91,14 @@ pub fn create_menu_bar(app: &AppHandle) -> tauri::Result<MenuBarState> {
         skip_item,
         stop_item,
         mute_item,
+        goal_item,
         last_title: None,
         last_running: None,
         last_paused: None,
         last_muted: None,
         last_pause_label: None,
         last_mute_label: None,
+        last_goal_line: None,
     })
 }
 
@@ -125,6 +140,24 @@ pub fn sync_menu_bar(app: &AppHandle) {
     update_menu_bar(&mut menu_state, &snapshot, muted);
 }
 
+/// Shows today's goal progress in the tray menu.
+pub fn set_goal_line(app: &AppHandle, line: &str) {
+    let Some(menu_state) = app.try_state::<Mutex<MenuBarState>>() else {
+        return;
+    };
+    let Ok(mut menu_state) = menu_state.lock() else {
+        eprintln!("Menu bar state lock failed");
+        return;
+    };
+    if menu_state.last_goal_line.as_deref() == Some(line) {
+        return;
+    }
+    if let Err(err) = menu_state.goal_item.set_text(line) {
+        eprintln!("Failed to update goal line: {err}");
+    }
+    menu_state.last_goal_line = Some(line.to_string());
+}
+
 fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
     match event.id().as_ref() {
         MENU_START_ID => handle_start(app),
@@ -11,6 +11,8 @@ pub struct Routine {
     pub notifications: bool,
     pub sound_default: SoundSetting,
     pub sound_scheme: SoundScheme,
+    #[serde(default)]
+    pub sounds: SoundAssets,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +26,11 @@ pub struct Step {
     pub sound_override: SoundOverride,
     pub count_as_break: bool,
     pub check_in: CheckInConfig,
+    /// Overrides the routine's sounds for this step.
+    #[serde(default)]
+    pub sounds: SoundAssets,
+    #[serde(default)]
+    pub warnings: StepWarnings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,12 +52,85 @@ pub struct Session {
     pub step_runs: Vec<StepRun>,
     pub totals: SessionTotals,
     pub muted_during_session: bool,
+    /// The user's zone when the session started, used to place the session at
+    /// the hour of day it was started at wherever that was. `None` for
+    /// sessions recorded before zones were stored, which fall back to the zone
+    /// stats are viewed in.
+    #[serde(default)]
+    pub zone: Option<SessionZone>,
+    #[serde(default)]
+    pub excluded_from_stats: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub annotation: Option<String>,
+    /// Audit trail of manual edits, oldest first.
+    #[serde(default)]
+    pub edits: Vec<SessionEdit>,
+    /// How the session ended; `None` for sessions recorded before outcomes
+    /// were stored, see `session_stats::session_outcome`.
+    #[serde(default)]
+    pub outcome: Option<SessionOutcome>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionOutcome {
+    /// The routine ran to its end.
+    Completed,
+    /// The user stopped the timer part way.
+    Stopped,
+    /// Rebuilt at startup from the snapshot of a session the app crashed or
+    /// quit during.
+    Recovered,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionZone {
+    pub utc_offset_minutes: i32,
+    /// IANA name such as `Asia/Tokyo`, when the platform reports one.
+    pub time_zone: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEdit {
+    #[serde(default)]
+    pub id: String,
+    pub edited_at: String,
+    #[serde(flatten)]
+    pub change: SessionChange,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SessionChange {
+    #[serde(rename_all = "camelCase")]
+    ExcludedFromStats { excluded: bool },
+    #[serde(rename_all = "camelCase")]
+    Tags { previous: Vec<String> },
+    #[serde(rename_all = "camelCase")]
+    Annotation { previous: Option<String> },
+    #[serde(rename_all = "camelCase")]
+    StepRunResult {
+        run_index: usize,
+        step_id: String,
+        previous: StepRunResult,
+        result: StepRunResult,
+        previous_totals: SessionTotals,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StepRun {
     pub step_id: String,
+    /// The step's label when it ran, so history keeps the name a step had
+    /// after it is renamed or deleted. `None` for runs recorded before labels
+    /// were stored.
+    #[serde(default)]
+    pub label: Option<String>,
     pub planned_duration_seconds: u32,
     pub actual_duration_seconds: u32,
     pub started_at: String,
@@ -81,7 +161,7 @@ pub struct CheckInResponse {
     pub response_time_ms: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionTotals {
     pub total_seconds: u32,
@@ -90,12 +170,51 @@ pub struct SessionTotals {
     pub cycles_count: u32,
     pub check_in_done_count: u32,
     pub check_in_skip_count: u32,
+    /// Planned seconds of the step runs. Zero for sessions recorded before
+    /// adherence was tracked; stats then fall back to their step runs.
+    #[serde(default)]
+    pub planned_seconds: u32,
+    /// Planned seconds actually run, overtime not counted.
+    #[serde(default)]
+    pub executed_seconds: u32,
+    /// Seconds step runs went past their planned duration.
+    #[serde(default)]
+    pub overtime_seconds: u32,
+    /// Planned seconds left unused by skipped step runs.
+    #[serde(default)]
+    pub skip_loss_seconds: u32,
+    #[serde(default)]
+    pub skipped_count: u32,
+}
+
+/// Sessions of one routine on one UTC day, folded together once the individual
+/// sessions age out of the retention window. Range queries and charts place
+/// the whole day at noon UTC; see `session_stats::aggregate_instant`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyAggregate {
+    pub date: String,
+    pub routine_id: String,
+    pub sessions_count: u32,
+    pub muted_sessions_count: u32,
+    /// Aggregates folded before outcomes were counted have none stopped or
+    /// recovered.
+    #[serde(default)]
+    pub stopped_sessions_count: u32,
+    #[serde(default)]
+    pub recovered_sessions_count: u32,
+    pub totals: SessionTotals,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionStats {
     pub sessions_count: u32,
+    pub completed_sessions_count: u32,
+    pub stopped_sessions_count: u32,
+    pub recovered_sessions_count: u32,
+    /// Share of sessions stopped or recovered rather than completed.
+    pub abort_rate: f32,
     pub cycles_count: u32,
     pub total_seconds: u32,
     pub work_seconds: u32,
@@ -103,6 +222,202 @@ pub struct SessionStats {
     pub check_in_done_count: u32,
     pub check_in_skip_count: u32,
     pub mute_rate: f32,
+    pub planned_seconds: u32,
+    pub executed_seconds: u32,
+    pub overtime_seconds: u32,
+    pub skip_loss_seconds: u32,
+    pub skipped_count: u32,
+    /// Share of planned time actually run, overtime not counted.
+    pub executed_ratio: f32,
+    /// Planned seconds left unused per skipped step run.
+    pub average_skip_loss_seconds: f32,
+}
+
+/// Check-in response times in milliseconds. Percentiles interpolate between
+/// neighbouring samples and are `None` without any; timed-out check-ins have
+/// no response time and are only counted.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseTimeSummary {
+    pub responses: u32,
+    pub timed_out_count: u32,
+    pub p50_ms: Option<u64>,
+    pub p90_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StepResponseTimes {
+    pub routine_id: String,
+    pub step_id: String,
+    pub label: Option<String>,
+    pub mode: CheckInMode,
+    pub summary: ResponseTimeSummary,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModeResponseTimes {
+    pub mode: CheckInMode,
+    pub summary: ResponseTimeSummary,
+}
+
+/// Gate responses that took at least `min_ms` and less than `max_ms`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitBucket {
+    pub min_ms: u64,
+    pub max_ms: Option<u64>,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyResponseTimes {
+    pub start_date: String,
+    pub from: String,
+    pub to: String,
+    pub summary: ResponseTimeSummary,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckInAnalytics {
+    pub by_step: Vec<StepResponseTimes>,
+    pub by_mode: Vec<ModeResponseTimes>,
+    pub gate_waits: Vec<WaitBucket>,
+    pub weekly: Vec<WeeklyResponseTimes>,
+}
+
+/// Where sessions were abandoned: the step they ended in and how far into its
+/// planned duration, as a share from 0 to 1.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AbandonedStep {
+    pub routine_id: String,
+    pub step_id: String,
+    pub label: Option<String>,
+    pub stopped_count: u32,
+    pub recovered_count: u32,
+    pub average_progress: f32,
+    /// Abandonments per quarter of the step, the last also holding overruns.
+    pub progress_quarters: [u32; 4],
+}
+
+/// Sessions started within one local hour of the day, over all days.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HourlyAbortRate {
+    pub hour: u8,
+    pub sessions_count: u32,
+    pub aborted_count: u32,
+    pub abort_rate: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AbortAnalytics {
+    pub completed_count: u32,
+    pub stopped_count: u32,
+    pub recovered_count: u32,
+    /// Most often abandoned first.
+    pub abandoned_steps: Vec<AbandonedStep>,
+    /// One entry per hour, 0 to 23.
+    pub by_hour: Vec<HourlyAbortRate>,
+}
+
+/// Which sessions a stats query counts. Empty lists and `None` match
+/// everything; a session matches `tags` when it carries any of them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsFilter {
+    #[serde(default)]
+    pub routine_ids: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub weekdays: Vec<DayOfWeek>,
+    #[serde(default)]
+    pub time_of_day: Option<TimeWindow>,
+}
+
+/// Local start times from `start` up to but not including `end`, both
+/// "HH:MM". A window with `end` before `start` runs past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeWindow {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DayOfWeek {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StatsGroupBy {
+    Routine,
+    Tag,
+    Weekday,
+}
+
+/// Stats for one routine, tag or weekday. `key` is the routine id, the tag
+/// or the camelCase weekday; `label` is the routine's current name.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsGroup {
+    pub key: String,
+    pub label: Option<String>,
+    pub stats: SessionStats,
+}
+
+/// How runs of one step of one routine went. `label` comes from the routine's
+/// current definition and is `None` when the step has since been removed.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StepStats {
+    pub routine_id: String,
+    pub step_id: String,
+    pub label: Option<String>,
+    pub runs: u32,
+    pub completed_count: u32,
+    pub skipped_count: u32,
+    pub aborted_count: u32,
+    pub average_planned_seconds: f32,
+    pub average_actual_seconds: f32,
+    pub executed_ratio: f32,
+    pub average_skip_loss_seconds: f32,
+    pub overtime_seconds: u32,
+    pub check_in_count: u32,
+    pub check_in_done_rate: f32,
+    pub check_in_skip_rate: f32,
+    pub check_in_timeout_rate: f32,
+    pub median_response_time_ms: Option<u64>,
+}
+
+/// Totals for one period of a stats series. Time from step runs that cross a
+/// period boundary is split between the periods it overlaps.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsBucket {
+    pub start_date: String,
+    pub from: String,
+    pub to: String,
+    pub sessions_count: u32,
+    pub cycles_count: u32,
+    pub work_seconds: u32,
+    pub break_seconds: u32,
+    pub step_runs_count: u32,
+    pub skip_rate: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -122,6 +437,14 @@ pub struct TimerState {
 pub struct AppSettings {
     pub notifications_enabled: bool,
     pub sound_default: SoundSetting,
+    #[serde(default)]
+    pub retention: RetentionSettings,
+    #[serde(default)]
+    pub calendar: CalendarSettings,
+    #[serde(default)]
+    pub sync: SyncSettings,
+    #[serde(default)]
+    pub report: ReportSettings,
 }
 
 impl Default for AppSettings {
@@ -129,10 +452,88 @@ impl Default for AppSettings {
         Self {
             notifications_enabled: true,
             sound_default: SoundSetting::On,
+            retention: RetentionSettings::default(),
+            calendar: CalendarSettings::default(),
+            sync: SyncSettings::default(),
+            report: ReportSettings::default(),
         }
     }
 }
 
+/// A daily target such as "3 sessions on weekdays", for one routine or all.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Goal {
+    #[serde(default)]
+    pub id: String,
+    /// `None` counts sessions of every routine.
+    pub routine_id: Option<String>,
+    pub metric: GoalMetric,
+    pub target: u32,
+    pub days: GoalDays,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GoalMetric {
+    Sessions,
+    WorkMinutes,
+    Cycles,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GoalDays {
+    Every,
+    Weekdays,
+    Weekends,
+}
+
+/// Shared folder (Syncthing, Dropbox, ...) this device syncs through, if any.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSettings {
+    pub folder: Option<String>,
+}
+
+/// Folder each finished week's report is written to, if any.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportSettings {
+    pub folder: Option<String>,
+}
+
+/// How days and weeks are cut in the user's local zone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarSettings {
+    pub week_start: WeekStart,
+    /// Local hour (0-23) at which a new day begins; 4 keeps a session that
+    /// runs past midnight on the previous day.
+    pub day_rollover_hour: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+    Saturday,
+}
+
+/// How long session history is kept in full detail. `None` keeps it forever,
+/// which is the default: pruning only runs once the user sets a limit.
+/// Daily aggregates are never pruned.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionSettings {
+    /// Days to keep per-step runs; older sessions keep only their totals.
+    pub step_runs_days: Option<u32>,
+    /// Months to keep individual sessions; older ones fold into daily aggregates.
+    pub session_totals_months: Option<u32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum RepeatMode {
@@ -169,7 +570,52 @@ pub enum SoundScheme {
     EndDifferent,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Imported sound files to play instead of the scheme's, by event. Each names
+/// a file in the data directory's `sounds` folder; events left unset fall back
+/// to the routine's assets and then to the scheme.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SoundAssets {
+    pub step_start: Option<String>,
+    pub step_end: Option<String>,
+    pub routine_completed: Option<String>,
+    pub check_in_required: Option<String>,
+    pub countdown_warning: Option<String>,
+}
+
+impl SoundAssets {
+    pub fn is_empty(&self) -> bool {
+        self.entries().iter().all(|(_, name)| name.is_none())
+    }
+
+    /// The configured names paired with their field names.
+    pub fn entries(&self) -> [(&'static str, Option<&str>); 5] {
+        [
+            ("stepStart", self.step_start.as_deref()),
+            ("stepEnd", self.step_end.as_deref()),
+            ("routineCompleted", self.routine_completed.as_deref()),
+            ("checkInRequired", self.check_in_required.as_deref()),
+            ("countdownWarning", self.countdown_warning.as_deref()),
+        ]
+    }
+}
+
+/// Cues played while a step runs, ahead of its end.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepWarnings {
+    /// Seconds before the end of the step at which to warn, e.g. 30.
+    #[serde(default)]
+    pub remaining_seconds: Vec<u32>,
+    /// Warn once half of the step has passed.
+    #[serde(default)]
+    pub halfway: bool,
+    /// Also show a notification when a warning fires.
+    #[serde(default)]
+    pub notify: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CheckInMode {
     Off,
@@ -184,7 +630,7 @@ pub enum CheckInChoice {
     Skip,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum StepRunResult {
     Completed,
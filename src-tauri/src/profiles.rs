@@ -0,0 +1,440 @@
+//! Profiles: separate routines, sessions and settings under one data folder.
+//!
+//! The data folder holds `profiles.json` and one subdirectory per profile in
+//! `profiles/`; each subdirectory is a complete `DataManager` directory. The
+//! data files of a folder from before profiles existed are moved into
+//! `profiles/default` the first time it is opened; other files in the folder
+//! are left alone. The data folder lock covers every profile.
+
+use crate::data_crypto;
+use crate::data_manager::{DataError, DataManager, DataResult};
+use crate::ids::{new_id, IdKind};
+use crate::models::{DailyAggregate, Session, SessionStats};
+use crate::routine_validator::FieldError;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const INDEX_FILE: &str = "profiles.json";
+const PROFILES_DIR: &str = "profiles";
+const DEFAULT_PROFILE_ID: &str = "default";
+const DEFAULT_PROFILE_NAME: &str = "デフォルト";
+const MAX_NAME_CHARS: usize = 40;
+/// Stems of the JSON files a folder from before profiles holds. These, their
+/// `.corrupt-*` copies, `encryption*.json` and the backups folder are moved
+/// into the default profile; anything else in the folder stays put.
+const LEGACY_FILES: [&str; 8] = [
+    "routines",
+    "sessions",
+    "settings",
+    "daily_aggregates",
+    "goals",
+    "active_session",
+    "sync_state",
+    "stats_cache",
+];
+const LEGACY_BACKUPS_DIR: &str = "backups";
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Data(DataError),
+    NotFound(String),
+    Invalid(FieldError),
+    SessionInProgress,
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::Data(err) => write!(f, "{err}"),
+            ProfileError::NotFound(id) => write!(f, "Profile not found: {id}"),
+            ProfileError::Invalid(err) => write!(f, "{}: {}", err.path, err.message),
+            ProfileError::SessionInProgress => {
+                write!(f, "Cannot switch profiles while a session is running")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<DataError> for ProfileError {
+    fn from(value: DataError) -> Self {
+        Self::Data(value)
+    }
+}
+
+impl From<io::Error> for ProfileError {
+    fn from(value: io::Error) -> Self {
+        Self::Data(DataError::from(value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<ProfileInfo>,
+}
+
+/// Sessions and aggregates gathered from several profiles for stats. Profiles
+/// that could not be opened, such as encrypted ones without a key available,
+/// are listed in `skipped`.
+#[derive(Debug, Default)]
+pub struct ProfileHistory {
+    pub sessions: Vec<Session>,
+    pub aggregates: Vec<DailyAggregate>,
+    pub skipped: Vec<String>,
+}
+
+/// Stats for one profile or all of them, with the profiles left out.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileStats {
+    pub stats: SessionStats,
+    pub skipped_profiles: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileStore {
+    root: PathBuf,
+}
+
+impl ProfileStore {
+    /// Opens the profile index in `root`, creating it (and moving data from a
+    /// pre-profile layout into the default profile) when missing.
+    pub fn open(root: impl Into<PathBuf>) -> DataResult<Self> {
+        let store = Self { root: root.into() };
+        if !store.index_path().exists() {
+            store.migrate_legacy_layout()?;
+        }
+        Ok(store)
+    }
+
+    pub fn list(&self) -> DataResult<ProfileList> {
+        let contents = fs::read_to_string(self.index_path())?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn active(&self) -> DataResult<ProfileInfo> {
+        let list = self.list()?;
+        let active = list
+            .profiles
+            .iter()
+            .find(|profile| profile.id == list.active)
+            .or(list.profiles.first())
+            .cloned();
+        active.ok_or_else(|| {
+            DataError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "profile index lists no profiles",
+            ))
+        })
+    }
+
+    pub fn profile_dir(&self, profile_id: &str) -> PathBuf {
+        self.root.join(PROFILES_DIR).join(profile_id)
+    }
+
+    pub fn create(&self, name: &str) -> Result<ProfileInfo, ProfileError> {
+        let mut list = self.list()?;
+        let name = validate_name(name, &list.profiles)?;
+        let profile = ProfileInfo {
+            id: new_id(IdKind::Profile),
+            name,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        fs::create_dir_all(self.profile_dir(&profile.id))?;
+        list.profiles.push(profile.clone());
+        self.write_list(&list)?;
+        Ok(profile)
+    }
+
+    /// Directory of `profile_id`, or of the active profile when `None`.
+    pub fn resolve_dir(&self, profile_id: Option<&str>) -> Result<PathBuf, ProfileError> {
+        let profile_id = match profile_id {
+            Some(id) => {
+                if !self.list()?.profiles.iter().any(|profile| profile.id == id) {
+                    return Err(ProfileError::NotFound(id.to_string()));
+                }
+                id.to_string()
+            }
+            None => self.active()?.id,
+        };
+        Ok(self.profile_dir(&profile_id))
+    }
+
+    /// Makes `profile_id` the profile opened at the next start.
+    pub fn set_active(&self, profile_id: &str) -> Result<ProfileInfo, ProfileError> {
+        let mut list = self.list()?;
+        let profile = list
+            .profiles
+            .iter()
+            .find(|profile| profile.id == profile_id)
+            .cloned()
+            .ok_or_else(|| ProfileError::NotFound(profile_id.to_string()))?;
+        list.active = profile.id.clone();
+        self.write_list(&list)?;
+        Ok(profile)
+    }
+
+    /// Loads history from `profile_id`, or from every profile when `None`.
+    /// `active` is used for the open profile so its files are not reopened.
+    pub fn load_history(
+        &self,
+        active: &DataManager,
+        profile_id: Option<&str>,
+        from: &str,
+        to: &str,
+    ) -> Result<ProfileHistory, ProfileError> {
+        let list = self.list()?;
+        let profiles: Vec<&ProfileInfo> = match profile_id {
+            Some(id) => vec![list
+                .profiles
+                .iter()
+                .find(|profile| profile.id == id)
+                .ok_or_else(|| ProfileError::NotFound(id.to_string()))?],
+            None => list.profiles.iter().collect(),
+        };
+
+        let mut history = ProfileHistory::default();
+        for profile in profiles {
+            let manager = if profile.id == list.active {
+                active.clone()
+            } else {
+                match self.open_inactive(&profile.id) {
+                    Ok(manager) => manager,
+                    Err(DataError::KeyRequired | DataError::WrongKey) => {
+                        history.skipped.push(profile.id.clone());
+                        continue;
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            };
+            history
+                .sessions
+                .extend(manager.load_sessions_in_range(from, to)?);
+            history
+                .aggregates
+                .extend(manager.load_daily_aggregates_in_range(from, to)?);
+        }
+        Ok(history)
+    }
+
+    fn open_inactive(&self, profile_id: &str) -> DataResult<DataManager> {
+        let dir = self.profile_dir(profile_id);
+        let key_source = data_crypto::startup_key_source(&dir)?;
+        DataManager::open(dir, key_source.as_ref())
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.root.join(INDEX_FILE)
+    }
+
+    fn migrate_legacy_layout(&self) -> DataResult<()> {
+        let default_dir = self.profile_dir(DEFAULT_PROFILE_ID);
+        fs::create_dir_all(&default_dir)?;
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if !name.to_str().is_some_and(is_legacy_entry) {
+                continue;
+            }
+            let target = default_dir.join(&name);
+            if !target.exists() {
+                fs::rename(entry.path(), target)?;
+            }
+        }
+        // Written last so an interrupted move is finished on the next start.
+        self.write_list(&ProfileList {
+            active: DEFAULT_PROFILE_ID.to_string(),
+            profiles: vec![ProfileInfo {
+                id: DEFAULT_PROFILE_ID.to_string(),
+                name: DEFAULT_PROFILE_NAME.to_string(),
+                created_at: Utc::now().to_rfc3339(),
+            }],
+        })
+    }
+
+    fn write_list(&self, list: &ProfileList) -> DataResult<()> {
+        fs::create_dir_all(&self.root)?;
+        let path = self.index_path();
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(list)?)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+}
+
+fn is_legacy_entry(name: &str) -> bool {
+    if name == LEGACY_BACKUPS_DIR {
+        return true;
+    }
+    let Some(stem) = name.strip_suffix(".json") else {
+        return false;
+    };
+    stem.starts_with("encryption")
+        || LEGACY_FILES.iter().any(|file| {
+            stem.strip_prefix(file)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(".corrupt-"))
+        })
+}
+
+fn validate_name(name: &str, existing: &[ProfileInfo]) -> Result<String, ProfileError> {
+    let name = name.trim();
+    let invalid = |message: &str| ProfileError::Invalid(FieldError::new("name", message));
+    if name.is_empty() {
+        return Err(invalid("プロファイル名を入力してください"));
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(invalid("プロファイル名は40文字以内にしてください"));
+    }
+    if existing
+        .iter()
+        .any(|profile| profile.name.to_lowercase() == name.to_lowercase())
+    {
+        return Err(invalid("同じ名前のプロファイルがあります"));
+    }
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProfileError, ProfileStore, DEFAULT_PROFILE_ID};
+    use crate::data_manager::DataManager;
+    use crate::models::{Session, SessionTotals};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_profiles_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn session(id: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            routine_id: "routine-1".to_string(),
+            started_at: "2025-06-14T09:00:00Z".to_string(),
+            ended_at: Some("2025-06-14T09:10:00Z".to_string()),
+            step_runs: Vec::new(),
+            totals: SessionTotals::default(),
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn moves_legacy_data_into_default_profile() {
+        let root = temp_dir();
+        let legacy = DataManager::new(&root).expect("legacy manager");
+        legacy
+            .save_session(session("session-legacy"))
+            .expect("save");
+        fs::write(root.join(".lock"), "1").expect("lock file");
+        fs::write(root.join("notes.txt"), "mine").expect("unrelated file");
+        fs::write(root.join("other.json"), "{}").expect("unrelated json");
+        fs::create_dir_all(root.join("photos")).expect("unrelated folder");
+        fs::create_dir_all(root.join("backups")).expect("backups folder");
+
+        let store = ProfileStore::open(&root).expect("open store");
+        let active = store.active().expect("active profile");
+        assert_eq!(active.id, DEFAULT_PROFILE_ID);
+        assert!(root.join(".lock").exists());
+        assert!(!root.join("sessions.json").exists());
+        assert!(!root.join("backups").exists());
+        for name in ["notes.txt", "other.json", "photos"] {
+            assert!(root.join(name).exists(), "{name}");
+        }
+
+        let manager = DataManager::new(store.profile_dir(&active.id)).expect("profile manager");
+        assert_eq!(manager.load_sessions().expect("sessions").len(), 1);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn creates_switches_and_validates_profiles() {
+        let root = temp_dir();
+        let store = ProfileStore::open(&root).expect("open store");
+        let work = store.create(" 仕事 ").expect("create work");
+        assert_eq!(work.name, "仕事");
+        assert!(store.profile_dir(&work.id).is_dir());
+        assert!(matches!(
+            store.create("仕事"),
+            Err(ProfileError::Invalid(_))
+        ));
+        assert!(matches!(store.create("  "), Err(ProfileError::Invalid(_))));
+
+        store.set_active(&work.id).expect("switch");
+        assert_eq!(store.active().expect("active").id, work.id);
+        assert!(matches!(
+            store.set_active("profile-missing"),
+            Err(ProfileError::NotFound(_))
+        ));
+        let reopened = ProfileStore::open(&root).expect("reopen");
+        assert_eq!(reopened.active().expect("active"), work);
+        assert_eq!(reopened.list().expect("list").profiles.len(), 2);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn loads_history_per_profile_or_across_all() {
+        let root = temp_dir();
+        let store = ProfileStore::open(&root).expect("open store");
+        let personal = store.create("個人").expect("create personal");
+        let active = DataManager::new(store.profile_dir(DEFAULT_PROFILE_ID)).expect("default");
+        active
+            .save_session(session("session-work"))
+            .expect("save work");
+        DataManager::new(store.profile_dir(&personal.id))
+            .expect("personal")
+            .save_session(session("session-personal"))
+            .expect("save personal");
+
+        let (from, to) = ("2025-06-01T00:00:00Z", "2025-06-30T00:00:00Z");
+        let all = store
+            .load_history(&active, None, from, to)
+            .expect("all profiles");
+        assert_eq!(all.sessions.len(), 2);
+        assert!(all.skipped.is_empty());
+
+        let only = store
+            .load_history(&active, Some(&personal.id), from, to)
+            .expect("personal only");
+        assert_eq!(only.sessions.len(), 1);
+        assert_eq!(only.sessions[0].id, "session-personal");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::models::SessionZone;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +13,8 @@ pub struct ActiveSessionSnapshot {
     pub current_step_sound_played: bool,
     pub paused_at: Option<String>,
     pub muted_during_session: bool,
+    #[serde(default)]
+    pub zone: Option<SessionZone>,
 }
 
 impl ActiveSessionSnapshot {
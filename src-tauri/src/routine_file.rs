@@ -0,0 +1,744 @@
+//! Human-editable routine files.
+//!
+//! A routine is exported as a single TOML document. Durations are written as
+//! `h`/`m`/`s` components (`"4m30s"`, `"1h"`, `"90s"`); a bare integer is read
+//! as seconds. Step order follows the order of the `[[steps]]` tables.
+//!
+//! ```toml
+//! format_version = 1
+//! id = "routine-template-10min"
+//! name = "10分ミニ・スプリント"
+//! auto_advance = true
+//! notifications = true
+//! sound_default = "on"        # on | off
+//! sound_scheme = "default"    # default | endDifferent
+//!
+//! [repeat]
+//! mode = "count"              # infinite | count | duration
+//! count = 3                   # mode = "count"
+//! # total = "1h"              # mode = "duration"
+//!
+//! [[steps]]
+//! id = "step-template-4"
+//! label = "集中"
+//! duration = "4m"
+//! instruction = ""
+//! sound = "inherit"           # inherit | on | off
+//! count_as_break = false
+//!
+//! [steps.check_in]
+//! mode = "prompt"             # off | prompt | gate
+//! title = "進んだ？"
+//! timeout = "30s"             # required for prompt
+//!
+//! [steps.warnings]
+//! remaining = ["30s"]         # cues before the end of the step
+//! halfway = true
+//! notify = false
+//! ```
+
+use crate::data_manager::{BackupReason, DataError, DataFile, DataManager};
+use crate::ids::{new_id, IdKind};
+use crate::models::{
+    CheckInConfig, CheckInMode, RepeatMode, Routine, SoundAssets, SoundOverride, SoundScheme,
+    SoundSetting, Step, StepWarnings,
+};
+use crate::routine_validator::{validate_routine, ValidationErrors};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const ROUTINE_FILE_VERSION: u32 = 1;
+
+const FILE_HEADER: &str = "# McCall routine file\n\
+# Durations accept h/m/s components such as \"4m30s\", \"1h\" or \"90s\".\n\
+# Steps run in the order they appear below.\n\n";
+
+#[derive(Debug)]
+pub enum RoutineFileError {
+    Io(io::Error),
+    Data(DataError),
+    Parse(String),
+    Format(String),
+    NotFound(String),
+    Invalid(ValidationErrors),
+}
+
+impl std::fmt::Display for RoutineFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoutineFileError::Io(err) => write!(f, "I/O error: {err}"),
+            RoutineFileError::Data(err) => write!(f, "{err}"),
+            RoutineFileError::Parse(err) => write!(f, "Routine file parse error: {err}"),
+            RoutineFileError::Format(err) => write!(f, "Routine file format error: {err}"),
+            RoutineFileError::NotFound(id) => write!(f, "Routine not found: {id}"),
+            RoutineFileError::Invalid(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for RoutineFileError {}
+
+impl From<io::Error> for RoutineFileError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<DataError> for RoutineFileError {
+    fn from(value: DataError) -> Self {
+        Self::Data(value)
+    }
+}
+
+impl From<ValidationErrors> for RoutineFileError {
+    fn from(value: ValidationErrors) -> Self {
+        Self::Invalid(value)
+    }
+}
+
+pub type RoutineFileResult<T> = Result<T, RoutineFileError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportConflictStrategy {
+    /// Keep the existing routine and import under a fresh id and a suffixed name.
+    #[default]
+    Rename,
+    /// Overwrite the existing routine with the same id.
+    Replace,
+    /// Always assign fresh routine and step ids.
+    NewIds,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RoutineDocument {
+    format_version: u32,
+    id: String,
+    name: String,
+    auto_advance: bool,
+    notifications: bool,
+    sound_default: SoundSetting,
+    sound_scheme: SoundScheme,
+    #[serde(default, skip_serializing_if = "SoundAssets::is_empty")]
+    sounds: SoundAssets,
+    repeat: RepeatDocument,
+    steps: Vec<StepDocument>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+enum RepeatDocument {
+    Infinite,
+    Count { count: u32 },
+    Duration { total: DurationValue },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StepDocument {
+    id: String,
+    label: String,
+    duration: DurationValue,
+    #[serde(default)]
+    instruction: String,
+    #[serde(default = "default_sound_override")]
+    sound: SoundOverride,
+    #[serde(default)]
+    count_as_break: bool,
+    #[serde(default)]
+    check_in: CheckInDocument,
+    #[serde(default, skip_serializing_if = "SoundAssets::is_empty")]
+    sounds: SoundAssets,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    warnings: Option<WarningsDocument>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CheckInDocument {
+    mode: CheckInMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<DurationValue>,
+}
+
+impl Default for CheckInDocument {
+    fn default() -> Self {
+        Self {
+            mode: CheckInMode::Off,
+            title: None,
+            body: None,
+            timeout: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WarningsDocument {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    remaining: Vec<DurationValue>,
+    #[serde(default)]
+    halfway: bool,
+    #[serde(default)]
+    notify: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum DurationValue {
+    Seconds(u32),
+    Text(String),
+}
+
+impl DurationValue {
+    fn from_seconds(seconds: u32) -> Self {
+        Self::Text(format_duration(seconds))
+    }
+
+    fn to_seconds(&self, field: &str) -> RoutineFileResult<u32> {
+        match self {
+            DurationValue::Seconds(seconds) => Ok(*seconds),
+            DurationValue::Text(text) => parse_duration(text).ok_or_else(|| {
+                RoutineFileError::Format(format!("{field}: invalid duration \"{text}\""))
+            }),
+        }
+    }
+}
+
+fn default_sound_override() -> SoundOverride {
+    SoundOverride::Inherit
+}
+
+pub fn format_duration(total_seconds: u32) -> String {
+    if total_seconds == 0 {
+        return "0s".to_string();
+    }
+    let hours = total_seconds / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+    let mut text = String::new();
+    if hours > 0 {
+        text.push_str(&format!("{hours}h"));
+    }
+    if minutes > 0 {
+        text.push_str(&format!("{minutes}m"));
+    }
+    if seconds > 0 {
+        text.push_str(&format!("{seconds}s"));
+    }
+    text
+}
+
+pub fn parse_duration(value: &str) -> Option<u32> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if let Ok(seconds) = trimmed.parse::<u32>() {
+        return Some(seconds);
+    }
+
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+    let mut last_unit_rank = 0;
+    for ch in trimmed.chars() {
+        if ch.is_ascii_digit() {
+            digits.push(ch);
+            continue;
+        }
+        if ch.is_whitespace() {
+            continue;
+        }
+        let (rank, multiplier) = match ch.to_ascii_lowercase() {
+            'h' => (3, 3600),
+            'm' => (2, 60),
+            's' => (1, 1),
+            _ => return None,
+        };
+        if digits.is_empty() || (last_unit_rank != 0 && rank >= last_unit_rank) {
+            return None;
+        }
+        let amount: u64 = digits.parse().ok()?;
+        total = total.checked_add(amount.checked_mul(multiplier)?)?;
+        digits.clear();
+        last_unit_rank = rank;
+    }
+    if !digits.is_empty() {
+        return None;
+    }
+    u32::try_from(total).ok()
+}
+
+pub fn routine_to_toml(routine: &Routine) -> RoutineFileResult<String> {
+    let document = RoutineDocument::from(routine);
+    let body = toml::to_string_pretty(&document)
+        .map_err(|err| RoutineFileError::Format(err.to_string()))?;
+    Ok(format!("{FILE_HEADER}{body}"))
+}
+
+pub fn routine_from_toml(contents: &str) -> RoutineFileResult<Routine> {
+    let document: RoutineDocument =
+        toml::from_str(contents).map_err(|err| RoutineFileError::Parse(err.to_string()))?;
+    if document.format_version != ROUTINE_FILE_VERSION {
+        return Err(RoutineFileError::Format(format!(
+            "unsupported format_version {}",
+            document.format_version
+        )));
+    }
+    document.into_routine()
+}
+
+pub fn export_routine(
+    data_manager: &DataManager,
+    routine_id: &str,
+    path: &Path,
+) -> RoutineFileResult<()> {
+    let routine = data_manager
+        .load_routines()?
+        .into_iter()
+        .find(|item| item.id == routine_id)
+        .ok_or_else(|| RoutineFileError::NotFound(routine_id.to_string()))?;
+    let contents = routine_to_toml(&routine)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+pub fn import_routine(
+    data_manager: &DataManager,
+    path: &Path,
+    strategy: ImportConflictStrategy,
+) -> RoutineFileResult<Routine> {
+    let contents = fs::read_to_string(path)?;
+    let imported = routine_from_toml(&contents)?;
+    validate_routine(&imported)?;
+
+    let existing = data_manager.load_routines()?;
+    let routine = resolve_conflicts(imported, &existing, strategy);
+    data_manager.create_backup(DataFile::Routines, BackupReason::Import)?;
+    data_manager.save_routine(routine.clone())?;
+    Ok(routine)
+}
+
+fn resolve_conflicts(
+    mut routine: Routine,
+    existing: &[Routine],
+    strategy: ImportConflictStrategy,
+) -> Routine {
+    let routine_conflict = existing.iter().any(|item| item.id == routine.id);
+    match strategy {
+        ImportConflictStrategy::Replace => {
+            let other_step_ids = step_ids_excluding(existing, &routine.id);
+            reassign_conflicting_steps(&mut routine, &other_step_ids);
+        }
+        ImportConflictStrategy::Rename => {
+            if routine_conflict {
+                routine.id = new_id(IdKind::Routine);
+                routine.name = imported_name(&routine.name);
+            }
+            let other_step_ids = step_ids_excluding(existing, &routine.id);
+            reassign_conflicting_steps(&mut routine, &other_step_ids);
+        }
+        ImportConflictStrategy::NewIds => {
+            routine.id = new_id(IdKind::Routine);
+            for step in &mut routine.steps {
+                step.id = new_id(IdKind::Step);
+            }
+        }
+    }
+    routine
+}
+
+fn step_ids_excluding<'a>(routines: &'a [Routine], routine_id: &str) -> HashSet<&'a str> {
+    routines
+        .iter()
+        .filter(|item| item.id != routine_id)
+        .flat_map(|item| item.steps.iter().map(|step| step.id.as_str()))
+        .collect()
+}
+
+fn reassign_conflicting_steps(routine: &mut Routine, taken: &HashSet<&str>) {
+    for step in &mut routine.steps {
+        if taken.contains(step.id.as_str()) {
+            step.id = new_id(IdKind::Step);
+        }
+    }
+}
+
+fn imported_name(name: &str) -> String {
+    let base = name.trim();
+    if base.is_empty() {
+        "インポート".to_string()
+    } else {
+        format!("{base}（インポート）")
+    }
+}
+
+impl From<&Routine> for RoutineDocument {
+    fn from(routine: &Routine) -> Self {
+        let mut steps: Vec<&Step> = routine.steps.iter().collect();
+        steps.sort_by_key(|step| step.order);
+        Self {
+            format_version: ROUTINE_FILE_VERSION,
+            id: routine.id.clone(),
+            name: routine.name.clone(),
+            auto_advance: routine.auto_advance,
+            notifications: routine.notifications,
+            sound_default: routine.sound_default.clone(),
+            sound_scheme: routine.sound_scheme.clone(),
+            sounds: routine.sounds.clone(),
+            repeat: match &routine.repeat_mode {
+                RepeatMode::Infinite => RepeatDocument::Infinite,
+                RepeatMode::Count { value } => RepeatDocument::Count { count: *value },
+                RepeatMode::Duration { total_seconds } => RepeatDocument::Duration {
+                    total: DurationValue::from_seconds(*total_seconds),
+                },
+            },
+            steps: steps.into_iter().map(StepDocument::from).collect(),
+        }
+    }
+}
+
+impl From<&Step> for StepDocument {
+    fn from(step: &Step) -> Self {
+        Self {
+            id: step.id.clone(),
+            label: step.label.clone(),
+            duration: DurationValue::from_seconds(step.duration_seconds),
+            instruction: step.instruction.clone(),
+            sound: step.sound_override.clone(),
+            count_as_break: step.count_as_break,
+            check_in: CheckInDocument {
+                mode: step.check_in.mode,
+                title: step.check_in.prompt_title.clone(),
+                body: step.check_in.prompt_body.clone(),
+                timeout: step
+                    .check_in
+                    .prompt_timeout_seconds
+                    .map(DurationValue::from_seconds),
+            },
+            sounds: step.sounds.clone(),
+            warnings: (step.warnings != StepWarnings::default()).then(|| WarningsDocument {
+                remaining: step
+                    .warnings
+                    .remaining_seconds
+                    .iter()
+                    .map(|seconds| DurationValue::from_seconds(*seconds))
+                    .collect(),
+                halfway: step.warnings.halfway,
+                notify: step.warnings.notify,
+            }),
+        }
+    }
+}
+
+impl RoutineDocument {
+    fn into_routine(self) -> RoutineFileResult<Routine> {
+        let repeat_mode = match self.repeat {
+            RepeatDocument::Infinite => RepeatMode::Infinite,
+            RepeatDocument::Count { count } => RepeatMode::Count { value: count },
+            RepeatDocument::Duration { total } => RepeatMode::Duration {
+                total_seconds: total.to_seconds("repeat.total")?,
+            },
+        };
+        let steps = self
+            .steps
+            .into_iter()
+            .enumerate()
+            .map(|(index, step)| step.into_step(index))
+            .collect::<RoutineFileResult<Vec<_>>>()?;
+        Ok(Routine {
+            id: self.id,
+            name: self.name,
+            steps,
+            repeat_mode,
+            auto_advance: self.auto_advance,
+            notifications: self.notifications,
+            sound_default: self.sound_default,
+            sound_scheme: self.sound_scheme,
+            sounds: self.sounds,
+        })
+    }
+}
+
+impl StepDocument {
+    fn into_step(self, index: usize) -> RoutineFileResult<Step> {
+        let duration_seconds = self
+            .duration
+            .to_seconds(&format!("steps[{index}].duration"))?;
+        let prompt_timeout_seconds = self
+            .check_in
+            .timeout
+            .map(|timeout| timeout.to_seconds(&format!("steps[{index}].check_in.timeout")))
+            .transpose()?;
+        let warnings = match self.warnings {
+            Some(warnings) => StepWarnings {
+                remaining_seconds: warnings
+                    .remaining
+                    .iter()
+                    .map(|value| value.to_seconds(&format!("steps[{index}].warnings.remaining")))
+                    .collect::<RoutineFileResult<_>>()?,
+                halfway: warnings.halfway,
+                notify: warnings.notify,
+            },
+            None => StepWarnings::default(),
+        };
+        Ok(Step {
+            id: self.id,
+            order: index.try_into().unwrap_or(u32::MAX),
+            label: self.label,
+            duration_seconds,
+            instruction: self.instruction,
+            sound_override: self.sound,
+            count_as_break: self.count_as_break,
+            check_in: CheckInConfig {
+                mode: self.check_in.mode,
+                prompt_title: self.check_in.title,
+                prompt_body: self.check_in.body,
+                prompt_timeout_seconds,
+            },
+            sounds: self.sounds,
+            warnings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        format_duration, import_routine, parse_duration, routine_from_toml, routine_to_toml,
+        ImportConflictStrategy, RoutineFileError,
+    };
+    use crate::data_manager::DataManager;
+    use crate::models::{CheckInMode, RepeatMode, Routine, StepWarnings};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_routine_file_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn template_routine(manager: &DataManager) -> Routine {
+        manager
+            .load_routines()
+            .expect("load routines")
+            .into_iter()
+            .next()
+            .expect("template routine")
+    }
+
+    fn assert_same_routine(left: &Routine, right: &Routine) {
+        assert_eq!(
+            serde_json::to_value(left).expect("serialize left"),
+            serde_json::to_value(right).expect("serialize right")
+        );
+    }
+
+    #[test]
+    fn formats_and_parses_durations() {
+        assert_eq!(format_duration(270), "4m30s");
+        assert_eq!(format_duration(3600), "1h");
+        assert_eq!(format_duration(3725), "1h2m5s");
+        assert_eq!(format_duration(0), "0s");
+
+        assert_eq!(parse_duration("4m30s"), Some(270));
+        assert_eq!(parse_duration("1h 5m"), Some(3900));
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("30s4m"), None);
+        assert_eq!(parse_duration("4x"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn template_round_trips_through_toml() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let routine = template_routine(&manager);
+
+        let text = routine_to_toml(&routine).expect("export");
+        assert!(text.starts_with("# McCall routine file"));
+        assert!(text.contains("duration = \"4m\""));
+        let parsed = routine_from_toml(&text).expect("import");
+
+        assert_same_routine(&parsed, &routine);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn round_trips_repeat_modes_and_prompt_timeout() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let mut routine = template_routine(&manager);
+        routine.steps[0].check_in.mode = CheckInMode::Prompt;
+        routine.steps[0].check_in.prompt_body = Some("本文".to_string());
+        routine.steps[0].check_in.prompt_timeout_seconds = Some(45);
+
+        for repeat_mode in [
+            RepeatMode::Count { value: 3 },
+            RepeatMode::Duration {
+                total_seconds: 5400,
+            },
+        ] {
+            routine.repeat_mode = repeat_mode;
+            let text = routine_to_toml(&routine).expect("export");
+            let parsed = routine_from_toml(&text).expect("import");
+            assert_same_routine(&parsed, &routine);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn round_trips_sound_assets() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let mut routine = template_routine(&manager);
+        routine.sounds.routine_completed = Some("fanfare.wav".to_string());
+        routine.steps[1].sounds.step_start = Some("gong.ogg".to_string());
+
+        let text = routine_to_toml(&routine).expect("export");
+        let parsed = routine_from_toml(&text).expect("import");
+
+        assert_eq!(parsed.sounds, routine.sounds);
+        assert_eq!(parsed.steps[1].sounds, routine.steps[1].sounds);
+        assert!(parsed.steps[0].sounds.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn round_trips_step_warnings() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let mut routine = template_routine(&manager);
+        routine.steps[0].warnings.remaining_seconds = vec![90, 30];
+        routine.steps[0].warnings.halfway = true;
+
+        let text = routine_to_toml(&routine).expect("export");
+        assert!(text.contains("\"1m30s\""));
+        let parsed = routine_from_toml(&text).expect("import");
+
+        assert_eq!(parsed.steps[0].warnings, routine.steps[0].warnings);
+        assert_eq!(parsed.steps[1].warnings, StepWarnings::default());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reads_hand_written_file() {
+        let text = r#"
+format_version = 1
+id = "morning"
+name = "Morning"
+auto_advance = false
+notifications = true
+sound_default = "off"
+sound_scheme = "endDifferent"
+
+[repeat]
+mode = "count"
+count = 2
+
+[[steps]]
+id = "plan"
+label = "Plan"
+duration = "2m30s"
+
+[[steps]]
+id = "work"
+label = "Work"
+duration = 1500
+
+[steps.check_in]
+mode = "gate"
+title = "Done?"
+"#;
+        let routine = routine_from_toml(text).expect("import");
+        assert_eq!(routine.steps.len(), 2);
+        assert_eq!(routine.steps[0].duration_seconds, 150);
+        assert_eq!(routine.steps[1].duration_seconds, 1500);
+        assert_eq!(routine.steps[1].order, 1);
+        assert_eq!(routine.steps[1].check_in.mode, CheckInMode::Gate);
+        assert!(matches!(
+            routine.repeat_mode,
+            RepeatMode::Count { value: 2 }
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_duration() {
+        let text = r#"
+format_version = 1
+id = "bad"
+name = "Bad"
+auto_advance = true
+notifications = true
+sound_default = "on"
+sound_scheme = "default"
+
+[repeat]
+mode = "infinite"
+
+[[steps]]
+id = "step"
+label = "Step"
+duration = "soon"
+"#;
+        let err = routine_from_toml(text).expect_err("should fail");
+        assert!(matches!(err, RoutineFileError::Format(_)));
+    }
+
+    #[test]
+    fn import_resolves_id_conflicts() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let routine = template_routine(&manager);
+        let path = dir.join("routine.toml");
+        fs::write(&path, routine_to_toml(&routine).expect("export")).expect("write file");
+
+        let renamed =
+            import_routine(&manager, &path, ImportConflictStrategy::Rename).expect("rename");
+        assert_ne!(renamed.id, routine.id);
+        assert_eq!(renamed.name, "10分ミニ・スプリント（インポート）");
+        assert!(renamed
+            .steps
+            .iter()
+            .all(|step| routine.steps.iter().all(|original| original.id != step.id)));
+        assert_eq!(manager.load_routines().expect("load").len(), 2);
+
+        let replaced =
+            import_routine(&manager, &path, ImportConflictStrategy::Replace).expect("replace");
+        assert_eq!(replaced.id, routine.id);
+        assert_eq!(manager.load_routines().expect("load").len(), 2);
+
+        let fresh =
+            import_routine(&manager, &path, ImportConflictStrategy::NewIds).expect("new ids");
+        assert_ne!(fresh.id, routine.id);
+        assert_eq!(fresh.name, routine.name);
+        assert_eq!(manager.load_routines().expect("load").len(), 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
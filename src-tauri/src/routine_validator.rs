@@ -0,0 +1,308 @@
+use crate::models::{CheckInMode, RepeatMode, Routine, SoundAssets};
+use crate::sound_assets::validate_name;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldError {
+    pub path: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn into_errors(self) -> Vec<FieldError> {
+        self.errors
+    }
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let items = self
+            .errors
+            .iter()
+            .map(|error| format!("{}: {}", error.path, error.message))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "Validation failed: {items}")
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+pub fn validate_routine(routine: &Routine) -> Result<(), ValidationErrors> {
+    let errors = collect_routine_errors(routine);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors { errors })
+    }
+}
+
+pub fn collect_routine_errors(routine: &Routine) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    if routine.id.trim().is_empty() {
+        errors.push(FieldError::new("id", "ルーチンIDが空です"));
+    }
+    if routine.name.trim().is_empty() {
+        errors.push(FieldError::new("name", "ルーチン名を入力してください"));
+    }
+
+    match &routine.repeat_mode {
+        RepeatMode::Count { value } if *value == 0 => {
+            errors.push(FieldError::new(
+                "repeatMode.value",
+                "繰り返し回数は1以上にしてください",
+            ));
+        }
+        RepeatMode::Duration { total_seconds } if *total_seconds == 0 => {
+            errors.push(FieldError::new(
+                "repeatMode.totalSeconds",
+                "繰り返し時間は1秒以上にしてください",
+            ));
+        }
+        _ => {}
+    }
+
+    push_sound_errors(&mut errors, "sounds", &routine.sounds);
+
+    if routine.steps.is_empty() {
+        errors.push(FieldError::new(
+            "steps",
+            "ステップを1つ以上追加してください",
+        ));
+        return errors;
+    }
+
+    let mut seen_ids: HashMap<&str, usize> = HashMap::new();
+    let mut order_counts: HashMap<u32, usize> = HashMap::new();
+    for step in &routine.steps {
+        *order_counts.entry(step.order).or_default() += 1;
+    }
+    let step_count = routine.steps.len();
+
+    for (index, step) in routine.steps.iter().enumerate() {
+        let prefix = format!("steps[{index}]");
+
+        if step.id.trim().is_empty() {
+            errors.push(FieldError::new(
+                format!("{prefix}.id"),
+                "ステップIDが空です",
+            ));
+        } else if let Some(first) = seen_ids.get(step.id.as_str()) {
+            errors.push(FieldError::new(
+                format!("{prefix}.id"),
+                format!("ステップIDが重複しています (steps[{first}])"),
+            ));
+        } else {
+            seen_ids.insert(step.id.as_str(), index);
+        }
+
+        if step.label.trim().is_empty() {
+            errors.push(FieldError::new(
+                format!("{prefix}.label"),
+                "ステップ名を入力してください",
+            ));
+        }
+
+        if step.duration_seconds == 0 {
+            errors.push(FieldError::new(
+                format!("{prefix}.durationSeconds"),
+                "ステップ時間は1秒以上にしてください",
+            ));
+        }
+
+        let order_in_range = (step.order as usize) < step_count;
+        let order_unique = order_counts.get(&step.order).copied().unwrap_or(0) == 1;
+        if !order_in_range || !order_unique {
+            errors.push(FieldError::new(
+                format!("{prefix}.order"),
+                format!("並び順は0から{}までの連番にしてください", step_count - 1),
+            ));
+        }
+
+        if step.check_in.mode == CheckInMode::Prompt {
+            match step.check_in.prompt_timeout_seconds {
+                None => errors.push(FieldError::new(
+                    format!("{prefix}.checkIn.promptTimeoutSeconds"),
+                    "プロンプトのタイムアウトを設定してください",
+                )),
+                Some(0) => errors.push(FieldError::new(
+                    format!("{prefix}.checkIn.promptTimeoutSeconds"),
+                    "タイムアウトは1秒以上にしてください",
+                )),
+                Some(_) => {}
+            }
+        }
+
+        push_sound_errors(&mut errors, &format!("{prefix}.sounds"), &step.sounds);
+
+        let warning_out_of_range = step
+            .warnings
+            .remaining_seconds
+            .iter()
+            .any(|seconds| *seconds == 0 || *seconds >= step.duration_seconds);
+        if warning_out_of_range {
+            errors.push(FieldError::new(
+                format!("{prefix}.warnings.remainingSeconds"),
+                "警告はステップ時間より短い1秒以上の値にしてください",
+            ));
+        }
+    }
+
+    errors
+}
+
+fn push_sound_errors(errors: &mut Vec<FieldError>, prefix: &str, sounds: &SoundAssets) {
+    for (field, name) in sounds.entries() {
+        if name.is_some_and(|name| validate_name(name).is_err()) {
+            errors.push(FieldError::new(
+                format!("{prefix}.{field}"),
+                "サウンドはインポート済みのファイル名で指定してください",
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_routine_errors, validate_routine};
+    use crate::models::{
+        CheckInConfig, CheckInMode, RepeatMode, Routine, SoundAssets, SoundOverride, SoundScheme,
+        SoundSetting, Step, StepWarnings,
+    };
+
+    fn build_step(id: &str, order: u32) -> Step {
+        Step {
+            id: id.to_string(),
+            order,
+            label: format!("Step {order}"),
+            duration_seconds: 60,
+            instruction: "".to_string(),
+            sound_override: SoundOverride::Inherit,
+            count_as_break: false,
+            sounds: SoundAssets::default(),
+            warnings: StepWarnings::default(),
+            check_in: CheckInConfig {
+                mode: CheckInMode::Off,
+                prompt_title: None,
+                prompt_body: None,
+                prompt_timeout_seconds: None,
+            },
+        }
+    }
+
+    fn build_routine(steps: Vec<Step>) -> Routine {
+        Routine {
+            id: "routine-1".to_string(),
+            name: "Sample".to_string(),
+            steps,
+            repeat_mode: RepeatMode::Infinite,
+            auto_advance: true,
+            notifications: true,
+            sound_default: SoundSetting::On,
+            sound_scheme: SoundScheme::Default,
+            sounds: SoundAssets::default(),
+        }
+    }
+
+    fn paths(routine: &Routine) -> Vec<String> {
+        collect_routine_errors(routine)
+            .into_iter()
+            .map(|error| error.path)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_valid_routine() {
+        let routine = build_routine(vec![build_step("step-1", 0), build_step("step-2", 1)]);
+        assert!(validate_routine(&routine).is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_steps_and_name() {
+        let mut routine = build_routine(Vec::new());
+        routine.name = "  ".to_string();
+        assert_eq!(paths(&routine), vec!["name", "steps"]);
+    }
+
+    #[test]
+    fn reports_field_paths_for_step_errors() {
+        let mut zero = build_step("step-1", 0);
+        zero.duration_seconds = 0;
+        let mut unlabeled = build_step("step-2", 1);
+        unlabeled.label = "".to_string();
+        let duplicate = build_step("step-1", 2);
+        let mut prompt = build_step("step-4", 3);
+        prompt.check_in.mode = CheckInMode::Prompt;
+        let routine = build_routine(vec![zero, unlabeled, duplicate, prompt]);
+
+        assert_eq!(
+            paths(&routine),
+            vec![
+                "steps[0].durationSeconds",
+                "steps[1].label",
+                "steps[2].id",
+                "steps[3].checkIn.promptTimeoutSeconds",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_non_contiguous_order() {
+        let routine = build_routine(vec![
+            build_step("step-1", 0),
+            build_step("step-2", 2),
+            build_step("step-3", 2),
+        ]);
+        assert_eq!(paths(&routine), vec!["steps[1].order", "steps[2].order"]);
+    }
+
+    #[test]
+    fn rejects_zero_repeat_values() {
+        let mut routine = build_routine(vec![build_step("step-1", 0)]);
+        routine.repeat_mode = RepeatMode::Count { value: 0 };
+        assert_eq!(paths(&routine), vec!["repeatMode.value"]);
+
+        routine.repeat_mode = RepeatMode::Duration { total_seconds: 0 };
+        assert_eq!(paths(&routine), vec!["repeatMode.totalSeconds"]);
+    }
+
+    #[test]
+    fn rejects_sound_names_with_paths() {
+        let mut routine = build_routine(vec![build_step("step-1", 0)]);
+        routine.sounds.step_start = Some("chime.wav".to_string());
+        routine.sounds.routine_completed = Some("../settings.json".to_string());
+        routine.steps[0].sounds.countdown_warning = Some("/tmp/beep.wav".to_string());
+        assert_eq!(
+            paths(&routine),
+            vec![
+                "sounds.routineCompleted",
+                "steps[0].sounds.countdownWarning"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_warnings_outside_the_step() {
+        let mut routine = build_routine(vec![build_step("step-1", 0), build_step("step-2", 1)]);
+        routine.steps[0].warnings.remaining_seconds = vec![30];
+        routine.steps[1].warnings.remaining_seconds = vec![10, 60];
+        assert_eq!(paths(&routine), vec!["steps[1].warnings.remainingSeconds"]);
+    }
+}
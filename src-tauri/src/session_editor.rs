@@ -0,0 +1,513 @@
+use crate::data_manager::{BackupReason, DataError, DataFile, DataManager};
+use crate::ids::{new_id, IdKind};
+use crate::models::{Session, SessionChange, SessionEdit, SessionTotals, StepRunResult};
+use crate::routine_validator::FieldError;
+use crate::session_stats::{add_session_totals, subtract_session_totals};
+use crate::session_tracker::calculate_totals;
+use chrono::Utc;
+
+const MAX_TAG_CHARS: usize = 32;
+const MAX_ANNOTATION_CHARS: usize = 200;
+
+#[derive(Debug)]
+pub enum SessionEditError {
+    Data(DataError),
+    NotFound(String),
+    StepRunNotFound {
+        session_id: String,
+        run_index: usize,
+    },
+    Invalid(FieldError),
+}
+
+impl std::fmt::Display for SessionEditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionEditError::Data(err) => write!(f, "{err}"),
+            SessionEditError::NotFound(id) => write!(f, "Session not found: {id}"),
+            SessionEditError::StepRunNotFound {
+                session_id,
+                run_index,
+            } => write!(f, "Step run {run_index} not found in session {session_id}"),
+            SessionEditError::Invalid(err) => write!(f, "{}: {}", err.path, err.message),
+        }
+    }
+}
+
+impl std::error::Error for SessionEditError {}
+
+impl From<DataError> for SessionEditError {
+    fn from(value: DataError) -> Self {
+        Self::Data(value)
+    }
+}
+
+pub type SessionEditResult<T> = Result<T, SessionEditError>;
+
+/// Removes a session for good. The sessions file is backed up first since the
+/// audit trail goes with the session.
+pub fn delete_session(data_manager: &DataManager, session_id: &str) -> SessionEditResult<()> {
+    let _updates = data_manager.lock_updates();
+    let mut sessions = data_manager.load_sessions()?;
+    let before = sessions.len();
+    sessions.retain(|item| item.id != session_id);
+    if sessions.len() == before {
+        return Err(SessionEditError::NotFound(session_id.to_string()));
+    }
+    data_manager.create_backup(DataFile::Sessions, BackupReason::Edit)?;
+    data_manager.save_sessions(&sessions)?;
+    Ok(())
+}
+
+pub fn set_excluded_from_stats(
+    data_manager: &DataManager,
+    session_id: &str,
+    excluded: bool,
+) -> SessionEditResult<Session> {
+    edit_session(data_manager, session_id, |session| {
+        if session.excluded_from_stats == excluded {
+            return Ok(None);
+        }
+        session.excluded_from_stats = excluded;
+        Ok(Some(SessionChange::ExcludedFromStats { excluded }))
+    })
+}
+
+pub fn set_tags(
+    data_manager: &DataManager,
+    session_id: &str,
+    tags: Vec<String>,
+) -> SessionEditResult<Session> {
+    let tags = normalize_tags(tags)?;
+    edit_session(data_manager, session_id, |session| {
+        if session.tags == tags {
+            return Ok(None);
+        }
+        let previous = std::mem::replace(&mut session.tags, tags);
+        Ok(Some(SessionChange::Tags { previous }))
+    })
+}
+
+pub fn set_annotation(
+    data_manager: &DataManager,
+    session_id: &str,
+    annotation: Option<String>,
+) -> SessionEditResult<Session> {
+    let annotation = normalize_annotation(annotation)?;
+    edit_session(data_manager, session_id, |session| {
+        if session.annotation == annotation {
+            return Ok(None);
+        }
+        let previous = std::mem::replace(&mut session.annotation, annotation);
+        Ok(Some(SessionChange::Annotation { previous }))
+    })
+}
+
+/// Changes the recorded result of one step run and applies the difference the
+/// new result makes to the stored totals. The totals are not recomputed: the
+/// routine may have been edited or deleted since, and recovered sessions count
+/// a wall-clock span their runs do not add up to. Without the routine the run
+/// cannot be told to end a cycle, so the cycle count is left alone.
+pub fn correct_step_run_result(
+    data_manager: &DataManager,
+    session_id: &str,
+    run_index: usize,
+    result: StepRunResult,
+) -> SessionEditResult<Session> {
+    let routines = data_manager.load_routines()?;
+    edit_session(data_manager, session_id, |session| {
+        let Some(run) = session.step_runs.get(run_index) else {
+            return Err(SessionEditError::StepRunNotFound {
+                session_id: session.id.clone(),
+                run_index,
+            });
+        };
+        if run.result == result {
+            return Ok(None);
+        }
+        let steps = routines
+            .iter()
+            .find(|item| item.id == session.routine_id)
+            .map(|routine| routine.steps.as_slice())
+            .unwrap_or_default();
+        let last_step_id = steps
+            .last()
+            .map(|step| step.id.as_str())
+            .unwrap_or_default();
+        // Totals saved before adherence was tracked get it filled in from the
+        // runs when read, so the difference must not add it here too.
+        let legacy = session.totals.planned_seconds == 0;
+        let run_totals = |run| {
+            let totals = calculate_totals(std::slice::from_ref(run), steps, last_step_id);
+            if legacy {
+                SessionTotals {
+                    planned_seconds: 0,
+                    executed_seconds: 0,
+                    overtime_seconds: 0,
+                    skip_loss_seconds: 0,
+                    skipped_count: 0,
+                    ..totals
+                }
+            } else {
+                totals
+            }
+        };
+
+        let before = run_totals(run);
+        let mut corrected = run.clone();
+        corrected.result = result;
+        let after = run_totals(&corrected);
+        let previous = std::mem::replace(&mut session.step_runs[run_index], corrected).result;
+        let step_id = session.step_runs[run_index].step_id.clone();
+        let mut totals = session.totals.clone();
+        add_session_totals(&mut totals, &after);
+        subtract_session_totals(&mut totals, &before);
+        let previous_totals = std::mem::replace(&mut session.totals, totals);
+        Ok(Some(SessionChange::StepRunResult {
+            run_index,
+            step_id,
+            previous,
+            result,
+            previous_totals,
+        }))
+    })
+}
+
+fn edit_session<F>(
+    data_manager: &DataManager,
+    session_id: &str,
+    apply: F,
+) -> SessionEditResult<Session>
+where
+    F: FnOnce(&mut Session) -> SessionEditResult<Option<SessionChange>>,
+{
+    let _updates = data_manager.lock_updates();
+    let mut sessions = data_manager.load_sessions()?;
+    let session = sessions
+        .iter_mut()
+        .find(|item| item.id == session_id)
+        .ok_or_else(|| SessionEditError::NotFound(session_id.to_string()))?;
+
+    let Some(change) = apply(session)? else {
+        return Ok(session.clone());
+    };
+    session.edits.push(SessionEdit {
+        id: new_id(IdKind::Revision),
+        edited_at: Utc::now().to_rfc3339(),
+        change,
+    });
+    let updated = session.clone();
+    data_manager.save_sessions(&sessions)?;
+    Ok(updated)
+}
+
+fn normalize_tags(tags: Vec<String>) -> SessionEditResult<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::new();
+    for (index, tag) in tags.into_iter().enumerate() {
+        let tag = tag.trim();
+        if tag.is_empty() || normalized.iter().any(|item| item == tag) {
+            continue;
+        }
+        if tag.chars().count() > MAX_TAG_CHARS {
+            return Err(SessionEditError::Invalid(FieldError::new(
+                format!("tags[{index}]"),
+                format!("タグは{MAX_TAG_CHARS}文字以内にしてください"),
+            )));
+        }
+        if tag.contains([';', ',']) || tag.contains(char::is_control) {
+            return Err(SessionEditError::Invalid(FieldError::new(
+                format!("tags[{index}]"),
+                "タグに区切り文字や改行は使えません",
+            )));
+        }
+        normalized.push(tag.to_string());
+    }
+    Ok(normalized)
+}
+
+fn normalize_annotation(annotation: Option<String>) -> SessionEditResult<Option<String>> {
+    let Some(annotation) = annotation else {
+        return Ok(None);
+    };
+    let annotation = annotation.trim();
+    if annotation.is_empty() {
+        return Ok(None);
+    }
+    if annotation.contains(char::is_control) {
+        return Err(SessionEditError::Invalid(FieldError::new(
+            "annotation",
+            "メモは1行で入力してください",
+        )));
+    }
+    if annotation.chars().count() > MAX_ANNOTATION_CHARS {
+        return Err(SessionEditError::Invalid(FieldError::new(
+            "annotation",
+            format!("メモは{MAX_ANNOTATION_CHARS}文字以内にしてください"),
+        )));
+    }
+    Ok(Some(annotation.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        correct_step_run_result, delete_session, set_annotation, set_excluded_from_stats, set_tags,
+        SessionEditError,
+    };
+    use crate::data_integrity::verify_data;
+    use crate::data_manager::{BackupReason, DataManager};
+    use crate::models::{
+        Session, SessionChange, SessionOutcome, SessionTotals, StepRun, StepRunResult,
+    };
+    use crate::session_tracker::calculate_totals;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_editor_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn run(step_id: &str, seconds: u32, result: StepRunResult) -> StepRun {
+        StepRun {
+            step_id: step_id.to_string(),
+            label: None,
+            planned_duration_seconds: seconds,
+            actual_duration_seconds: seconds,
+            started_at: "2025-01-01T10:00:00Z".to_string(),
+            ended_at: Some("2025-01-01T10:05:00Z".to_string()),
+            result,
+            check_in_result: None,
+            sound_played: false,
+        }
+    }
+
+    /// Saves a session against the seeded template routine and returns its id.
+    fn seed_session(manager: &DataManager) -> String {
+        let routine = manager.load_routines().expect("load routines").remove(0);
+        let last_step = routine.steps.last().expect("last step").id.clone();
+        let session = Session {
+            id: "session-1".to_string(),
+            routine_id: routine.id.clone(),
+            started_at: "2025-01-01T10:00:00Z".to_string(),
+            ended_at: Some("2025-01-01T10:05:00Z".to_string()),
+            step_runs: vec![
+                run(&routine.steps[0].id, 240, StepRunResult::Completed),
+                run(&last_step, 60, StepRunResult::Aborted),
+            ],
+            totals: SessionTotals {
+                total_seconds: 300,
+                work_seconds: 300,
+                ..SessionTotals::default()
+            },
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        };
+        manager.save_session(session).expect("save session");
+        "session-1".to_string()
+    }
+
+    #[test]
+    fn records_audit_trail_for_each_change() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let id = seed_session(&manager);
+
+        set_excluded_from_stats(&manager, &id, true).expect("exclude");
+        set_excluded_from_stats(&manager, &id, true).expect("exclude again");
+        set_tags(
+            &manager,
+            &id,
+            vec![" focus ".to_string(), "".to_string(), "focus".to_string()],
+        )
+        .expect("tags");
+        let session = set_annotation(&manager, &id, Some("  started by accident ".to_string()))
+            .expect("annotation");
+
+        assert!(session.excluded_from_stats);
+        assert_eq!(session.tags, vec!["focus"]);
+        assert_eq!(session.annotation.as_deref(), Some("started by accident"));
+        assert_eq!(session.edits.len(), 3);
+        assert!(matches!(
+            session.edits[0].change,
+            SessionChange::ExcludedFromStats { excluded: true }
+        ));
+
+        let stored = manager.load_sessions().expect("load sessions");
+        assert_eq!(stored[0].edits.len(), 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_multiline_annotation_and_long_tags() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let id = seed_session(&manager);
+
+        let err = set_annotation(&manager, &id, Some("one\ntwo".to_string()))
+            .expect_err("multiline annotation");
+        assert!(matches!(err, SessionEditError::Invalid(ref field) if field.path == "annotation"));
+
+        let err = set_tags(&manager, &id, vec!["x".repeat(40)]).expect_err("long tag");
+        assert!(matches!(err, SessionEditError::Invalid(ref field) if field.path == "tags[0]"));
+        assert!(manager.load_sessions().expect("load")[0].edits.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn correcting_step_run_recomputes_totals() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let id = seed_session(&manager);
+
+        let session =
+            correct_step_run_result(&manager, &id, 1, StepRunResult::Completed).expect("correct");
+
+        assert_eq!(session.step_runs[1].result, StepRunResult::Completed);
+        assert_eq!(session.totals.cycles_count, 1);
+        match &session.edits[0].change {
+            SessionChange::StepRunResult {
+                run_index,
+                previous,
+                previous_totals,
+                ..
+            } => {
+                assert_eq!(*run_index, 1);
+                assert_eq!(*previous, StepRunResult::Aborted);
+                assert_eq!(previous_totals.cycles_count, 0);
+            }
+            other => panic!("unexpected change {other:?}"),
+        }
+
+        let err = correct_step_run_result(&manager, &id, 5, StepRunResult::Skipped)
+            .expect_err("missing run");
+        assert!(matches!(err, SessionEditError::StepRunNotFound { .. }));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn correcting_keeps_totals_when_the_routine_changed_or_went_away() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let id = seed_session(&manager);
+        let mut routines = manager.load_routines().expect("load routines");
+        routines[0].steps[0].count_as_break = true;
+        manager.save_routines(&routines).expect("edit routine");
+
+        let session =
+            correct_step_run_result(&manager, &id, 1, StepRunResult::Completed).expect("correct");
+        assert_eq!(
+            (
+                session.totals.work_seconds,
+                session.totals.break_seconds,
+                session.totals.cycles_count
+            ),
+            (300, 0, 1)
+        );
+
+        manager.save_routines(&[]).expect("delete routine");
+        let session =
+            correct_step_run_result(&manager, &id, 0, StepRunResult::Skipped).expect("correct");
+        assert_eq!(session.step_runs[0].result, StepRunResult::Skipped);
+        assert_eq!(
+            (session.totals.total_seconds, session.totals.cycles_count),
+            (300, 1)
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn correcting_a_recovered_session_keeps_its_wall_clock_totals() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let routine = manager.load_routines().expect("load routines").remove(0);
+        let last_step = routine.steps.last().expect("last step").id.clone();
+        let step_runs = vec![run(&last_step, 60, StepRunResult::Aborted)];
+        let mut totals = calculate_totals(&step_runs, &routine.steps, &last_step);
+        totals.total_seconds = 900;
+        totals.work_seconds = 900 - totals.break_seconds;
+        manager
+            .save_session(Session {
+                id: "session-recovered".to_string(),
+                routine_id: routine.id.clone(),
+                started_at: "2025-01-01T09:50:00Z".to_string(),
+                ended_at: Some("2025-01-01T10:05:00Z".to_string()),
+                step_runs,
+                totals: totals.clone(),
+                muted_during_session: false,
+                zone: None,
+                excluded_from_stats: false,
+                tags: Vec::new(),
+                annotation: None,
+                edits: Vec::new(),
+                outcome: Some(SessionOutcome::Recovered),
+            })
+            .expect("save session");
+
+        let session =
+            correct_step_run_result(&manager, "session-recovered", 0, StepRunResult::Completed)
+                .expect("correct");
+
+        assert_eq!(session.totals.total_seconds, 900);
+        assert_eq!(session.totals.work_seconds, totals.work_seconds);
+        assert_eq!(session.totals.cycles_count, totals.cycles_count + 1);
+        match &session.edits[0].change {
+            SessionChange::StepRunResult {
+                previous_totals, ..
+            } => assert_eq!(previous_totals, &totals),
+            other => panic!("unexpected change {other:?}"),
+        }
+        let report = verify_data(&manager, false).expect("verify");
+        assert!(!report
+            .issues
+            .iter()
+            .any(|issue| issue.code == "totals-mismatch"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn delete_backs_up_sessions_first() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let id = seed_session(&manager);
+
+        delete_session(&manager, &id).expect("delete");
+
+        assert!(manager.load_sessions().expect("load").is_empty());
+        assert!(manager
+            .list_backups()
+            .expect("list backups")
+            .iter()
+            .any(|item| item.reason == BackupReason::Edit));
+        assert!(matches!(
+            delete_session(&manager, &id),
+            Err(SessionEditError::NotFound(_))
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,610 @@
+use crate::data_manager::{DataError, DataManager};
+use crate::models::{CheckInChoice, Routine, Session, SessionTotals, StepRun, StepRunResult};
+use crate::session_stats::{effective_totals, session_adherence, step_label};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SESSIONS_CSV: &str = "sessions.csv";
+const STEP_RUNS_CSV: &str = "step_runs.csv";
+const BUNDLE_JSON: &str = "sessions.json";
+
+const SESSION_COLUMNS: [&str; 20] = [
+    "session_id",
+    "routine_id",
+    "routine_name",
+    "started_at",
+    "ended_at",
+    "total_seconds",
+    "work_seconds",
+    "break_seconds",
+    "cycles_count",
+    "check_in_done_count",
+    "check_in_skip_count",
+    "planned_seconds",
+    "executed_seconds",
+    "overtime_seconds",
+    "adherence",
+    "muted_during_session",
+    "step_run_count",
+    "excluded_from_stats",
+    "tags",
+    "annotation",
+];
+
+const STEP_RUN_COLUMNS: [&str; 15] = [
+    "session_id",
+    "run_index",
+    "routine_id",
+    "routine_name",
+    "step_id",
+    "step_label",
+    "started_at",
+    "ended_at",
+    "planned_seconds",
+    "actual_seconds",
+    "result",
+    "check_in_choice",
+    "check_in_timed_out",
+    "response_time_ms",
+    "sound_played",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Data(DataError),
+    Serde(serde_json::Error),
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::Io(err) => write!(f, "I/O error: {err}"),
+            ExportError::Data(err) => write!(f, "{err}"),
+            ExportError::Serde(err) => write!(f, "Serialization error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<io::Error> for ExportError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<DataError> for ExportError {
+    fn from(value: DataError) -> Self {
+        Self::Data(value)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Serde(value)
+    }
+}
+
+pub type ExportResult<T> = Result<T, ExportError>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionBundle {
+    pub exported_at: String,
+    pub from: String,
+    pub to: String,
+    pub routines: Vec<BundleRoutine>,
+    pub steps: Vec<BundleStep>,
+    pub sessions: Vec<BundleSession>,
+    pub step_runs: Vec<BundleStepRun>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleRoutine {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleStep {
+    pub routine_id: String,
+    pub step_id: String,
+    pub label: String,
+    pub count_as_break: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleSession {
+    pub id: String,
+    pub routine_id: String,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    /// Totals with adherence filled in for sessions recorded before it was
+    /// stored, matching the sessions CSV.
+    pub totals: SessionTotals,
+    pub muted_during_session: bool,
+    pub excluded_from_stats: bool,
+    pub tags: Vec<String>,
+    pub annotation: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleStepRun {
+    pub session_id: String,
+    pub run_index: usize,
+    pub step_id: String,
+    pub label: String,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub planned_duration_seconds: u32,
+    pub actual_duration_seconds: u32,
+    pub result: StepRunResult,
+    pub check_in_choice: Option<CheckInChoice>,
+    pub check_in_timed_out: bool,
+    #[serde(rename = "responseTimeMs")]
+    pub response_time_ms: Option<u64>,
+    pub sound_played: bool,
+}
+
+pub fn export_sessions(
+    data_manager: &DataManager,
+    from: &str,
+    to: &str,
+    format: ExportFormat,
+    directory: &Path,
+) -> ExportResult<Vec<PathBuf>> {
+    let sessions = data_manager.load_sessions_in_range(from, to)?;
+    let routines = data_manager.load_routines()?;
+    fs::create_dir_all(directory)?;
+
+    match format {
+        ExportFormat::Csv => {
+            let sessions_path = directory.join(SESSIONS_CSV);
+            let step_runs_path = directory.join(STEP_RUNS_CSV);
+            fs::write(&sessions_path, sessions_csv(&sessions, &routines))?;
+            fs::write(&step_runs_path, step_runs_csv(&sessions, &routines))?;
+            Ok(vec![sessions_path, step_runs_path])
+        }
+        ExportFormat::Json => {
+            let bundle_path = directory.join(BUNDLE_JSON);
+            let bundle = build_bundle(&sessions, &routines, from, to);
+            let mut contents = serde_json::to_string_pretty(&bundle)?;
+            contents.push('\n');
+            fs::write(&bundle_path, contents)?;
+            Ok(vec![bundle_path])
+        }
+    }
+}
+
+pub fn sessions_csv(sessions: &[Session], routines: &[Routine]) -> String {
+    let mut output = csv_row(SESSION_COLUMNS.iter().map(|column| column.to_string()));
+    for session in sessions {
+        let totals = effective_totals(session);
+        output.push_str(&csv_row([
+            session.id.clone(),
+            session.routine_id.clone(),
+            routine_name(routines, &session.routine_id),
+            session.started_at.clone(),
+            session.ended_at.clone().unwrap_or_default(),
+            totals.total_seconds.to_string(),
+            totals.work_seconds.to_string(),
+            totals.break_seconds.to_string(),
+            totals.cycles_count.to_string(),
+            totals.check_in_done_count.to_string(),
+            totals.check_in_skip_count.to_string(),
+            totals.planned_seconds.to_string(),
+            totals.executed_seconds.to_string(),
+            totals.overtime_seconds.to_string(),
+            session_adherence(session)
+                .map(|value| format!("{value:.3}"))
+                .unwrap_or_default(),
+            session.muted_during_session.to_string(),
+            session.step_runs.len().to_string(),
+            session.excluded_from_stats.to_string(),
+            session.tags.join(";"),
+            session.annotation.clone().unwrap_or_default(),
+        ]));
+    }
+    output
+}
+
+pub fn step_runs_csv(sessions: &[Session], routines: &[Routine]) -> String {
+    let mut output = csv_row(STEP_RUN_COLUMNS.iter().map(|column| column.to_string()));
+    for session in sessions {
+        let name = routine_name(routines, &session.routine_id);
+        for (index, run) in session.step_runs.iter().enumerate() {
+            let check_in = run.check_in_result.as_ref();
+            output.push_str(&csv_row([
+                session.id.clone(),
+                index.to_string(),
+                session.routine_id.clone(),
+                name.clone(),
+                run.step_id.clone(),
+                run_label(routines, &session.routine_id, run),
+                run.started_at.clone(),
+                run.ended_at.clone().unwrap_or_default(),
+                run.planned_duration_seconds.to_string(),
+                run.actual_duration_seconds.to_string(),
+                result_label(&run.result).to_string(),
+                check_in
+                    .and_then(|result| result.choice)
+                    .map(choice_label)
+                    .unwrap_or_default()
+                    .to_string(),
+                check_in
+                    .map(|result| result.timed_out.to_string())
+                    .unwrap_or_default(),
+                check_in
+                    .and_then(|result| result.response_time_ms)
+                    .map(|value| value.to_string())
+                    .unwrap_or_default(),
+                run.sound_played.to_string(),
+            ]));
+        }
+    }
+    output
+}
+
+pub fn build_bundle(
+    sessions: &[Session],
+    routines: &[Routine],
+    from: &str,
+    to: &str,
+) -> SessionBundle {
+    let mut bundle_routines: Vec<BundleRoutine> = Vec::new();
+    let mut bundle_steps: Vec<BundleStep> = Vec::new();
+    for session in sessions {
+        if bundle_routines
+            .iter()
+            .any(|item| item.id == session.routine_id)
+        {
+            continue;
+        }
+        bundle_routines.push(BundleRoutine {
+            id: session.routine_id.clone(),
+            name: routine_name(routines, &session.routine_id),
+        });
+        if let Some(routine) = routines.iter().find(|item| item.id == session.routine_id) {
+            bundle_steps.extend(routine.steps.iter().map(|step| BundleStep {
+                routine_id: routine.id.clone(),
+                step_id: step.id.clone(),
+                label: step.label.clone(),
+                count_as_break: step.count_as_break,
+            }));
+        }
+    }
+
+    let bundle_sessions = sessions
+        .iter()
+        .map(|session| BundleSession {
+            id: session.id.clone(),
+            routine_id: session.routine_id.clone(),
+            started_at: session.started_at.clone(),
+            ended_at: session.ended_at.clone(),
+            totals: effective_totals(session),
+            muted_during_session: session.muted_during_session,
+            excluded_from_stats: session.excluded_from_stats,
+            tags: session.tags.clone(),
+            annotation: session.annotation.clone(),
+        })
+        .collect();
+
+    let step_runs = sessions
+        .iter()
+        .flat_map(|session| {
+            session
+                .step_runs
+                .iter()
+                .enumerate()
+                .map(move |(index, run)| BundleStepRun {
+                    session_id: session.id.clone(),
+                    run_index: index,
+                    step_id: run.step_id.clone(),
+                    label: run_label(routines, &session.routine_id, run),
+                    started_at: run.started_at.clone(),
+                    ended_at: run.ended_at.clone(),
+                    planned_duration_seconds: run.planned_duration_seconds,
+                    actual_duration_seconds: run.actual_duration_seconds,
+                    result: run.result,
+                    check_in_choice: run
+                        .check_in_result
+                        .as_ref()
+                        .and_then(|result| result.choice),
+                    check_in_timed_out: run
+                        .check_in_result
+                        .as_ref()
+                        .map(|result| result.timed_out)
+                        .unwrap_or(false),
+                    response_time_ms: run
+                        .check_in_result
+                        .as_ref()
+                        .and_then(|result| result.response_time_ms),
+                    sound_played: run.sound_played,
+                })
+        })
+        .collect();
+
+    SessionBundle {
+        exported_at: Utc::now().to_rfc3339(),
+        from: from.to_string(),
+        to: to.to_string(),
+        routines: bundle_routines,
+        steps: bundle_steps,
+        sessions: bundle_sessions,
+        step_runs,
+    }
+}
+
+fn routine_name(routines: &[Routine], routine_id: &str) -> String {
+    routines
+        .iter()
+        .find(|item| item.id == routine_id)
+        .map(|item| item.name.clone())
+        .unwrap_or_default()
+}
+
+/// The label recorded with `run`, or the step's current one for runs
+/// recorded before labels were stored.
+fn run_label(routines: &[Routine], routine_id: &str, run: &StepRun) -> String {
+    run.label
+        .clone()
+        .or_else(|| step_label(routines, routine_id, &run.step_id))
+        .unwrap_or_default()
+}
+
+fn result_label(result: &StepRunResult) -> &'static str {
+    match result {
+        StepRunResult::Completed => "completed",
+        StepRunResult::Skipped => "skipped",
+        StepRunResult::Aborted => "aborted",
+    }
+}
+
+fn choice_label(choice: CheckInChoice) -> &'static str {
+    match choice {
+        CheckInChoice::Done => "done",
+        CheckInChoice::Skip => "skip",
+    }
+}
+
+fn csv_row<I>(fields: I) -> String
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut row = fields
+        .into_iter()
+        .map(|field| csv_escape(&field))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_bundle, csv_escape, export_sessions, sessions_csv, step_runs_csv, ExportFormat,
+    };
+    use crate::data_manager::DataManager;
+    use crate::models::{
+        CheckInChoice, CheckInMode, CheckInResult, Session, SessionTotals, StepRun, StepRunResult,
+    };
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_export_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn sample_session(id: &str, started_at: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            routine_id: "routine-template-10min".to_string(),
+            started_at: started_at.to_string(),
+            ended_at: Some(started_at.to_string()),
+            step_runs: vec![
+                StepRun {
+                    step_id: "step-template-4".to_string(),
+                    label: None,
+                    planned_duration_seconds: 240,
+                    actual_duration_seconds: 200,
+                    started_at: started_at.to_string(),
+                    ended_at: None,
+                    result: StepRunResult::Skipped,
+                    check_in_result: None,
+                    sound_played: true,
+                },
+                StepRun {
+                    step_id: "step-template-6".to_string(),
+                    label: None,
+                    planned_duration_seconds: 110,
+                    actual_duration_seconds: 110,
+                    started_at: started_at.to_string(),
+                    ended_at: None,
+                    result: StepRunResult::Completed,
+                    check_in_result: Some(CheckInResult {
+                        mode: CheckInMode::Gate,
+                        responded_at: None,
+                        choice: Some(CheckInChoice::Done),
+                        response_time_ms: Some(1500),
+                        timed_out: false,
+                    }),
+                    sound_played: false,
+                },
+            ],
+            totals: SessionTotals {
+                total_seconds: 310,
+                work_seconds: 310,
+                break_seconds: 0,
+                cycles_count: 0,
+                check_in_done_count: 1,
+                check_in_skip_count: 0,
+                ..SessionTotals::default()
+            },
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn escapes_csv_fields() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn session_rows_include_adherence() {
+        let sessions = vec![sample_session("session-1", "2025-01-01T10:00:00Z")];
+
+        let csv = sessions_csv(&sessions, &[]);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(",planned_seconds,executed_seconds,overtime_seconds,adherence,"));
+        assert!(
+            lines[1].contains(",1,0,350,310,0,0.886,false,"),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn step_run_rows_resolve_labels() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let routines = manager.load_routines().expect("load routines");
+        let sessions = vec![sample_session("session-1", "2025-01-01T10:00:00Z")];
+
+        let csv = step_runs_csv(&sessions, &routines);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("session_id,run_index,routine_id"));
+        assert_eq!(
+            lines[1],
+            "session-1,0,routine-template-10min,10分ミニ・スプリント,step-template-4,集中,\
+2025-01-01T10:00:00Z,,240,200,skipped,,,,true"
+        );
+        assert_eq!(
+            lines[2],
+            "session-1,1,routine-template-10min,10分ミニ・スプリント,step-template-6,メモ,\
+2025-01-01T10:00:00Z,,110,110,completed,done,false,1500,false"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn bundle_normalizes_routines_and_runs() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        let routines = manager.load_routines().expect("load routines");
+        let sessions = vec![
+            sample_session("session-1", "2025-01-01T10:00:00Z"),
+            sample_session("session-2", "2025-01-02T10:00:00Z"),
+        ];
+
+        let bundle = build_bundle(&sessions, &routines, "from", "to");
+
+        assert_eq!(bundle.routines.len(), 1);
+        assert_eq!(bundle.steps.len(), 8);
+        assert_eq!(bundle.sessions.len(), 2);
+        assert_eq!(bundle.step_runs.len(), 4);
+        assert_eq!(bundle.step_runs[3].session_id, "session-2");
+        assert_eq!(bundle.step_runs[3].response_time_ms, Some(1500));
+        assert_eq!(
+            (
+                bundle.sessions[0].totals.planned_seconds,
+                bundle.sessions[0].totals.executed_seconds
+            ),
+            (350, 310)
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn export_writes_files_for_range() {
+        let dir = temp_dir();
+        let manager = DataManager::new(&dir).expect("create manager");
+        manager
+            .save_sessions(&[
+                sample_session("session-1", "2025-01-01T10:00:00Z"),
+                sample_session("session-2", "2025-02-01T10:00:00Z"),
+            ])
+            .expect("save sessions");
+        let out_dir = dir.join("export");
+
+        let written = export_sessions(
+            &manager,
+            "2025-01-01T00:00:00Z",
+            "2025-01-31T23:59:59Z",
+            ExportFormat::Csv,
+            &out_dir,
+        )
+        .expect("export csv");
+        assert_eq!(written.len(), 2);
+        let sessions_csv = fs::read_to_string(&written[0]).expect("read sessions csv");
+        assert_eq!(sessions_csv.lines().count(), 2);
+
+        let written = export_sessions(
+            &manager,
+            "2025-01-01T00:00:00Z",
+            "2025-12-31T23:59:59Z",
+            ExportFormat::Json,
+            &out_dir,
+        )
+        .expect("export json");
+        let contents = fs::read_to_string(&written[0]).expect("read bundle");
+        let value: serde_json::Value = serde_json::from_str(&contents).expect("parse bundle");
+        assert_eq!(value["sessions"].as_array().map(Vec::len), Some(2));
+        assert_eq!(value["stepRuns"].as_array().map(Vec::len), Some(4));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
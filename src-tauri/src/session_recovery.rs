@@ -1,21 +1,14 @@
+use crate::calendar::current_zone;
 use crate::data_manager::{DataManager, DataResult};
-use crate::models::{Session, SessionTotals, Step, StepRun, StepRunResult};
+use crate::models::{Session, SessionOutcome, SessionTotals, Step, StepRun, StepRunResult};
 use crate::recovery_state::ActiveSessionSnapshot;
+use crate::session_tracker::add_adherence;
 use chrono::{DateTime, Utc};
-use std::time::{SystemTime, UNIX_EPOCH};
 
 fn now_rfc3339() -> String {
     Utc::now().to_rfc3339()
 }
 
-fn generate_session_id() -> String {
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos();
-    format!("session-{nanos}-{}", std::process::id())
-}
-
 fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
     DateTime::parse_from_rfc3339(value)
         .ok()
@@ -35,13 +28,14 @@ fn duration_seconds_between(start: &str, end: &str) -> Option<u32> {
 
 pub fn start_active_session(
     data_manager: &DataManager,
+    session_id: &str,
     routine_id: &str,
     step: &Step,
     muted: bool,
 ) -> DataResult<ActiveSessionSnapshot> {
     let now = now_rfc3339();
     let snapshot = ActiveSessionSnapshot {
-        session_id: generate_session_id(),
+        session_id: session_id.to_string(),
         routine_id: routine_id.to_string(),
         started_at: now.clone(),
         current_step_id: step.id.clone(),
@@ -49,6 +43,7 @@ pub fn start_active_session(
         current_step_sound_played: false,
         paused_at: None,
         muted_during_session: muted,
+        zone: Some(current_zone()),
     };
     data_manager.save_active_session(&snapshot)?;
     Ok(snapshot)
@@ -125,6 +120,7 @@ pub fn recover_aborted_session(data_manager: &DataManager) -> DataResult<Option<
     let routines = data_manager.load_routines()?;
     let mut planned_duration_seconds = 0;
     let mut count_as_break = false;
+    let mut label = None;
     if let Some(routine) = routines.iter().find(|item| item.id == snapshot.routine_id) {
         if let Some(step) = routine
             .steps
@@ -133,6 +129,7 @@ pub fn recover_aborted_session(data_manager: &DataManager) -> DataResult<Option<
         {
             planned_duration_seconds = step.duration_seconds;
             count_as_break = step.count_as_break;
+            label = Some(step.label.clone());
         }
     }
 
@@ -145,17 +142,16 @@ pub fn recover_aborted_session(data_manager: &DataManager) -> DataResult<Option<
         work_seconds = total_seconds.saturating_sub(break_seconds);
     }
 
-    let totals = SessionTotals {
+    let mut totals = SessionTotals {
         total_seconds,
         work_seconds,
         break_seconds,
-        cycles_count: 0,
-        check_in_done_count: 0,
-        check_in_skip_count: 0,
+        ..SessionTotals::default()
     };
 
     let step_run = StepRun {
         step_id: snapshot.current_step_id.clone(),
+        label,
         planned_duration_seconds,
         actual_duration_seconds: step_seconds,
         started_at: snapshot.current_step_started_at.clone(),
@@ -164,6 +160,7 @@ pub fn recover_aborted_session(data_manager: &DataManager) -> DataResult<Option<
         check_in_result: None,
         sound_played: snapshot.current_step_sound_played,
     };
+    add_adherence(&mut totals, &step_run);
 
     let session = Session {
         id: snapshot.session_id.clone(),
@@ -173,6 +170,12 @@ pub fn recover_aborted_session(data_manager: &DataManager) -> DataResult<Option<
         step_runs: vec![step_run],
         totals,
         muted_during_session: snapshot.muted_during_session,
+        zone: snapshot.zone.clone(),
+        excluded_from_stats: false,
+        tags: Vec::new(),
+        annotation: None,
+        edits: Vec::new(),
+        outcome: Some(SessionOutcome::Recovered),
     };
 
     data_manager.save_session(session.clone())?;
@@ -185,7 +188,8 @@ pub fn recover_aborted_session(data_manager: &DataManager) -> DataResult<Option<
 mod tests {
     use super::{recover_aborted_session, start_active_session};
     use crate::data_manager::DataManager;
-    use crate::models::StepRunResult;
+    use crate::ids::{new_id, IdKind};
+    use crate::models::{SessionOutcome, StepRunResult};
     use std::fs;
     use std::path::PathBuf;
     use std::sync::atomic::{AtomicUsize, Ordering};
@@ -216,7 +220,8 @@ mod tests {
         let routine = routines.first().expect("routine exists");
         let step = routine.steps.first().expect("step exists");
 
-        start_active_session(&manager, &routine.id, step, false).expect("start session");
+        start_active_session(&manager, &new_id(IdKind::Session), &routine.id, step, false)
+            .expect("start session");
         let recovered = recover_aborted_session(&manager)
             .expect("recover session")
             .expect("session saved");
@@ -224,6 +229,7 @@ mod tests {
         assert_eq!(recovered.routine_id, routine.id);
         assert!(recovered.ended_at.is_some());
         assert_eq!(recovered.step_runs.len(), 1);
+        assert_eq!(recovered.outcome, Some(SessionOutcome::Recovered));
         assert!(matches!(
             recovered.step_runs[0].result,
             StepRunResult::Aborted
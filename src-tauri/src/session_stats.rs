@@ -1,30 +1,49 @@
-use crate::models::{Session, SessionStats};
-
-pub fn calculate_session_stats(sessions: &[Session]) -> SessionStats {
-    let sessions_count = sessions.len().try_into().unwrap_or(u32::MAX);
-    let mut stats = SessionStats {
-        sessions_count,
-        cycles_count: 0,
-        ..SessionStats::default()
-    };
+use crate::calendar::{
+    day_containing, day_of_week, parse_instant, parse_time_of_day, week_position, CalendarError,
+    Period,
+};
+use crate::models::{
+    CalendarSettings, CheckInChoice, DailyAggregate, DayOfWeek, Routine, Session, SessionOutcome,
+    SessionStats, SessionTotals, StatsBucket, StatsFilter, StatsGroup, StatsGroupBy, StepRun,
+    StepRunResult, StepStats,
+};
+use crate::session_tracker::add_adherence;
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
-    let mut muted_sessions: u32 = 0;
-    for session in sessions {
-        let totals = &session.totals;
-        stats.total_seconds = stats.total_seconds.saturating_add(totals.total_seconds);
-        stats.work_seconds = stats.work_seconds.saturating_add(totals.work_seconds);
-        stats.break_seconds = stats.break_seconds.saturating_add(totals.break_seconds);
-        stats.cycles_count = stats.cycles_count.saturating_add(totals.cycles_count);
-        stats.check_in_done_count = stats
-            .check_in_done_count
-            .saturating_add(totals.check_in_done_count);
-        stats.check_in_skip_count = stats
-            .check_in_skip_count
-            .saturating_add(totals.check_in_skip_count);
+/// Stats over individual sessions plus the daily aggregates that older
+/// sessions were folded into by retention pruning. Sessions marked as
+/// excluded are left out entirely.
+pub fn calculate_session_stats(
+    sessions: &[Session],
+    aggregates: &[DailyAggregate],
+) -> SessionStats {
+    let mut stats = SessionStats::default();
 
+    let mut muted_sessions: u32 = 0;
+    for session in sessions.iter().filter(|item| !item.excluded_from_stats) {
+        stats.sessions_count = stats.sessions_count.saturating_add(1);
+        add_totals(&mut stats, &effective_totals(session));
         if session.muted_during_session {
             muted_sessions = muted_sessions.saturating_add(1);
         }
+        let (stopped, recovered) = aborted_counts(session);
+        stats.stopped_sessions_count = stats.stopped_sessions_count.saturating_add(stopped);
+        stats.recovered_sessions_count = stats.recovered_sessions_count.saturating_add(recovered);
+    }
+    for aggregate in aggregates {
+        stats.sessions_count = stats
+            .sessions_count
+            .saturating_add(aggregate.sessions_count);
+        add_totals(&mut stats, &aggregate.totals);
+        muted_sessions = muted_sessions.saturating_add(aggregate.muted_sessions_count);
+        stats.stopped_sessions_count = stats
+            .stopped_sessions_count
+            .saturating_add(aggregate.stopped_sessions_count);
+        stats.recovered_sessions_count = stats
+            .recovered_sessions_count
+            .saturating_add(aggregate.recovered_sessions_count);
     }
 
     stats.mute_rate = if stats.sessions_count == 0 {
@@ -32,14 +51,792 @@ pub fn calculate_session_stats(sessions: &[Session]) -> SessionStats {
     } else {
         muted_sessions as f32 / stats.sessions_count as f32
     };
+    let aborted = stats
+        .stopped_sessions_count
+        .saturating_add(stats.recovered_sessions_count);
+    stats.completed_sessions_count = stats.sessions_count.saturating_sub(aborted);
+    stats.abort_rate = ratio(u64::from(aborted), stats.sessions_count);
 
     stats
 }
 
+/// Stats for the sessions and aggregates matching `filter`, one entry per
+/// routine, tag or local weekday when `group_by` is set and a single `"all"`
+/// entry otherwise. Groups without any matching history are left out.
+///
+/// Daily aggregates carry neither tags nor start times, so they only count
+/// when the filter uses neither and results are not grouped by tag. Their
+/// weekday is that of the UTC date they were folded under. A session with
+/// several tags counts towards each of them; untagged sessions are left out
+/// when grouping by tag.
+pub fn calculate_grouped_stats<Tz: TimeZone>(
+    sessions: &[Session],
+    aggregates: &[DailyAggregate],
+    routines: &[Routine],
+    filter: &StatsFilter,
+    group_by: Option<StatsGroupBy>,
+    zone: &Tz,
+    settings: &CalendarSettings,
+) -> Result<Vec<StatsGroup>, CalendarError> {
+    let window = filter
+        .time_of_day
+        .as_ref()
+        .map(|window| {
+            Ok((
+                parse_time_of_day(&window.start)?,
+                parse_time_of_day(&window.end)?,
+            ))
+        })
+        .transpose()?;
+    let in_window = |time: NaiveTime| match window {
+        None => true,
+        Some((start, end)) if start < end => start <= time && time < end,
+        Some((start, end)) => time >= start || time < end,
+    };
+    let matches_routine = |routine_id: &str| {
+        filter.routine_ids.is_empty() || filter.routine_ids.iter().any(|id| id == routine_id)
+    };
+    let matches_day = |day: DayOfWeek| filter.weekdays.is_empty() || filter.weekdays.contains(&day);
+
+    let mut grouped: HashMap<String, (Vec<Session>, Vec<DailyAggregate>)> = HashMap::new();
+    for session in sessions.iter().filter(|item| !item.excluded_from_stats) {
+        let Ok(started_at) = parse_instant(&session.started_at) else {
+            continue;
+        };
+        let day = day_of_week(day_containing(started_at, zone, settings));
+        if !matches_routine(&session.routine_id)
+            || !matches_day(day)
+            || !in_window(started_at.with_timezone(zone).time())
+            || !(filter.tags.is_empty() || session.tags.iter().any(|tag| filter.tags.contains(tag)))
+        {
+            continue;
+        }
+        let keys = match group_by {
+            None => vec![ALL_GROUP.to_string()],
+            Some(StatsGroupBy::Routine) => vec![session.routine_id.clone()],
+            Some(StatsGroupBy::Tag) => session.tags.clone(),
+            Some(StatsGroupBy::Weekday) => vec![day_key(day)],
+        };
+        for key in keys {
+            grouped.entry(key).or_default().0.push(session.clone());
+        }
+    }
+
+    let aggregates_apply =
+        filter.tags.is_empty() && window.is_none() && group_by != Some(StatsGroupBy::Tag);
+    for aggregate in aggregates.iter().filter(|_| aggregates_apply) {
+        let Ok(date) = NaiveDate::parse_from_str(&aggregate.date, "%Y-%m-%d") else {
+            continue;
+        };
+        let day = day_of_week(date);
+        if !matches_routine(&aggregate.routine_id) || !matches_day(day) {
+            continue;
+        }
+        let key = match group_by {
+            Some(StatsGroupBy::Routine) => aggregate.routine_id.clone(),
+            Some(StatsGroupBy::Weekday) => day_key(day),
+            _ => ALL_GROUP.to_string(),
+        };
+        grouped.entry(key).or_default().1.push(aggregate.clone());
+    }
+
+    let mut groups: Vec<StatsGroup> = grouped
+        .into_iter()
+        .map(|(key, (sessions, aggregates))| StatsGroup {
+            label: (group_by == Some(StatsGroupBy::Routine))
+                .then(|| routines.iter().find(|routine| routine.id == key))
+                .flatten()
+                .map(|routine| routine.name.clone()),
+            stats: calculate_session_stats(&sessions, &aggregates),
+            key,
+        })
+        .collect();
+    match group_by {
+        Some(StatsGroupBy::Routine) => groups.sort_by_key(|group| {
+            let position = routines.iter().position(|routine| routine.id == group.key);
+            (position.is_none(), position, group.key.clone())
+        }),
+        Some(StatsGroupBy::Weekday) => groups.sort_by_key(|group| {
+            WEEK_DAYS
+                .iter()
+                .find(|&&day| day_key(day) == group.key)
+                .map(|&day| week_position(day, settings))
+        }),
+        _ => groups.sort_by(|left, right| left.key.cmp(&right.key)),
+    }
+    Ok(groups)
+}
+
+const ALL_GROUP: &str = "all";
+
+const WEEK_DAYS: [DayOfWeek; 7] = [
+    DayOfWeek::Monday,
+    DayOfWeek::Tuesday,
+    DayOfWeek::Wednesday,
+    DayOfWeek::Thursday,
+    DayOfWeek::Friday,
+    DayOfWeek::Saturday,
+    DayOfWeek::Sunday,
+];
+
+/// The weekday as it is serialized.
+fn day_key(day: DayOfWeek) -> String {
+    match day {
+        DayOfWeek::Monday => "monday",
+        DayOfWeek::Tuesday => "tuesday",
+        DayOfWeek::Wednesday => "wednesday",
+        DayOfWeek::Thursday => "thursday",
+        DayOfWeek::Friday => "friday",
+        DayOfWeek::Saturday => "saturday",
+        DayOfWeek::Sunday => "sunday",
+    }
+    .to_string()
+}
+
+/// How `session` ended. Sessions recorded before outcomes were stored are
+/// judged by their last run: when it was aborted the session was stopped, or
+/// recovered if that run is the only one and started after the session did,
+/// as recovery keeps just the step that was running. A crash during the first
+/// step cannot be told from a stop there.
+pub fn session_outcome(session: &Session) -> SessionOutcome {
+    if let Some(outcome) = session.outcome {
+        return outcome;
+    }
+    match session.step_runs.as_slice() {
+        [] => SessionOutcome::Completed,
+        [.., last] if last.result != StepRunResult::Aborted => SessionOutcome::Completed,
+        [only] if only.started_at != session.started_at => SessionOutcome::Recovered,
+        _ => SessionOutcome::Stopped,
+    }
+}
+
+/// Whether `session` counts as stopped and as recovered, as 0 or 1 each.
+pub fn aborted_counts(session: &Session) -> (u32, u32) {
+    match session_outcome(session) {
+        SessionOutcome::Completed => (0, 0),
+        SessionOutcome::Stopped => (1, 0),
+        SessionOutcome::Recovered => (0, 1),
+    }
+}
+
+/// Share of the session's planned step time that was actually run, or `None`
+/// when nothing was planned.
+pub fn session_adherence(session: &Session) -> Option<f32> {
+    let totals = effective_totals(session);
+    (totals.planned_seconds > 0).then(|| executed_ratio(&totals))
+}
+
+/// The session's totals, with adherence rebuilt from its step runs when they
+/// were recorded before adherence was part of the totals.
+pub fn effective_totals(session: &Session) -> SessionTotals {
+    let mut totals = session.totals.clone();
+    if totals.planned_seconds == 0 {
+        for run in &session.step_runs {
+            add_adherence(&mut totals, run);
+        }
+    }
+    totals
+}
+
+fn executed_ratio(totals: &SessionTotals) -> f32 {
+    ratio(u64::from(totals.executed_seconds), totals.planned_seconds)
+}
+
+/// Adds `added` into `totals`, field by field.
+pub fn add_session_totals(totals: &mut SessionTotals, added: &SessionTotals) {
+    combine_totals(totals, added, u32::saturating_add);
+}
+
+/// Takes `removed` out of `totals`, field by field, stopping at zero.
+pub fn subtract_session_totals(totals: &mut SessionTotals, removed: &SessionTotals) {
+    combine_totals(totals, removed, u32::saturating_sub);
+}
+
+fn combine_totals(totals: &mut SessionTotals, other: &SessionTotals, op: fn(u32, u32) -> u32) {
+    totals.total_seconds = op(totals.total_seconds, other.total_seconds);
+    totals.work_seconds = op(totals.work_seconds, other.work_seconds);
+    totals.break_seconds = op(totals.break_seconds, other.break_seconds);
+    totals.cycles_count = op(totals.cycles_count, other.cycles_count);
+    totals.check_in_done_count = op(totals.check_in_done_count, other.check_in_done_count);
+    totals.check_in_skip_count = op(totals.check_in_skip_count, other.check_in_skip_count);
+    totals.planned_seconds = op(totals.planned_seconds, other.planned_seconds);
+    totals.executed_seconds = op(totals.executed_seconds, other.executed_seconds);
+    totals.overtime_seconds = op(totals.overtime_seconds, other.overtime_seconds);
+    totals.skip_loss_seconds = op(totals.skip_loss_seconds, other.skip_loss_seconds);
+    totals.skipped_count = op(totals.skipped_count, other.skipped_count);
+}
+
+/// Bumped whenever `CachedDay` gains counts that older caches lack.
+const STATS_CACHE_VERSION: u32 = 1;
+
+/// Per-day, per-routine sums of the sessions in `sessions.json`, kept in
+/// `stats_cache.json` so range queries need not re-read and re-sum every
+/// session. Days are local days in the zone and rollover hour recorded here;
+/// the cache is rebuilt when either changes, when it was written in an older
+/// layout, or when the sessions file no longer matches `sessions_stamp`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsCache {
+    #[serde(default)]
+    pub version: u32,
+    pub sessions_stamp: u64,
+    pub zone: String,
+    pub day_rollover_hour: u8,
+    pub days: Vec<CachedDay>,
+}
+
+/// One routine's sessions on one local day. `first_started_at` and
+/// `last_started_at` bound the start times; they are not narrowed when a
+/// session is removed, so they stay safe to compare against.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedDay {
+    pub date: String,
+    pub routine_id: String,
+    pub first_started_at: String,
+    pub last_started_at: String,
+    pub sessions_count: u32,
+    pub muted_sessions_count: u32,
+    #[serde(default)]
+    pub stopped_sessions_count: u32,
+    #[serde(default)]
+    pub recovered_sessions_count: u32,
+    pub totals: SessionTotals,
+}
+
+impl StatsCache {
+    pub fn build<Tz: TimeZone>(
+        sessions: &[Session],
+        sessions_stamp: u64,
+        zone: &Tz,
+        zone_key: &str,
+        settings: &CalendarSettings,
+    ) -> Self {
+        let mut cache = Self {
+            version: STATS_CACHE_VERSION,
+            sessions_stamp,
+            zone: zone_key.to_string(),
+            day_rollover_hour: settings.day_rollover_hour,
+            days: Vec::new(),
+        };
+        for session in sessions {
+            cache.add_session(session, zone, settings);
+        }
+        cache
+    }
+
+    /// Whether the days were cut the way `zone_key` and `settings` cut them,
+    /// in the current layout.
+    pub fn is_for(&self, zone_key: &str, settings: &CalendarSettings) -> bool {
+        self.version == STATS_CACHE_VERSION
+            && self.zone == zone_key
+            && self.day_rollover_hour == settings.day_rollover_hour
+    }
+
+    pub fn add_session<Tz: TimeZone>(
+        &mut self,
+        session: &Session,
+        zone: &Tz,
+        settings: &CalendarSettings,
+    ) {
+        let Some(index) = self.day_index(session, zone, settings, true) else {
+            return;
+        };
+        let day = &mut self.days[index];
+        day.first_started_at = earlier(&day.first_started_at, &session.started_at);
+        day.last_started_at = later(&day.last_started_at, &session.started_at);
+        day.sessions_count = day.sessions_count.saturating_add(1);
+        if session.muted_during_session {
+            day.muted_sessions_count = day.muted_sessions_count.saturating_add(1);
+        }
+        let (stopped, recovered) = aborted_counts(session);
+        day.stopped_sessions_count = day.stopped_sessions_count.saturating_add(stopped);
+        day.recovered_sessions_count = day.recovered_sessions_count.saturating_add(recovered);
+        add_session_totals(&mut day.totals, &effective_totals(session));
+    }
+
+    pub fn remove_session<Tz: TimeZone>(
+        &mut self,
+        session: &Session,
+        zone: &Tz,
+        settings: &CalendarSettings,
+    ) {
+        let Some(index) = self.day_index(session, zone, settings, false) else {
+            return;
+        };
+        let day = &mut self.days[index];
+        day.sessions_count = day.sessions_count.saturating_sub(1);
+        if session.muted_during_session {
+            day.muted_sessions_count = day.muted_sessions_count.saturating_sub(1);
+        }
+        let (stopped, recovered) = aborted_counts(session);
+        day.stopped_sessions_count = day.stopped_sessions_count.saturating_sub(stopped);
+        day.recovered_sessions_count = day.recovered_sessions_count.saturating_sub(recovered);
+        subtract_session_totals(&mut day.totals, &effective_totals(session));
+        if day.sessions_count == 0 {
+            self.days.remove(index);
+        }
+    }
+
+    /// Stats for sessions started within `from..=to` plus `aggregates`, the
+    /// same numbers `calculate_session_stats` gives, or `None` when the range
+    /// cuts through a day so that the cached sums cannot tell which of its
+    /// sessions are inside.
+    pub fn stats_between<Tz: TimeZone>(
+        &self,
+        aggregates: &[DailyAggregate],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        zone: &Tz,
+        settings: &CalendarSettings,
+    ) -> Option<SessionStats> {
+        let first_day = day_containing(from, zone, settings)
+            .format("%Y-%m-%d")
+            .to_string();
+        let last_day = day_containing(to, zone, settings)
+            .format("%Y-%m-%d")
+            .to_string();
+        let mut rows = aggregates.to_vec();
+        for day in &self.days {
+            if day.date < first_day || day.date > last_day {
+                continue;
+            }
+            let first = parse_instant(&day.first_started_at).ok()?;
+            let last = parse_instant(&day.last_started_at).ok()?;
+            if first > to || last < from {
+                continue;
+            }
+            if first < from || last > to {
+                return None;
+            }
+            rows.push(DailyAggregate {
+                date: day.date.clone(),
+                routine_id: day.routine_id.clone(),
+                sessions_count: day.sessions_count,
+                muted_sessions_count: day.muted_sessions_count,
+                stopped_sessions_count: day.stopped_sessions_count,
+                recovered_sessions_count: day.recovered_sessions_count,
+                totals: day.totals.clone(),
+            });
+        }
+        Some(calculate_session_stats(&[], &rows))
+    }
+
+    /// Index of the row `session` belongs to, inserted in date order when
+    /// `create` is set. Excluded sessions and unreadable start times have none.
+    fn day_index<Tz: TimeZone>(
+        &mut self,
+        session: &Session,
+        zone: &Tz,
+        settings: &CalendarSettings,
+        create: bool,
+    ) -> Option<usize> {
+        if session.excluded_from_stats {
+            return None;
+        }
+        let started_at = parse_instant(&session.started_at).ok()?;
+        let date = day_containing(started_at, zone, settings)
+            .format("%Y-%m-%d")
+            .to_string();
+        let key = (date.as_str(), session.routine_id.as_str());
+        match self
+            .days
+            .binary_search_by(|day| (day.date.as_str(), day.routine_id.as_str()).cmp(&key))
+        {
+            Ok(index) => Some(index),
+            Err(index) if create => {
+                self.days.insert(
+                    index,
+                    CachedDay {
+                        date,
+                        routine_id: session.routine_id.clone(),
+                        first_started_at: session.started_at.clone(),
+                        last_started_at: session.started_at.clone(),
+                        sessions_count: 0,
+                        muted_sessions_count: 0,
+                        stopped_sessions_count: 0,
+                        recovered_sessions_count: 0,
+                        totals: SessionTotals::default(),
+                    },
+                );
+                Some(index)
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+fn earlier(current: &str, candidate: &str) -> String {
+    match (parse_instant(current), parse_instant(candidate)) {
+        (Ok(current_at), Ok(candidate_at)) if candidate_at < current_at => candidate.to_string(),
+        _ => current.to_string(),
+    }
+}
+
+fn later(current: &str, candidate: &str) -> String {
+    match (parse_instant(current), parse_instant(candidate)) {
+        (Ok(current_at), Ok(candidate_at)) if candidate_at > current_at => candidate.to_string(),
+        _ => current.to_string(),
+    }
+}
+
+fn add_totals(stats: &mut SessionStats, totals: &SessionTotals) {
+    stats.total_seconds = stats.total_seconds.saturating_add(totals.total_seconds);
+    stats.work_seconds = stats.work_seconds.saturating_add(totals.work_seconds);
+    stats.break_seconds = stats.break_seconds.saturating_add(totals.break_seconds);
+    stats.cycles_count = stats.cycles_count.saturating_add(totals.cycles_count);
+    stats.check_in_done_count = stats
+        .check_in_done_count
+        .saturating_add(totals.check_in_done_count);
+    stats.check_in_skip_count = stats
+        .check_in_skip_count
+        .saturating_add(totals.check_in_skip_count);
+    stats.planned_seconds = stats.planned_seconds.saturating_add(totals.planned_seconds);
+    stats.executed_seconds = stats
+        .executed_seconds
+        .saturating_add(totals.executed_seconds);
+    stats.overtime_seconds = stats
+        .overtime_seconds
+        .saturating_add(totals.overtime_seconds);
+    stats.skip_loss_seconds = stats
+        .skip_loss_seconds
+        .saturating_add(totals.skip_loss_seconds);
+    stats.skipped_count = stats.skipped_count.saturating_add(totals.skipped_count);
+    stats.executed_ratio = ratio(u64::from(stats.executed_seconds), stats.planned_seconds);
+    stats.average_skip_loss_seconds =
+        ratio(u64::from(stats.skip_loss_seconds), stats.skipped_count);
+}
+
+/// Per-step breakdown of the runs in `sessions`, one entry per routine and
+/// step id. Steps follow their routine's order; steps no longer in any routine
+/// come last. Aggregated history carries no step runs and is not included.
+pub fn calculate_step_stats(sessions: &[Session], routines: &[Routine]) -> Vec<StepStats> {
+    let mut runs: HashMap<(&str, &str), Vec<&StepRun>> = HashMap::new();
+    for session in sessions.iter().filter(|item| !item.excluded_from_stats) {
+        for run in &session.step_runs {
+            runs.entry((session.routine_id.as_str(), run.step_id.as_str()))
+                .or_default()
+                .push(run);
+        }
+    }
+
+    let mut keys: Vec<(&str, &str)> = runs.keys().copied().collect();
+    keys.sort_by_key(|&(routine_id, step_id)| {
+        let position = step_position(routines, routine_id, step_id);
+        (position.is_none(), position, routine_id, step_id)
+    });
+
+    let labels = recorded_labels(sessions);
+    keys.into_iter()
+        .map(|key| {
+            let mut stats = step_stats(key.0, key.1, &runs[&key]);
+            stats.label = run_label(&labels, routines, key.0, key.1);
+            stats
+        })
+        .collect()
+}
+
+/// Where a step sits among `routines`: the routine's index and the step's
+/// order, or `None` once the step is gone.
+pub fn step_position(
+    routines: &[Routine],
+    routine_id: &str,
+    step_id: &str,
+) -> Option<(usize, u32)> {
+    let routine_index = routines
+        .iter()
+        .position(|routine| routine.id == routine_id)?;
+    routines[routine_index]
+        .steps
+        .iter()
+        .find(|step| step.id == step_id)
+        .map(|step| (routine_index, step.order))
+}
+
+/// The step's label in its routine's current definition.
+pub fn step_label(routines: &[Routine], routine_id: &str, step_id: &str) -> Option<String> {
+    routines
+        .iter()
+        .find(|routine| routine.id == routine_id)
+        .and_then(|routine| routine.steps.iter().find(|step| step.id == step_id))
+        .map(|step| step.label.clone())
+}
+
+/// The label each routine and step last ran under, from the runs in
+/// `sessions` that recorded one. Later sessions win.
+pub fn recorded_labels(sessions: &[Session]) -> HashMap<(&str, &str), &str> {
+    let mut labels = HashMap::new();
+    for session in sessions {
+        for run in &session.step_runs {
+            if let Some(label) = run.label.as_deref() {
+                labels.insert((session.routine_id.as_str(), run.step_id.as_str()), label);
+            }
+        }
+    }
+    labels
+}
+
+/// The label from `labels` a step last ran under, or its current one for
+/// steps whose runs were recorded before labels were stored.
+pub fn run_label(
+    labels: &HashMap<(&str, &str), &str>,
+    routines: &[Routine],
+    routine_id: &str,
+    step_id: &str,
+) -> Option<String> {
+    labels
+        .get(&(routine_id, step_id))
+        .map(|label| label.to_string())
+        .or_else(|| step_label(routines, routine_id, step_id))
+}
+
+fn step_stats(routine_id: &str, step_id: &str, runs: &[&StepRun]) -> StepStats {
+    let mut stats = StepStats {
+        routine_id: routine_id.to_string(),
+        step_id: step_id.to_string(),
+        runs: runs.len() as u32,
+        ..StepStats::default()
+    };
+
+    let mut actual_seconds: u64 = 0;
+    let mut adherence = SessionTotals::default();
+    let (mut done, mut skipped, mut timed_out) = (0u32, 0u32, 0u32);
+    let mut response_times = Vec::new();
+    for run in runs {
+        match run.result {
+            StepRunResult::Completed => stats.completed_count += 1,
+            StepRunResult::Skipped => stats.skipped_count += 1,
+            StepRunResult::Aborted => stats.aborted_count += 1,
+        }
+        actual_seconds += u64::from(run.actual_duration_seconds);
+        add_adherence(&mut adherence, run);
+        let Some(check_in) = run.check_in_result.as_ref() else {
+            continue;
+        };
+        stats.check_in_count += 1;
+        match check_in.choice {
+            Some(CheckInChoice::Done) => done += 1,
+            Some(CheckInChoice::Skip) => skipped += 1,
+            None => {}
+        }
+        if check_in.timed_out {
+            timed_out += 1;
+        }
+        response_times.extend(check_in.response_time_ms);
+    }
+
+    stats.average_planned_seconds = ratio(u64::from(adherence.planned_seconds), stats.runs);
+    stats.average_actual_seconds = ratio(actual_seconds, stats.runs);
+    stats.executed_ratio = executed_ratio(&adherence);
+    stats.average_skip_loss_seconds = ratio(
+        u64::from(adherence.skip_loss_seconds),
+        adherence.skipped_count,
+    );
+    stats.overtime_seconds = adherence.overtime_seconds;
+    stats.check_in_done_rate = ratio(u64::from(done), stats.check_in_count);
+    stats.check_in_skip_rate = ratio(u64::from(skipped), stats.check_in_count);
+    stats.check_in_timeout_rate = ratio(u64::from(timed_out), stats.check_in_count);
+    stats.median_response_time_ms = median(&mut response_times);
+    stats
+}
+
+#[derive(Debug, Default)]
+struct BucketTotals {
+    sessions: u32,
+    cycles: u32,
+    work_seconds: f64,
+    break_seconds: f64,
+    step_runs: u32,
+    skipped: u32,
+}
+
+/// Series of totals over `periods`, which must be consecutive and in order as
+/// returned by `calendar::periods_between`. Sessions count in the period they
+/// started in and cycles in the period their last step ended in. Aggregated
+/// history counts at `aggregate_instant`.
+pub fn calculate_stats_series(
+    sessions: &[Session],
+    aggregates: &[DailyAggregate],
+    routines: &[Routine],
+    periods: &[Period],
+) -> Vec<StatsBucket> {
+    let ranges: Vec<(DateTime<Utc>, DateTime<Utc>)> = periods
+        .iter()
+        .filter_map(|period| {
+            let from = parse_instant(&period.from).ok()?;
+            let to = parse_instant(&period.to).ok()?;
+            Some((from, to + TimeDelta::milliseconds(1)))
+        })
+        .collect();
+    let mut buckets: Vec<BucketTotals> = ranges.iter().map(|_| BucketTotals::default()).collect();
+    let bucket_at = |instant: DateTime<Utc>| {
+        let index = ranges.partition_point(|(from, _)| *from <= instant);
+        index
+            .checked_sub(1)
+            .filter(|&index| instant < ranges[index].1)
+    };
+
+    let break_steps: HashSet<(&str, &str)> = routines
+        .iter()
+        .flat_map(|routine| {
+            routine
+                .steps
+                .iter()
+                .filter(|step| step.count_as_break)
+                .map(|step| (routine.id.as_str(), step.id.as_str()))
+        })
+        .collect();
+
+    for session in sessions.iter().filter(|item| !item.excluded_from_stats) {
+        let Ok(started_at) = parse_instant(&session.started_at) else {
+            continue;
+        };
+        if let Some(index) = bucket_at(started_at) {
+            buckets[index].sessions += 1;
+        }
+
+        if session.step_runs.is_empty() {
+            // Step runs pruned by retention; only the totals are left.
+            if let Some(index) = bucket_at(started_at) {
+                let bucket = &mut buckets[index];
+                bucket.work_seconds += f64::from(session.totals.work_seconds);
+                bucket.break_seconds += f64::from(session.totals.break_seconds);
+                bucket.cycles += session.totals.cycles_count;
+            }
+            continue;
+        }
+
+        let mut last_end = started_at;
+        for run in &session.step_runs {
+            let start = parse_instant(&run.started_at).unwrap_or(started_at);
+            let end = run
+                .ended_at
+                .as_deref()
+                .and_then(|value| parse_instant(value).ok())
+                .unwrap_or(start + TimeDelta::seconds(i64::from(run.actual_duration_seconds)));
+            last_end = last_end.max(end);
+            if let Some(index) = bucket_at(start) {
+                buckets[index].step_runs += 1;
+                if run.result == StepRunResult::Skipped {
+                    buckets[index].skipped += 1;
+                }
+            }
+            let is_break =
+                break_steps.contains(&(session.routine_id.as_str(), run.step_id.as_str()));
+            let seconds = f64::from(run.actual_duration_seconds);
+            for (index, share) in split_across(&ranges, start, end) {
+                let value = seconds * share;
+                if is_break {
+                    buckets[index].break_seconds += value;
+                } else {
+                    buckets[index].work_seconds += value;
+                }
+            }
+        }
+        let cycles_at = (last_end - TimeDelta::milliseconds(1)).max(started_at);
+        if let Some(index) = bucket_at(cycles_at) {
+            buckets[index].cycles += session.totals.cycles_count;
+        }
+    }
+
+    for aggregate in aggregates {
+        if let Some(index) = aggregate_instant(aggregate).and_then(bucket_at) {
+            let bucket = &mut buckets[index];
+            bucket.sessions += aggregate.sessions_count;
+            bucket.cycles += aggregate.totals.cycles_count;
+            bucket.work_seconds += f64::from(aggregate.totals.work_seconds);
+            bucket.break_seconds += f64::from(aggregate.totals.break_seconds);
+        }
+    }
+
+    periods
+        .iter()
+        .zip(buckets)
+        .map(|(period, bucket)| StatsBucket {
+            start_date: period.start_date.clone(),
+            from: period.from.clone(),
+            to: period.to.clone(),
+            sessions_count: bucket.sessions,
+            cycles_count: bucket.cycles,
+            work_seconds: bucket.work_seconds.round() as u32,
+            break_seconds: bucket.break_seconds.round() as u32,
+            step_runs_count: bucket.step_runs,
+            skip_rate: ratio(u64::from(bucket.skipped), bucket.step_runs),
+        })
+        .collect()
+}
+
+/// The one instant a daily aggregate counts at: noon UTC of its date.
+pub fn aggregate_instant(aggregate: &DailyAggregate) -> Option<DateTime<Utc>> {
+    // Aggregates are bucketed by UTC date, and noon UTC falls on that same
+    // local date in every zone from UTC-12 to UTC+11, so an aggregate lands in
+    // exactly one of a run of consecutive local periods.
+    let date = NaiveDate::parse_from_str(&aggregate.date, "%Y-%m-%d").ok()?;
+    Some(date.and_time(NaiveTime::from_hms_opt(12, 0, 0)?).and_utc())
+}
+
+/// Fractions of `start..end` falling into each range it overlaps. An empty
+/// interval goes wholly to the range containing `start`.
+fn split_across(
+    ranges: &[(DateTime<Utc>, DateTime<Utc>)],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(usize, f64)> {
+    let first = ranges.partition_point(|(_, range_end)| *range_end <= start);
+    if end <= start {
+        return ranges
+            .get(first)
+            .filter(|(from, _)| *from <= start)
+            .map(|_| vec![(first, 1.0)])
+            .unwrap_or_default();
+    }
+    let length = (end - start).num_milliseconds() as f64;
+    ranges[first..]
+        .iter()
+        .enumerate()
+        .take_while(|(_, (from, _))| *from < end)
+        .filter_map(|(offset, (from, range_end))| {
+            let overlap = (end.min(*range_end) - start.max(*from)).num_milliseconds();
+            (overlap > 0).then(|| (first + offset, overlap as f64 / length))
+        })
+        .collect()
+}
+
+fn ratio(value: u64, count: u32) -> f32 {
+    if count == 0 {
+        0.0
+    } else {
+        value as f32 / count as f32
+    }
+}
+
+fn median(values: &mut [u64]) -> Option<u64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let middle = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2
+    } else {
+        values[middle]
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::calculate_session_stats;
-    use crate::models::{Session, SessionTotals};
+    use super::{
+        calculate_grouped_stats, calculate_session_stats, calculate_stats_series,
+        calculate_step_stats, session_adherence, session_outcome, StatsCache,
+    };
+    use crate::calendar::{parse_instant, periods_between, PeriodKind};
+    use crate::models::{
+        CalendarSettings, CheckInChoice, CheckInConfig, CheckInMode, CheckInResult, DailyAggregate,
+        DayOfWeek, RepeatMode, Routine, Session, SessionOutcome, SessionTotals, SoundAssets,
+        SoundOverride, SoundScheme, SoundSetting, StatsFilter, StatsGroupBy, Step, StepRun,
+        StepRunResult, StepWarnings, TimeWindow, WeekStart,
+    };
+    use chrono::Utc;
 
     fn sample_totals(
         total_seconds: u32,
@@ -56,6 +853,7 @@ mod tests {
             cycles_count,
             check_in_done_count,
             check_in_skip_count,
+            ..SessionTotals::default()
         }
     }
 
@@ -68,12 +866,18 @@ mod tests {
             step_runs: Vec::new(),
             totals,
             muted_during_session: muted,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
         }
     }
 
     #[test]
     fn calculates_empty_stats() {
-        let stats = calculate_session_stats(&[]);
+        let stats = calculate_session_stats(&[], &[]);
 
         assert_eq!(stats.sessions_count, 0);
         assert_eq!(stats.cycles_count, 0);
@@ -92,7 +896,7 @@ mod tests {
             sample_session("s2", sample_totals(300, 200, 100, 1, 1, 2), true),
         ];
 
-        let stats = calculate_session_stats(&sessions);
+        let stats = calculate_session_stats(&sessions, &[]);
 
         assert_eq!(stats.sessions_count, 2);
         assert_eq!(stats.cycles_count, 3);
@@ -103,4 +907,469 @@ mod tests {
         assert_eq!(stats.check_in_skip_count, 3);
         assert!((stats.mute_rate - 0.5).abs() < f32::EPSILON);
     }
+
+    #[test]
+    fn skips_excluded_sessions() {
+        let mut excluded = sample_session("s2", sample_totals(300, 200, 100, 1, 1, 2), true);
+        excluded.excluded_from_stats = true;
+        let sessions = vec![
+            sample_session("s1", sample_totals(600, 500, 100, 2, 3, 1), false),
+            excluded,
+        ];
+
+        let stats = calculate_session_stats(&sessions, &[]);
+
+        assert_eq!(stats.sessions_count, 1);
+        assert_eq!(stats.total_seconds, 600);
+        assert!((stats.mute_rate - 0.0).abs() < f32::EPSILON);
+    }
+
+    fn sample_routine() -> Routine {
+        let step = |id: &str, order: u32, label: &str| Step {
+            id: id.to_string(),
+            order,
+            label: label.to_string(),
+            duration_seconds: 60,
+            instruction: String::new(),
+            sound_override: SoundOverride::Inherit,
+            count_as_break: false,
+            sounds: SoundAssets::default(),
+            warnings: StepWarnings::default(),
+            check_in: CheckInConfig {
+                mode: CheckInMode::Prompt,
+                prompt_title: None,
+                prompt_body: None,
+                prompt_timeout_seconds: None,
+            },
+        };
+        Routine {
+            id: "routine-1".to_string(),
+            name: "Sample".to_string(),
+            steps: vec![step("step-1", 0, "Focus"), step("step-2", 1, "Review")],
+            repeat_mode: RepeatMode::Infinite,
+            auto_advance: true,
+            notifications: true,
+            sound_default: SoundSetting::On,
+            sound_scheme: SoundScheme::Default,
+            sounds: SoundAssets::default(),
+        }
+    }
+
+    fn step_run(step_id: &str, result: StepRunResult, actual: u32) -> StepRun {
+        StepRun {
+            step_id: step_id.to_string(),
+            label: None,
+            planned_duration_seconds: 60,
+            actual_duration_seconds: actual,
+            started_at: "2025-01-01T00:00:00Z".to_string(),
+            ended_at: None,
+            result,
+            check_in_result: None,
+            sound_played: false,
+        }
+    }
+
+    #[test]
+    fn counts_session_outcomes() {
+        let mut stopped = sample_session("s1", SessionTotals::default(), false);
+        stopped.outcome = Some(SessionOutcome::Stopped);
+        let mut legacy_stopped = sample_session("s2", SessionTotals::default(), false);
+        legacy_stopped.step_runs = vec![
+            step_run("step-1", StepRunResult::Completed, 60),
+            step_run("step-2", StepRunResult::Aborted, 20),
+        ];
+        let mut legacy_recovered = sample_session("s3", SessionTotals::default(), false);
+        let mut run = step_run("step-2", StepRunResult::Aborted, 20);
+        run.started_at = "2025-01-01T00:01:00Z".to_string();
+        legacy_recovered.step_runs = vec![run];
+        let mut completed = sample_session("s4", SessionTotals::default(), false);
+        completed.step_runs = vec![step_run("step-1", StepRunResult::Completed, 60)];
+        let aggregates = vec![DailyAggregate {
+            date: "2024-12-31".to_string(),
+            routine_id: "routine-1".to_string(),
+            sessions_count: 4,
+            muted_sessions_count: 0,
+            stopped_sessions_count: 1,
+            recovered_sessions_count: 1,
+            totals: SessionTotals::default(),
+        }];
+
+        assert_eq!(session_outcome(&legacy_stopped), SessionOutcome::Stopped);
+        assert_eq!(
+            session_outcome(&legacy_recovered),
+            SessionOutcome::Recovered
+        );
+        assert_eq!(session_outcome(&completed), SessionOutcome::Completed);
+
+        let stats = calculate_session_stats(
+            &[stopped, legacy_stopped, legacy_recovered, completed],
+            &aggregates,
+        );
+
+        assert_eq!(stats.sessions_count, 8);
+        assert_eq!(stats.completed_sessions_count, 3);
+        assert_eq!(stats.stopped_sessions_count, 3);
+        assert_eq!(stats.recovered_sessions_count, 2);
+        assert!((stats.abort_rate - 0.625).abs() < f32::EPSILON);
+    }
+
+    fn check_in(choice: Option<CheckInChoice>, response_time_ms: Option<u64>) -> CheckInResult {
+        CheckInResult {
+            mode: CheckInMode::Prompt,
+            responded_at: None,
+            choice,
+            response_time_ms,
+            timed_out: choice.is_none(),
+        }
+    }
+
+    #[test]
+    fn breaks_down_runs_per_step() {
+        let routine = sample_routine();
+        let first = routine.steps[0].id.clone();
+        let second = routine.steps[1].id.clone();
+
+        let mut done = step_run(&second, StepRunResult::Completed, 60);
+        done.check_in_result = Some(check_in(Some(CheckInChoice::Done), Some(1_000)));
+        let mut skipped = step_run(&second, StepRunResult::Skipped, 20);
+        skipped.check_in_result = Some(check_in(Some(CheckInChoice::Skip), Some(4_000)));
+        let mut ignored = step_run(&second, StepRunResult::Aborted, 10);
+        ignored.check_in_result = Some(check_in(None, None));
+
+        let mut session = sample_session("s1", SessionTotals::default(), false);
+        session.routine_id = routine.id.clone();
+        session.step_runs = vec![
+            step_run("step-removed", StepRunResult::Completed, 60),
+            done,
+            step_run(&first, StepRunResult::Completed, 60),
+            skipped,
+            ignored,
+        ];
+        let mut excluded = session.clone();
+        excluded.excluded_from_stats = true;
+
+        let stats = calculate_step_stats(&[session, excluded], &[routine]);
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].step_id, first);
+        assert_eq!(stats[0].label.as_deref(), Some("Focus"));
+        assert_eq!(stats[2].step_id, "step-removed");
+        assert_eq!(stats[2].label, None);
+
+        let second_stats = &stats[1];
+        assert_eq!(second_stats.runs, 3);
+        assert_eq!(
+            (
+                second_stats.completed_count,
+                second_stats.skipped_count,
+                second_stats.aborted_count
+            ),
+            (1, 1, 1)
+        );
+        assert!((second_stats.average_planned_seconds - 60.0).abs() < f32::EPSILON);
+        assert!((second_stats.average_actual_seconds - 30.0).abs() < f32::EPSILON);
+        assert!((second_stats.executed_ratio - 0.5).abs() < f32::EPSILON);
+        assert!((second_stats.average_skip_loss_seconds - 40.0).abs() < f32::EPSILON);
+        assert_eq!(second_stats.overtime_seconds, 0);
+        assert_eq!(second_stats.check_in_count, 3);
+        assert!((second_stats.check_in_timeout_rate - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(second_stats.median_response_time_ms, Some(2_500));
+    }
+
+    #[test]
+    fn labels_steps_as_they_were_when_they_ran() {
+        let routine = sample_routine();
+        let mut renamed = step_run(&routine.steps[0].id, StepRunResult::Completed, 60);
+        renamed.label = Some("Deep work".to_string());
+        let mut removed = step_run("step-removed", StepRunResult::Completed, 60);
+        removed.label = Some("Stretch".to_string());
+        let mut session = sample_session("s1", SessionTotals::default(), false);
+        session.routine_id = routine.id.clone();
+        session.step_runs = vec![
+            renamed,
+            removed,
+            step_run(&routine.steps[1].id, StepRunResult::Completed, 60),
+        ];
+
+        let stats = calculate_step_stats(&[session], &[routine]);
+
+        let labels: Vec<Option<&str>> = stats.iter().map(|item| item.label.as_deref()).collect();
+        assert_eq!(
+            labels,
+            vec![Some("Deep work"), Some("Review"), Some("Stretch")]
+        );
+    }
+
+    #[test]
+    fn measures_adherence_to_planned_durations() {
+        let mut on_plan = sample_session("s1", SessionTotals::default(), false);
+        on_plan.step_runs = vec![
+            step_run("step-1", StepRunResult::Completed, 90),
+            step_run("step-2", StepRunResult::Completed, 60),
+        ];
+        let mut cut_short = sample_session("s2", SessionTotals::default(), false);
+        cut_short.step_runs = vec![
+            step_run("step-1", StepRunResult::Skipped, 15),
+            step_run("step-2", StepRunResult::Skipped, 45),
+        ];
+        assert_eq!(session_adherence(&on_plan), Some(1.0));
+        assert_eq!(session_adherence(&cut_short), Some(0.5));
+        assert_eq!(
+            session_adherence(&sample_session("s3", SessionTotals::default(), false)),
+            None
+        );
+
+        let stats = calculate_session_stats(&[on_plan, cut_short], &[]);
+
+        assert_eq!(stats.planned_seconds, 240);
+        assert!((stats.executed_ratio - 0.75).abs() < f32::EPSILON);
+        assert!((stats.average_skip_loss_seconds - 30.0).abs() < f32::EPSILON);
+        assert_eq!(stats.overtime_seconds, 30);
+    }
+
+    fn timed_run(step_id: &str, result: StepRunResult, start: &str, end: &str) -> StepRun {
+        let seconds = (parse_instant(end).expect("end") - parse_instant(start).expect("start"))
+            .num_seconds() as u32;
+        let mut run = step_run(step_id, result, seconds);
+        run.started_at = start.to_string();
+        run.ended_at = Some(end.to_string());
+        run
+    }
+
+    #[test]
+    fn splits_runs_across_buckets() {
+        let mut routine = sample_routine();
+        routine.steps[1].count_as_break = true;
+
+        let mut session = sample_session("s1", SessionTotals::default(), false);
+        session.started_at = "2025-01-01T09:50:00Z".to_string();
+        session.totals.cycles_count = 1;
+        session.step_runs = vec![
+            timed_run(
+                "step-1",
+                StepRunResult::Completed,
+                "2025-01-01T09:50:00Z",
+                "2025-01-01T10:10:00Z",
+            ),
+            timed_run(
+                "step-2",
+                StepRunResult::Skipped,
+                "2025-01-01T10:10:00Z",
+                "2025-01-01T10:15:00Z",
+            ),
+        ];
+        let mut excluded = session.clone();
+        excluded.excluded_from_stats = true;
+
+        let periods = periods_between(
+            PeriodKind::Hour,
+            parse_instant("2025-01-01T09:00:00Z").expect("from"),
+            parse_instant("2025-01-01T11:59:59Z").expect("to"),
+            &Utc,
+            &CalendarSettings::default(),
+        )
+        .expect("periods");
+        let series = calculate_stats_series(&[session, excluded], &[], &[routine], &periods);
+
+        assert_eq!(series.len(), 3);
+        assert_eq!(series[0].sessions_count, 1);
+        assert_eq!(series[0].work_seconds, 600);
+        assert_eq!(series[0].break_seconds, 0);
+        assert_eq!(series[0].cycles_count, 0);
+        assert_eq!(series[1].sessions_count, 0);
+        assert_eq!(series[1].work_seconds, 600);
+        assert_eq!(series[1].break_seconds, 300);
+        assert_eq!(series[1].cycles_count, 1);
+        assert_eq!(series[1].step_runs_count, 1);
+        assert!((series[1].skip_rate - 1.0).abs() < f32::EPSILON);
+        assert_eq!(series[2].start_date, "2025-01-01T11:00");
+        assert_eq!(series[2].work_seconds + series[2].step_runs_count, 0);
+    }
+
+    fn tagged_session(
+        id: &str,
+        routine_id: &str,
+        started_at: &str,
+        tags: &[&str],
+        work: u32,
+    ) -> Session {
+        let mut session = sample_session(id, sample_totals(work, work, 0, 1, 0, 0), false);
+        session.routine_id = routine_id.to_string();
+        session.started_at = started_at.to_string();
+        session.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        session
+    }
+
+    #[test]
+    fn groups_filtered_stats() {
+        let sessions = vec![
+            tagged_session("s1", "routine-1", "2025-01-06T08:00:00Z", &["deep"], 100),
+            tagged_session(
+                "s2",
+                "routine-2",
+                "2025-01-07T15:00:00Z",
+                &["deep", "review"],
+                200,
+            ),
+            tagged_session("s3", "routine-1", "2025-01-07T09:00:00Z", &[], 300),
+        ];
+        let aggregates = vec![DailyAggregate {
+            date: "2025-01-05".to_string(),
+            routine_id: "routine-1".to_string(),
+            sessions_count: 1,
+            muted_sessions_count: 0,
+            stopped_sessions_count: 0,
+            recovered_sessions_count: 0,
+            totals: sample_totals(50, 50, 0, 1, 0, 0),
+        }];
+        let routines = vec![sample_routine()];
+        let settings = CalendarSettings {
+            week_start: WeekStart::Sunday,
+            ..CalendarSettings::default()
+        };
+        let grouped = |filter: &StatsFilter, group_by: Option<StatsGroupBy>| {
+            calculate_grouped_stats(
+                &sessions,
+                &aggregates,
+                &routines,
+                filter,
+                group_by,
+                &Utc,
+                &settings,
+            )
+            .expect("grouped stats")
+            .into_iter()
+            .map(|group| (group.key, group.label, group.stats.work_seconds))
+            .collect::<Vec<_>>()
+        };
+        let label = |name: &str| Some(name.to_string());
+
+        let everything = StatsFilter::default();
+        assert_eq!(
+            grouped(&everything, Some(StatsGroupBy::Routine)),
+            vec![
+                ("routine-1".to_string(), label("Sample"), 450),
+                ("routine-2".to_string(), None, 200),
+            ]
+        );
+        assert_eq!(
+            grouped(&everything, Some(StatsGroupBy::Tag)),
+            vec![
+                ("deep".to_string(), None, 300),
+                ("review".to_string(), None, 200)
+            ]
+        );
+        assert_eq!(
+            grouped(&everything, Some(StatsGroupBy::Weekday)),
+            vec![
+                ("sunday".to_string(), None, 50),
+                ("monday".to_string(), None, 100),
+                ("tuesday".to_string(), None, 500),
+            ]
+        );
+
+        let mornings = StatsFilter {
+            time_of_day: Some(TimeWindow {
+                start: "07:00".to_string(),
+                end: "10:00".to_string(),
+            }),
+            ..StatsFilter::default()
+        };
+        assert_eq!(
+            grouped(&mornings, None),
+            vec![("all".to_string(), None, 400)]
+        );
+        let overnight = StatsFilter {
+            time_of_day: Some(TimeWindow {
+                start: "22:00".to_string(),
+                end: "09:00".to_string(),
+            }),
+            weekdays: vec![DayOfWeek::Monday],
+            ..StatsFilter::default()
+        };
+        assert_eq!(
+            grouped(&overnight, None),
+            vec![("all".to_string(), None, 100)]
+        );
+        let routine_two = StatsFilter {
+            routine_ids: vec!["routine-2".to_string()],
+            tags: vec!["review".to_string()],
+            ..StatsFilter::default()
+        };
+        assert_eq!(
+            grouped(&routine_two, Some(StatsGroupBy::Routine)),
+            vec![("routine-2".to_string(), None, 200)]
+        );
+
+        let invalid = StatsFilter {
+            time_of_day: Some(TimeWindow {
+                start: "9am".to_string(),
+                end: "10:00".to_string(),
+            }),
+            ..StatsFilter::default()
+        };
+        assert!(calculate_grouped_stats(
+            &sessions,
+            &aggregates,
+            &routines,
+            &invalid,
+            None,
+            &Utc,
+            &settings
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn cache_answers_whole_days_like_summing_sessions() {
+        let mut sessions = vec![
+            tagged_session("s1", "routine-1", "2025-01-06T08:00:00Z", &[], 100),
+            tagged_session("s2", "routine-1", "2025-01-06T15:00:00Z", &[], 200),
+            tagged_session("s3", "routine-2", "2025-01-07T09:00:00Z", &[], 300),
+        ];
+        sessions[1].muted_during_session = true;
+        sessions[2].outcome = Some(SessionOutcome::Stopped);
+        let settings = CalendarSettings::default();
+        let mut cache = StatsCache::build(&sessions, 1, &Utc, "UTC", &settings);
+        assert!(cache.is_for("UTC", &settings));
+        assert!(!cache.is_for("Asia/Tokyo", &settings));
+        let outdated = StatsCache {
+            version: 0,
+            ..cache.clone()
+        };
+        assert!(!outdated.is_for("UTC", &settings));
+
+        let mut edited = sessions[2].clone();
+        edited.totals.work_seconds = 350;
+        cache.remove_session(&sessions[2], &Utc, &settings);
+        cache.add_session(&edited, &Utc, &settings);
+        sessions[2] = edited;
+        let mut excluded = tagged_session("s4", "routine-2", "2025-01-07T10:00:00Z", &[], 900);
+        excluded.excluded_from_stats = true;
+        cache.add_session(&excluded, &Utc, &settings);
+        assert_eq!(cache.days.len(), 2);
+
+        let stats_between = |from: &str, to: &str| {
+            cache.stats_between(
+                &[],
+                parse_instant(from).expect("from"),
+                parse_instant(to).expect("to"),
+                &Utc,
+                &settings,
+            )
+        };
+        let whole_days =
+            stats_between("2025-01-06T00:00:00Z", "2025-01-07T12:00:00Z").expect("whole days");
+        let summed = calculate_session_stats(&sessions, &[]);
+        assert_eq!(whole_days.sessions_count, summed.sessions_count);
+        assert_eq!(whole_days.work_seconds, 650);
+        assert!((whole_days.mute_rate - summed.mute_rate).abs() < f32::EPSILON);
+        assert_eq!(whole_days.stopped_sessions_count, 1);
+        assert_eq!(whole_days.completed_sessions_count, 2);
+
+        let second_day =
+            stats_between("2025-01-06T20:00:00Z", "2025-01-07T23:59:59Z").expect("second day");
+        assert_eq!(second_day.work_seconds, 350);
+        assert!(stats_between("2025-01-06T12:00:00Z", "2025-01-07T23:59:59Z").is_none());
+    }
 }
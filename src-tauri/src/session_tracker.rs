@@ -1,9 +1,9 @@
+use crate::calendar::current_zone;
 use crate::models::{
-    CheckInChoice, CheckInMode, CheckInResult, Routine, Session, SessionTotals, Step, StepRun,
-    StepRunResult,
+    CheckInChoice, CheckInMode, CheckInResult, Routine, Session, SessionOutcome, SessionTotals,
+    SessionZone, Step, StepRun, StepRunResult,
 };
 use chrono::Utc;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Default)]
 pub struct SessionTracker {
@@ -20,6 +20,7 @@ struct ActiveSession {
     current_step: Option<CurrentStep>,
     step_runs: Vec<StepRun>,
     muted_during_session: bool,
+    zone: SessionZone,
 }
 
 #[derive(Debug, Clone)]
@@ -34,8 +35,7 @@ impl SessionTracker {
         Self::default()
     }
 
-    pub fn start_session(&mut self, routine: &Routine, step: &Step, muted: bool) -> String {
-        let id = generate_session_id();
+    pub fn start_session(&mut self, id: String, routine: &Routine, step: &Step, muted: bool) {
         let started_at = now_rfc3339();
         let last_step_id = routine
             .steps
@@ -49,7 +49,7 @@ impl SessionTracker {
         });
 
         self.active = Some(ActiveSession {
-            id: id.clone(),
+            id,
             routine_id: routine.id.clone(),
             steps: routine.steps.clone(),
             last_step_id,
@@ -57,9 +57,8 @@ impl SessionTracker {
             current_step,
             step_runs: Vec::new(),
             muted_during_session: muted,
+            zone: current_zone(),
         });
-
-        id
     }
 
     pub fn start_step(&mut self, step: &Step, sound_played: bool) {
@@ -94,10 +93,11 @@ impl SessionTracker {
         let current_step = active.current_step.take().expect("current step checked");
         let step_meta = active.step_by_id(&current_step.step_id);
         let planned_duration_seconds = step_meta.map(|step| step.duration_seconds).unwrap_or(0);
-        let check_in_result = initial_check_in_result(result.clone(), step_meta);
+        let check_in_result = initial_check_in_result(result, step_meta);
 
         let step_run = StepRun {
             step_id: current_step.step_id,
+            label: step_meta.map(|step| step.label.clone()),
             planned_duration_seconds,
             actual_duration_seconds,
             started_at: current_step.started_at,
@@ -182,7 +182,7 @@ impl SessionTracker {
         }
     }
 
-    pub fn finish_session(&mut self, ended_at: String) -> Option<Session> {
+    pub fn finish_session(&mut self, ended_at: String, outcome: SessionOutcome) -> Option<Session> {
         let active = self.active.take()?;
         let totals = build_totals(&active);
         Some(Session {
@@ -193,6 +193,12 @@ impl SessionTracker {
             step_runs: active.step_runs,
             totals,
             muted_during_session: active.muted_during_session,
+            zone: Some(active.zone.clone()),
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: Some(outcome),
         })
     }
 
@@ -207,6 +213,12 @@ impl SessionTracker {
             step_runs: active.step_runs.clone(),
             totals,
             muted_during_session: active.muted_during_session,
+            zone: Some(active.zone.clone()),
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
         })
     }
 }
@@ -244,21 +256,41 @@ fn initial_check_in_result(
 }
 
 fn build_totals(active: &ActiveSession) -> SessionTotals {
-    let mut totals = SessionTotals {
-        total_seconds: 0,
-        work_seconds: 0,
-        break_seconds: 0,
-        cycles_count: 0,
-        check_in_done_count: 0,
-        check_in_skip_count: 0,
-    };
+    calculate_totals(&active.step_runs, &active.steps, &active.last_step_id)
+}
+
+/// Adds `run`'s planned-versus-actual time to the adherence fields of `totals`.
+pub fn add_adherence(totals: &mut SessionTotals, run: &StepRun) {
+    let planned = run.planned_duration_seconds;
+    let actual = run.actual_duration_seconds;
+    totals.planned_seconds = totals.planned_seconds.saturating_add(planned);
+    totals.executed_seconds = totals.executed_seconds.saturating_add(actual.min(planned));
+    totals.overtime_seconds = totals
+        .overtime_seconds
+        .saturating_add(actual.saturating_sub(planned));
+    if matches!(run.result, StepRunResult::Skipped) {
+        totals.skip_loss_seconds = totals
+            .skip_loss_seconds
+            .saturating_add(planned.saturating_sub(actual));
+        totals.skipped_count = totals.skipped_count.saturating_add(1);
+    }
+}
+
+pub fn calculate_totals(
+    step_runs: &[StepRun],
+    steps: &[Step],
+    last_step_id: &str,
+) -> SessionTotals {
+    let mut totals = SessionTotals::default();
 
-    for run in &active.step_runs {
+    for run in step_runs {
+        add_adherence(&mut totals, run);
         totals.total_seconds = totals
             .total_seconds
             .saturating_add(run.actual_duration_seconds);
-        let count_as_break = active
-            .step_by_id(&run.step_id)
+        let count_as_break = steps
+            .iter()
+            .find(|step| step.id == run.step_id)
             .map(|step| step.count_as_break)
             .unwrap_or(false);
         if count_as_break {
@@ -271,7 +303,7 @@ fn build_totals(active: &ActiveSession) -> SessionTotals {
                 .saturating_add(run.actual_duration_seconds);
         }
 
-        if run.step_id == active.last_step_id && !matches!(run.result, StepRunResult::Aborted) {
+        if run.step_id == last_step_id && !matches!(run.result, StepRunResult::Aborted) {
             totals.cycles_count = totals.cycles_count.saturating_add(1);
         }
 
@@ -299,20 +331,12 @@ fn now_rfc3339() -> String {
     Utc::now().to_rfc3339()
 }
 
-fn generate_session_id() -> String {
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos();
-    format!("session-{nanos}-{}", std::process::id())
-}
-
 #[cfg(test)]
 mod tests {
     use super::SessionTracker;
     use crate::models::{
-        CheckInChoice, CheckInConfig, CheckInMode, RepeatMode, Routine, SoundOverride, SoundScheme,
-        SoundSetting, Step, StepRunResult,
+        CheckInChoice, CheckInConfig, CheckInMode, RepeatMode, Routine, SessionOutcome,
+        SoundAssets, SoundOverride, SoundScheme, SoundSetting, Step, StepRunResult, StepWarnings,
     };
 
     fn build_step(id: &str, duration: u32, count_as_break: bool, mode: CheckInMode) -> Step {
@@ -324,6 +348,8 @@ mod tests {
             instruction: "".to_string(),
             sound_override: SoundOverride::Inherit,
             count_as_break,
+            sounds: SoundAssets::default(),
+            warnings: StepWarnings::default(),
             check_in: CheckInConfig {
                 mode,
                 prompt_title: None,
@@ -343,6 +369,7 @@ mod tests {
             notifications: true,
             sound_default: SoundSetting::On,
             sound_scheme: SoundScheme::Default,
+            sounds: SoundAssets::default(),
         }
     }
 
@@ -353,7 +380,7 @@ mod tests {
         let routine = build_routine(vec![step1.clone(), step2.clone()]);
         let mut tracker = SessionTracker::new();
 
-        tracker.start_session(&routine, &step1, false);
+        tracker.start_session("session-1".to_string(), &routine, &step1, false);
         tracker.finalize_current_step(
             "step-1",
             StepRunResult::Completed,
@@ -376,10 +403,14 @@ mod tests {
         tracker.mark_muted();
 
         let session = tracker
-            .finish_session("2025-01-01T00:01:30Z".to_string())
+            .finish_session(
+                "2025-01-01T00:01:30Z".to_string(),
+                SessionOutcome::Completed,
+            )
             .expect("session");
 
         assert!(session.muted_during_session);
+        assert_eq!(session.outcome, Some(SessionOutcome::Completed));
         assert_eq!(session.step_runs.len(), 2);
         assert!(!session.step_runs[0].sound_played);
         assert!(session.step_runs[1].sound_played);
@@ -397,7 +428,7 @@ mod tests {
         let routine = build_routine(vec![step.clone()]);
         let mut tracker = SessionTracker::new();
 
-        tracker.start_session(&routine, &step, false);
+        tracker.start_session("session-1".to_string(), &routine, &step, false);
         tracker.finalize_current_step(
             "step-1",
             StepRunResult::Completed,
@@ -407,7 +438,10 @@ mod tests {
         tracker.record_check_in_timeout("step-1");
 
         let session = tracker
-            .finish_session("2025-01-01T00:00:10Z".to_string())
+            .finish_session(
+                "2025-01-01T00:00:10Z".to_string(),
+                SessionOutcome::Completed,
+            )
             .expect("session");
 
         assert_eq!(session.totals.check_in_skip_count, 1);
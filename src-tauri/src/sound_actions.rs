@@ -1,7 +1,11 @@
 use crate::app_error::AppError;
-use crate::audio_manager::{AudioManager, SoundEvent, SoundPlaybackReason, SoundPlaybackRecord};
+use crate::audio_manager::{
+    custom_sound_names, AudioManager, SoundChoice, SoundEvent, SoundPlaybackReason,
+    SoundPlaybackRecord,
+};
+use crate::data_manager::DataManager;
 use crate::events::emit_app_error;
-use crate::models::{SoundOverride, SoundScheme, SoundSetting, Step};
+use crate::models::{SoundAssets, SoundOverride, SoundScheme, SoundSetting, Step};
 use crate::timer_engine::TimerEngine;
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager};
@@ -13,6 +17,17 @@ pub struct SoundContext {
     pub routine_default: SoundSetting,
     pub step_override: SoundOverride,
     pub sound_scheme: SoundScheme,
+    pub routine_sounds: SoundAssets,
+    pub step_sounds: Option<SoundAssets>,
+    /// The step that just ended, for transitions.
+    pub previous_step_sounds: Option<SoundAssets>,
+}
+
+impl SoundContext {
+    pub fn after_step(mut self, previous: Option<&Step>) -> Self {
+        self.previous_step_sounds = previous.map(|step| step.sounds.clone());
+        self
+    }
 }
 
 pub fn build_sound_context(engine: &TimerEngine, step: Option<&Step>) -> Option<SoundContext> {
@@ -25,6 +40,9 @@ pub fn build_sound_context(engine: &TimerEngine, step: Option<&Step>) -> Option<
             .map(|item| item.sound_override.clone())
             .unwrap_or(SoundOverride::Inherit),
         sound_scheme: routine.sound_scheme.clone(),
+        routine_sounds: routine.sounds.clone(),
+        step_sounds: step.map(|item| item.sounds.clone()),
+        previous_step_sounds: None,
     })
 }
 
@@ -34,6 +52,18 @@ pub fn play_sound_for_event(
     event: SoundEvent,
 ) -> Option<SoundPlaybackRecord> {
     let context = context?;
+    let mut choice = SoundChoice::scheme(context.sound_scheme.clone());
+    if let Some(data_manager) = app.try_state::<DataManager>() {
+        choice = choice.with_custom(
+            data_manager.base_dir().to_path_buf(),
+            custom_sound_names(
+                event,
+                &context.routine_sounds,
+                context.step_sounds.as_ref(),
+                context.previous_step_sounds.as_ref(),
+            ),
+        );
+    }
     let audio_state = app.try_state::<Mutex<AudioManager>>()?;
 
     let mut manager = match audio_state.lock() {
@@ -52,7 +82,7 @@ pub fn play_sound_for_event(
         context.step_id.as_deref(),
         context.routine_default,
         context.step_override,
-        context.sound_scheme,
+        &choice,
         event,
     );
 
@@ -0,0 +1,389 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Folder inside the data directory holding imported sound files.
+pub const SOUNDS_DIR: &str = "sounds";
+
+/// Packets read while looking for one that decodes. MP3 streams may open
+/// with a few frames that need earlier data before any audio comes out.
+const MAX_PROBE_PACKETS: usize = 16;
+
+#[derive(Debug)]
+pub enum SoundAssetError {
+    Io(io::Error),
+    InvalidName(String),
+    NotFound(String),
+    UnsupportedFormat(String),
+}
+
+impl std::fmt::Display for SoundAssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SoundAssetError::Io(err) => write!(f, "I/O error: {err}"),
+            SoundAssetError::InvalidName(name) => write!(f, "Invalid sound name: {name}"),
+            SoundAssetError::NotFound(name) => write!(f, "Sound not found: {name}"),
+            SoundAssetError::UnsupportedFormat(name) => {
+                write!(f, "Not a supported audio file: {name}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SoundAssetError {}
+
+impl From<io::Error> for SoundAssetError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+pub type SoundAssetResult<T> = Result<T, SoundAssetError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SoundFormat {
+    Wav,
+    Aiff,
+    Ogg,
+    Flac,
+    Mp3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SoundAsset {
+    pub name: String,
+    pub format: SoundFormat,
+    pub size_bytes: u64,
+}
+
+pub fn sounds_dir(base_dir: &Path) -> PathBuf {
+    base_dir.join(SOUNDS_DIR)
+}
+
+/// The container format of `path`, judged by its header alone. This is cheap
+/// enough for listing the folder; `verify_sound` checks the file also decodes.
+pub fn probe_sound(path: &Path) -> SoundAssetResult<SoundFormat> {
+    let mut header = [0u8; 12];
+    let mut file = fs::File::open(path)?;
+    let mut read = 0;
+    while read < header.len() {
+        match file.read(&mut header[read..])? {
+            0 => break,
+            count => read += count,
+        }
+    }
+    let header = &header[..read];
+    let format = match header {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(SoundFormat::Wav),
+        [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => {
+            Some(SoundFormat::Aiff)
+        }
+        [b'O', b'g', b'g', b'S', ..] => Some(SoundFormat::Ogg),
+        [b'f', b'L', b'a', b'C', ..] => Some(SoundFormat::Flac),
+        [b'I', b'D', b'3', ..] => Some(SoundFormat::Mp3),
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 => Some(SoundFormat::Mp3),
+        _ => None,
+    };
+    format.ok_or_else(|| SoundAssetError::UnsupportedFormat(path.display().to_string()))
+}
+
+/// The format of `path` once at least one frame of its audio decoded.
+/// Files with a known header but a body that does not decode are refused.
+pub fn verify_sound(path: &Path) -> SoundAssetResult<SoundFormat> {
+    let format = probe_sound(path)?;
+    let unsupported = || SoundAssetError::UnsupportedFormat(path.display().to_string());
+    let stream = MediaSourceStream::new(Box::new(fs::File::open(path)?), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension(match format {
+        SoundFormat::Wav => "wav",
+        SoundFormat::Aiff => "aiff",
+        SoundFormat::Ogg => "ogg",
+        SoundFormat::Flac => "flac",
+        SoundFormat::Mp3 => "mp3",
+    });
+    let mut reader = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|_| unsupported())?
+        .format;
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(unsupported)?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|_| unsupported())?;
+    for _ in 0..MAX_PROBE_PACKETS {
+        let packet = reader.next_packet().map_err(|_| unsupported())?;
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(buffer) if buffer.frames() > 0 => return Ok(format),
+            Ok(_) | Err(DecodeError::DecodeError(_)) => continue,
+            Err(_) => break,
+        }
+    }
+    Err(unsupported())
+}
+
+/// Path of the imported sound `name`, checked to be a plain file name that
+/// exists and decodes.
+pub fn resolve_sound(base_dir: &Path, name: &str) -> SoundAssetResult<PathBuf> {
+    validate_name(name)?;
+    let path = sounds_dir(base_dir).join(name);
+    if !path.is_file() {
+        return Err(SoundAssetError::NotFound(name.to_string()));
+    }
+    verify_sound(&path)?;
+    Ok(path)
+}
+
+/// Copies `source` into the sounds folder under its own file name, suffixed
+/// when another sound already has it. Only files that decode are copied.
+pub fn import_sound(base_dir: &Path, source: &Path) -> SoundAssetResult<SoundAsset> {
+    let format = verify_sound(source)?;
+    let file_name = source
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| SoundAssetError::InvalidName(source.display().to_string()))?;
+    validate_name(file_name)?;
+
+    let dir = sounds_dir(base_dir);
+    fs::create_dir_all(&dir)?;
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (file_name, None),
+    };
+    let mut name = file_name.to_string();
+    let mut counter = 2;
+    while dir.join(&name).exists() {
+        name = match extension {
+            Some(extension) => format!("{stem}-{counter}.{extension}"),
+            None => format!("{stem}-{counter}"),
+        };
+        counter += 1;
+    }
+    let size_bytes = fs::copy(source, dir.join(&name))?;
+    Ok(SoundAsset {
+        name,
+        format,
+        size_bytes,
+    })
+}
+
+/// Imported sounds by name. Files in the folder that are not audio are
+/// left out.
+pub fn list_sounds(base_dir: &Path) -> SoundAssetResult<Vec<SoundAsset>> {
+    let dir = sounds_dir(base_dir);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut sounds = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        if let Ok(format) = probe_sound(&entry.path()) {
+            sounds.push(SoundAsset {
+                name,
+                format,
+                size_bytes: metadata.len(),
+            });
+        }
+    }
+    sounds.sort_by(|left, right| left.name.cmp(&right.name));
+    Ok(sounds)
+}
+
+pub fn delete_sound(base_dir: &Path, name: &str) -> SoundAssetResult<()> {
+    validate_name(name)?;
+    let path = sounds_dir(base_dir).join(name);
+    if !path.is_file() {
+        return Err(SoundAssetError::NotFound(name.to_string()));
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Sound names are bare file names so they cannot point outside the folder.
+pub fn validate_name(name: &str) -> SoundAssetResult<()> {
+    let valid =
+        !name.trim().is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0']);
+    if valid {
+        Ok(())
+    } else {
+        Err(SoundAssetError::InvalidName(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        delete_sound, import_sound, list_sounds, probe_sound, resolve_sound, sounds_dir,
+        verify_sound, SoundAssetError, SoundFormat,
+    };
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_sound_assets_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    const WAV_HEADER: &[u8] = b"RIFF\x24\x00\x00\x00WAVEfmt ";
+
+    /// A mono 16-bit PCM WAV file holding `samples` samples of silence.
+    fn wav_bytes(samples: u32) -> Vec<u8> {
+        let data_len = samples * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&8000u32.to_le_bytes());
+        bytes.extend_from_slice(&16000u32.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        bytes.resize(bytes.len() + data_len as usize, 0);
+        bytes
+    }
+
+    #[test]
+    fn probes_audio_headers() {
+        let dir = temp_dir();
+        let cases: [(&str, &[u8], Option<SoundFormat>); 5] = [
+            ("a.wav", WAV_HEADER, Some(SoundFormat::Wav)),
+            ("a.ogg", b"OggS\x00\x02", Some(SoundFormat::Ogg)),
+            ("a.mp3", b"ID3\x04\x00", Some(SoundFormat::Mp3)),
+            (
+                "a.aiff",
+                b"FORM\x00\x00\x00\x00AIFF",
+                Some(SoundFormat::Aiff),
+            ),
+            ("a.txt", b"hello", None),
+        ];
+        for (name, bytes, expected) in cases {
+            let path = dir.join(name);
+            fs::write(&path, bytes).expect("write sample");
+            assert_eq!(probe_sound(&path).ok(), expected, "{name}");
+        }
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn imports_lists_and_deletes_sounds() {
+        let base = temp_dir();
+        let source = temp_dir().join("chime.wav");
+        fs::write(&source, wav_bytes(800)).expect("write source");
+        let text = source.with_file_name("notes.txt");
+        fs::write(&text, "not audio").expect("write text");
+
+        let first = import_sound(&base, &source).expect("import");
+        let second = import_sound(&base, &source).expect("import again");
+        assert_eq!(first.name, "chime.wav");
+        assert_eq!(second.name, "chime-2.wav");
+        assert_eq!(second.format, SoundFormat::Wav);
+        assert!(matches!(
+            import_sound(&base, &text),
+            Err(SoundAssetError::UnsupportedFormat(_))
+        ));
+        fs::write(sounds_dir(&base).join("stray.txt"), "x").expect("write stray");
+
+        let names: Vec<String> = list_sounds(&base)
+            .expect("list")
+            .into_iter()
+            .map(|sound| sound.name)
+            .collect();
+        assert_eq!(names, vec!["chime-2.wav", "chime.wav"]);
+        assert_eq!(
+            resolve_sound(&base, "chime.wav").expect("resolve"),
+            sounds_dir(&base).join("chime.wav")
+        );
+
+        delete_sound(&base, "chime.wav").expect("delete");
+        assert!(matches!(
+            resolve_sound(&base, "chime.wav"),
+            Err(SoundAssetError::NotFound(_))
+        ));
+        fs::remove_dir_all(base).ok();
+        fs::remove_dir_all(source.parent().expect("parent")).ok();
+    }
+
+    #[test]
+    fn refuses_sounds_that_do_not_decode() {
+        let base = temp_dir();
+        let source = temp_dir().join("broken.wav");
+        fs::write(&source, WAV_HEADER).expect("write source");
+        assert_eq!(probe_sound(&source).ok(), Some(SoundFormat::Wav));
+        assert!(matches!(
+            import_sound(&base, &source),
+            Err(SoundAssetError::UnsupportedFormat(_))
+        ));
+
+        fs::create_dir_all(sounds_dir(&base)).expect("create sounds dir");
+        fs::write(sounds_dir(&base).join("broken.wav"), WAV_HEADER).expect("write sound");
+        assert!(matches!(
+            resolve_sound(&base, "broken.wav"),
+            Err(SoundAssetError::UnsupportedFormat(_))
+        ));
+        fs::write(&source, wav_bytes(800)).expect("write source");
+        assert_eq!(verify_sound(&source).ok(), Some(SoundFormat::Wav));
+        fs::remove_dir_all(base).ok();
+        fs::remove_dir_all(source.parent().expect("parent")).ok();
+    }
+
+    #[test]
+    fn rejects_names_outside_the_folder() {
+        let base = temp_dir();
+        for name in ["../settings.json", "a/b.wav", "", ".."] {
+            assert!(matches!(
+                resolve_sound(&base, name),
+                Err(SoundAssetError::InvalidName(_))
+            ));
+        }
+        assert!(matches!(
+            delete_sound(&base, "../routines.json"),
+            Err(SoundAssetError::InvalidName(_))
+        ));
+        fs::remove_dir_all(base).ok();
+    }
+}
@@ -0,0 +1,184 @@
+use crate::audio_manager::SoundEvent;
+use crate::models::Step;
+use serde::Serialize;
+use std::time::Duration;
+
+/// A cue fired while a step runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StepWarning {
+    Halfway,
+    Remaining { seconds: u32 },
+}
+
+impl StepWarning {
+    pub fn sound_event(self) -> SoundEvent {
+        match self {
+            StepWarning::Halfway => SoundEvent::HalfwayWarning,
+            StepWarning::Remaining { .. } => SoundEvent::CountdownWarning,
+        }
+    }
+
+    /// Notification text, e.g. "残り30秒".
+    pub fn message(self) -> String {
+        match self {
+            StepWarning::Halfway => "折り返し地点です".to_string(),
+            StepWarning::Remaining { seconds } if seconds >= 60 && seconds % 60 == 0 => {
+                format!("残り{}分", seconds / 60)
+            }
+            StepWarning::Remaining { seconds } => format!("残り{seconds}秒"),
+        }
+    }
+
+    fn remaining_at(self, step: &Step) -> Duration {
+        match self {
+            StepWarning::Halfway => Duration::from_millis(u64::from(step.duration_seconds) * 500),
+            StepWarning::Remaining { seconds } => Duration::from_secs(u64::from(seconds)),
+        }
+    }
+}
+
+/// The step's cues ordered by when they fire. Countdown cues outside the
+/// step's length could never fire and are left out.
+pub fn configured_warnings(step: &Step) -> Vec<StepWarning> {
+    let mut warnings: Vec<StepWarning> = step
+        .warnings
+        .remaining_seconds
+        .iter()
+        .filter(|seconds| **seconds > 0 && **seconds < step.duration_seconds)
+        .map(|seconds| StepWarning::Remaining { seconds: *seconds })
+        .collect();
+    if step.warnings.halfway && step.duration_seconds > 1 {
+        warnings.push(StepWarning::Halfway);
+    }
+    warnings.sort_by_key(|warning| std::cmp::Reverse(warning.remaining_at(step)));
+    warnings.dedup();
+    warnings
+}
+
+/// Watches the remaining time of the running step from tick to tick and
+/// reports the cues whose moment passed in between.
+#[derive(Debug, Default)]
+pub struct WarningTracker {
+    step_id: Option<String>,
+    last_remaining: Option<Duration>,
+}
+
+impl WarningTracker {
+    /// Cues crossed since the previous call. The first sighting of a step, and
+    /// a restart of the same step on the next repeat, only set the baseline.
+    pub fn observe(&mut self, step: &Step, remaining: Duration) -> Vec<StepWarning> {
+        let same_step = self.step_id.as_deref() == Some(step.id.as_str());
+        let previous = self
+            .last_remaining
+            .filter(|previous| same_step && *previous >= remaining);
+        self.step_id = Some(step.id.clone());
+        self.last_remaining = Some(remaining);
+
+        let Some(previous) = previous else {
+            return Vec::new();
+        };
+        configured_warnings(step)
+            .into_iter()
+            .filter(|warning| {
+                let at = warning.remaining_at(step);
+                previous > at && remaining <= at
+            })
+            .collect()
+    }
+
+    pub fn reset(&mut self) {
+        self.step_id = None;
+        self.last_remaining = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{configured_warnings, StepWarning, WarningTracker};
+    use crate::models::{
+        CheckInConfig, CheckInMode, SoundAssets, SoundOverride, Step, StepWarnings,
+    };
+    use std::time::Duration;
+
+    fn build_step(id: &str, duration_seconds: u32, remaining: &[u32], halfway: bool) -> Step {
+        Step {
+            id: id.to_string(),
+            order: 0,
+            label: "集中".to_string(),
+            duration_seconds,
+            instruction: String::new(),
+            sound_override: SoundOverride::Inherit,
+            count_as_break: false,
+            check_in: CheckInConfig {
+                mode: CheckInMode::Off,
+                prompt_title: None,
+                prompt_body: None,
+                prompt_timeout_seconds: None,
+            },
+            sounds: SoundAssets::default(),
+            warnings: StepWarnings {
+                remaining_seconds: remaining.to_vec(),
+                halfway,
+                notify: false,
+            },
+        }
+    }
+
+    #[test]
+    fn orders_warnings_and_drops_unreachable_ones() {
+        let step = build_step("step-1", 120, &[10, 0, 30, 120, 30], true);
+        assert_eq!(
+            configured_warnings(&step),
+            vec![
+                StepWarning::Halfway,
+                StepWarning::Remaining { seconds: 30 },
+                StepWarning::Remaining { seconds: 10 },
+            ]
+        );
+    }
+
+    #[test]
+    fn fires_each_warning_once_as_time_passes() {
+        let step = build_step("step-1", 100, &[30], true);
+        let mut tracker = WarningTracker::default();
+        let mut fired = Vec::new();
+        for remaining in (0..=100).rev() {
+            fired.extend(tracker.observe(&step, Duration::from_secs(remaining)));
+        }
+        assert_eq!(
+            fired,
+            vec![StepWarning::Halfway, StepWarning::Remaining { seconds: 30 }]
+        );
+    }
+
+    #[test]
+    fn catches_up_after_a_long_tick_and_rearms_on_repeat() {
+        let step = build_step("step-1", 60, &[30, 10], false);
+        let mut tracker = WarningTracker::default();
+        assert!(tracker.observe(&step, Duration::from_secs(40)).is_empty());
+        assert_eq!(
+            tracker.observe(&step, Duration::from_secs(5)),
+            vec![
+                StepWarning::Remaining { seconds: 30 },
+                StepWarning::Remaining { seconds: 10 },
+            ]
+        );
+
+        assert!(tracker.observe(&step, Duration::from_secs(60)).is_empty());
+        assert_eq!(
+            tracker.observe(&step, Duration::from_secs(30)),
+            vec![StepWarning::Remaining { seconds: 30 }]
+        );
+
+        let other = build_step("step-2", 60, &[30], false);
+        assert!(tracker.observe(&other, Duration::from_secs(20)).is_empty());
+    }
+
+    #[test]
+    fn formats_warning_messages() {
+        assert_eq!(StepWarning::Remaining { seconds: 30 }.message(), "残り30秒");
+        assert_eq!(StepWarning::Remaining { seconds: 120 }.message(), "残り2分");
+        assert_eq!(StepWarning::Halfway.message(), "折り返し地点です");
+    }
+}
@@ -4,7 +4,11 @@ use crate::data_manager::DataManager;
 use crate::events::{
     emit_step_changed, emit_timer_paused, emit_timer_resumed, emit_timer_stopped, emit_timer_tick,
 };
-use crate::models::{CheckInChoice, CheckInMode, CheckInResponse, Routine, Step, StepRunResult};
+use crate::ids::{new_id, IdKind};
+use crate::models::{
+    CheckInChoice, CheckInMode, CheckInResponse, Routine, SessionOutcome, Step, StepRunResult,
+};
+use crate::routine_validator::validate_routine;
 use crate::runtime_state::RuntimeState;
 use crate::session_recovery;
 use crate::session_tracker::SessionTracker;
@@ -70,6 +74,7 @@ pub fn start_routine(
     runtime_state: &Mutex<RuntimeState>,
     app: &AppHandle,
 ) -> Result<(), AppError> {
+    validate_routine(&routine).map_err(AppError::from)?;
     let routine_id = routine.id.clone();
     let routine_snapshot = routine.clone();
     let mut engine = timer_engine.lock().map_err(|_| timer_lock_error())?;
@@ -94,11 +99,18 @@ pub fn start_routine(
             .try_state::<Mutex<AudioManager>>()
             .and_then(|state| state.lock().ok().map(|manager| manager.is_muted()))
             .unwrap_or(false);
-        session_recovery::start_active_session(data_manager, &routine_id, &step, muted)
-            .map_err(AppError::from)?;
+        let session_id = new_id(IdKind::Session);
+        session_recovery::start_active_session(
+            data_manager,
+            &session_id,
+            &routine_id,
+            &step,
+            muted,
+        )
+        .map_err(AppError::from)?;
         if let Some(tracker_state) = app.try_state::<Mutex<SessionTracker>>() {
             if let Ok(mut tracker) = tracker_state.lock() {
-                tracker.start_session(&routine_snapshot, &step, muted);
+                tracker.start_session(session_id, &routine_snapshot, &step, muted);
             }
         }
     }
@@ -162,9 +174,10 @@ pub fn skip_step(timer_engine: &Mutex<TimerEngine>, app: &AppHandle) -> Result<(
     let auto_pause_event = engine.take_auto_pause_event();
     let step_sound_context = step_changed
         .as_ref()
-        .and_then(|(step, _)| build_sound_context(&engine, Some(step)));
+        .and_then(|(step, _)| build_sound_context(&engine, Some(step)))
+        .map(|context| context.after_step(current_step.as_ref()));
     let routine_sound_context = if routine_completed {
-        routine_base_context
+        routine_base_context.map(|context| context.after_step(current_step.as_ref()))
     } else {
         None
     };
@@ -225,7 +238,9 @@ pub fn skip_step(timer_engine: &Mutex<TimerEngine>, app: &AppHandle) -> Result<(
             }
 
             if routine_completed {
-                if let Some(session) = tracker.finish_session(now_rfc3339()) {
+                if let Some(session) =
+                    tracker.finish_session(now_rfc3339(), SessionOutcome::Completed)
+                {
                     if let Some(manager) = app.try_state::<DataManager>() {
                         if let Err(err) = manager.save_session(session) {
                             eprintln!("Failed to save session: {err}");
@@ -267,7 +282,7 @@ pub fn stop_timer(timer_engine: &Mutex<TimerEngine>, app: &AppHandle) -> Result<
                     now_rfc3339(),
                 );
             }
-            if let Some(session) = tracker.finish_session(now_rfc3339()) {
+            if let Some(session) = tracker.finish_session(now_rfc3339(), SessionOutcome::Stopped) {
                 if let Some(manager) = app.try_state::<DataManager>() {
                     if let Err(err) = manager.save_session(session) {
                         eprintln!("Failed to save session: {err}");
@@ -285,6 +300,7 @@ pub fn respond_to_check_in(
     app: &AppHandle,
 ) -> Result<(), AppError> {
     let mut engine = timer_engine.lock().map_err(|_| timer_lock_error())?;
+    let current_step = engine.current_step().cloned();
     let routine_base_context = build_sound_context(&engine, None);
     let result = engine
         .respond_to_check_in(
@@ -298,9 +314,10 @@ pub fn respond_to_check_in(
     let auto_pause_event = engine.take_auto_pause_event();
     let step_sound_context = step_changed
         .as_ref()
-        .and_then(|(step, _)| build_sound_context(&engine, Some(step)));
+        .and_then(|(step, _)| build_sound_context(&engine, Some(step)))
+        .map(|context| context.after_step(current_step.as_ref()));
     let routine_sound_context = if routine_completed {
-        routine_base_context
+        routine_base_context.map(|context| context.after_step(current_step.as_ref()))
     } else {
         None
     };
@@ -350,7 +367,9 @@ pub fn respond_to_check_in(
             }
 
             if routine_completed {
-                if let Some(session) = tracker.finish_session(now_rfc3339()) {
+                if let Some(session) =
+                    tracker.finish_session(now_rfc3339(), SessionOutcome::Completed)
+                {
                     if let Some(manager) = app.try_state::<DataManager>() {
                         if let Err(err) = manager.save_session(session) {
                             eprintln!("Failed to save session: {err}");
@@ -579,8 +579,8 @@ struct PendingCheckIn {
 mod tests {
     use super::{AdvanceResult, TimerEngine, TimerError};
     use crate::models::{
-        CheckInChoice, CheckInConfig, CheckInMode, RepeatMode, Routine, SoundOverride, SoundScheme,
-        SoundSetting, Step,
+        CheckInChoice, CheckInConfig, CheckInMode, RepeatMode, Routine, SoundAssets, SoundOverride,
+        SoundScheme, SoundSetting, Step, StepWarnings,
     };
     use std::thread::sleep;
     use std::time::{Duration, Instant};
@@ -594,6 +594,8 @@ mod tests {
             instruction: "Do work".to_string(),
             sound_override: SoundOverride::Inherit,
             count_as_break: false,
+            sounds: SoundAssets::default(),
+            warnings: StepWarnings::default(),
             check_in: CheckInConfig {
                 mode: CheckInMode::Off,
                 prompt_title: None,
@@ -613,6 +615,7 @@ mod tests {
             notifications: true,
             sound_default: SoundSetting::On,
             sound_scheme: SoundScheme::Default,
+            sounds: SoundAssets::default(),
         }
     }
 
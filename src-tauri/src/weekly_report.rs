@@ -0,0 +1,717 @@
+//! Weekly summary reports.
+//!
+//! A report covers one local week as cut by the calendar settings and compares
+//! it with the week before. It renders to Markdown or to a self-contained HTML
+//! page, and can be written to the report folder once a week has ended.
+
+use crate::calendar::{
+    day_of_week, parse_instant, period_containing, periods_between, CalendarError, Period,
+    PeriodKind,
+};
+use crate::data_manager::{DataError, DataManager};
+use crate::models::{
+    CalendarSettings, DailyAggregate, DayOfWeek, Routine, Session, SessionStats, StatsBucket,
+};
+use crate::session_stats::{calculate_session_stats, calculate_stats_series, calculate_step_stats};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MOST_SKIPPED_LIMIT: usize = 3;
+const REPORT_FILE_PREFIX: &str = "mccall-weekly-";
+
+#[derive(Debug)]
+pub enum ReportError {
+    Io(io::Error),
+    Data(DataError),
+    Calendar(CalendarError),
+}
+
+impl std::fmt::Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportError::Io(err) => write!(f, "I/O error: {err}"),
+            ReportError::Data(err) => write!(f, "{err}"),
+            ReportError::Calendar(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+impl From<io::Error> for ReportError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<DataError> for ReportError {
+    fn from(value: DataError) -> Self {
+        Self::Data(value)
+    }
+}
+
+impl From<CalendarError> for ReportError {
+    fn from(value: CalendarError) -> Self {
+        Self::Calendar(value)
+    }
+}
+
+pub type ReportResult<T> = Result<T, ReportError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MostSkippedStep {
+    pub routine_name: Option<String>,
+    pub step_id: String,
+    pub label: Option<String>,
+    pub skipped_count: u32,
+    pub runs: u32,
+}
+
+/// One local week next to the week before it. Skip rates are over step runs
+/// and `None` when none are on record.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyReport {
+    pub week: Period,
+    pub end_date: String,
+    pub stats: SessionStats,
+    pub previous_stats: SessionStats,
+    pub skip_rate: Option<f32>,
+    pub previous_skip_rate: Option<f32>,
+    pub most_skipped: Vec<MostSkippedStep>,
+    pub best_day: Option<StatsBucket>,
+}
+
+/// Report for the local week containing `instant`.
+pub fn load_weekly_report<Tz: TimeZone>(
+    data_manager: &DataManager,
+    instant: DateTime<Utc>,
+    zone: &Tz,
+    settings: &CalendarSettings,
+) -> ReportResult<WeeklyReport> {
+    let week = period_containing(PeriodKind::Week, instant, zone, settings);
+    let week_from = parse_instant(&week.from)?;
+    let previous = period_containing(
+        PeriodKind::Week,
+        week_from - TimeDelta::milliseconds(1),
+        zone,
+        settings,
+    );
+    let days = periods_between(
+        PeriodKind::Day,
+        week_from,
+        parse_instant(&week.to)?,
+        zone,
+        settings,
+    )?;
+    let sessions = data_manager.load_sessions_in_range(&previous.from, &week.to)?;
+    let aggregates = data_manager.load_daily_aggregates_in_range(&previous.from, &week.to)?;
+    let routines = data_manager.load_routines()?;
+    Ok(build_weekly_report(
+        &sessions,
+        &aggregates,
+        &routines,
+        &week,
+        &previous,
+        &days,
+    ))
+}
+
+/// Builds the report for `week` from history covering it and `previous`.
+/// `days` are the local days of `week`, in order.
+pub fn build_weekly_report(
+    sessions: &[Session],
+    aggregates: &[DailyAggregate],
+    routines: &[Routine],
+    week: &Period,
+    previous: &Period,
+    days: &[Period],
+) -> WeeklyReport {
+    let (week_sessions, week_aggregates) = history_in(sessions, aggregates, week);
+    let (previous_sessions, previous_aggregates) = history_in(sessions, aggregates, previous);
+
+    let step_stats = calculate_step_stats(&week_sessions, routines);
+    let mut most_skipped: Vec<MostSkippedStep> = step_stats
+        .iter()
+        .filter(|stats| stats.skipped_count > 0)
+        .map(|stats| MostSkippedStep {
+            routine_name: routines
+                .iter()
+                .find(|routine| routine.id == stats.routine_id)
+                .map(|routine| routine.name.clone()),
+            step_id: stats.step_id.clone(),
+            label: stats.label.clone(),
+            skipped_count: stats.skipped_count,
+            runs: stats.runs,
+        })
+        .collect();
+    most_skipped.sort_by_key(|step| std::cmp::Reverse(step.skipped_count));
+    most_skipped.truncate(MOST_SKIPPED_LIMIT);
+
+    let best_day = calculate_stats_series(&week_sessions, &week_aggregates, routines, days)
+        .into_iter()
+        .filter(|day| day.work_seconds > 0)
+        .fold(None::<StatsBucket>, |best, day| match best {
+            Some(best) if best.work_seconds >= day.work_seconds => Some(best),
+            _ => Some(day),
+        });
+
+    WeeklyReport {
+        week: week.clone(),
+        end_date: days
+            .last()
+            .map(|day| day.start_date.clone())
+            .unwrap_or_else(|| week.start_date.clone()),
+        stats: calculate_session_stats(&week_sessions, &week_aggregates),
+        previous_stats: calculate_session_stats(&previous_sessions, &previous_aggregates),
+        skip_rate: skip_rate(&week_sessions, routines),
+        previous_skip_rate: skip_rate(&previous_sessions, routines),
+        most_skipped,
+        best_day,
+    }
+}
+
+/// Sessions started within `period`, and aggregates whose day falls into it.
+fn history_in(
+    sessions: &[Session],
+    aggregates: &[DailyAggregate],
+    period: &Period,
+) -> (Vec<Session>, Vec<DailyAggregate>) {
+    let (Ok(from), Ok(to)) = (parse_instant(&period.from), parse_instant(&period.to)) else {
+        return (Vec::new(), Vec::new());
+    };
+    let contains = |instant: DateTime<Utc>| from <= instant && instant <= to;
+    let sessions = sessions
+        .iter()
+        .filter(|session| parse_instant(&session.started_at).is_ok_and(contains))
+        .cloned()
+        .collect();
+    let aggregates = aggregates
+        .iter()
+        .filter(|aggregate| {
+            NaiveDate::parse_from_str(&aggregate.date, "%Y-%m-%d").is_ok_and(|date| {
+                contains(
+                    date.and_time(NaiveTime::from_hms_opt(12, 0, 0).unwrap_or_default())
+                        .and_utc(),
+                )
+            })
+        })
+        .cloned()
+        .collect();
+    (sessions, aggregates)
+}
+
+fn skip_rate(sessions: &[Session], routines: &[Routine]) -> Option<f32> {
+    let (skipped, runs) = calculate_step_stats(sessions, routines)
+        .iter()
+        .fold((0u32, 0u32), |(skipped, runs), stats| {
+            (skipped + stats.skipped_count, runs + stats.runs)
+        });
+    (runs > 0).then(|| skipped as f32 / runs as f32)
+}
+
+pub fn render_report(report: &WeeklyReport, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(report),
+        ReportFormat::Html => render_html(report),
+    }
+}
+
+/// Writes the report for the last local week that has fully ended before
+/// `now`, in both formats, unless it is already in `folder`. Returns the
+/// written files.
+pub fn write_finished_week_report<Tz: TimeZone>(
+    data_manager: &DataManager,
+    folder: &Path,
+    now: DateTime<Utc>,
+    zone: &Tz,
+    settings: &CalendarSettings,
+) -> ReportResult<Vec<PathBuf>> {
+    let current = period_containing(PeriodKind::Week, now, zone, settings);
+    let last_week_end = parse_instant(&current.from)? - TimeDelta::milliseconds(1);
+    let last_week = period_containing(PeriodKind::Week, last_week_end, zone, settings);
+    if report_path(folder, &last_week, ReportFormat::Markdown).exists() {
+        return Ok(Vec::new());
+    }
+    let report = load_weekly_report(data_manager, last_week_end, zone, settings)?;
+    write_report(folder, &report)
+}
+
+/// Writes `report` as Markdown and HTML into `folder`.
+pub fn write_report(folder: &Path, report: &WeeklyReport) -> ReportResult<Vec<PathBuf>> {
+    fs::create_dir_all(folder)?;
+    // Markdown goes last: its presence marks the week as done.
+    [ReportFormat::Html, ReportFormat::Markdown]
+        .into_iter()
+        .map(|format| {
+            let path = report_path(folder, &report.week, format);
+            fs::write(&path, render_report(report, format))?;
+            Ok(path)
+        })
+        .collect()
+}
+
+fn report_path(folder: &Path, week: &Period, format: ReportFormat) -> PathBuf {
+    folder.join(format!(
+        "{REPORT_FILE_PREFIX}{}.{}",
+        week.start_date,
+        format.extension()
+    ))
+}
+
+struct Row {
+    label: &'static str,
+    value: String,
+    delta: String,
+}
+
+fn summary_rows(report: &WeeklyReport) -> Vec<Row> {
+    let stats = &report.stats;
+    let previous = &report.previous_stats;
+    let mute_rate = |stats: &SessionStats| (stats.sessions_count > 0).then_some(stats.mute_rate);
+    vec![
+        Row {
+            label: "セッション数",
+            value: format!("{}回", stats.sessions_count),
+            delta: format!(
+                "{}回",
+                signed(i64::from(stats.sessions_count) - i64::from(previous.sessions_count))
+            ),
+        },
+        Row {
+            label: "作業時間",
+            value: format_duration(stats.work_seconds),
+            delta: duration_delta(stats.work_seconds, previous.work_seconds),
+        },
+        Row {
+            label: "休憩時間",
+            value: format_duration(stats.break_seconds),
+            delta: duration_delta(stats.break_seconds, previous.break_seconds),
+        },
+        Row {
+            label: "skip率",
+            value: format_rate(report.skip_rate),
+            delta: rate_delta(report.skip_rate, report.previous_skip_rate),
+        },
+        Row {
+            label: "ミュート率",
+            value: format_rate(mute_rate(stats)),
+            delta: rate_delta(mute_rate(stats), mute_rate(previous)),
+        },
+    ]
+}
+
+fn title(report: &WeeklyReport) -> String {
+    format!(
+        "週次レポート {}〜{}",
+        report.week.start_date, report.end_date
+    )
+}
+
+fn skipped_line(step: &MostSkippedStep) -> String {
+    let label = step.label.as_deref().unwrap_or(&step.step_id);
+    let name = match &step.routine_name {
+        Some(routine) => format!("{label}（{routine}）"),
+        None => label.to_string(),
+    };
+    format!("{name}: {}回 / {}回中", step.skipped_count, step.runs)
+}
+
+fn best_day_line(day: &StatsBucket) -> String {
+    let weekday = NaiveDate::parse_from_str(&day.start_date, "%Y-%m-%d")
+        .map(|date| format!("（{}）", weekday_name(day_of_week(date))))
+        .unwrap_or_default();
+    format!(
+        "{}{weekday}: 作業時間 {}",
+        day.start_date,
+        format_duration(day.work_seconds)
+    )
+}
+
+fn render_markdown(report: &WeeklyReport) -> String {
+    let mut output = format!("# {}\n\n", title(report));
+    output.push_str("| 項目 | 今週 | 前週比 |\n| --- | --- | --- |\n");
+    for row in summary_rows(report) {
+        output.push_str(&format!(
+            "| {} | {} | {} |\n",
+            row.label, row.value, row.delta
+        ));
+    }
+
+    output.push_str("\n## よくskipされたステップ\n\n");
+    if report.most_skipped.is_empty() {
+        output.push_str("skipされたステップはありません\n");
+    }
+    for (index, step) in report.most_skipped.iter().enumerate() {
+        output.push_str(&format!(
+            "{}. {}\n",
+            index + 1,
+            markdown_text(&skipped_line(step))
+        ));
+    }
+
+    output.push_str("\n## ベストデー\n\n");
+    match &report.best_day {
+        Some(day) => output.push_str(&format!("{}\n", best_day_line(day))),
+        None => output.push_str("記録なし\n"),
+    }
+    output
+}
+
+fn render_html(report: &WeeklyReport) -> String {
+    let title = html_escape(&title(report));
+    let mut output = format!(
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n\
+<title>{title}</title>\n<style>\n\
+body {{ font-family: system-ui, sans-serif; margin: 2rem; color: #222; }}\n\
+table {{ border-collapse: collapse; }}\n\
+th, td {{ border: 1px solid #ccc; padding: 0.4rem 0.8rem; text-align: left; }}\n\
+</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+<table>\n<tr><th>項目</th><th>今週</th><th>前週比</th></tr>\n"
+    );
+    for row in summary_rows(report) {
+        output.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            row.label,
+            html_escape(&row.value),
+            html_escape(&row.delta)
+        ));
+    }
+    output.push_str("</table>\n<h2>よくskipされたステップ</h2>\n");
+    if report.most_skipped.is_empty() {
+        output.push_str("<p>skipされたステップはありません</p>\n");
+    } else {
+        output.push_str("<ol>\n");
+        for step in &report.most_skipped {
+            output.push_str(&format!("<li>{}</li>\n", html_escape(&skipped_line(step))));
+        }
+        output.push_str("</ol>\n");
+    }
+    output.push_str("<h2>ベストデー</h2>\n");
+    let best_day = report
+        .best_day
+        .as_ref()
+        .map(best_day_line)
+        .unwrap_or_else(|| "記録なし".to_string());
+    output.push_str(&format!(
+        "<p>{}</p>\n</body>\n</html>\n",
+        html_escape(&best_day)
+    ));
+    output
+}
+
+/// Same wording as the stats view: "1時間5分", "1分未満".
+fn format_duration(seconds: u32) -> String {
+    let minutes = seconds / 60;
+    if minutes == 0 {
+        return if seconds > 0 { "1分未満" } else { "0分" }.to_string();
+    }
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}分"),
+        (hours, 0) => format!("{hours}時間"),
+        (hours, minutes) => format!("{hours}時間{minutes}分"),
+    }
+}
+
+fn format_rate(rate: Option<f32>) -> String {
+    rate.map(|value| format!("{}%", (value * 100.0).round()))
+        .unwrap_or_else(|| "—".to_string())
+}
+
+fn signed(value: i64) -> String {
+    match value {
+        0 => "±0".to_string(),
+        value if value > 0 => format!("+{value}"),
+        value => value.to_string(),
+    }
+}
+
+fn duration_delta(current: u32, previous: u32) -> String {
+    let sign = match current.cmp(&previous) {
+        std::cmp::Ordering::Equal => return "±0分".to_string(),
+        std::cmp::Ordering::Greater => "+",
+        std::cmp::Ordering::Less => "-",
+    };
+    format!("{sign}{}", format_duration(current.abs_diff(previous)))
+}
+
+fn rate_delta(current: Option<f32>, previous: Option<f32>) -> String {
+    match (current, previous) {
+        (Some(current), Some(previous)) => {
+            let points = ((current - previous) * 100.0).round() as i64;
+            format!("{}pt", signed(points))
+        }
+        _ => "—".to_string(),
+    }
+}
+
+fn weekday_name(day: DayOfWeek) -> &'static str {
+    match day {
+        DayOfWeek::Monday => "月",
+        DayOfWeek::Tuesday => "火",
+        DayOfWeek::Wednesday => "水",
+        DayOfWeek::Thursday => "木",
+        DayOfWeek::Friday => "金",
+        DayOfWeek::Saturday => "土",
+        DayOfWeek::Sunday => "日",
+    }
+}
+
+/// Keeps user-entered labels from turning into Markdown markup.
+fn markdown_text(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '\n' | '\r' => output.push(' '),
+            '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#' | '|' => {
+                output.push('\\');
+                output.push(character);
+            }
+            _ => output.push(character),
+        }
+    }
+    output
+}
+
+fn html_escape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            _ => output.push(character),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_weekly_report, render_report, write_finished_week_report, ReportFormat, WeeklyReport,
+    };
+    use crate::calendar::{parse_instant, period_containing, periods_between, PeriodKind};
+    use crate::data_manager::DataManager;
+    use crate::models::{
+        CalendarSettings, CheckInConfig, CheckInMode, RepeatMode, Routine, Session, SessionTotals,
+        SoundAssets, SoundOverride, SoundScheme, SoundSetting, Step, StepRun, StepRunResult,
+        StepWarnings,
+    };
+    use chrono::{TimeDelta, Utc};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!(
+            "mccall_report_test_{nanos}_{counter}_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn routine() -> Routine {
+        let step = |id: &str, order: u32, label: &str| Step {
+            id: id.to_string(),
+            order,
+            label: label.to_string(),
+            duration_seconds: 300,
+            instruction: String::new(),
+            sound_override: SoundOverride::Inherit,
+            count_as_break: false,
+            sounds: SoundAssets::default(),
+            warnings: StepWarnings::default(),
+            check_in: CheckInConfig {
+                mode: CheckInMode::Off,
+                prompt_title: None,
+                prompt_body: None,
+                prompt_timeout_seconds: None,
+            },
+        };
+        Routine {
+            id: "routine-1".to_string(),
+            name: "Morning <b>".to_string(),
+            steps: vec![step("step-a", 0, "Focus"), step("step-b", 1, "Stretch*")],
+            repeat_mode: RepeatMode::Infinite,
+            auto_advance: true,
+            notifications: true,
+            sound_default: SoundSetting::On,
+            sound_scheme: SoundScheme::Default,
+            sounds: SoundAssets::default(),
+        }
+    }
+
+    fn run(step_id: &str, result: StepRunResult, seconds: u32, started_at: &str) -> StepRun {
+        StepRun {
+            step_id: step_id.to_string(),
+            label: None,
+            planned_duration_seconds: 300,
+            actual_duration_seconds: seconds,
+            started_at: started_at.to_string(),
+            ended_at: None,
+            result,
+            check_in_result: None,
+            sound_played: false,
+        }
+    }
+
+    fn session(id: &str, started_at: &str, step_runs: Vec<StepRun>, work: u32) -> Session {
+        Session {
+            id: id.to_string(),
+            routine_id: "routine-1".to_string(),
+            started_at: started_at.to_string(),
+            ended_at: Some(started_at.to_string()),
+            step_runs,
+            totals: SessionTotals {
+                total_seconds: work,
+                work_seconds: work,
+                ..SessionTotals::default()
+            },
+            muted_during_session: false,
+            zone: None,
+            excluded_from_stats: false,
+            tags: Vec::new(),
+            annotation: None,
+            edits: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    fn sample_report() -> WeeklyReport {
+        let first = "2025-01-06T09:00:00Z";
+        let second = "2025-01-08T09:00:00Z";
+        let sessions = vec![
+            session("previous", "2025-01-03T10:00:00Z", Vec::new(), 600),
+            session(
+                "first",
+                first,
+                vec![
+                    run("step-a", StepRunResult::Completed, 1500, first),
+                    run("step-b", StepRunResult::Skipped, 60, first),
+                ],
+                1560,
+            ),
+            session(
+                "second",
+                second,
+                vec![
+                    run("step-a", StepRunResult::Completed, 1500, second),
+                    run("step-b", StepRunResult::Skipped, 30, second),
+                ],
+                1530,
+            ),
+        ];
+        let settings = CalendarSettings::default();
+        let at = parse_instant("2025-01-08T12:00:00Z").expect("instant");
+        let week = period_containing(PeriodKind::Week, at, &Utc, &settings);
+        let week_from = parse_instant(&week.from).expect("from");
+        let previous = period_containing(
+            PeriodKind::Week,
+            week_from - TimeDelta::milliseconds(1),
+            &Utc,
+            &settings,
+        );
+        let days = periods_between(
+            PeriodKind::Day,
+            week_from,
+            parse_instant(&week.to).expect("to"),
+            &Utc,
+            &settings,
+        )
+        .expect("days");
+        build_weekly_report(&sessions, &[], &[routine()], &week, &previous, &days)
+    }
+
+    #[test]
+    fn compares_week_with_the_previous_one() {
+        let report = sample_report();
+
+        assert_eq!(report.stats.sessions_count, 2);
+        assert_eq!(report.previous_stats.sessions_count, 1);
+        assert_eq!(report.end_date, "2025-01-12");
+        assert_eq!(report.skip_rate, Some(0.5));
+        assert_eq!(report.previous_skip_rate, None);
+        assert_eq!(report.most_skipped.len(), 1);
+        assert_eq!(report.most_skipped[0].step_id, "step-b");
+        assert_eq!(
+            report.best_day.as_ref().map(|day| day.start_date.as_str()),
+            Some("2025-01-06")
+        );
+
+        let markdown = render_report(&report, ReportFormat::Markdown);
+        assert!(markdown.starts_with("# 週次レポート 2025-01-06〜2025-01-12\n"));
+        assert!(markdown.contains("| セッション数 | 2回 | +1回 |"));
+        assert!(markdown.contains("| 作業時間 | 51分 | +41分 |"));
+        assert!(markdown.contains("| skip率 | 50% | — |"));
+        assert!(markdown.contains("1. Stretch\\*（Morning \\<b\\>）: 2回 / 2回中"));
+        assert!(markdown.contains("2025-01-06（月）: 作業時間 26分"));
+
+        let html = render_report(&report, ReportFormat::Html);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<li>Stretch*（Morning &lt;b&gt;）: 2回 / 2回中</li>"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[test]
+    fn writes_each_finished_week_once() {
+        let dir = temp_dir();
+        let manager = DataManager::new(dir.join("data")).expect("create manager");
+        manager
+            .save_session(session("s1", "2025-01-07T09:00:00Z", Vec::new(), 1200))
+            .expect("save session");
+        let folder = dir.join("reports");
+        let now = parse_instant("2025-01-13T08:00:00Z").expect("now");
+        let settings = CalendarSettings::default();
+
+        let written = write_finished_week_report(&manager, &folder, now, &Utc, &settings)
+            .expect("write report");
+
+        assert_eq!(
+            written,
+            vec![
+                folder.join("mccall-weekly-2025-01-06.html"),
+                folder.join("mccall-weekly-2025-01-06.md"),
+            ]
+        );
+        let markdown = fs::read_to_string(&written[1]).expect("read report");
+        assert!(markdown.contains("| 作業時間 | 20分 | +20分 |"));
+        let again = write_finished_week_report(&manager, &folder, now, &Utc, &settings)
+            .expect("write again");
+        assert!(again.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -46,6 +46,8 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
       instruction: "集中して作業する",
       soundOverride: "inherit",
       countAsBreak: false,
+      sounds: {},
+      warnings: { remainingSeconds: [], halfway: false, notify: false },
       checkIn: { mode: "off" },
     },
   ],
@@ -54,6 +56,7 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
   notifications: true,
   soundDefault: "on",
   soundScheme: "default",
+  sounds: {},
   ...overrides,
 });
 
@@ -68,6 +71,20 @@ const buildTimerState = (overrides?: Partial<TimerState>): TimerState => ({
 const defaultSettings: AppSettings = {
   notificationsEnabled: true,
   soundDefault: "on",
+  retention: {
+    stepRunsDays: 180,
+    sessionTotalsMonths: 24,
+  },
+  calendar: {
+    weekStart: "monday",
+    dayRolloverHour: 0,
+  },
+  sync: {
+    folder: null,
+  },
+  report: {
+    folder: null,
+  },
 };
 
 const setupInvoke = (
@@ -122,6 +139,51 @@ describe("App", () => {
     expect(screen.getByRole("button", { name: "Start" })).toBeEnabled();
   });
 
+  test("asks for the passphrase before loading locked data", async () => {
+    const routine = buildRoutine();
+    const timerState = buildTimerState({ remainingSeconds: 90 });
+    const user = userEvent.setup();
+    let locked = true;
+
+    invokeMock.mockImplementation((command: string) => {
+      if (command === "get_unlock_state") {
+        return Promise.resolve({
+          locked,
+          keySource: { kind: "passphrase", salt: "c2FsdA==" },
+        });
+      }
+      if (command === "unlock_data") {
+        locked = false;
+        return Promise.resolve(undefined);
+      }
+      if (locked) {
+        return Promise.reject(new Error(`${command} called while locked`));
+      }
+      if (command === "get_timer_state") {
+        return Promise.resolve(timerState);
+      }
+      if (command === "load_routines") {
+        return Promise.resolve([routine]);
+      }
+      if (command === "load_settings") {
+        return Promise.resolve(defaultSettings);
+      }
+      return Promise.resolve(undefined);
+    });
+
+    render(<App />);
+
+    await user.type(
+      await screen.findByLabelText("パスフレーズ"),
+      "secret{Enter}",
+    );
+
+    expect(await screen.findByText("集中")).toBeInTheDocument();
+    expect(invokeMock).toHaveBeenCalledWith("unlock_data", {
+      keySource: { kind: "passphrase", passphrase: "secret" },
+    });
+  });
+
   test("confirms stop before invoking stop_timer", async () => {
     const routine = buildRoutine();
     const timerState = buildTimerState({ isRunning: true });
@@ -196,6 +258,32 @@ describe("App", () => {
     });
   });
 
+  test("shows step warnings that ask for a notification", async () => {
+    const routine = buildRoutine();
+    setupInvoke(buildTimerState({ remainingSeconds: 90 }), [routine]);
+
+    render(<App />);
+
+    await waitFor(() => {
+      expect(listenerMap.get("step-warning")).toBeDefined();
+    });
+    const handler = listenerMap.get("step-warning");
+
+    act(() => {
+      handler?.({
+        payload: {
+          stepId: "step-1",
+          stepLabel: "集中",
+          warning: { type: "remaining", seconds: 30 },
+          message: "残り30秒",
+          notify: true,
+        },
+      });
+    });
+
+    expect(await screen.findByText("残り30秒")).toBeInTheDocument();
+  });
+
   test("switches views via buttons and shortcuts", async () => {
     const routine = buildRoutine();
     const timerState = buildTimerState({ remainingSeconds: 60 });
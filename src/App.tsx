@@ -1,10 +1,17 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import {
   isPermissionGranted,
   requestPermission,
   sendNotification,
 } from "@tauri-apps/plugin-notification";
-import { useCallback, useEffect, useRef, useState } from "react";
+import {
+  type ReactNode,
+  useCallback,
+  useEffect,
+  useRef,
+  useState,
+} from "react";
 import "./App.css";
 import { CheckInDialog } from "./components/CheckInDialog";
 import { CheckInPrompt } from "./components/CheckInPrompt";
@@ -14,6 +21,7 @@ import { RoutineEditor } from "./components/RoutineEditor";
 import { StatsView } from "./components/StatsView";
 import { StepNotificationToast } from "./components/StepNotificationToast";
 import { TimerView } from "./components/TimerView";
+import { UnlockView } from "./components/UnlockView";
 import { useTimerShortcuts } from "./hooks/useTimerShortcuts";
 import { AppStateProvider, useAppState } from "./state/appState";
 import type {
@@ -21,7 +29,10 @@ import type {
   CheckInChoice,
   Routine,
   Step,
+  StepWarningPayload,
+  KeySource,
   TimerState,
+  UnlockState,
 } from "./types/mccall";
 
 type NotificationFallback = {
@@ -246,11 +257,8 @@ const AppContent = () => {
     }
   }, []);
 
-  const notifyStepChange = useCallback(
-    async (step: Step) => {
-      const title = step.label?.trim() || "次のステップ";
-      const trimmed = step.instruction?.trim();
-      const body = trimmed && trimmed.length > 0 ? trimmed : undefined;
+  const sendAppNotification = useCallback(
+    async (title: string, body?: string) => {
       const allowed = await ensureNotificationPermission();
       if (!allowed) {
         showFallbackNotice(title, body);
@@ -266,6 +274,48 @@ const AppContent = () => {
     [ensureNotificationPermission, showFallbackNotice],
   );
 
+  const notifyStepChange = useCallback(
+    async (step: Step) => {
+      const title = step.label?.trim() || "次のステップ";
+      const trimmed = step.instruction?.trim();
+      const body = trimmed && trimmed.length > 0 ? trimmed : undefined;
+      await sendAppNotification(title, body);
+    },
+    [sendAppNotification],
+  );
+
+  const warningNotificationsEnabled =
+    state.settings.notificationsEnabled && Boolean(activeRoutine?.notifications);
+
+  useEffect(() => {
+    if (!warningNotificationsEnabled) {
+      return;
+    }
+    let disposed = false;
+    let unlisten: UnlistenFn | undefined;
+    listen<StepWarningPayload>("step-warning", (event) => {
+      if (!event.payload.notify) {
+        return;
+      }
+      const title = event.payload.stepLabel.trim() || "ステップ";
+      void sendAppNotification(title, event.payload.message);
+    })
+      .then((unlistenFn) => {
+        if (disposed) {
+          unlistenFn();
+          return;
+        }
+        unlisten = unlistenFn;
+      })
+      .catch((error) => {
+        console.warn("Failed to listen for step warnings", error);
+      });
+    return () => {
+      disposed = true;
+      unlisten?.();
+    };
+  }, [sendAppNotification, warningNotificationsEnabled]);
+
   useEffect(() => {
     if (checkInKey) {
       checkInStartRef.current = performance.now();
@@ -500,11 +550,67 @@ const AppContent = () => {
   );
 };
 
+// Holds the app back until the backend has its data open. An encrypted data
+// folder whose key could not be found at startup is unlocked here first.
+const UnlockGate = ({ children }: { children: ReactNode }) => {
+  const [unlockState, setUnlockState] = useState<UnlockState | null>(null);
+  const [unlockError, setUnlockError] = useState<string | null>(null);
+  const [unlocking, setUnlocking] = useState(false);
+
+  useEffect(() => {
+    let active = true;
+    invoke<UnlockState | undefined>("get_unlock_state")
+      .then((state) => {
+        if (active) {
+          setUnlockState(state ?? { locked: false });
+        }
+      })
+      .catch((error) => {
+        console.error("Failed to get unlock state", error);
+        if (active) {
+          setUnlockState({ locked: false });
+        }
+      });
+    return () => {
+      active = false;
+    };
+  }, []);
+
+  const unlock = useCallback(async (keySource: KeySource) => {
+    setUnlocking(true);
+    try {
+      await invoke("unlock_data", { keySource });
+      setUnlockState({ locked: false });
+    } catch (error) {
+      setUnlockError(String(error));
+    } finally {
+      setUnlocking(false);
+    }
+  }, []);
+
+  if (!unlockState) {
+    return null;
+  }
+  if (unlockState.locked) {
+    return (
+      <UnlockView
+        keySource={unlockState.keySource}
+        error={unlockError}
+        busy={unlocking}
+        onUnlock={unlock}
+      />
+    );
+  }
+  return <>{children}</>;
+};
+
 function App() {
   return (
-    <AppStateProvider>
-      <AppContent />
-    </AppStateProvider>
+    <UnlockGate>
+      <AppStateProvider>
+        <AppContent />
+      </AppStateProvider>
+    </UnlockGate>
   );
 }
 
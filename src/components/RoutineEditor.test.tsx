@@ -1,10 +1,17 @@
 import "@testing-library/jest-dom/vitest";
-import { cleanup, render, screen } from "@testing-library/react";
+import { invoke } from "@tauri-apps/api/core";
+import { cleanup, render, screen, waitFor } from "@testing-library/react";
 import userEvent from "@testing-library/user-event";
-import { afterEach, describe, expect, test, vi } from "vitest";
+import { afterEach, beforeEach, describe, expect, test, vi } from "vitest";
 import type { Routine } from "../types/mccall";
 import { RoutineEditor } from "./RoutineEditor";
 
+vi.mock("@tauri-apps/api/core", () => ({
+  invoke: vi.fn(),
+}));
+
+const invokeMock = vi.mocked(invoke);
+
 const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
   id: "routine-1",
   name: "朝のルーチン",
@@ -17,6 +24,8 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
       instruction: "環境を整える",
       soundOverride: "inherit",
       countAsBreak: false,
+      sounds: {},
+      warnings: { remainingSeconds: [], halfway: false, notify: false },
       checkIn: { mode: "off" },
     },
     {
@@ -27,6 +36,8 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
       instruction: "作業に入る",
       soundOverride: "inherit",
       countAsBreak: false,
+      sounds: {},
+      warnings: { remainingSeconds: [], halfway: false, notify: false },
       checkIn: { mode: "off" },
     },
   ],
@@ -35,12 +46,28 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
   notifications: true,
   soundDefault: "on",
   soundScheme: "default",
+  sounds: {},
   ...overrides,
 });
 
 describe("RoutineEditor", () => {
+  beforeEach(() => {
+    let next = 0;
+    invokeMock.mockImplementation(async (command, args) => {
+      if (command !== "new_ids") {
+        throw new Error(`unexpected command: ${command}`);
+      }
+      const { kind, count } = args as { kind: string; count: number };
+      return Array.from({ length: count }, () => {
+        next += 1;
+        return `${kind}-new-${next}`;
+      });
+    });
+  });
+
   afterEach(() => {
     cleanup();
+    vi.clearAllMocks();
   });
 
   test("renders empty state when no routines", () => {
@@ -82,10 +109,12 @@ describe("RoutineEditor", () => {
 
     await user.click(screen.getByRole("button", { name: "新規ルーチン" }));
 
-    expect(onUpsertRoutine).toHaveBeenCalledTimes(1);
+    await waitFor(() => expect(onUpsertRoutine).toHaveBeenCalledTimes(1));
     const created = onUpsertRoutine.mock.calls[0][0] as Routine;
     expect(created.name).toBe("新しいルーチン");
+    expect(created.id).toMatch(/^routine-new-/);
     expect(created.steps).toHaveLength(1);
+    expect(created.steps[0].id).toMatch(/^step-new-/);
     expect(onSelectRoutine).toHaveBeenCalledWith(created.id);
   });
 
@@ -136,9 +165,15 @@ describe("RoutineEditor", () => {
 
     await user.click(screen.getByRole("button", { name: "ステップを追加" }));
 
+    await waitFor(() => expect(onUpsertRoutine).toHaveBeenCalled());
     const lastCall =
       onUpsertRoutine.mock.calls[onUpsertRoutine.mock.calls.length - 1][0];
     expect(lastCall.steps).toHaveLength(3);
+    expect(lastCall.steps[2].id).toBe("step-new-1");
+    expect(invokeMock).toHaveBeenCalledWith("new_ids", {
+      kind: "step",
+      count: 1,
+    });
   });
 
   test("duplicates the selected routine", async () => {
@@ -158,12 +193,15 @@ describe("RoutineEditor", () => {
 
     await user.click(screen.getByRole("button", { name: "複製" }));
 
-    expect(onUpsertRoutine).toHaveBeenCalledTimes(1);
+    await waitFor(() => expect(onUpsertRoutine).toHaveBeenCalledTimes(1));
     const duplicated = onUpsertRoutine.mock.calls[0][0] as Routine;
     expect(duplicated.id).not.toBe(routine.id);
     expect(duplicated.name).toBe("朝のルーチン（コピー）");
     expect(duplicated.steps).toHaveLength(routine.steps.length);
     expect(duplicated.steps[0].id).not.toBe(routine.steps[0].id);
+    expect(new Set(duplicated.steps.map((step) => step.id)).size).toBe(
+      routine.steps.length,
+    );
     expect(onSelectRoutine).toHaveBeenCalledWith(duplicated.id);
   });
 });
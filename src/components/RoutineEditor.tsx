@@ -1,3 +1,4 @@
+import { invoke } from "@tauri-apps/api/core";
 import { useState } from "react";
 import type {
   CheckInMode,
@@ -29,15 +30,12 @@ const formatRepeatMode = (repeatMode: RepeatMode) => {
   }
 };
 
-const createId = () => {
-  if (globalThis.crypto?.randomUUID) {
-    return globalThis.crypto.randomUUID();
-  }
-  return `id-${Date.now()}-${Math.random().toString(16).slice(2)}`;
-};
+// Ids come from the backend ids module so every record shares one format.
+const newIds = (kind: "routine" | "step", count: number) =>
+  invoke<string[]>("new_ids", { kind, count });
 
-const createStep = (order: number): Step => ({
-  id: createId(),
+const createStep = (id: string, order: number): Step => ({
+  id,
   order,
   label: `ステップ ${order + 1}`,
   durationSeconds: 300,
@@ -45,32 +43,45 @@ const createStep = (order: number): Step => ({
   soundOverride: "inherit",
   countAsBreak: false,
   checkIn: { mode: "off" },
+  sounds: {},
+  warnings: { remainingSeconds: [], halfway: false, notify: false },
 });
 
-const createRoutine = (): Routine => ({
-  id: createId(),
+const createRoutine = (id: string, stepId: string): Routine => ({
+  id,
   name: "新しいルーチン",
-  steps: [createStep(0)],
+  steps: [createStep(stepId, 0)],
   repeatMode: { type: "infinite" },
   autoAdvance: true,
   notifications: true,
   soundDefault: "on",
   soundScheme: "default",
+  sounds: {},
 });
 
-const duplicateRoutine = (routine: Routine): Routine => {
+const duplicateRoutine = (
+  routine: Routine,
+  id: string,
+  stepIds: string[],
+): Routine => {
   const baseName = routine.name.trim();
   const name = baseName.length > 0 ? `${baseName}（コピー）` : "コピー";
   return {
     ...routine,
-    id: createId(),
+    id,
     name,
     steps: routine.steps.map((step, index) => ({
       ...step,
-      id: createId(),
+      id: stepIds[index],
       order: index,
       checkIn: { ...step.checkIn },
+      sounds: { ...step.sounds },
+      warnings: {
+        ...step.warnings,
+        remainingSeconds: [...step.warnings.remainingSeconds],
+      },
     })),
+    sounds: { ...routine.sounds },
   };
 };
 
@@ -141,30 +152,51 @@ export const RoutineEditor = ({
     (step) => step.id === resolvedSelectedStepId,
   );
 
-  const handleCreateRoutine = () => {
-    const routine = createRoutine();
-    commitRoutine(routine);
-    onSelectRoutine?.(routine.id);
+  const handleCreateRoutine = async () => {
+    try {
+      const [[routineId], [stepId]] = await Promise.all([
+        newIds("routine", 1),
+        newIds("step", 1),
+      ]);
+      const routine = createRoutine(routineId, stepId);
+      commitRoutine(routine);
+      onSelectRoutine?.(routine.id);
+    } catch (error) {
+      console.error("Failed to create routine", error);
+    }
   };
 
-  const handleDuplicateRoutine = () => {
+  const handleDuplicateRoutine = async () => {
     if (!activeRoutine) {
       return;
     }
-    const routine = duplicateRoutine(activeRoutine);
-    commitRoutine(routine);
-    onSelectRoutine?.(routine.id);
-    setSelectedStepId(routine.steps[0]?.id ?? null);
+    try {
+      const [[routineId], stepIds] = await Promise.all([
+        newIds("routine", 1),
+        newIds("step", activeRoutine.steps.length),
+      ]);
+      const routine = duplicateRoutine(activeRoutine, routineId, stepIds);
+      commitRoutine(routine);
+      onSelectRoutine?.(routine.id);
+      setSelectedStepId(routine.steps[0]?.id ?? null);
+    } catch (error) {
+      console.error("Failed to duplicate routine", error);
+    }
   };
 
-  const handleAddStep = () => {
+  const handleAddStep = async () => {
     if (!activeRoutine) {
       return;
     }
-    const newStep = createStep(activeRoutine.steps.length);
-    const nextSteps = normalizeSteps([...activeRoutine.steps, newStep]);
-    updateRoutine({ steps: nextSteps });
-    setSelectedStepId(newStep.id);
+    try {
+      const [stepId] = await newIds("step", 1);
+      const newStep = createStep(stepId, activeRoutine.steps.length);
+      const nextSteps = normalizeSteps([...activeRoutine.steps, newStep]);
+      updateRoutine({ steps: nextSteps });
+      setSelectedStepId(newStep.id);
+    } catch (error) {
+      console.error("Failed to add step", error);
+    }
   };
 
   const handleRemoveStep = (stepId: string) => {
@@ -267,6 +299,30 @@ export const RoutineEditor = ({
     );
   };
 
+  const updateWarnings = (updates: Partial<Step["warnings"]>) => {
+    if (!selectedStep) {
+      return;
+    }
+    updateStep(selectedStep.id, {
+      warnings: {
+        ...selectedStep.warnings,
+        ...updates,
+      },
+    });
+  };
+
+  const updateCountdownWarning = (value: string) => {
+    if (!selectedStep) {
+      return;
+    }
+    const seconds = Math.floor(Number(value));
+    const rest = selectedStep.warnings.remainingSeconds.slice(1);
+    updateWarnings({
+      remainingSeconds:
+        Number.isFinite(seconds) && seconds > 0 ? [seconds, ...rest] : rest,
+    });
+  };
+
   const updateSoundOverride = (value: string) => {
     if (!selectedStep) {
       return;
@@ -655,6 +711,49 @@ export const RoutineEditor = ({
                       />
                       休憩として集計
                     </label>
+                    <div className="routine-editor__form">
+                      <label
+                        className="routine-editor__label"
+                        htmlFor="step-warning-remaining"
+                      >
+                        残り時間の警告（秒）
+                      </label>
+                      <input
+                        id="step-warning-remaining"
+                        className="routine-editor__input routine-editor__input--short"
+                        type="number"
+                        min={0}
+                        placeholder="なし"
+                        value={selectedStep.warnings.remainingSeconds[0] ?? ""}
+                        onChange={(event) =>
+                          updateCountdownWarning(event.currentTarget.value)
+                        }
+                      />
+                    </div>
+                    <label className="routine-editor__toggle">
+                      <input
+                        type="checkbox"
+                        checked={selectedStep.warnings.halfway}
+                        onChange={(event) =>
+                          updateWarnings({
+                            halfway: event.currentTarget.checked,
+                          })
+                        }
+                      />
+                      折り返しで警告
+                    </label>
+                    <label className="routine-editor__toggle">
+                      <input
+                        type="checkbox"
+                        checked={selectedStep.warnings.notify}
+                        onChange={(event) =>
+                          updateWarnings({
+                            notify: event.currentTarget.checked,
+                          })
+                        }
+                      />
+                      警告を通知でも表示
+                    </label>
                     <div className="routine-editor__form">
                       <label
                         className="routine-editor__label"
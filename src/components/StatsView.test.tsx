@@ -18,6 +18,10 @@ afterEach(() => {
 
 const buildStats = (overrides?: Partial<SessionStats>): SessionStats => ({
   sessionsCount: 0,
+  completedSessionsCount: 0,
+  stoppedSessionsCount: 0,
+  recoveredSessionsCount: 0,
+  abortRate: 0,
   cyclesCount: 0,
   totalSeconds: 0,
   workSeconds: 0,
@@ -25,6 +29,13 @@ const buildStats = (overrides?: Partial<SessionStats>): SessionStats => ({
   checkInDoneCount: 0,
   checkInSkipCount: 0,
   muteRate: 0,
+  plannedSeconds: 0,
+  executedSeconds: 0,
+  overtimeSeconds: 0,
+  skipLossSeconds: 0,
+  skippedCount: 0,
+  executedRatio: 0,
+  averageSkipLossSeconds: 0,
   ...overrides,
 });
 
@@ -53,6 +53,14 @@ const buildStatItems = (stats: SessionStats): StatItem[] => {
       label: "Check-in skip率",
       value: formatRate(skipRate, checkInTotal > 0),
     },
+    {
+      label: "計画実行率",
+      value: formatRate(stats.executedRatio, stats.plannedSeconds > 0),
+    },
+    {
+      label: "中断率",
+      value: formatRate(stats.abortRate, stats.sessionsCount > 0),
+    },
     {
       label: "ミュート率",
       value: formatRate(stats.muteRate, stats.sessionsCount > 0),
@@ -20,6 +20,8 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
       instruction: "集中して作業する",
       soundOverride: "inherit",
       countAsBreak: false,
+      sounds: {},
+      warnings: { remainingSeconds: [], halfway: false, notify: false },
       checkIn: { mode: "off" },
     },
   ],
@@ -28,6 +30,7 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
   notifications: true,
   soundDefault: "on",
   soundScheme: "default",
+  sounds: {},
   ...overrides,
 });
 
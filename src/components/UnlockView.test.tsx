@@ -0,0 +1,55 @@
+import "@testing-library/jest-dom/vitest";
+import { cleanup, render, screen } from "@testing-library/react";
+import userEvent from "@testing-library/user-event";
+import { afterEach, describe, expect, test, vi } from "vitest";
+import { UnlockView } from "./UnlockView";
+
+afterEach(() => {
+  cleanup();
+});
+
+describe("UnlockView", () => {
+  test("submits the entered passphrase", async () => {
+    const onUnlock = vi.fn();
+    const user = userEvent.setup();
+
+    render(
+      <UnlockView
+        keySource={{ kind: "passphrase", salt: "c2FsdA==" }}
+        onUnlock={onUnlock}
+      />,
+    );
+
+    const unlockButton = screen.getByRole("button", { name: "Unlock" });
+    expect(unlockButton).toBeDisabled();
+    await user.type(screen.getByLabelText("パスフレーズ"), "secret{Enter}");
+
+    expect(onUnlock).toHaveBeenCalledWith({
+      kind: "passphrase",
+      passphrase: "secret",
+    });
+  });
+
+  test("asks for the keyfile path and shows the last error", async () => {
+    const onUnlock = vi.fn();
+    const user = userEvent.setup();
+
+    render(
+      <UnlockView
+        keySource={{ kind: "keyfile", path: "/keys/data.key" }}
+        error="暗号化キーが正しくありません"
+        onUnlock={onUnlock}
+      />,
+    );
+
+    expect(screen.getByRole("alert")).toHaveTextContent(
+      "暗号化キーが正しくありません",
+    );
+    await user.click(screen.getByRole("button", { name: "Unlock" }));
+
+    expect(onUnlock).toHaveBeenCalledWith({
+      kind: "keyfile",
+      path: "/keys/data.key",
+    });
+  });
+});
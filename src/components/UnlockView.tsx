@@ -0,0 +1,71 @@
+import { type FormEvent, useState } from "react";
+import type { KeySource, KeySourceConfig } from "../types/mccall";
+
+type UnlockViewProps = {
+  keySource?: KeySourceConfig;
+  error?: string | null;
+  busy?: boolean;
+  onUnlock: (keySource: KeySource) => void;
+};
+
+export const UnlockView = ({
+  keySource,
+  error,
+  busy = false,
+  onUnlock,
+}: UnlockViewProps) => {
+  const usesKeyfile = keySource?.kind === "keyfile";
+  const [value, setValue] = useState(
+    keySource?.kind === "keyfile" ? keySource.path : "",
+  );
+
+  const handleSubmit = (event: FormEvent<HTMLFormElement>) => {
+    event.preventDefault();
+    if (!value || busy) {
+      return;
+    }
+    onUnlock(
+      usesKeyfile
+        ? { kind: "keyfile", path: value }
+        : { kind: "passphrase", passphrase: value },
+    );
+  };
+
+  return (
+    <main className="unlock">
+      <form className="unlock__card" onSubmit={handleSubmit}>
+        <h1 className="unlock__title">データのロックを解除</h1>
+        <p className="unlock__body">
+          {usesKeyfile
+            ? "キーファイルが見つからないか一致しません。キーファイルの場所を指定してください。"
+            : "データは暗号化されています。パスフレーズを入力してください。"}
+        </p>
+        <label className="unlock__label" htmlFor="unlock-key">
+          {usesKeyfile ? "キーファイル" : "パスフレーズ"}
+        </label>
+        <input
+          id="unlock-key"
+          className="unlock__input"
+          type={usesKeyfile ? "text" : "password"}
+          autoFocus
+          value={value}
+          onChange={(event) => setValue(event.currentTarget.value)}
+        />
+        {error ? (
+          <p className="unlock__error" role="alert">
+            {error}
+          </p>
+        ) : null}
+        <div className="unlock__actions">
+          <button
+            className="button button--primary"
+            type="submit"
+            disabled={!value || busy}
+          >
+            Unlock
+          </button>
+        </div>
+      </form>
+    </main>
+  );
+};
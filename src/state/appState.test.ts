@@ -14,6 +14,8 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
       instruction: "Focus",
       soundOverride: "inherit",
       countAsBreak: false,
+      sounds: {},
+      warnings: { remainingSeconds: [], halfway: false, notify: false },
       checkIn: { mode: "off" },
     },
   ],
@@ -22,6 +24,7 @@ const buildRoutine = (overrides?: Partial<Routine>): Routine => ({
   notifications: true,
   soundDefault: "on",
   soundScheme: "default",
+  sounds: {},
   ...overrides,
 });
 
@@ -53,14 +56,26 @@ describe("appReducer", () => {
           cyclesCount: 0,
           checkInDoneCount: 0,
           checkInSkipCount: 0,
+          plannedSeconds: 0,
+          executedSeconds: 0,
+          overtimeSeconds: 0,
+          skipLossSeconds: 0,
+          skippedCount: 0,
         },
         mutedDuringSession: false,
+        excludedFromStats: false,
+        tags: [],
+        edits: [],
       },
     });
 
     const settings = {
       notificationsEnabled: false,
       soundDefault: "off" as const,
+      retention: { stepRunsDays: null, sessionTotalsMonths: 12 },
+      calendar: { weekStart: "sunday" as const, dayRolloverHour: 4 },
+      sync: { folder: "/home/me/Sync/mccall" },
+      report: { folder: null },
     };
 
     const nextState = appReducer(initialAppState, {
@@ -16,6 +16,7 @@ import type {
   AppSettings,
   AppState,
   CheckInConfig,
+  FieldError,
   Routine,
   Step,
   TimerState,
@@ -33,6 +34,20 @@ const defaultTimerState: TimerState = {
 const defaultSettings: AppSettings = {
   notificationsEnabled: true,
   soundDefault: "on",
+  retention: {
+    stepRunsDays: null,
+    sessionTotalsMonths: null,
+  },
+  calendar: {
+    weekStart: "monday",
+    dayRolloverHour: 0,
+  },
+  sync: {
+    folder: null,
+  },
+  report: {
+    folder: null,
+  },
 };
 
 export const initialAppState: AppState = {
@@ -69,6 +84,7 @@ type AppErrorPayload = {
   message: string;
   detail?: string;
   recoverable: boolean;
+  fieldErrors?: FieldError[];
 };
 
 type AppAction =
@@ -364,6 +380,9 @@ export const AppStateProvider = ({ children }: { children: ReactNode }) => {
           listen("timer-stopped", () => {
             dispatch({ type: "timer-stopped" });
           }),
+          listen("data-changed-externally", () => {
+            loadInitialState();
+          }),
           listen<AppErrorPayload>("app-error", (event) => {
             dispatch({
               type: "app-error",
@@ -10,7 +10,12 @@ export type SoundScheme = "default" | "endDifferent";
 export type CheckInMode = "off" | "prompt" | "gate";
 export type CheckInChoice = "done" | "skip";
 export type StepRunResult = "completed" | "skipped" | "aborted";
-export type AppErrorKind = "system" | "data" | "timer" | "audio";
+export type AppErrorKind =
+  | "system"
+  | "data"
+  | "timer"
+  | "audio"
+  | "validation";
 export type AppErrorAction = "reload-data" | "reset-timer";
 
 export interface Routine {
@@ -22,6 +27,7 @@ export interface Routine {
   notifications: boolean;
   soundDefault: SoundSetting;
   soundScheme: SoundScheme;
+  sounds: SoundAssets;
 }
 
 export interface Step {
@@ -33,6 +39,42 @@ export interface Step {
   soundOverride: SoundOverride;
   countAsBreak: boolean;
   checkIn: CheckInConfig;
+  sounds: SoundAssets;
+  warnings: StepWarnings;
+}
+
+export interface StepWarnings {
+  remainingSeconds: number[];
+  halfway: boolean;
+  notify: boolean;
+}
+
+export type StepWarning =
+  | { type: "halfway" }
+  | { type: "remaining"; seconds: number };
+
+export interface StepWarningPayload {
+  stepId: string;
+  stepLabel: string;
+  warning: StepWarning;
+  message: string;
+  notify: boolean;
+}
+
+export interface SoundAssets {
+  stepStart?: string;
+  stepEnd?: string;
+  routineCompleted?: string;
+  checkInRequired?: string;
+  countdownWarning?: string;
+}
+
+export type SoundFormat = "wav" | "aiff" | "ogg" | "flac" | "mp3";
+
+export interface SoundAsset {
+  name: string;
+  format: SoundFormat;
+  sizeBytes: number;
 }
 
 export interface CheckInConfig {
@@ -50,10 +92,42 @@ export interface Session {
   stepRuns: StepRun[];
   totals: SessionTotals;
   mutedDuringSession: boolean;
+  zone?: SessionZone;
+  excludedFromStats: boolean;
+  tags: string[];
+  annotation?: string;
+  edits: SessionEdit[];
+  outcome?: SessionOutcome;
 }
 
+export type SessionOutcome = "completed" | "stopped" | "recovered";
+
+export interface SessionZone {
+  utcOffsetMinutes: number;
+  timeZone?: string;
+}
+
+export type SessionChange =
+  | { kind: "excludedFromStats"; excluded: boolean }
+  | { kind: "tags"; previous: string[] }
+  | { kind: "annotation"; previous?: string }
+  | {
+      kind: "stepRunResult";
+      runIndex: number;
+      stepId: string;
+      previous: StepRunResult;
+      result: StepRunResult;
+      previousTotals: SessionTotals;
+    };
+
+export type SessionEdit = SessionChange & {
+  id: string;
+  editedAt: string;
+};
+
 export interface StepRun {
   stepId: string;
+  label?: string | null;
   plannedDurationSeconds: number;
   actualDurationSeconds: number;
   startedAt: string;
@@ -85,10 +159,19 @@ export interface SessionTotals {
   cyclesCount: number;
   checkInDoneCount: number;
   checkInSkipCount: number;
+  plannedSeconds: number;
+  executedSeconds: number;
+  overtimeSeconds: number;
+  skipLossSeconds: number;
+  skippedCount: number;
 }
 
 export interface SessionStats {
   sessionsCount: number;
+  completedSessionsCount: number;
+  stoppedSessionsCount: number;
+  recoveredSessionsCount: number;
+  abortRate: number;
   cyclesCount: number;
   totalSeconds: number;
   workSeconds: number;
@@ -96,6 +179,139 @@ export interface SessionStats {
   checkInDoneCount: number;
   checkInSkipCount: number;
   muteRate: number;
+  plannedSeconds: number;
+  executedSeconds: number;
+  overtimeSeconds: number;
+  skipLossSeconds: number;
+  skippedCount: number;
+  executedRatio: number;
+  averageSkipLossSeconds: number;
+}
+
+export type DayOfWeek =
+  | "monday"
+  | "tuesday"
+  | "wednesday"
+  | "thursday"
+  | "friday"
+  | "saturday"
+  | "sunday";
+
+export interface TimeWindow {
+  start: string;
+  end: string;
+}
+
+export interface StatsFilter {
+  routineIds?: string[];
+  tags?: string[];
+  weekdays?: DayOfWeek[];
+  timeOfDay?: TimeWindow | null;
+}
+
+export type StatsGroupBy = "routine" | "tag" | "weekday";
+
+export interface StatsGroup {
+  key: string;
+  label: string | null;
+  stats: SessionStats;
+}
+
+export interface ResponseTimeSummary {
+  responses: number;
+  timedOutCount: number;
+  p50Ms: number | null;
+  p90Ms: number | null;
+}
+
+export interface StepResponseTimes {
+  routineId: string;
+  stepId: string;
+  label: string | null;
+  mode: Exclude<CheckInMode, "off">;
+  summary: ResponseTimeSummary;
+}
+
+export interface ModeResponseTimes {
+  mode: Exclude<CheckInMode, "off">;
+  summary: ResponseTimeSummary;
+}
+
+export interface WaitBucket {
+  minMs: number;
+  maxMs: number | null;
+  count: number;
+}
+
+export interface WeeklyResponseTimes {
+  startDate: string;
+  from: string;
+  to: string;
+  summary: ResponseTimeSummary;
+}
+
+export interface CheckInAnalytics {
+  byStep: StepResponseTimes[];
+  byMode: ModeResponseTimes[];
+  gateWaits: WaitBucket[];
+  weekly: WeeklyResponseTimes[];
+}
+
+export interface AbandonedStep {
+  routineId: string;
+  stepId: string;
+  label?: string;
+  stoppedCount: number;
+  recoveredCount: number;
+  averageProgress: number;
+  progressQuarters: [number, number, number, number];
+}
+
+export interface HourlyAbortRate {
+  hour: number;
+  sessionsCount: number;
+  abortedCount: number;
+  abortRate: number;
+}
+
+export interface AbortAnalytics {
+  completedCount: number;
+  stoppedCount: number;
+  recoveredCount: number;
+  abandonedSteps: AbandonedStep[];
+  byHour: HourlyAbortRate[];
+}
+
+export interface StatsBucket {
+  startDate: string;
+  from: string;
+  to: string;
+  sessionsCount: number;
+  cyclesCount: number;
+  workSeconds: number;
+  breakSeconds: number;
+  stepRunsCount: number;
+  skipRate: number;
+}
+
+export interface StepStats {
+  routineId: string;
+  stepId: string;
+  label: string | null;
+  runs: number;
+  completedCount: number;
+  skippedCount: number;
+  abortedCount: number;
+  averagePlannedSeconds: number;
+  averageActualSeconds: number;
+  executedRatio: number;
+  averageSkipLossSeconds: number;
+  overtimeSeconds: number;
+  checkInCount: number;
+  checkInDoneRate: number;
+  checkInSkipRate: number;
+  checkInTimeoutRate: number;
+  medianResponseTimeMs: number | null;
 }
 
 export interface TimerState {
@@ -121,6 +337,107 @@ export interface AppState {
 export interface AppSettings {
   notificationsEnabled: boolean;
   soundDefault: SoundSetting;
+  retention: RetentionSettings;
+  calendar: CalendarSettings;
+  sync: SyncSettings;
+  report: ReportSettings;
+}
+
+export interface SyncSettings {
+  folder: string | null;
+}
+
+export interface ReportSettings {
+  folder: string | null;
+}
+
+export type ReportFormat = "markdown" | "html";
+
+export interface RoutineVersion {
+  revision: number;
+  deviceId: string;
+}
+
+export interface SyncConflict {
+  id: string;
+  routineId: string;
+  detectedAt: string;
+  kept: RoutineVersion;
+  discarded: RoutineVersion;
+  discardedRoutine: Routine | null;
+}
+
+export interface SyncReport {
+  exported: number;
+  imported: number;
+  skippedLines: number;
+  conflicts: number;
+  routinesChanged: boolean;
+  sessionsChanged: boolean;
+}
+
+export type GoalMetric = "sessions" | "workMinutes" | "cycles";
+
+export type GoalDays = "every" | "weekdays" | "weekends";
+
+export interface Goal {
+  id: string;
+  routineId: string | null;
+  metric: GoalMetric;
+  target: number;
+  days: GoalDays;
+}
+
+export interface GoalProgress {
+  goal: Goal;
+  date: string;
+  appliesToday: boolean;
+  value: number;
+  reached: boolean;
+  currentStreak: number;
+  bestStreak: number;
+}
+
+export interface ProfileInfo {
+  id: string;
+  name: string;
+  createdAt: string;
+}
+
+export interface ProfileList {
+  active: string;
+  profiles: ProfileInfo[];
+}
+
+export interface ProfileStats {
+  stats: SessionStats;
+  skippedProfiles: string[];
+}
+
+export interface RetentionSettings {
+  stepRunsDays: number | null;
+  sessionTotalsMonths: number | null;
+}
+
+export type WeekStart = "monday" | "sunday" | "saturday";
+
+export interface CalendarSettings {
+  weekStart: WeekStart;
+  dayRolloverHour: number;
+}
+
+export type PeriodKind = "hour" | "day" | "week" | "month";
+
+export interface Period {
+  kind: PeriodKind;
+  startDate: string;
+  from: string;
+  to: string;
+}
+
+export interface FieldError {
+  path: string;
+  message: string;
 }
 
 export interface AppErrorNotice {
@@ -130,3 +447,21 @@ export interface AppErrorNotice {
   kind: AppErrorKind;
   action?: AppErrorAction;
 }
+
+export type KeySource =
+  | { kind: "passphrase"; passphrase: string }
+  | { kind: "keyfile"; path: string };
+
+export type KeySourceConfig =
+  | { kind: "passphrase"; salt: string }
+  | { kind: "keyfile"; path: string };
+
+export interface EncryptionStatus {
+  enabled: boolean;
+  keySource?: KeySourceConfig;
+}
+
+export interface UnlockState {
+  locked: boolean;
+  keySource?: KeySourceConfig;
+}